@@ -1,15 +1,21 @@
-#[cfg(target_os = "windows")]
 extern crate vergen;
+#[cfg(target_os = "windows")]
 extern crate winres;
 
 fn main() {
     use vergen::{vergen, Config};
     // Generate the default 'cargo:' instruction output
     let _vers = vergen(Config::default());
+    compile_resources();
+}
+
+#[cfg(target_os = "windows")]
+fn compile_resources() {
     let res = winres::WindowsResource::new();
     //res.set_icon("xanthidae.ico");
     res.compile().unwrap();
 }
 
-#[cfg(target_os = "linux")]
-fn main() {}
+// winres is only available on Windows hosts
+#[cfg(not(target_os = "windows"))]
+fn compile_resources() {}
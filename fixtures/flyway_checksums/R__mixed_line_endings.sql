@@ -0,0 +1,2 @@
+create or replace view v_address asselect * from t_address
+where id > 0;
\ No newline at end of file
@@ -0,0 +1,2 @@
+alter table t_address add (city varchar2(100));
+comment on column t_address.city is 'City';
@@ -0,0 +1,4 @@
+create table t_address (
+  id number primary key,
+  street varchar2(100)
+);
@@ -0,0 +1,2 @@
+﻿insert into t_address (id, street) values (1, 'Müllerstraße');
+
@@ -0,0 +1,4 @@
+create or replace package pkg_address is
+  procedure add_address(p_street in varchar2);
+end pkg_address;
+/
@@ -0,0 +1,39 @@
+#!/bin/sh
+# Regenerate checksums.txt from a real Flyway run: the migrations of this folder are applied
+# to a throwaway Oracle database, and the checksums are read back from flyway_schema_history.
+# Needs Docker; pass the Flyway version to use, e.g. ./regenerate.sh 10.17.0
+set -eu
+
+FLYWAY_VERSION=${1:?usage: $0 <flyway version>}
+NETWORK=xanthidae-checksums
+PASSWORD=Xanthidae1
+URL=jdbc:oracle:thin:@oracle:1521/FREEPDB1
+cd "$(dirname "$0")"
+
+cleanup() {
+    docker rm -f xanthidae-checksums-oracle >/dev/null 2>&1 || true
+    docker network rm "$NETWORK" >/dev/null 2>&1 || true
+}
+trap cleanup EXIT
+
+docker network create "$NETWORK" >/dev/null
+docker run -d --name xanthidae-checksums-oracle --network "$NETWORK" --network-alias oracle \
+    -e ORACLE_PASSWORD="$PASSWORD" -e APP_USER=app -e APP_USER_PASSWORD="$PASSWORD" \
+    --health-cmd healthcheck.sh --health-interval 10s \
+    gvenzl/oracle-free:slim >/dev/null
+until [ "$(docker inspect -f '{{.State.Health.Status}}' xanthidae-checksums-oracle)" = healthy ]; do
+    sleep 5
+done
+
+docker run --rm --network "$NETWORK" -v "$PWD:/flyway/sql:ro" "flyway/flyway:$FLYWAY_VERSION" \
+    -url="$URL" -user=app -password="$PASSWORD" -locations=filesystem:/flyway/sql migrate
+
+{
+    echo "# file<TAB>checksum as stored in flyway_schema_history.checksum"
+    echo "# read back after \`flyway migrate\` with Flyway $FLYWAY_VERSION against Oracle, see regenerate.sh"
+    docker exec -i xanthidae-checksums-oracle sqlplus -s "app/$PASSWORD@FREEPDB1" <<'SQL'
+set heading off feedback off pagesize 0 trimspool on
+select script || chr(9) || nvl(checksum, 0) from flyway_schema_history
+ where script like '%.sql' order by script;
+SQL
+} | sed '/^$/d' > checksums.txt
@@ -0,0 +1,52 @@
+/*
+ * Stable C ABI of the Xanthidae plugin DLL for companion tools, e.g. a CLI in a build pipeline.
+ *
+ * All strings are NUL-terminated UTF-8. Results are written to buffers provided by the caller:
+ * the functions return the length of the complete result in bytes (without the terminating NUL),
+ * so the result is complete if the return value is less than out_len. Otherwise the buffer holds
+ * as much of the result as fits, cut at a character boundary and NUL-terminated, so it is always
+ * valid UTF-8; call again with a buffer of at least the returned length + 1. Passing NULL and 0
+ * as buffer just asks for the length. Negative return values are errors.
+ */
+#ifndef XANTHIDAE_H
+#define XANTHIDAE_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* compare with XanthidaeAbiVersion() before calling anything else */
+#define XANTHIDAE_ABI_VERSION 1
+
+/* an argument is NULL or not valid UTF-8, or out_buf is NULL while out_len is not 0 */
+#define XANTHIDAE_ERROR_INVALID_ARGUMENT (-1)
+/* the plugin failed unexpectedly; the buffer is left untouched */
+#define XANTHIDAE_ERROR_INTERNAL (-2)
+
+/* the version of the ABI implemented by the DLL */
+int XanthidaeAbiVersion(void);
+
+/* the version of the plugin, e.g. "1.0.2"; the string is owned by the DLL */
+const char *XanthidaeVersion(void);
+
+/*
+ * The DDL as written into repeatable migrations: the object name qualified with the owner,
+ * `force` injected for views and types etc. object_type is e.g. "PACKAGE" or "VIEW".
+ */
+int XanthidaeRewriteDdl(const char *ddl, const char *object_type, const char *owner,
+                        const char *name, char *out_buf, size_t out_len);
+
+/*
+ * The file name of a versioned migration created now, e.g. V2021_07_18_10_15_00__basename.sql;
+ * with millis_flag != 0, the timestamp includes milliseconds.
+ */
+int XanthidaeVersionedFilename(const char *basename, int millis_flag, char *out_buf,
+                               size_t out_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* XANTHIDAE_H */
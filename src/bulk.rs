@@ -0,0 +1,190 @@
+// Loops over many objects (bulk exports) that can be cancelled and have a time limit.
+//
+// Cancelling is cooperative: the Cancel button of the progress dialog sets a flag, which is
+// checked between objects, so an object is never left half written.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::logging::target;
+
+// set by the Cancel button of the progress dialog of the running bulk export
+pub static BULK_CANCEL: AtomicBool = AtomicBool::new(false);
+
+// The time source of the time limit, so tests can control it
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BulkOutcome {
+    Completed,
+    Cancelled,
+    // the time limit was reached
+    TimedOut,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BulkSummary {
+    pub outcome: BulkOutcome,
+    // the items processed before the loop stopped, and how many of them succeeded
+    pub processed: usize,
+    pub succeeded: usize,
+    pub total: usize,
+}
+
+impl BulkSummary {
+    // What stopped the loop early, if anything, for the final message
+    pub fn interruption(&self, timeout: Option<Duration>) -> Option<String> {
+        let reason = match self.outcome {
+            BulkOutcome::Completed => return None,
+            BulkOutcome::Cancelled => "Cancelled".to_string(),
+            BulkOutcome::TimedOut => format!(
+                "Stopped after the time limit of {} seconds",
+                timeout.map_or(0, |t| t.as_secs())
+            ),
+        };
+        Some(format!(
+            "{} after {} of {} objects.",
+            reason, self.processed, self.total
+        ))
+    }
+}
+
+// Process the items in order until all are done, the cancel flag is set or the time limit is
+// reached; `process` returns whether the item succeeded
+pub fn run_bulk<T, F>(
+    items: &[T],
+    cancel: &AtomicBool,
+    timeout: Option<Duration>,
+    clock: &dyn Clock,
+    mut process: F,
+) -> BulkSummary
+where
+    F: FnMut(usize, &T) -> bool,
+{
+    let deadline = timeout.map(|timeout| clock.now() + timeout);
+    let mut summary = BulkSummary {
+        outcome: BulkOutcome::Completed,
+        processed: 0,
+        succeeded: 0,
+        total: items.len(),
+    };
+    for (index, item) in items.iter().enumerate() {
+        if cancel.load(Ordering::SeqCst) {
+            summary.outcome = BulkOutcome::Cancelled;
+            break;
+        }
+        if deadline.is_some_and(|deadline| clock.now() >= deadline) {
+            summary.outcome = BulkOutcome::TimedOut;
+            break;
+        }
+        if process(index, item) {
+            summary.succeeded += 1;
+        }
+        summary.processed += 1;
+    }
+    if summary.outcome != BulkOutcome::Completed {
+        info!(target: target::FLYWAY,
+            "Bulk export {:?} after {} of {} items",
+            summary.outcome, summary.processed, summary.total
+        );
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+
+    use crate::bulk::*;
+
+    // Advances by one second whenever it is read
+    struct FakeClock {
+        start: Instant,
+        reads: Cell<u32>,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.reads.set(self.reads.get() + 1);
+            self.start + Duration::from_secs(self.reads.get() as u64)
+        }
+    }
+
+    fn fake_clock() -> FakeClock {
+        FakeClock {
+            start: Instant::now(),
+            reads: Cell::new(0),
+        }
+    }
+
+    #[test]
+    fn run_bulk_should_stop_after_cancel_flag_is_set() {
+        let cancel = AtomicBool::new(false);
+        let mut seen = vec![];
+        let summary = run_bulk(&[1, 2, 3, 4, 5], &cancel, None, &fake_clock(), |_, item| {
+            seen.push(*item);
+            // the user presses Cancel while the second item is exported
+            if *item == 2 {
+                cancel.store(true, Ordering::SeqCst);
+            }
+            *item != 1
+        });
+        assert_eq!(vec![1, 2], seen);
+        assert_eq!(
+            BulkSummary {
+                outcome: BulkOutcome::Cancelled,
+                processed: 2,
+                succeeded: 1,
+                total: 5,
+            },
+            summary
+        );
+        assert_eq!(
+            Some("Cancelled after 2 of 5 objects.".to_string()),
+            summary.interruption(None)
+        );
+    }
+
+    #[test]
+    fn run_bulk_should_stop_at_time_limit() {
+        let cancel = AtomicBool::new(false);
+        // the deadline is taken at the first read, then every item check advances a second
+        let timeout = Some(Duration::from_secs(3));
+        let summary = run_bulk(
+            &["a", "b", "c", "d"],
+            &cancel,
+            timeout,
+            &fake_clock(),
+            |_, _| true,
+        );
+        assert_eq!(BulkOutcome::TimedOut, summary.outcome);
+        assert_eq!(2, summary.processed);
+        assert_eq!(
+            Some("Stopped after the time limit of 3 seconds after 2 of 4 objects.".to_string()),
+            summary.interruption(timeout)
+        );
+    }
+
+    #[test]
+    fn run_bulk_should_complete_without_limit() {
+        let cancel = AtomicBool::new(false);
+        let summary = run_bulk(&[1, 2, 3], &cancel, None, &fake_clock(), |index, item| {
+            assert_eq!(index + 1, *item);
+            true
+        });
+        assert_eq!(BulkOutcome::Completed, summary.outcome);
+        assert_eq!((3, 3), (summary.processed, summary.succeeded));
+        assert_eq!(None, summary.interruption(None));
+    }
+}
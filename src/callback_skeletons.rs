@@ -0,0 +1,172 @@
+// Generate a set of Flyway callback stubs for a new migration repository.
+//
+// The file names and bodies come from the config; existing files are never overwritten.
+use std::path::Path;
+
+use chrono::Utc;
+
+use crate::config::Config;
+use crate::flyway::write_migration;
+use crate::logging::target;
+use crate::paths;
+use crate::plsqldev_api::PlsqlDevApi;
+use crate::template::{substitute_tokens, TemplateValues};
+use crate::ui::{dialogs, MessageKind};
+
+const CALLBACK_SKELETONS_CAPTION: &str = "Flyway callback skeletons";
+
+// File names of the written and of the skipped (already existing) callbacks
+#[derive(Debug, Default, PartialEq)]
+pub struct SkeletonSummary {
+    pub created: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+pub fn generate_callback_skeletons(api: &dyn PlsqlDevApi, config: &Config) {
+    let folder_name = dialogs().get_save_folder_name();
+    if folder_name.is_empty() {
+        return;
+    }
+    let (owner, _) = api.ide_get_connection_info();
+    let values = TemplateValues::new("", &owner.to_uppercase(), "", Utc::now());
+
+    match write_callback_skeletons(
+        Path::new(&folder_name),
+        &config.callback_skeletons,
+        &values,
+        config,
+    ) {
+        Ok(summary) => {
+            let message = format!(
+                "Created in {}: {}\nSkipped (already existing): {}",
+                paths::display(config.repo_root.as_deref(), &folder_name),
+                list_or_none(&summary.created),
+                list_or_none(&summary.skipped)
+            );
+            dialogs().show_message(
+                &message,
+                CALLBACK_SKELETONS_CAPTION,
+                MessageKind::Information,
+            );
+        }
+        Err(e) => dialogs().show_message(
+            &format!("Could not write the callbacks: {}", e),
+            CALLBACK_SKELETONS_CAPTION,
+            MessageKind::Error,
+        ),
+    }
+}
+
+fn list_or_none(file_names: &[String]) -> String {
+    match file_names.is_empty() {
+        true => "none".to_string(),
+        false => file_names.join(", "),
+    }
+}
+
+// Write each (file name, body) skeleton into the folder, substituting the template tokens,
+// unless a file of that name already exists
+pub fn write_callback_skeletons(
+    folder: &Path,
+    skeletons: &[(String, String)],
+    values: &TemplateValues,
+    config: &Config,
+) -> std::io::Result<SkeletonSummary> {
+    let mut summary = SkeletonSummary::default();
+    for (file_name, body) in skeletons {
+        let path = folder.join(file_name);
+        if path.exists() {
+            debug!(target: target::FLYWAY, "Callback {:?} already exists", path);
+            summary.skipped.push(file_name.to_string());
+            continue;
+        }
+        write_migration(&path, &substitute_tokens(body, values), config)?;
+        summary.created.push(file_name.to_string());
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::callback_skeletons::*;
+
+    fn create_test_values() -> TemplateValues {
+        TemplateValues {
+            owner: "SCOTT".to_string(),
+            date: "2021-07-18".to_string(),
+            author: "jdoe".to_string(),
+            ..TemplateValues::default()
+        }
+    }
+
+    #[test]
+    fn default_skeletons_should_contain_the_usual_callbacks() {
+        let config = Config::default();
+        let names: Vec<&str> = config
+            .callback_skeletons
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(
+            vec![
+                "beforeMigrate.sql",
+                "afterMigrate__recompile.sql",
+                "afterMigrateError__notify.sql"
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn write_callback_skeletons_should_render_tokens_and_skip_existing_files() {
+        let folder = std::env::temp_dir().join(format!(
+            "xanthidae_test_callback_skeletons_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("afterMigrate__recompile.sql"), "-- mine\n").unwrap();
+        let config = Config::default();
+
+        let summary = write_callback_skeletons(
+            &folder,
+            &config.callback_skeletons,
+            &create_test_values(),
+            &config,
+        )
+        .unwrap();
+
+        assert_eq!(
+            SkeletonSummary {
+                created: vec![
+                    "beforeMigrate.sql".to_string(),
+                    "afterMigrateError__notify.sql".to_string()
+                ],
+                skipped: vec!["afterMigrate__recompile.sql".to_string()],
+            },
+            summary
+        );
+        assert_eq!(
+            "-- mine\n",
+            fs::read_to_string(folder.join("afterMigrate__recompile.sql")).unwrap()
+        );
+        let before_migrate = fs::read_to_string(folder.join("beforeMigrate.sql")).unwrap();
+        assert!(before_migrate.starts_with(
+            "-- Runs before each migration of SCOTT\n-- Created 2021-07-18 by jdoe\n"
+        ));
+
+        // running it again doesn't touch anything
+        let summary = write_callback_skeletons(
+            &folder,
+            &config.callback_skeletons,
+            &create_test_values(),
+            &config,
+        )
+        .unwrap();
+        assert!(summary.created.is_empty());
+        assert_eq!(3, summary.skipped.len());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
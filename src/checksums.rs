@@ -0,0 +1,224 @@
+// Verify repeatable migrations against the live objects, to detect drift between the
+// repository and the database.
+//
+// Checksums are CRC32 (as used by Flyway) over the content normalized as configured, so that
+// e.g. reformatted whitespace doesn't count as a difference.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::flyway::{
+    render_repeatable_migration, repeatable_file_name, NO_OBJECT_SELECTED_CAPTION,
+    NO_OBJECT_SELECTED_MESSAGE, SUPPORTED_OBJECT_TYPES,
+};
+use crate::logging::target;
+use crate::plsqldev_api::{PlsqlDevApi, SelectedObject};
+use crate::project_config::config_for_folder;
+use crate::ui::{dialogs, MessageKind};
+
+const VERIFY_CHECKSUMS_CAPTION: &str = "Verify checksums";
+
+#[derive(Debug, PartialEq)]
+pub struct ChecksumDifference {
+    pub file_name: String,
+    pub file_checksum: u32,
+    pub db_checksum: u32,
+}
+
+pub fn verify_checksums(api: &dyn PlsqlDevApi, config: &Config) {
+    let selected_objects = api.ide_selected_objects();
+    if selected_objects.is_empty() {
+        dialogs().show_message(
+            NO_OBJECT_SELECTED_MESSAGE,
+            NO_OBJECT_SELECTED_CAPTION,
+            MessageKind::Information,
+        );
+        return;
+    }
+
+    let folder_name = dialogs().get_save_folder_name();
+    if folder_name.is_empty() {
+        return;
+    }
+
+    // the conventions of the target repository apply, as they did to the export
+    let config = &config_for_folder(config, Path::new(&folder_name));
+    let collected = collect_migrations(api, &selected_objects, Path::new(&folder_name), config);
+    let differences = compare_checksums(&collected.migrations, config);
+    let kind = match differences.is_empty() && collected.missing.is_empty() {
+        true => MessageKind::Information,
+        false => MessageKind::Error,
+    };
+    dialogs().show_message(
+        &checksum_report(collected.migrations.len(), &differences, &collected.missing),
+        VERIFY_CHECKSUMS_CAPTION,
+        kind,
+    );
+}
+
+// The migrations to compare, and the files that couldn't be compared (with the reason)
+#[derive(Debug, Default)]
+struct CollectedMigrations {
+    // (file name, file content, content an export would write now)
+    migrations: Vec<(String, String, String)>,
+    missing: Vec<String>,
+}
+
+fn collect_migrations(
+    api: &dyn PlsqlDevApi,
+    selected_objects: &[SelectedObject],
+    folder: &Path,
+    config: &Config,
+) -> CollectedMigrations {
+    let mut collected = CollectedMigrations::default();
+    for selected_object in selected_objects {
+        if !SUPPORTED_OBJECT_TYPES.contains(&selected_object.object_type.as_str()) {
+            debug!(target: target::FLYWAY, "Skipping checksum of {}", selected_object);
+            continue;
+        }
+        let file_name = repeatable_file_name(selected_object, config);
+        let path: PathBuf = folder.join(&file_name);
+        let file_content = match fs::read(&path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(e) => {
+                debug!(target: target::FLYWAY, "Could not read {:?}: {}", path, e);
+                collected.missing.push(file_name);
+                continue;
+            }
+        };
+        // compared with what an export would write, not with the bare source
+        match render_repeatable_migration(api, selected_object, folder, config) {
+            Ok(db_content) => collected
+                .migrations
+                .push((file_name, file_content, db_content)),
+            Err(e) => {
+                debug!(target: target::FLYWAY, "Could not render {}: {}", selected_object, e);
+                collected.missing.push(format!("{} ({})", file_name, e));
+            }
+        }
+    }
+    collected
+}
+
+fn checksum_report(
+    compared: usize,
+    differences: &[ChecksumDifference],
+    missing: &[String],
+) -> String {
+    let mut report = format!(
+        "{} of {} migrations match the database.",
+        compared - differences.len(),
+        compared
+    );
+    for difference in differences {
+        report.push_str(&format!(
+            "\n{} differs (file {:08x}, database {:08x})",
+            difference.file_name, difference.file_checksum, difference.db_checksum
+        ));
+    }
+    if !missing.is_empty() {
+        report.push_str(&format!("\nMissing: {}", missing.join(", ")));
+    }
+    report
+}
+
+// Compare (file name, file content, database content) triples, returning the ones whose
+// normalized checksums differ, in the given order
+pub fn compare_checksums(
+    migrations: &[(String, String, String)],
+    config: &Config,
+) -> Vec<ChecksumDifference> {
+    migrations
+        .iter()
+        .filter_map(|(file_name, file_content, db_content)| {
+            let file_checksum = checksum(file_content, config);
+            let db_checksum = checksum(db_content, config);
+            match file_checksum == db_checksum {
+                true => None,
+                false => Some(ChecksumDifference {
+                    file_name: file_name.to_string(),
+                    file_checksum,
+                    db_checksum,
+                }),
+            }
+        })
+        .collect()
+}
+
+pub fn checksum(content: &str, config: &Config) -> u32 {
+    crc32fast::hash(normalize(content, config).as_bytes())
+}
+
+// Drop a byte order mark; optionally collapse all whitespace and ignore case
+fn normalize(content: &str, config: &Config) -> String {
+    let content = content.strip_prefix('\u{FEFF}').unwrap_or(content);
+    let content = match config.checksum_ignore_whitespace {
+        true => content.split_whitespace().collect::<Vec<_>>().join(" "),
+        false => content.to_string(),
+    };
+    match config.checksum_ignore_case {
+        true => content.to_lowercase(),
+        false => content,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checksums::*;
+
+    #[test]
+    fn compare_checksums_should_report_only_mismatching_objects() {
+        let migrations = vec![
+            (
+                "R__V_SNAFU.sql".to_string(),
+                "create or replace view V_SNAFU as\r\n  select 1 from dual;\r\n".to_string(),
+                "create or replace view V_SNAFU as\nselect 1 from dual;\n".to_string(),
+            ),
+            (
+                "R__F_SNAFU.sql".to_string(),
+                "create or replace function F_SNAFU return number is begin return 1; end;\n"
+                    .to_string(),
+                "create or replace function F_SNAFU return number is begin return 2; end;\n"
+                    .to_string(),
+            ),
+        ];
+        let config = Config::default();
+
+        let differences = compare_checksums(&migrations, &config);
+
+        assert_eq!(1, differences.len());
+        assert_eq!("R__F_SNAFU.sql", differences[0].file_name);
+        assert_ne!(differences[0].file_checksum, differences[0].db_checksum);
+        assert_eq!(
+            format!(
+                "1 of 2 migrations match the database.\n\
+                 R__F_SNAFU.sql differs (file {:08x}, database {:08x})\n\
+                 Missing: R__P_GONE.sql",
+                differences[0].file_checksum, differences[0].db_checksum
+            ),
+            checksum_report(2, &differences, &["R__P_GONE.sql".to_string()])
+        );
+    }
+
+    #[test]
+    fn checksum_should_honor_normalization_config() {
+        let mut config = Config {
+            checksum_ignore_whitespace: false,
+            ..Config::default()
+        };
+        assert_ne!(
+            checksum("select 1\nfrom dual;", &config),
+            checksum("select 1 from dual;", &config)
+        );
+        assert_ne!(
+            checksum("SELECT 1 FROM DUAL;", &config),
+            checksum("select 1 from dual;", &config)
+        );
+        config.checksum_ignore_whitespace = true;
+        config.checksum_ignore_case = true;
+        assert_eq!(
+            checksum("\u{FEFF}SELECT 1\n  FROM DUAL;\n", &config),
+            checksum("select 1 from dual;", &config)
+        );
+    }
+}
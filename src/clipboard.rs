@@ -1,49 +1,133 @@
 use scopeguard::defer;
 use std::io::Error;
 use std::ptr;
-use winapi::shared::minwindef::FALSE;
+
+use crate::ui::{cf_html, check_clipboard_size, check_html_clipboard_size, ClipboardError};
+use winapi::shared::minwindef::{FALSE, HGLOBAL, UINT};
 use winapi::um::winbase::{GlobalAlloc, GlobalFree, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
-use winapi::um::winuser::{CloseClipboard, OpenClipboard, SetClipboardData, CF_UNICODETEXT};
+use winapi::um::winuser::{
+    CloseClipboard, EmptyClipboard, GetClipboardData, OpenClipboard, RegisterClipboardFormatW,
+    SetClipboardData, CF_UNICODETEXT,
+};
 
 /// copy the given text to the Windows clipboard
 /// taken from https://stackoverflow.com/a/62003949/610979
 /// TODO: we should probably use the windows crate provided by Microsoft for this instead
-pub fn copy_to_clipboard(text: &str) -> Result<(), Error> {
-    // Needs to be UTF-16 encoded
-    let mut text_utf16: Vec<u16> = text.encode_utf16().collect();
-    // And zero-terminated before passing it into `SetClipboardData`
-    text_utf16.push(0);
-    // Allocate memory
-    let hglob =
-        unsafe { GlobalAlloc(GMEM_MOVEABLE, text_utf16.len() * std::mem::size_of::<u16>()) };
-    if hglob == ptr::null_mut() {
-        return Err(Error::last_os_error());
+pub fn copy_to_clipboard(text: &str, max_bytes: usize) -> Result<(), ClipboardError> {
+    // Some clipboard viewers hang on huge texts, and GlobalAlloc may fail for them anyway
+    check_clipboard_size(text, max_bytes)?;
+    set_clipboard_data(&[(CF_UNICODETEXT, unicode_text(text))])
+}
+
+/// copy the markup to the Windows clipboard as text, and as "HTML Format" (see `cf_html`), so
+/// e.g. word processors and mail clients paste a formatted table
+pub fn copy_html_to_clipboard(html: &str, max_bytes: usize) -> Result<(), ClipboardError> {
+    let cf_html = cf_html(html);
+    check_html_clipboard_size(html, &cf_html, max_bytes)?;
+    let format_name: Vec<u16> = "HTML Format".encode_utf16().chain(Some(0)).collect();
+    // registered once by the first application asking, the same id for everyone after that
+    let html_format = unsafe { RegisterClipboardFormatW(format_name.as_ptr()) };
+    if html_format == 0 {
+        return Err(Error::last_os_error().into());
     }
-    // Ensure cleanup on scope exit
-    defer!(unsafe { GlobalFree(hglob) };);
+    // CF_HTML is UTF-8, zero-terminated as well
+    let mut cf_html = cf_html.into_bytes();
+    cf_html.push(0);
+    set_clipboard_data(&[(CF_UNICODETEXT, unicode_text(html)), (html_format, cf_html)])
+}
+
+// The text as CF_UNICODETEXT: UTF-16 encoded and zero-terminated
+fn unicode_text(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .chain(Some(0))
+        .flat_map(u16::to_ne_bytes)
+        .collect()
+}
+
+// Memory allocated for the clipboard; freed when dropped, unless the clipboard took it over
+struct ClipboardMemory(HGLOBAL);
 
+impl Drop for ClipboardMemory {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { GlobalFree(self.0) };
+        }
+    }
+}
+
+fn clipboard_memory(data: &[u8]) -> Result<ClipboardMemory, ClipboardError> {
+    // Allocate memory
+    let hglob = unsafe { GlobalAlloc(GMEM_MOVEABLE, data.len()) };
+    if hglob.is_null() {
+        return Err(ClipboardError::AllocationFailed(
+            data.len(),
+            Error::last_os_error(),
+        ));
+    }
+    let memory = ClipboardMemory(hglob);
     // Retrieve writeable pointer to memory
     let dst = unsafe { GlobalLock(hglob) };
-    if dst == ptr::null_mut() {
-        return Err(Error::last_os_error());
+    if dst.is_null() {
+        return Err(Error::last_os_error().into());
     }
     // Copy data
-    unsafe { ptr::copy_nonoverlapping(text_utf16.as_ptr(), dst as _, text_utf16.len()) };
+    unsafe { ptr::copy_nonoverlapping(data.as_ptr(), dst as *mut u8, data.len()) };
     // Release writeable pointer
     unsafe { GlobalUnlock(hglob) };
+    Ok(memory)
+}
+
+// Replace the content of the clipboard with the data, given per clipboard format; emptied
+// first, so nothing copied before (e.g. HTML of another application) is pasted instead
+fn set_clipboard_data(data: &[(UINT, Vec<u8>)]) -> Result<(), ClipboardError> {
+    let mut blocks = vec![];
+    for (format, bytes) in data {
+        blocks.push((*format, clipboard_memory(bytes)?));
+    }
 
     // Everything is set up now, let's open the clipboard
     let success = unsafe { OpenClipboard(ptr::null_mut()) } != FALSE;
     if !success {
-        return Err(Error::last_os_error());
+        return Err(Error::last_os_error().into());
     }
     // Ensure cleanup on scope exit
     defer!(unsafe { CloseClipboard() };);
-    // And apply data
-    let success = unsafe { SetClipboardData(CF_UNICODETEXT, hglob) } != ptr::null_mut();
+    let success = unsafe { EmptyClipboard() } != FALSE;
     if !success {
-        return Err(Error::last_os_error());
+        return Err(Error::last_os_error().into());
+    }
+    // And apply data
+    for (format, memory) in &mut blocks {
+        let success = !unsafe { SetClipboardData(*format, memory.0) }.is_null();
+        if !success {
+            return Err(Error::last_os_error().into());
+        }
+        // the clipboard owns this memory now, it must not be freed
+        memory.0 = ptr::null_mut();
     }
 
     Ok(())
 }
+
+/// read the text currently on the Windows clipboard
+pub fn get_clipboard_text() -> Result<String, ClipboardError> {
+    let success = unsafe { OpenClipboard(ptr::null_mut()) } != FALSE;
+    if !success {
+        return Err(Error::last_os_error().into());
+    }
+    defer!(unsafe { CloseClipboard() };);
+    // The clipboard owns this memory, it must not be freed
+    let hglob = unsafe { GetClipboardData(CF_UNICODETEXT) };
+    if hglob.is_null() {
+        return Err(Error::last_os_error().into());
+    }
+    let src = unsafe { GlobalLock(hglob) } as *const u16;
+    if src.is_null() {
+        return Err(Error::last_os_error().into());
+    }
+    defer!(unsafe { GlobalUnlock(hglob) };);
+    // CF_UNICODETEXT is zero-terminated
+    let len = (0..).take_while(|&i| unsafe { *src.add(i) } != 0).count();
+    let text_utf16 = unsafe { std::slice::from_raw_parts(src, len) };
+    Ok(String::from_utf16_lossy(text_utf16))
+}
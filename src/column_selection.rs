@@ -0,0 +1,165 @@
+// Interactive selection of the columns to export.
+//
+// The user's choice is remembered per distinct list of headers for the rest of the session,
+// so re-running the same query and exporting it again doesn't ask a second time.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use crate::export::{ExportData, ExportError};
+use crate::logging::target;
+
+lazy_static! {
+    pub static ref COLUMN_SELECTIONS: ColumnSelections = ColumnSelections::new();
+}
+
+// Identifies a header list; the same query yields the same key
+pub fn header_set_key(headers: &[String]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    headers.hash(&mut hasher);
+    hasher.finish()
+}
+
+pub struct ColumnSelections {
+    selections: Mutex<HashMap<u64, Vec<bool>>>,
+}
+
+impl ColumnSelections {
+    pub fn new() -> ColumnSelections {
+        ColumnSelections {
+            selections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, headers: &[String]) -> Option<Vec<bool>> {
+        let selections = self.selections.lock().unwrap();
+        selections
+            .get(&header_set_key(headers))
+            // guard against hash collisions between header lists of different length
+            .filter(|selection| selection.len() == headers.len())
+            .cloned()
+    }
+
+    pub fn remember(&self, headers: &[String], selection: &[bool]) {
+        let mut selections = self.selections.lock().unwrap();
+        selections.insert(header_set_key(headers), selection.to_vec());
+    }
+}
+
+// Return the remembered selection for these headers, or ask the user (and remember the answer).
+// Returns None if the user cancelled.
+pub fn choose_columns<F>(cache: &ColumnSelections, headers: &[String], ask: F) -> Option<Vec<bool>>
+where
+    F: FnOnce(&[String]) -> Option<Vec<bool>>,
+{
+    if let Some(selection) = cache.get(headers) {
+        debug!(target: target::EXPORT, "Using remembered column selection {:?}", selection);
+        return Some(selection);
+    }
+    let selection = ask(headers)?;
+    // an empty selection is refused later on, so the user gets asked again next time
+    if selection.iter().any(|&selected| selected) {
+        cache.remember(headers, &selection);
+    }
+    Some(selection)
+}
+
+// Copy of the export data containing only the selected columns
+pub fn retain_columns(
+    export_data: &ExportData,
+    selection: &[bool],
+) -> Result<ExportData, ExportError> {
+    if !selection.iter().any(|&selected| selected) {
+        return Err(ExportError::NoColumnsSelected);
+    }
+    fn retain<T: Clone>(row: &[T], selection: &[bool]) -> Vec<T> {
+        row.iter()
+            .zip(selection.iter())
+            .filter(|(_, &selected)| selected)
+            .map(|(cell, _)| cell.clone())
+            .collect()
+    }
+    let mut result = ExportData::new();
+    result.headers = retain(&export_data.headers, selection);
+    result.data = export_data
+        .data
+        .iter()
+        .map(|row| retain(row, selection))
+        .collect();
+    result.prepared = export_data.prepared;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::column_selection::*;
+
+    #[test]
+    fn choose_columns_should_ask_only_once_per_header_set() {
+        let cache = ColumnSelections::new();
+        let headers = vec_of_strings!["ID", "NAME", "CITY"];
+        let asked = Cell::new(0);
+        let ask = |_: &[String]| {
+            asked.set(asked.get() + 1);
+            Some(vec![true, false, true])
+        };
+
+        assert_eq!(
+            Some(vec![true, false, true]),
+            choose_columns(&cache, &headers, ask)
+        );
+        assert_eq!(
+            Some(vec![true, false, true]),
+            choose_columns(&cache, &headers, ask)
+        );
+        assert_eq!(1, asked.get());
+
+        // a different query asks again
+        let other_headers = vec_of_strings!["ID", "NAME"];
+        choose_columns(&cache, &other_headers, |_| Some(vec![true, true]));
+        assert_eq!(Some(vec![true, true]), cache.get(&other_headers));
+        assert_eq!(Some(vec![true, false, true]), cache.get(&headers));
+    }
+
+    #[test]
+    fn choose_columns_should_not_remember_cancelled_or_empty_selection() {
+        let cache = ColumnSelections::new();
+        let headers = vec_of_strings!["ID", "NAME"];
+        assert_eq!(None, choose_columns(&cache, &headers, |_| None));
+        assert_eq!(None, cache.get(&headers));
+        choose_columns(&cache, &headers, |_| Some(vec![false, false]));
+        assert_eq!(None, cache.get(&headers));
+    }
+
+    #[test]
+    fn retain_columns_should_drop_unselected_columns() {
+        let mut export_data = ExportData::new();
+        export_data.headers = vec_of_strings!["ID", "NAME", "CITY"];
+        export_data.data = vec![
+            vec_of_cells!["1", "O'Brien", "Dublin"],
+            vec_of_cells!["2", "Meier", ""],
+        ];
+        export_data.prepared = true;
+
+        let retained = retain_columns(&export_data, &[true, false, true]).unwrap();
+        assert_eq!(vec_of_strings!["ID", "CITY"], retained.headers);
+        assert_eq!(
+            vec![vec_of_cells!["1", "Dublin"], vec_of_cells!["2", ""]],
+            retained.data
+        );
+        assert_eq!("||ID||CITY||\n|1|Dublin|\n|2| |\n", retained.to_string(" "));
+    }
+
+    #[test]
+    fn retain_columns_should_refuse_empty_selection() {
+        let mut export_data = ExportData::new();
+        export_data.headers = vec_of_strings!["ID"];
+        assert_eq!(
+            Err(ExportError::NoColumnsSelected),
+            retain_columns(&export_data, &[false]).map(|d| d.headers)
+        );
+    }
+}
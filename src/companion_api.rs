@@ -0,0 +1,271 @@
+// Exports for companion tools linking the plugin DLL, e.g. a CLI in a build pipeline.
+//
+// The ABI is stable and documented in include/xanthidae.h: NUL-terminated UTF-8 strings,
+// results in caller-provided buffers, the length of the complete result as return value and
+// negative error codes. The functions are thin wrappers over the core modules, and no panic
+// crosses the boundary.
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, UnwindSafe};
+use std::ptr;
+
+use chrono::Utc;
+
+use crate::flyway::{ensure_owner_in_ddl, get_versioned_filename_impl};
+use crate::prelude::config;
+
+// bumped on every incompatible change of the exports below
+pub const ABI_VERSION: c_int = 1;
+pub const ERROR_INVALID_ARGUMENT: c_int = -1;
+pub const ERROR_INTERNAL: c_int = -2;
+
+lazy_static! {
+    static ref VERSION: CString = CString::new(env!("CARGO_PKG_VERSION")).unwrap();
+}
+
+// The string behind the pointer, if it isn't null and is valid UTF-8
+unsafe fn input<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+    CStr::from_ptr(ptr).to_str().ok()
+}
+
+// Copy as much of the text as fits into the buffer, cut at a character boundary and
+// NUL-terminated; returns the length of the whole text
+unsafe fn write_result(text: &str, out_buf: *mut c_char, out_len: usize) -> c_int {
+    if out_buf.is_null() && out_len > 0 {
+        return ERROR_INVALID_ARGUMENT;
+    }
+    let length = match c_int::try_from(text.len()) {
+        Ok(length) => length,
+        Err(_) => return ERROR_INTERNAL,
+    };
+    if out_len > 0 {
+        let mut end = std::cmp::min(text.len(), out_len - 1);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        ptr::copy_nonoverlapping(text.as_ptr(), out_buf as *mut u8, end);
+        *out_buf.add(end) = 0;
+    }
+    length
+}
+
+// Run the export; a panic is reported as ERROR_INTERNAL instead of unwinding into the caller
+fn guarded<F: FnOnce() -> c_int + UnwindSafe>(f: F) -> c_int {
+    catch_unwind(f).unwrap_or(ERROR_INTERNAL)
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn XanthidaeAbiVersion() -> c_int {
+    ABI_VERSION
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn XanthidaeVersion() -> *const c_char {
+    VERSION.as_ptr()
+}
+
+// Safety: the string arguments are null or NUL-terminated, and out_buf is null or points to
+// at least out_len writable bytes
+#[allow(non_snake_case)]
+#[no_mangle]
+pub unsafe extern "C" fn XanthidaeRewriteDdl(
+    ddl: *const c_char,
+    object_type: *const c_char,
+    owner: *const c_char,
+    name: *const c_char,
+    out_buf: *mut c_char,
+    out_len: usize,
+) -> c_int {
+    guarded(|| unsafe {
+        match (input(ddl), input(object_type), input(owner), input(name)) {
+            (Some(ddl), Some(object_type), Some(owner), Some(name)) => {
+                let object_type = object_type.to_uppercase();
+                let rewritten = ensure_owner_in_ddl(ddl, &object_type, owner, name, &config());
+                write_result(&rewritten, out_buf, out_len)
+            }
+            _ => ERROR_INVALID_ARGUMENT,
+        }
+    })
+}
+
+// Safety: basename is null or NUL-terminated, and out_buf is null or points to at least
+// out_len writable bytes
+#[allow(non_snake_case)]
+#[no_mangle]
+pub unsafe extern "C" fn XanthidaeVersionedFilename(
+    basename: *const c_char,
+    millis_flag: c_int,
+    out_buf: *mut c_char,
+    out_len: usize,
+) -> c_int {
+    guarded(|| unsafe {
+        match input(basename) {
+            Some(basename) => {
+                let mut config = config();
+                config.use_millisecond_precision = millis_flag != 0;
+                let file_name = get_versioned_filename_impl(&config, Utc::now(), basename);
+                write_result(&file_name, out_buf, out_len)
+            }
+            None => ERROR_INVALID_ARGUMENT,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+    use std::os::raw::c_char;
+    use std::ptr;
+
+    use regex::Regex;
+
+    use crate::companion_api::*;
+    use crate::config::Config;
+    use crate::flyway::ensure_owner_in_ddl;
+
+    // Call with a buffer of the given length, filled with garbage; returns the result and the
+    // buffer content up to the NUL
+    fn call_with_buffer<F>(out_len: usize, f: F) -> (c_int, Vec<u8>)
+    where
+        F: FnOnce(*mut c_char, usize) -> c_int,
+    {
+        let mut buffer = vec![0x55u8; out_len + 1];
+        let result = f(buffer.as_mut_ptr() as *mut c_char, out_len);
+        if out_len == 0 {
+            assert_eq!(0x55, buffer[0], "a zero length buffer must not be written");
+            return (result, vec![]);
+        }
+        let nul = buffer[..out_len]
+            .iter()
+            .position(|&b| b == 0)
+            .expect("the buffer must be NUL-terminated");
+        assert_eq!(0x55, buffer[out_len], "written beyond the buffer");
+        (result, buffer[..nul].to_vec())
+    }
+
+    #[test]
+    fn write_result_should_truncate_at_character_boundaries_for_every_buffer_length() {
+        // one, two, three and four byte characters
+        let text = "aöЖ€😀z";
+        for out_len in 0..=text.len() + 2 {
+            let (result, written) =
+                call_with_buffer(out_len, |buf, len| unsafe { write_result(text, buf, len) });
+            assert_eq!(text.len() as c_int, result);
+            let written = String::from_utf8(written).expect("valid UTF-8 at every length");
+            assert!(text.starts_with(&written));
+            // as much as fits: the next character would not have fit
+            let fits = out_len.saturating_sub(1);
+            assert!(written.len() <= fits);
+            if written.len() < text.len() {
+                let next = text[written.len()..].chars().next().unwrap();
+                assert!(written.len() + next.len_utf8() > fits, "length {}", out_len);
+            }
+            assert_eq!(out_len > text.len(), written == text, "length {}", out_len);
+        }
+    }
+
+    #[test]
+    fn write_result_should_answer_length_queries_and_reject_null_buffers() {
+        unsafe {
+            assert_eq!(3, write_result("abc", ptr::null_mut(), 0));
+            assert_eq!(
+                ERROR_INVALID_ARGUMENT,
+                write_result("abc", ptr::null_mut(), 4)
+            );
+            assert_eq!(0, write_result("", ptr::null_mut(), 0));
+        }
+        let (result, written) =
+            call_with_buffer(1, |buf, len| unsafe { write_result("", buf, len) });
+        assert_eq!((0, vec![]), (result, written));
+    }
+
+    #[test]
+    fn version_exports_should_report_crate_and_abi_version() {
+        assert_eq!(1, XanthidaeAbiVersion());
+        let version = unsafe { CStr::from_ptr(XanthidaeVersion()) };
+        assert_eq!(env!("CARGO_PKG_VERSION"), version.to_str().unwrap());
+    }
+
+    #[test]
+    fn rewrite_ddl_should_wrap_ensure_owner_in_ddl() {
+        let ddl = CString::new("create or replace view v_ämter as select 1 x from dual").unwrap();
+        let object_type = CString::new("view").unwrap();
+        let owner = CString::new("APP").unwrap();
+        let name = CString::new("V_ÄMTER").unwrap();
+        let expected = ensure_owner_in_ddl(
+            ddl.to_str().unwrap(),
+            "VIEW",
+            "APP",
+            "V_ÄMTER",
+            &Config::default(),
+        );
+        let call = |buf: *mut c_char, len: usize| unsafe {
+            XanthidaeRewriteDdl(
+                ddl.as_ptr(),
+                object_type.as_ptr(),
+                owner.as_ptr(),
+                name.as_ptr(),
+                buf,
+                len,
+            )
+        };
+        let (result, written) = call_with_buffer(expected.len() + 1, call);
+        assert_eq!(expected.len() as c_int, result);
+        assert_eq!(expected.as_bytes(), &written[..]);
+        // too small: the length needed, and a valid prefix
+        let (result, written) = call_with_buffer(10, call);
+        assert_eq!(expected.len() as c_int, result);
+        assert!(expected.starts_with(std::str::from_utf8(&written).unwrap()));
+    }
+
+    #[test]
+    fn rewrite_ddl_should_reject_null_and_invalid_utf8_arguments() {
+        let valid = CString::new("VIEW").unwrap();
+        let invalid = CString::new(vec![0xC3u8, 0x28]).unwrap();
+        let mut buffer = [0 as c_char; 16];
+        let out = buffer.as_mut_ptr();
+        for (ddl, object_type) in [
+            (ptr::null(), valid.as_ptr()),
+            (valid.as_ptr(), ptr::null()),
+            (invalid.as_ptr(), valid.as_ptr()),
+        ] {
+            assert_eq!(ERROR_INVALID_ARGUMENT, unsafe {
+                XanthidaeRewriteDdl(ddl, object_type, valid.as_ptr(), valid.as_ptr(), out, 16)
+            });
+        }
+    }
+
+    #[test]
+    fn versioned_filename_should_honor_millis_flag() {
+        let basename = CString::new("add_column.sql").unwrap();
+        let pattern = |millis: &str| {
+            Regex::new(&format!(
+                r"^V\d{{4}}_\d{{2}}_\d{{2}}_\d{{2}}_\d{{2}}_\d{{2}}{}__add_column\.sql$",
+                millis
+            ))
+            .unwrap()
+        };
+        for (flag, millis) in [(0, ""), (1, r"\.\d{3}"), (-7, r"\.\d{3}")] {
+            let (result, written) = call_with_buffer(64, |buf, len| unsafe {
+                XanthidaeVersionedFilename(basename.as_ptr(), flag, buf, len)
+            });
+            let file_name = String::from_utf8(written).unwrap();
+            assert_eq!(file_name.len() as c_int, result);
+            assert!(pattern(millis).is_match(&file_name), "{}", file_name);
+        }
+        assert_eq!(ERROR_INVALID_ARGUMENT, unsafe {
+            XanthidaeVersionedFilename(ptr::null(), 0, ptr::null_mut(), 0)
+        });
+    }
+
+    #[test]
+    fn guarded_should_turn_panics_into_internal_errors() {
+        assert_eq!(ERROR_INTERNAL, guarded(|| panic!("boom")));
+        assert_eq!(42, guarded(|| 42));
+    }
+}
@@ -1,11 +1,544 @@
+use log::LevelFilter;
+
+// How line endings of exported files are written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    // keep the line endings as delivered by PL/SQL Developer,
+    // unless an .editorconfig in the export folder says otherwise
+    Keep,
+    Lf,
+    CrLf,
+}
+
+// How the object source delivered by PL/SQL Developer is decoded
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SourceEncoding {
+    // UTF-8, repairing Windows-1252 input and double encoded text
+    Auto,
+    // always UTF-8, as requested with CHARMODE=UTF8
+    Utf8,
+    // always Windows-1252, for IDE versions ignoring CHARMODE
+    Cp1252,
+}
+
+// What a versioned migration does when nothing is selected in the editor
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmptySelection {
+    // refuse to create an empty migration
+    Error,
+    // silently use the whole text of the window
+    WholeText,
+    // ask whether the whole text of the window should be used
+    Prompt,
+}
+
+// How a confirmation is answered: by asking the user, or with a predefined answer, e.g. when
+// migrations are created unattended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PromptPolicy {
+    Ask,
+    AlwaysYes,
+    AlwaysNo,
+}
+
+impl PromptPolicy {
+    // The predefined answer, or the user's answer if there is none
+    pub fn answer(self, ask: impl FnOnce() -> bool) -> bool {
+        match self {
+            PromptPolicy::Ask => ask(),
+            PromptPolicy::AlwaysYes => true,
+            PromptPolicy::AlwaysNo => false,
+        }
+    }
+}
+
+// Which parts of packages and types are exported
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PackageParts {
+    SpecOnly,
+    BodyOnly,
+    // the spec followed by the body
+    Both,
+}
+
+// How object types without an abbreviation appear in migration file names
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnmappedObjectType {
+    // R__<NAME>.sql
+    Omit,
+    // R__<TYPE>_<NAME>.sql
+    RawType,
+}
+
+// The unit of indentation written by the formatters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Indent {
+    // this many spaces per level
+    Spaces(usize),
+    Tab,
+}
+
+impl Indent {
+    // The whitespace of the given indentation level
+    pub fn repeat(&self, level: usize) -> String {
+        match self {
+            Indent::Spaces(n) => " ".repeat(n * level),
+            Indent::Tab => "\t".repeat(level),
+        }
+    }
+}
+
+// How the values of a masked column are exported
+#[derive(Clone, Debug, PartialEq)]
+pub enum MaskingStrategy {
+    // a pseudonym: the first 8 hex digits of the SHA-256 of the value with a random salt, which
+    // is kept for the IDE session, so equal values give equal tokens in all of its exports.
+    // Removing a column altogether is done with redact_columns.
+    Hash,
+    // the first n characters, followed by ***
+    KeepFirst(usize),
+}
+
+// The format used by the grid export
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    // Jira / Confluence wiki table
+    Wiki,
+    // GitHub-flavored Markdown table
+    Markdown,
+    // UPDATE statements, table name and key columns are asked for at export time
+    Update,
+    // SQL*Loader control file plus data file, the table name is asked for at export time
+    SqlLoader,
+    // fixed-width table in a fenced Markdown code block, for results too wide for a Markdown table
+    MarkdownCodeBlock,
+    // PostgreSQL COPY ... FROM stdin block in text format, the table name is asked for at
+    // export time
+    PostgresCopy,
+    // comma-separated values as of RFC 4180, with the configured delimiter
+    Csv,
+    // JSON array with an object per row, keyed by the column headers
+    Json,
+    // INSERT statements, the table name is asked for at export time
+    Insert,
+    // HTML table, e.g. for pasting into mails
+    Html,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 10] = [
+        ExportFormat::Wiki,
+        ExportFormat::Markdown,
+        ExportFormat::Update,
+        ExportFormat::SqlLoader,
+        ExportFormat::MarkdownCodeBlock,
+        ExportFormat::PostgresCopy,
+        ExportFormat::Csv,
+        ExportFormat::Json,
+        ExportFormat::Insert,
+        ExportFormat::Html,
+    ];
+}
+
+// Where the grid export goes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportTarget {
+    // the clipboard, or a file chosen by the user if it's too large for it
+    Clipboard,
+    // a new file in the temp folder, whose path is copied to the clipboard
+    TempFile,
+}
+
+// How the version of versioned migrations is determined
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Versioning {
+    // V<timestamp>__<name>.sql
+    Timestamp,
+    // V<n>__<name>.sql, where n is one higher than the highest version in the target folder
+    Sequential,
+}
+
+// Whose clock the timestamps of versioned migrations come from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimestampSource {
+    Workstation,
+    // SYSTIMESTAMP of the database the IDE is connected to, converted to UTC like the
+    // workstation's time
+    DatabaseSysdate,
+}
+
+// what PL/SQL Developer hands to exporters instead of the value of unfetched LOB / LONG columns
+pub const DEFAULT_LOB_PLACEHOLDERS: [&str; 4] = ["<LONG>", "<CLOB>", "<BLOB>", "<RAW>"];
+
+const DEFAULT_CALLBACK_SKELETONS: [(&str, &str); 3] = [
+    (
+        "beforeMigrate.sql",
+        "-- Runs before each migration of {owner}
+-- Created {date} by {author}
+begin
+  null;
+end;
+/
+",
+    ),
+    (
+        "afterMigrate__recompile.sql",
+        "-- Runs after each successful migration of {owner}
+-- Created {date} by {author}
+begin
+  dbms_utility.compile_schema(schema => user, compile_all => false);
+end;
+/
+",
+    ),
+    (
+        "afterMigrateError__notify.sql",
+        "-- Runs after a failed migration of {owner}
+-- Created {date} by {author}
+begin
+  dbms_output.put_line('Migration of {owner} failed');
+end;
+/
+",
+    ),
+];
+
+const DEFAULT_MAX_CLIPBOARD_BYTES: usize = 64 * 1024 * 1024;
+
+const DEFAULT_CALLBACK_FILE_NAME: &str = "afterEachMigrate__recompile.sql";
+const DEFAULT_CALLBACK_BODY: &str = "begin
+  dbms_utility.compile_schema(schema => user, compile_all => false);
+end;
+/
+";
+
+#[derive(Clone, Debug)]
 pub struct Config {
+    // switched from the menu; stored in the .xanthidae.toml of repo_root if there is one
     pub use_millisecond_precision: bool,
+    // inject the `force` keyword into `create or replace view` statements
+    pub force_views: bool,
+    // append `comment on` statements for the comments of exported views and their columns
+    pub include_view_comments: bool,
+    // inject the `force` keyword into `create or replace type` statements
+    pub force_types: bool,
+    // objects of these schemas (case-insensitive) are created without the owner, e.g. the
+    // schema the migrations are deployed into
+    pub unqualified_owners: Vec<String>,
+    // also write the object name of the closing `end <name>;` in the case of the selected name
+    pub normalize_end_label: bool,
+    // rewrite the leading whitespace of the object source with `indent`; note that this also
+    // touches string literals spanning lines
+    pub reindent_ddl: bool,
+    pub indent: Indent,
+    pub package_parts: PackageParts,
+    // start repeatable migrations with a comment recording the object's LAST_DDL_TIME
+    pub include_header_comment: bool,
+    pub empty_selection: EmptySelection,
+    // a "selection" identical to the whole window text and longer than this (in characters)
+    // is only used after confirmation, as some windows return everything when nothing is selected
+    pub whole_buffer_confirmation_threshold: usize,
+    pub whole_buffer_prompt: PromptPolicy,
+    // normalization applied before checksums of migrations and live objects are compared
+    pub checksum_ignore_whitespace: bool,
+    pub checksum_ignore_case: bool,
+    // names of the menu items in the order they are shown, e.g. ["versioned_migration"];
+    // items not listed follow in their default order
+    pub menu_order: Vec<String>,
+    // show the "Run self-test" menu item, for support requests
+    pub enable_self_test: bool,
+    // root folder of the migration repository; paths below it are displayed and stored
+    // relative to it, so they work regardless of where colleagues checked out the repository
+    pub repo_root: Option<String>,
+    // folder for temporary spill files; defaults to %TEMP%\xanthidae
+    pub spill_dir: Option<String>,
+    pub line_ending: LineEnding,
+    pub source_encoding: SourceEncoding,
+    // CHARMODE requested from the IDE at startup (e.g. UTF8 or ANSI);
+    // None skips the request, for IDE versions that choke on it
+    pub charmode: Option<String>,
+    pub export_format: ExportFormat,
+    // SQL*Loader exports always ask for their files
+    pub export_target: ExportTarget,
+    // language tag of the code block written by the MarkdownCodeBlock format, e.g. "text"
+    pub markdown_codeblock_language: Option<String>,
+    // cells of Wiki and MarkdownCodeBlock exports longer than this are wrapped onto several lines,
+    // so e.g. Jira doesn't need to scroll the table horizontally
+    pub wrap_width: Option<usize>,
+    // annotate the headers of Wiki and Markdown exports with the SQL data types of the columns,
+    // if the IDE provides them
+    pub export_column_types: bool,
+    // read written files back and compare them with what was written, so files removed or
+    // changed right away (e.g. quarantined by a virus scanner) make the export fail
+    pub verify_written_files: bool,
+    // migrations smaller than this (in bytes) are flagged in the export summary, as e.g. a
+    // truncated object source; 0 switches the check off
+    pub min_migration_bytes: usize,
+    // ask whether a flagged migration should be kept, deleting it otherwise
+    pub confirm_small_migrations: bool,
+    // end export summaries with a link to the Flyway documentation of the files created
+    pub show_help_links: bool,
+    // write a .gitattributes fixing the line endings of *.sql files into export folders without one
+    pub manage_gitattributes: bool,
+    // also write a Flyway callback file into the folder chosen for repeatable migrations
+    pub write_callback: bool,
+    pub callback_file_name: String,
+    pub callback_body: String,
+    // (file name, body) of the callbacks written by "Generate Flyway callback skeletons";
+    // the bodies support the same tokens as the header template
+    pub callback_skeletons: Vec<(String, String)>,
+    pub versioning: Versioning,
+    pub timestamp_source: TimestampSource,
+    // before a repeatable export, list the files it would write (and which of them exist already)
+    // and ask whether to go ahead
+    pub dry_run: bool,
+    // allow "Repeatable + versioned migration" for several objects with timestamp versions:
+    // each object gets V<timestamp>__<description>_<NAME>.sql, one second (or millisecond) apart,
+    // with the description asked for once
+    pub multi_object_versioned: bool,
+    // "Repeatable + versioned migration" of several objects writes a single versioned migration
+    // V<version>__<description>.sql with the sources of all of them (see combine_sources),
+    // with the description asked for once
+    pub combine_versioned: bool,
+    // blank lines between the objects of a combined migration
+    pub object_separator_blank_lines: u8,
+    // precede each object of a combined or multi-object versioned migration with
+    // `PROMPT Creating OWNER.NAME`, for SQL*Plus deploy logs
+    pub emit_prompts: bool,
+    // prefix of versioned migrations, Flyway's sqlMigrationPrefix
+    pub version_prefix: String,
+    // prefix of repeatable migrations, Flyway's repeatableSqlMigrationPrefix
+    pub repeatable_prefix: String,
+    // prefix of undo migrations, Flyway's undoSqlMigrationPrefix
+    pub undo_prefix: String,
+    // between the version and the description of versioned migrations, Flyway's sqlMigrationSeparator
+    pub version_description_separator: String,
+    // folders of versioned / repeatable migrations, relative ones below repo_root; migrations are
+    // written there without asking for a folder (versioned ones ask for the description only)
+    pub versioned_folder: Option<String>,
+    pub repeatable_folder: Option<String>,
+    // for every export, read the folders, prefixes and separator from the flyway.toml or
+    // flyway.conf in repo_root (or its conf folder); they take precedence over the settings
+    // above, while a .xanthidae.toml still overrides them
+    pub detect_flyway_config: bool,
+    // between the fields (year, month, ...) of timestamp versions
+    pub timestamp_field_separator: String,
+    // chrono format of timestamp versions instead of the fields joined by the separator, e.g.
+    // "%Y%m%d%H%M%S", or "%s" for the seconds since the epoch; millisecond precision appends
+    // "%.3f" to either. Invalid formats are ignored.
+    pub version_format: Option<String>,
+    // Flyway's schema history table, for the INSERTs recording migrations applied by hand
+    pub flyway_table: String,
+    // objects exported together are ordered by these weights (lowest first), so e.g. types
+    // get lower sequential versions than the packages using them; unlisted types come last
+    pub object_type_weights: Vec<(String, u32)>,
+    // put the weight of the object type after the R__ of repeatable migrations, zero-padded to
+    // the given number of digits (e.g. R__010_T_ADDRESS.sql), as Flyway applies them in
+    // alphabetical order; unlisted types get no prefix, so they come last. The prefix is widened
+    // to the digits of the largest weight, as a shorter one would sort in the wrong place
+    pub repeatable_order_prefix: bool,
+    pub repeatable_order_digits: usize,
+    // bulk exports stop (between objects) after this many seconds; None waits for the Cancel button
+    pub bulk_timeout_secs: Option<u64>,
+    // the IDE sometimes returns an empty object source over a flaky connection; ask again this
+    // many times, waiting the delay in between, before the source counts as not available
+    pub source_retries: u32,
+    pub source_retry_delay_ms: u64,
+    // write the object source as the IDE returns it, even if it's an Oracle error message
+    // (e.g. `ORA-04043: object ... does not exist`) rather than DDL
+    pub trust_source_verbatim: bool,
+    // abbreviations of object types put in front of the name in migration file names,
+    // e.g. (PROCEDURE, proc) gives R__proc_MY_PROC.sql
+    pub object_type_abbreviations: Vec<(String, String)>,
+    pub unmapped_object_type: UnmappedObjectType,
+    // split grid exports into one table per distinct value of this column
+    pub group_by: Option<String>,
+    // remove the group column from the per-group tables
+    pub group_by_drop_column: bool,
+    // exports larger than this (in bytes of UTF-16 text) are written to a file instead of the clipboard
+    pub max_clipboard_bytes: usize,
+    // decimal separator used for numeric columns in text exports (e.g. ',' for European spreadsheets)
+    pub decimal_separator: char,
+    // field delimiter of CSV exports, e.g. ';' for Excel with German regional settings
+    pub csv_delimiter: char,
+    // encloses CSV fields containing the delimiter, itself or a line break
+    pub csv_qualifier: char,
+    // written for NULL values in CSV exports, e.g. NULL or \N; empty by default
+    pub csv_null: String,
+    // JSON exports with a member per line; otherwise the whole array is a single line, e.g. for
+    // piping into scripts
+    pub json_pretty: bool,
+    // precede HTML exports with a <style> block giving the table borders and padding; otherwise
+    // the bare table takes the styles of wherever it's pasted
+    pub html_style: bool,
+    // written for NULL values in Wiki, Markdown and code block exports, e.g. NULL or \N; a
+    // single space by default, as an empty cell would break Wiki tables. The Markdown export of
+    // ExportFinishedMarkdown always leaves them empty, see ExportData::to_gfm_markdown
+    pub null_representation: String,
+    // replaces line breaks inside cells of the formats writing a row per line, e.g. " ", "\\n"
+    // or "<br>". Wiki tables can't hold line breaks, so they get a space if this isn't set;
+    // Markdown (which writes <br>) and CSV (which quotes them) keep them unless it is set
+    pub newline_replacement: Option<String>,
+    // let the user pick the exported columns in a dialog (remembered per query for the session)
+    pub ask_for_columns: bool,
+    // cells with these values are exported as NULL (with a TODO comment) by the SQL formats
+    pub lob_placeholders: Vec<String>,
+    // written before / after the content of each migration; supports the tokens {object_type},
+    // {owner}, {name}, {timestamp}, {date}, {author} and {version}
+    pub header_template: Option<String>,
+    pub footer_template: Option<String>,
+    // values of these columns (matched case-insensitively by header) are exported as ***
+    pub redact_columns: Vec<String>,
+    // (column name pattern, strategy) masking personal data before grid exports are formatted;
+    // patterns are case-insensitive and may contain * and ?
+    pub masking_rules: Vec<(String, MaskingStrategy)>,
+    // drop the first data row of a grid export if it repeats the headers (an IDE bug)
+    pub drop_repeated_headers: bool,
+    // after a panic during an export, keep the complete rows captured so far instead of
+    // discarding the export data
+    pub salvage_poisoned_export: bool,
+    // level of the plugin log, and of the targets differing from it (see logging::target), e.g.
+    // ("xanthidae::winapi", Warn) to leave out the Windows API details
+    pub log_level: LevelFilter,
+    pub log_levels: Vec<(String, LevelFilter)>,
+    // "Copy last session log to clipboard" keeps the start and the end of larger logs
+    pub max_log_copy_bytes: usize,
 }
 
 impl Config {
     pub fn new(use_millisecond_precision: bool) -> Config {
         Config {
             use_millisecond_precision,
+            force_views: true,
+            include_view_comments: false,
+            force_types: true,
+            unqualified_owners: vec![],
+            normalize_end_label: false,
+            reindent_ddl: false,
+            indent: Indent::Spaces(2),
+            include_header_comment: false,
+            empty_selection: EmptySelection::Error,
+            package_parts: PackageParts::Both,
+            whole_buffer_confirmation_threshold: 2000,
+            whole_buffer_prompt: PromptPolicy::Ask,
+            checksum_ignore_whitespace: true,
+            checksum_ignore_case: false,
+            menu_order: vec![],
+            enable_self_test: false,
+            repo_root: None,
+            spill_dir: None,
+            line_ending: LineEnding::Keep,
+            source_encoding: SourceEncoding::Auto,
+            charmode: Some("UTF8".to_string()),
+            export_format: ExportFormat::Wiki,
+            export_target: ExportTarget::Clipboard,
+            markdown_codeblock_language: None,
+            wrap_width: None,
+            export_column_types: false,
+            verify_written_files: true,
+            min_migration_bytes: 50,
+            show_help_links: true,
+            confirm_small_migrations: false,
+            manage_gitattributes: false,
+            write_callback: false,
+            callback_file_name: DEFAULT_CALLBACK_FILE_NAME.to_string(),
+            callback_body: DEFAULT_CALLBACK_BODY.to_string(),
+            callback_skeletons: DEFAULT_CALLBACK_SKELETONS
+                .iter()
+                .map(|(name, body)| (name.to_string(), body.to_string()))
+                .collect(),
+            versioning: Versioning::Timestamp,
+            timestamp_source: TimestampSource::Workstation,
+            dry_run: false,
+            multi_object_versioned: false,
+            combine_versioned: false,
+            object_separator_blank_lines: 1,
+            emit_prompts: false,
+            version_prefix: "V".to_string(),
+            repeatable_prefix: "R".to_string(),
+            undo_prefix: "U".to_string(),
+            version_description_separator: "__".to_string(),
+            versioned_folder: None,
+            repeatable_folder: None,
+            detect_flyway_config: false,
+            timestamp_field_separator: "_".to_string(),
+            version_format: None,
+            flyway_table: "flyway_schema_history".to_string(),
+            object_type_abbreviations: vec![],
+            unmapped_object_type: UnmappedObjectType::Omit,
+            object_type_weights: vec![
+                ("TYPE".to_string(), 10),
+                ("VIEW".to_string(), 20),
+                ("MATERIALIZED VIEW".to_string(), 25),
+                ("FUNCTION".to_string(), 30),
+                ("PROCEDURE".to_string(), 30),
+                ("PACKAGE".to_string(), 40),
+                ("TRIGGER".to_string(), 50),
+            ],
+            repeatable_order_prefix: false,
+            repeatable_order_digits: 3,
+            bulk_timeout_secs: None,
+            source_retries: 2,
+            source_retry_delay_ms: 500,
+            trust_source_verbatim: false,
+            group_by: None,
+            group_by_drop_column: true,
+            max_clipboard_bytes: DEFAULT_MAX_CLIPBOARD_BYTES,
+            decimal_separator: '.',
+            csv_delimiter: ',',
+            csv_qualifier: '"',
+            csv_null: String::new(),
+            json_pretty: true,
+            html_style: true,
+            null_representation: " ".to_string(),
+            newline_replacement: None,
+            ask_for_columns: false,
+            lob_placeholders: DEFAULT_LOB_PLACEHOLDERS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            header_template: None,
+            footer_template: None,
+            redact_columns: vec![],
+            masking_rules: vec![],
+            drop_repeated_headers: true,
+            salvage_poisoned_export: false,
+            log_level: LevelFilter::Debug,
+            log_levels: vec![],
+            max_log_copy_bytes: 256 * 1024,
+        }
+    }
+
+    pub fn object_type_weight(&self, object_type: &str) -> u32 {
+        self.object_type_weights
+            .iter()
+            .find(|(t, _)| t.eq_ignore_ascii_case(object_type))
+            .map_or(u32::MAX, |(_, weight)| *weight)
+    }
+
+    // The number of digits of the repeatable order prefix, enough for every listed weight
+    pub fn repeatable_order_width(&self) -> usize {
+        let widest = self
+            .object_type_weights
+            .iter()
+            .map(|(_, weight)| weight.to_string().len())
+            .max()
+            .unwrap_or(0);
+        self.repeatable_order_digits.max(widest)
+    }
+
+    // The word for the object type in migration file names, if any
+    pub fn object_type_prefix(&self, object_type: &str) -> Option<String> {
+        match self
+            .object_type_abbreviations
+            .iter()
+            .find(|(t, _)| t.eq_ignore_ascii_case(object_type))
+        {
+            Some((_, abbreviation)) => Some(abbreviation.to_string()),
+            None => match self.unmapped_object_type {
+                UnmappedObjectType::Omit => None,
+                UnmappedObjectType::RawType => Some(object_type.replace(' ', "_")),
+            },
         }
     }
 }
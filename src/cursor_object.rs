@@ -0,0 +1,270 @@
+// Export the object whose name is under the cursor in a SQL, test or command window.
+//
+// The word under the cursor is resolved to an object by probing the object source for the
+// candidate object types in turn; the first (and only) type with a source wins.
+use std::fmt::{Display, Formatter};
+
+use crate::config::Config;
+use crate::flyway::export_repeatable_migrations;
+use crate::logging::target;
+use crate::plsqldev_api::{PlsqlDevApi, SelectedObject};
+use crate::ui::{dialogs, MessageKind};
+
+const CURSOR_OBJECT_CAPTION: &str = "Export object under cursor";
+
+// the object types probed, in this order
+pub const CURSOR_OBJECT_TYPES: [&str; 5] = ["PACKAGE", "VIEW", "FUNCTION", "PROCEDURE", "TYPE"];
+
+#[derive(Debug, PartialEq)]
+pub enum ResolveError {
+    NoWordUnderCursor,
+    NotConnected,
+    NotFound(String),
+    // the name and the object types it was found as
+    Ambiguous(String, Vec<String>),
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            ResolveError::NoWordUnderCursor => {
+                "Please place the cursor on the name of an object first!".to_string()
+            }
+            ResolveError::NotConnected => {
+                "Not connected, the schema of the object can't be determined".to_string()
+            }
+            ResolveError::NotFound(name) => format!(
+                "No {} named {} was found",
+                CURSOR_OBJECT_TYPES.join(", ").to_lowercase(),
+                name
+            ),
+            ResolveError::Ambiguous(name, object_types) => format!(
+                "{} is ambiguous, it exists as {}",
+                name,
+                object_types.join(" and ")
+            ),
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+pub fn export_object_under_cursor(api: &dyn PlsqlDevApi, config: &Config) {
+    match resolve_cursor_word(api, &api.ide_get_cursor_word()) {
+        Ok(selected_object) => {
+            debug!(target: target::FLYWAY, "Object under cursor: {}", selected_object);
+            export_repeatable_migrations(api, config, false, vec![selected_object]);
+        }
+        Err(e) => {
+            let kind = match e {
+                ResolveError::NoWordUnderCursor => MessageKind::Information,
+                _ => MessageKind::Error,
+            };
+            dialogs().show_message(&format!("{}", e), CURSOR_OBJECT_CAPTION, kind);
+        }
+    }
+}
+
+// Resolve `name`, `owner.name` or `package.member` to an object; unqualified names are looked up
+// in the schema of the current connection.
+pub fn resolve_cursor_word(
+    api: &dyn PlsqlDevApi,
+    word: &str,
+) -> Result<SelectedObject, ResolveError> {
+    let parts: Vec<String> = word
+        .trim()
+        .split('.')
+        .map(normalize_identifier)
+        .filter(|part| !part.is_empty())
+        .collect();
+    if parts.is_empty() {
+        return Err(ResolveError::NoWordUnderCursor);
+    }
+    let current_schema = || {
+        let (username, _) = api.ide_get_connection_info();
+        match username.is_empty() {
+            true => Err(ResolveError::NotConnected),
+            false => Ok(username.to_uppercase()),
+        }
+    };
+
+    match parts.as_slice() {
+        [name] => probe(api, &current_schema()?, name),
+        [first, second, ..] => match probe(api, first, second) {
+            // not owner.name, maybe package.member in the current schema
+            Err(ResolveError::NotFound(_)) => {
+                probe(api, &current_schema()?, first).map_err(|e| match e {
+                    ResolveError::NotFound(_) => {
+                        ResolveError::NotFound(format!("{}.{}", first, second))
+                    }
+                    e => e,
+                })
+            }
+            result => result,
+        },
+        [] => unreachable!(),
+    }
+}
+
+// Unquoted identifiers are stored in upper case, quoted ones as they are
+pub fn normalize_identifier(identifier: &str) -> String {
+    let identifier = identifier.trim();
+    match identifier
+        .strip_prefix('"')
+        .and_then(|i| i.strip_suffix('"'))
+    {
+        Some(quoted) => quoted.to_string(),
+        None => identifier.to_uppercase(),
+    }
+}
+
+fn probe(api: &dyn PlsqlDevApi, owner: &str, name: &str) -> Result<SelectedObject, ResolveError> {
+    let found: Vec<&str> = CURSOR_OBJECT_TYPES
+        .iter()
+        .filter(|object_type| {
+            !api.ide_get_object_source(object_type, owner, name)
+                .trim()
+                .is_empty()
+        })
+        .copied()
+        .collect();
+    match found.as_slice() {
+        [] => Err(ResolveError::NotFound(format!("{}.{}", owner, name))),
+        [object_type] => Ok(SelectedObject::new(object_type, owner, name, "")),
+        _ => Err(ResolveError::Ambiguous(
+            format!("{}.{}", owner, name),
+            found.iter().map(|t| t.to_string()).collect(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use crate::cursor_object::*;
+    use crate::plsqldev_api::PlsqlDevApi;
+
+    // Answers object source requests from a script, and records them
+    struct ScriptedApi {
+        username: String,
+        objects: Vec<(&'static str, &'static str, &'static str)>,
+        probes: RefCell<Vec<String>>,
+    }
+
+    impl ScriptedApi {
+        fn new(
+            username: &str,
+            objects: Vec<(&'static str, &'static str, &'static str)>,
+        ) -> ScriptedApi {
+            ScriptedApi {
+                username: username.to_string(),
+                objects,
+                probes: RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl PlsqlDevApi for ScriptedApi {
+        fn ide_get_connection_info(&self) -> (String, String) {
+            (self.username.clone(), "XE".to_string())
+        }
+
+        fn ide_get_object_source(
+            &self,
+            object_type: &str,
+            object_owner: &str,
+            object_name: &str,
+        ) -> String {
+            self.probes
+                .borrow_mut()
+                .push(format!("{} {}.{}", object_type, object_owner, object_name));
+            match self
+                .objects
+                .contains(&(object_type, object_owner, object_name))
+            {
+                true => format!("create or replace {} {}", object_type, object_name),
+                false => "".to_string(),
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_cursor_word_should_probe_types_in_order_in_current_schema() {
+        let api = ScriptedApi::new("scott", vec![("VIEW", "SCOTT", "V_EMP")]);
+        let resolved = resolve_cursor_word(&api, "v_emp").unwrap();
+        assert_eq!("VIEW", resolved.object_type);
+        assert_eq!("SCOTT", resolved.object_owner);
+        assert_eq!("V_EMP", resolved.object_name);
+        assert_eq!(
+            vec![
+                "PACKAGE SCOTT.V_EMP",
+                "VIEW SCOTT.V_EMP",
+                "FUNCTION SCOTT.V_EMP",
+                "PROCEDURE SCOTT.V_EMP",
+                "TYPE SCOTT.V_EMP"
+            ],
+            *api.probes.borrow()
+        );
+    }
+
+    #[test]
+    fn resolve_cursor_word_should_try_owner_then_package_member() {
+        let api = ScriptedApi::new(
+            "SCOTT",
+            vec![
+                ("PACKAGE", "APP", "PKG_SNAFU"),
+                ("PACKAGE", "SCOTT", "PKG_UTIL"),
+            ],
+        );
+        let resolved = resolve_cursor_word(&api, "App.pkg_snafu").unwrap();
+        assert_eq!(
+            ("PACKAGE", "APP"),
+            (
+                resolved.object_type.as_str(),
+                resolved.object_owner.as_str()
+            )
+        );
+
+        let resolved = resolve_cursor_word(&api, "pkg_util.do_it").unwrap();
+        assert_eq!("SCOTT", resolved.object_owner);
+        assert_eq!("PKG_UTIL", resolved.object_name);
+    }
+
+    #[test]
+    fn resolve_cursor_word_should_keep_case_of_quoted_names() {
+        let api = ScriptedApi::new("SCOTT", vec![("TYPE", "SCOTT", "MixedCase")]);
+        let resolved = resolve_cursor_word(&api, "\"MixedCase\"").unwrap();
+        assert_eq!("MixedCase", resolved.object_name);
+    }
+
+    #[test]
+    fn resolve_cursor_word_should_report_not_found_and_ambiguity() {
+        let api = ScriptedApi::new(
+            "SCOTT",
+            vec![("FUNCTION", "SCOTT", "F_X"), ("PROCEDURE", "SCOTT", "F_X")],
+        );
+        assert_eq!(
+            Err(ResolveError::NotFound("SCOTT.NOPE".to_string())),
+            resolve_cursor_word(&api, "nope").map(|o| o.object_name)
+        );
+        assert_eq!(
+            Err(ResolveError::NotFound("NOPE.NADA".to_string())),
+            resolve_cursor_word(&api, "nope.nada").map(|o| o.object_name)
+        );
+        assert_eq!(
+            Err(ResolveError::Ambiguous(
+                "SCOTT.F_X".to_string(),
+                vec!["FUNCTION".to_string(), "PROCEDURE".to_string()]
+            )),
+            resolve_cursor_word(&api, "f_x").map(|o| o.object_name)
+        );
+        assert_eq!(
+            Err(ResolveError::NoWordUnderCursor),
+            resolve_cursor_word(&api, "  ").map(|o| o.object_name)
+        );
+        assert_eq!(
+            Err(ResolveError::NotConnected),
+            resolve_cursor_word(&ScriptedApi::new("", vec![]), "f_x").map(|o| o.object_name)
+        );
+    }
+}
@@ -0,0 +1,261 @@
+// Format the DBMS_OUTPUT of a Test Window as a table, e.g. the pipe-delimited debug tables
+// printed by test scripts, through the same formats and destinations as a result grid export.
+//
+// None of the IDE's callbacks reaches the pane, so its text is taken from the clipboard: the pane
+// is copied (Ctrl+A, Ctrl+C) before choosing the menu item.
+use crate::config::Config;
+use crate::export::{export_as_configured, ExportData};
+use crate::ui::{clipboard, dialogs, MessageKind};
+
+const OUTPUT_CAPTION: &str = "Format DBMS_OUTPUT as table";
+const DEFAULT_DELIMITER: char = '|';
+// the delimiters recognised in the output, the most likely first
+const DELIMITERS: [char; 4] = ['|', '\t', ';', ','];
+// encloses fields containing the delimiter, doubled inside them
+const QUOTE: char = '"';
+
+pub fn format_dbms_output_as_table(config: &Config) {
+    let text = clipboard().get_clipboard_text().unwrap_or_default();
+    if text.trim().is_empty() {
+        dialogs().show_message(
+            "There is no output to format.\nPlease copy the DBMS_OUTPUT pane (Ctrl+A, Ctrl+C) first.",
+            OUTPUT_CAPTION,
+            MessageKind::Information,
+        );
+        return;
+    }
+    let delimiter = detect_delimiter(&text);
+    let question = format!(
+        "Format the {} lines copied to the clipboard as a table, with the columns separated by {}?",
+        text.lines().filter(|line| !line.trim().is_empty()).count(),
+        match delimiter {
+            '\t' => "tabs".to_string(),
+            other => format!("'{}'", other),
+        }
+    );
+    if !dialogs().ask_yes_no(&question, OUTPUT_CAPTION) {
+        return;
+    }
+    let first_line_headers = dialogs().ask_yes_no(
+        "Does the first line hold the column headers?",
+        OUTPUT_CAPTION,
+    );
+    let export_data = parse_output(&text, delimiter, first_line_headers);
+    if export_data.data.is_empty() {
+        dialogs().show_message(
+            "The output has no rows to format.",
+            OUTPUT_CAPTION,
+            MessageKind::Information,
+        );
+        return;
+    }
+    export_as_configured(&export_data, &[], config);
+}
+
+// The delimiter found on the most lines, in the order of DELIMITERS on a tie; a pipe if none is
+fn detect_delimiter(text: &str) -> char {
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    DELIMITERS
+        .iter()
+        .rev()
+        .map(|&delimiter| {
+            let count = lines.iter().filter(|line| line.contains(delimiter)).count();
+            (delimiter, count)
+        })
+        .filter(|&(_, count)| count > 0)
+        // the last of equal maximums, so the first in DELIMITERS
+        .max_by_key(|&(_, count)| count)
+        .map_or(DEFAULT_DELIMITER, |(delimiter, _)| delimiter)
+}
+
+// The rows of the text, one per non-blank line; empty fields are NULL, rows shorter than the
+// longest one are padded with NULLs, and headers not given are named COLUMN_1, COLUMN_2, ...
+pub fn parse_output(text: &str, delimiter: char, first_line_headers: bool) -> ExportData {
+    let mut rows: Vec<Vec<String>> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !is_rule(line, delimiter))
+        .map(|line| split_line(line, delimiter))
+        .collect();
+    let mut headers = match first_line_headers && !rows.is_empty() {
+        true => rows.remove(0),
+        false => vec![],
+    };
+    let width = rows.iter().map(Vec::len).chain([headers.len()]).max();
+    let width = width.unwrap_or_default();
+    for row in rows.iter_mut() {
+        row.resize(width, String::new());
+    }
+    for i in headers.len()..width {
+        headers.push(format!("COLUMN_{}", i + 1));
+    }
+    let mut export_data = ExportData::new();
+    export_data.headers = headers;
+    export_data.data = rows
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|field| Some(field).filter(|field| !field.is_empty()))
+                .collect()
+        })
+        .collect();
+    export_data
+}
+
+// A line separating the headers from the rows, e.g. |---|---| or +----+----+: every field is
+// a line of at least three dashes (or equals signs), so a row of single dashes is data
+fn is_rule(line: &str, delimiter: char) -> bool {
+    let line = line.trim();
+    let line = line.strip_prefix([delimiter, '+']).unwrap_or(line);
+    let line = line.strip_suffix([delimiter, '+']).unwrap_or(line);
+    line.split([delimiter, '+']).all(|field| {
+        let field = field.trim().trim_matches(':');
+        field.len() >= 3 && field.chars().all(|c| matches!(c, '-' | '='))
+    })
+}
+
+// The fields of a line, trimmed; a field enclosed in double quotes may contain the delimiter,
+// and "" inside it stands for a quote. A delimiter at the start of the line (| a | b |) encloses
+// the row, so the one at the end doesn't start another field.
+fn split_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut line = line.trim();
+    if let Some(rest) = line.strip_prefix(delimiter) {
+        line = rest.strip_suffix(delimiter).unwrap_or(rest);
+    }
+    let mut fields = vec![];
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars
+            .next_if(|c| *c != delimiter && c.is_whitespace())
+            .is_some()
+        {}
+        let mut field = String::new();
+        if chars.next_if_eq(&QUOTE).is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    QUOTE if chars.next_if_eq(&QUOTE).is_some() => field.push(QUOTE),
+                    QUOTE => break,
+                    c => field.push(c),
+                }
+            }
+        }
+        // up to the delimiter; after a quoted part, anything before the delimiter is kept too
+        let mut rest = String::new();
+        let mut ended_by_delimiter = false;
+        for c in chars.by_ref() {
+            if c == delimiter {
+                ended_by_delimiter = true;
+                break;
+            }
+            rest.push(c);
+        }
+        field.push_str(rest.trim());
+        fields.push(field);
+        if !ended_by_delimiter {
+            return fields;
+        }
+        if chars.peek().is_none() {
+            fields.push(String::new());
+            return fields;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::dbms_output::*;
+
+    fn cells(export_data: &ExportData) -> Vec<Vec<Option<&str>>> {
+        export_data
+            .data
+            .iter()
+            .map(|row| row.iter().map(Option::as_deref).collect())
+            .collect()
+    }
+
+    #[test]
+    fn parse_output_should_pad_ragged_rows() {
+        let text = indoc! {"
+            | ID | NAME  | CITY |
+            |----|-------|------|
+            | 1  | Alice | Cork |
+
+            | 2  | Bob   |
+        "};
+
+        let export_data = parse_output(text, '|', true);
+
+        assert_eq!(vec!["ID", "NAME", "CITY"], export_data.headers);
+        assert_eq!(
+            vec![
+                vec![Some("1"), Some("Alice"), Some("Cork")],
+                vec![Some("2"), Some("Bob"), None]
+            ],
+            cells(&export_data)
+        );
+    }
+
+    #[test]
+    fn parse_output_should_keep_rows_of_single_dashes() {
+        let text = indoc! {"
+            +-----+------+
+            | ID  | NAME |
+            +=====+======+
+            | 1   | -    |
+            | -   | -    |
+            |:---:|-----:|
+        "};
+
+        let export_data = parse_output(text, '|', true);
+
+        assert_eq!(vec!["ID", "NAME"], export_data.headers);
+        assert_eq!(
+            vec![vec![Some("1"), Some("-")], vec![Some("-"), Some("-")]],
+            cells(&export_data)
+        );
+    }
+
+    #[test]
+    fn parse_output_should_name_missing_headers() {
+        let export_data = parse_output("a;b\nc;d;e\n", ';', false);
+        assert_eq!(
+            vec!["COLUMN_1", "COLUMN_2", "COLUMN_3"],
+            export_data.headers
+        );
+        assert_eq!(
+            vec![
+                vec![Some("a"), Some("b"), None],
+                vec![Some("c"), Some("d"), Some("e")]
+            ],
+            cells(&export_data)
+        );
+
+        let export_data = parse_output("ID\n1|x\n", '|', true);
+        assert_eq!(vec!["ID", "COLUMN_2"], export_data.headers);
+        assert!(parse_output("  \n", '|', true).data.is_empty());
+    }
+
+    #[test]
+    fn split_line_should_keep_quoted_delimiters() {
+        assert_eq!(
+            vec!["1", "a | b", "say \"hi\"", ""],
+            split_line(r#"1 | "a | b" | "say ""hi""" |"#, '|')
+        );
+        assert_eq!(vec!["x", "", "y", ""], split_line("x,,y,", ','));
+        assert_eq!(vec!["a", "", "b"], split_line("| a || b |", '|'));
+        assert_eq!(vec!["a\tb"], split_line("\"a\tb\"", '\t'));
+    }
+
+    #[test]
+    fn detect_delimiter_should_default_to_pipe() {
+        assert_eq!('|', detect_delimiter("ID\n1\n"));
+        assert_eq!('|', detect_delimiter("| ID | A;B |\n| 1  | C;D |\n"));
+        assert_eq!(';', detect_delimiter("ID;NAME\n1;Doe, John\n2;Roe\n"));
+        assert_eq!('\t', detect_delimiter("ID\tNAME\n1\tDoe\n"));
+        assert_eq!(',', detect_delimiter("ID,NAME\n1,Doe\n"));
+    }
+}
@@ -0,0 +1,1005 @@
+// The steps turning the DDL read from the IDE into the content of a migration.
+//
+// Each step is a `DdlTransform`; `ddl_transforms` composes the ones for an object type as
+// configured, and `migration_transforms` the ones turning the result into a migration. A new
+// option should become a new transform, rather than yet another special case in the regex of
+// the OwnerQualifier.
+use regex::{Captures, Regex, RegexBuilder};
+
+use crate::config::{Config, Indent};
+use crate::plsqldev_api::{PlsqlDevApi, SelectedObject};
+use crate::template::{apply_templates, TemplateValues};
+
+// The object the transformed DDL belongs to; the type is that of the part, e.g. PACKAGE BODY
+pub struct DdlContext<'a> {
+    pub object_type: &'a str,
+    pub object_owner: &'a str,
+    pub object_name: &'a str,
+}
+
+impl DdlContext<'_> {
+    pub fn of(selected_object: &SelectedObject) -> DdlContext<'_> {
+        DdlContext {
+            object_type: &selected_object.object_type,
+            object_owner: &selected_object.object_owner,
+            object_name: &selected_object.object_name,
+        }
+    }
+}
+
+pub trait DdlTransform {
+    fn apply(&self, ddl: &str, context: &DdlContext) -> String;
+}
+
+// Qualify the object name with the owner in `create or replace ...`, writing the keywords in
+// lowercase. A `force` keyword is dropped, see ForceKeywordPolicy, and an edition keyword is
+// left to the EditionKeywordPolicy.
+pub struct OwnerQualifier;
+
+impl DdlTransform for OwnerQualifier {
+    fn apply(&self, ddl: &str, context: &DdlContext) -> String {
+        lazy_static! {
+            static ref DDL: Regex = RegexBuilder::new(r#"create or replace (editionable|noneditionable)?\s*(editioning\s+)?(package|type|view|trigger|function|procedure)\s*(body )?([a-z0-9_$"]+\.)?[a-z0-9_$"]+\s*(\([a-z0-9._$", ]+\))?\s*(force )?(is|as)?(.*)"#)
+                                .case_insensitive(true)
+                                .build()
+                                .unwrap();
+        }
+
+        // It's necessary to replace $ with $$ as it's used by the Regex crate for capture group references
+        // Update 2021-04-02: Seems no longer necessary for whatever reasons, maybe because of the lambda
+        DDL.replace(ddl, |caps: &Captures| {
+            format!("create or replace {editionable}{editioning}{object_type} {body}{object_owner}.{object_name}{parameter_list} {is_or_as}{rest_of_line}",
+                    editionable = caps.get(1).map_or(String::new(), |m| format!("{} ", m.as_str())),
+                    editioning = match caps.get(2) {
+                        Some(_) => "editioning ",
+                        None => ""
+                    },
+                    object_type = (caps.get(3).map_or("", |m| m.as_str())).to_lowercase(),
+                    body = (caps.get(4).map_or("", |m| m.as_str())).to_lowercase(),
+                    object_owner = context.object_owner,
+                    object_name = context.object_name,
+                    parameter_list = caps.get(6).map_or("", |m| m.as_str()),
+                    is_or_as = match context.object_type {
+                        "TRIGGER" => "\n".to_string(),
+                        _ => (caps.get(8).map_or("", |m| m.as_str())).to_lowercase()
+                    }, // insert a line break for triggers
+                    rest_of_line = caps.get(9).map_or("", |m| m.as_str())
+            )
+        })
+        .into_owned()
+    }
+}
+
+// Qualify the table name with the owner in every statement on the table at the start of a line:
+// `create table` (which has no `or replace`), and the `alter table`, `create index`,
+// `comment on` and `grant` statements following it, so running the DDL with another default
+// schema doesn't split the table from its constraints. Indexes get the owner as well, and the
+// keywords are written in lowercase.
+pub struct TableOwnerQualifier;
+
+impl DdlTransform for TableOwnerQualifier {
+    fn apply(&self, ddl: &str, context: &DdlContext) -> String {
+        lazy_static! {
+            static ref TABLE: Regex = RegexBuilder::new(
+                r#"(?m)^(\s*)create\s+((?:global|private)\s+temporary\s+)?table\s+(?:[a-z0-9_$#"]+\s*\.\s*)?[a-z0-9_$#"]+"#
+            )
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+            // the statements naming the table right after their keywords
+            static ref STATEMENT: Regex = RegexBuilder::new(
+                r#"(?m)^(\s*)(alter\s+table|comment\s+on\s+table|comment\s+on\s+column|grant\s+([^;]+?)\s+on)\s+(?:[a-z0-9_$#"]+\s*\.\s*)?([a-z0-9_$#"]+)([\s.;]|$)"#
+            )
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+            static ref INDEX: Regex = RegexBuilder::new(
+                r#"(?m)^(\s*)create\s+((?:unique|bitmap)\s+)?index\s+(?:[a-z0-9_$#"]+\s*\.\s*)?([a-z0-9_$#"]+)\s+on\s+(?:[a-z0-9_$#"]+\s*\.\s*)?([a-z0-9_$#"]+)"#
+            )
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        }
+        let keyword = |m: &str| {
+            let words: Vec<&str> = m.split_whitespace().collect();
+            words.join(" ").to_lowercase()
+        };
+        let qualified = format!("{}.{}", context.object_owner, context.object_name);
+        let ddl = TABLE.replace(ddl, |caps: &Captures| {
+            let temporary = caps
+                .get(2)
+                .map_or(String::new(), |m| format!("{} ", keyword(m.as_str())));
+            format!("{}create {}table {}", &caps[1], temporary, qualified)
+        });
+        let ddl = STATEMENT.replace_all(&ddl, |caps: &Captures| {
+            if !is_object_name(&caps[4], context.object_name) {
+                return caps[0].to_string();
+            }
+            let statement = match caps.get(3) {
+                Some(privileges) => format!("grant {} on", privileges.as_str()),
+                None => keyword(&caps[2]),
+            };
+            format!("{}{} {}{}", &caps[1], statement, qualified, &caps[5])
+        });
+        INDEX
+            .replace_all(&ddl, |caps: &Captures| {
+                if !is_object_name(&caps[4], context.object_name) {
+                    return caps[0].to_string();
+                }
+                let kind = caps
+                    .get(2)
+                    .map_or(String::new(), |m| format!("{} ", keyword(m.as_str())));
+                format!(
+                    "{}create {}index {}.{} on {}",
+                    &caps[1], kind, context.object_owner, &caps[3], qualified
+                )
+            })
+            .into_owned()
+    }
+}
+
+// Whether the name as written in DDL (quoted or not) is that of the object
+fn is_object_name(name: &str, object_name: &str) -> bool {
+    match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(quoted) => quoted == object_name,
+        None => name.eq_ignore_ascii_case(object_name),
+    }
+}
+
+// Qualify the name of a materialized view with the owner in `create materialized view ...`,
+// which has no `or replace` either. The clauses between the name and `as` (column aliases,
+// storage, `build immediate`, `refresh fast on commit`, ...) are kept as they are.
+pub struct MaterializedViewOwnerQualifier;
+
+impl DdlTransform for MaterializedViewOwnerQualifier {
+    fn apply(&self, ddl: &str, context: &DdlContext) -> String {
+        lazy_static! {
+            static ref MATERIALIZED_VIEW: Regex = RegexBuilder::new(
+                r#"(?m)^(\s*)create\s+materialized\s+view\s+(?:[a-z0-9_$#"]+\s*\.\s*)?[a-z0-9_$#"]+"#
+            )
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        }
+        MATERIALIZED_VIEW
+            .replace(ddl, |caps: &Captures| {
+                format!(
+                    "{}create materialized view {}.{}",
+                    &caps[1], context.object_owner, context.object_name
+                )
+            })
+            .into_owned()
+    }
+}
+
+// Write the `editionable` / `noneditionable` keyword in lowercase, like the other keywords
+// (works on the DDL written by the OwnerQualifier)
+pub struct EditionKeywordPolicy;
+
+impl DdlTransform for EditionKeywordPolicy {
+    fn apply(&self, ddl: &str, _context: &DdlContext) -> String {
+        lazy_static! {
+            static ref EDITION_KEYWORD: Regex =
+                RegexBuilder::new(r"create or replace (editionable|noneditionable) ")
+                    .case_insensitive(true)
+                    .build()
+                    .unwrap();
+        }
+        EDITION_KEYWORD
+            .replace(ddl, |caps: &Captures| {
+                format!("create or replace {} ", caps[1].to_lowercase())
+            })
+            .into_owned()
+    }
+}
+
+// Enforce the creation of views and types with invalid dependencies by the `force` keyword
+// (works on the DDL written by the OwnerQualifier)
+pub struct ForceKeywordPolicy {
+    pub force_views: bool,
+    pub force_types: bool,
+}
+
+impl DdlTransform for ForceKeywordPolicy {
+    fn apply(&self, ddl: &str, context: &DdlContext) -> String {
+        lazy_static! {
+            static ref VIEW: Regex = Regex::new(
+                r"create or replace ((?:editionable |noneditionable )?)((?:editioning )?view )"
+            )
+            .unwrap();
+            static ref TYPE: Regex = Regex::new(
+                r"create or replace (?:editionable |noneditionable )?type (\S+?)(?:\([^)]*\))? "
+            )
+            .unwrap();
+        }
+        match context.object_type {
+            "VIEW" if self.force_views => VIEW
+                .replace(ddl, "create or replace ${1}force ${2}")
+                .into_owned(),
+            // create or replace type OWNER.NAME force as ...
+            "TYPE" if self.force_types => TYPE
+                .replace_all(ddl, |caps: &Captures| {
+                    let name = format!("{}.{}", context.object_owner, context.object_name);
+                    match caps[1] == name {
+                        true => format!("{}force ", &caps[0]),
+                        false => caps[0].to_string(),
+                    }
+                })
+                .into_owned(),
+            _ => ddl.to_string(),
+        }
+    }
+}
+
+// Leave the object name unqualified if the object belongs to one of the schemas migrations are
+// deployed into, so the migration can be deployed into another schema, too
+// (works on the DDL written by the OwnerQualifier and the ForceKeywordPolicy)
+pub struct OwnerPrefixPolicy {
+    pub unqualified_owners: Vec<String>,
+}
+
+impl DdlTransform for OwnerPrefixPolicy {
+    fn apply(&self, ddl: &str, context: &DdlContext) -> String {
+        let unqualified = self
+            .unqualified_owners
+            .iter()
+            .any(|owner| owner.trim().eq_ignore_ascii_case(context.object_owner));
+        if !unqualified {
+            return ddl.to_string();
+        }
+        lazy_static! {
+            // names may end in $ or #, so there's no word boundary after them; what follows the
+            // name is captured and written again instead (a column name, in `comment on column`)
+            static ref HEADER: Regex = Regex::new(
+                r#"(create or replace (?:editionable |noneditionable )?(?:force )?(?:editioning )?(?:package|type|view|trigger|function|procedure) (?:body )?|create (?:(?:global|private) temporary )?table |create materialized view |comment on (?:table|column|materialized view) |alter table |grant [^;]+? on )([A-Za-z0-9_$#"]+)\.([A-Za-z0-9_$#"]+)([\s(.]|$)"#
+            )
+            .unwrap();
+            // an index on a table, see TableOwnerQualifier
+            static ref INDEX: Regex = Regex::new(
+                r#"(create (?:unique |bitmap )?index )([A-Za-z0-9_$#"]+)\.([A-Za-z0-9_$#"]+ on )([A-Za-z0-9_$#"]+)\.([A-Za-z0-9_$#"]+)([\s(]|$)"#
+            )
+            .unwrap();
+        }
+        let ddl = HEADER.replace_all(ddl, |caps: &Captures| {
+            match &caps[2] == context.object_owner && &caps[3] == context.object_name {
+                true => format!("{}{}{}", &caps[1], context.object_name, &caps[4]),
+                false => caps[0].to_string(),
+            }
+        });
+        INDEX
+            .replace_all(&ddl, |caps: &Captures| {
+                let owner = context.object_owner;
+                match &caps[2] == owner && &caps[4] == owner && &caps[5] == context.object_name {
+                    true => format!("{}{}{}{}", &caps[1], &caps[3], &caps[5], &caps[6]),
+                    false => caps[0].to_string(),
+                }
+            })
+            .into_owned()
+    }
+}
+
+// Write `end <name>;` with the object name as given, e.g. `end pkg_snafu;` -> `end PKG_SNAFU;`.
+// Labels of other blocks (e.g. procedures inside a package body) and quoted names are kept.
+pub struct EndLabelNormalizer;
+
+impl DdlTransform for EndLabelNormalizer {
+    fn apply(&self, ddl: &str, context: &DdlContext) -> String {
+        lazy_static! {
+            static ref END_LABEL: Regex = RegexBuilder::new(r"\b(end\s+)([a-z0-9_$#]+)(\s*;)")
+                .case_insensitive(true)
+                .build()
+                .unwrap();
+        }
+        END_LABEL
+            .replace_all(ddl, |caps: &Captures| {
+                if caps[2].eq_ignore_ascii_case(context.object_name) {
+                    format!("{}{}{}", &caps[1], context.object_name, &caps[3])
+                } else {
+                    caps[0].to_string()
+                }
+            })
+            .into_owned()
+    }
+}
+
+// Rewrite the leading whitespace of each line with the given indent unit. A tab is one level,
+// and so are as many spaces as the smallest indentation by spaces in the DDL; spaces left over
+// (e.g. aligning continuation lines) are kept as they are. Blank lines lose their whitespace.
+pub struct WhitespaceNormalizer {
+    pub indent: Indent,
+}
+
+impl DdlTransform for WhitespaceNormalizer {
+    fn apply(&self, ddl: &str, _context: &DdlContext) -> String {
+        let leading_whitespace =
+            |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+        let space_unit = ddl
+            .split('\n')
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line[..leading_whitespace(line)].matches(' ').count())
+            .filter(|spaces| *spaces > 0)
+            .min()
+            .unwrap_or(1);
+        ddl.split('\n')
+            .map(|line| {
+                if line.trim().is_empty() {
+                    return line.trim_start_matches([' ', '\t']).to_string();
+                }
+                let (whitespace, code) = line.split_at(leading_whitespace(line));
+                let tabs = whitespace.matches('\t').count();
+                let spaces = whitespace.len() - tabs;
+                format!(
+                    "{}{}{}",
+                    self.indent.repeat(tabs + spaces / space_unit),
+                    " ".repeat(spaces % space_unit),
+                    code
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+// Terminate the (trimmed) DDL with a line containing `/`, as SQL*Plus and Flyway expect
+// after PL/SQL
+pub struct TerminatorAppender;
+
+impl DdlTransform for TerminatorAppender {
+    fn apply(&self, ddl: &str, _context: &DdlContext) -> String {
+        format!("{}\n/\n", ddl.trim())
+    }
+}
+
+// Drop the materialized view first, if it exists, as `create materialized view` has no
+// `or replace`: a repeatable migration is run again whenever it changes, which would fail with
+// ORA-12006 otherwise. The name is taken as written in the `create` statement, with or without
+// the owner (works on the DDL written by the MaterializedViewOwnerQualifier).
+pub struct MaterializedViewDropPrologue;
+
+impl DdlTransform for MaterializedViewDropPrologue {
+    fn apply(&self, ddl: &str, _context: &DdlContext) -> String {
+        lazy_static! {
+            static ref NAME: Regex =
+                Regex::new(r#"create materialized view ([A-Za-z0-9_$#".]+)"#).unwrap();
+        }
+        let name = match NAME.captures(ddl) {
+            Some(caps) => caps[1].replace('\'', "''"),
+            None => return ddl.to_string(),
+        };
+        format!(
+            "declare\n\
+             \x20 materialized_view_missing exception;\n\
+             \x20 pragma exception_init(materialized_view_missing, -12003);\n\
+             begin\n\
+             \x20 execute immediate 'drop materialized view {}';\n\
+             exception\n\
+             \x20 when materialized_view_missing then\n\
+             \x20   null;\n\
+             end;\n\
+             /\n\n{}",
+            name, ddl
+        )
+    }
+}
+
+// Append `comment on` statements for the comments of a view and its columns
+pub struct CommentAppender<'a> {
+    pub api: &'a dyn PlsqlDevApi,
+}
+
+impl DdlTransform for CommentAppender<'_> {
+    fn apply(&self, ddl: &str, context: &DdlContext) -> String {
+        let comments = self
+            .api
+            .ide_get_comments(context.object_owner, context.object_name);
+        format!(
+            "{}{}",
+            ddl,
+            comment_statements(context.object_owner, context.object_name, &comments)
+        )
+    }
+}
+
+// `comment on` statements for the comments of a table or view and its columns, preceded by
+// an empty line; the comment of the table or view itself has an empty column name
+fn comment_statements(
+    object_owner: &str,
+    object_name: &str,
+    comments: &[(String, String)],
+) -> String {
+    fn identifier(name: &str) -> String {
+        lazy_static! {
+            static ref SIMPLE: Regex = Regex::new(r"^[A-Z][A-Z0-9_$#]*$").unwrap();
+        }
+        match SIMPLE.is_match(name) {
+            true => name.to_string(),
+            false => format!("\"{}\"", name),
+        }
+    }
+
+    let object = format!("{}.{}", identifier(object_owner), identifier(object_name));
+    let mut statements = String::new();
+    for (column, comment) in comments {
+        let target = match column.is_empty() {
+            true => format!("table {}", object),
+            false => format!("column {}.{}", object, identifier(column)),
+        };
+        statements.push_str(&format!(
+            "comment on {} is '{}';\n",
+            target,
+            comment.replace('\'', "''")
+        ));
+    }
+    match statements.is_empty() {
+        true => statements,
+        false => format!("\n{}", statements),
+    }
+}
+
+// Start the migration with a comment recording the object's LAST_DDL_TIME, if the IDE can tell
+pub struct HeaderComment<'a> {
+    pub api: &'a dyn PlsqlDevApi,
+}
+
+impl DdlTransform for HeaderComment<'_> {
+    fn apply(&self, ddl: &str, context: &DdlContext) -> String {
+        match self.api.ide_get_object_last_ddl_time(
+            context.object_type,
+            context.object_owner,
+            context.object_name,
+        ) {
+            Some(last_ddl_time) => format!("-- last_ddl_time: {}\n{}", last_ddl_time, ddl),
+            None => ddl.to_string(),
+        }
+    }
+}
+
+// Announce the object in the SQL*Plus output before its DDL is run
+pub struct PromptLine;
+
+impl DdlTransform for PromptLine {
+    fn apply(&self, ddl: &str, context: &DdlContext) -> String {
+        format!(
+            "PROMPT Creating {}.{}\n{}",
+            context.object_owner, context.object_name, ddl
+        )
+    }
+}
+
+// Surround the migration with the configured header and footer templates
+pub struct PrologueEpilogue<'a> {
+    pub config: &'a Config,
+    pub values: &'a TemplateValues,
+}
+
+impl DdlTransform for PrologueEpilogue<'_> {
+    fn apply(&self, ddl: &str, _context: &DdlContext) -> String {
+        apply_templates(ddl, self.values, self.config).into_owned()
+    }
+}
+
+// The transforms giving the DDL of an object (part) its owner and the configured keywords
+// and layout
+pub fn owner_transforms(object_type: &str, config: &Config) -> Vec<Box<dyn DdlTransform>> {
+    let mut transforms: Vec<Box<dyn DdlTransform>> = match object_type {
+        "TABLE" => vec![Box::new(TableOwnerQualifier)],
+        "MATERIALIZED VIEW" => vec![Box::new(MaterializedViewOwnerQualifier)],
+        _ => vec![Box::new(OwnerQualifier)],
+    };
+    transforms.push(Box::new(EditionKeywordPolicy));
+    if matches!(object_type, "VIEW" | "TYPE") {
+        transforms.push(Box::new(ForceKeywordPolicy {
+            force_views: config.force_views,
+            force_types: config.force_types,
+        }));
+    }
+    if !config.unqualified_owners.is_empty() {
+        transforms.push(Box::new(OwnerPrefixPolicy {
+            unqualified_owners: config.unqualified_owners.clone(),
+        }));
+    }
+    if config.normalize_end_label {
+        transforms.push(Box::new(EndLabelNormalizer));
+    }
+    if config.reindent_ddl {
+        transforms.push(Box::new(WhitespaceNormalizer {
+            indent: config.indent,
+        }));
+    }
+    transforms
+}
+
+// All transforms of the DDL of an object (part) of the given type, as configured
+pub fn ddl_transforms<'a>(
+    api: &'a dyn PlsqlDevApi,
+    object_type: &str,
+    config: &Config,
+) -> Vec<Box<dyn DdlTransform + 'a>> {
+    let mut transforms: Vec<Box<dyn DdlTransform + 'a>> = owner_transforms(object_type, config)
+        .into_iter()
+        .map(|transform| transform as Box<dyn DdlTransform + 'a>)
+        .collect();
+    match object_type {
+        "PACKAGE" | "PACKAGE BODY" | "TYPE" | "TYPE BODY" => {
+            transforms.push(Box::new(TerminatorAppender))
+        }
+        "MATERIALIZED VIEW" => transforms.push(Box::new(MaterializedViewDropPrologue)),
+        "VIEW" if config.include_view_comments => {
+            transforms.push(Box::new(CommentAppender { api }));
+            // the comments are appended with the owner
+            if !config.unqualified_owners.is_empty() {
+                transforms.push(Box::new(OwnerPrefixPolicy {
+                    unqualified_owners: config.unqualified_owners.clone(),
+                }));
+            }
+        }
+        _ => (),
+    }
+    transforms
+}
+
+// The transforms turning the DDL of an object into the content of its migration: the header
+// comment, a PROMPT line in versioned migrations (the ones run with SQL*Plus in an emergency),
+// and the header and footer templates
+pub fn migration_transforms<'a>(
+    api: &'a dyn PlsqlDevApi,
+    config: &'a Config,
+    values: &'a TemplateValues,
+    versioned: bool,
+) -> Vec<Box<dyn DdlTransform + 'a>> {
+    let mut transforms: Vec<Box<dyn DdlTransform + 'a>> = vec![];
+    if config.include_header_comment {
+        transforms.push(Box::new(HeaderComment { api }));
+    }
+    if versioned && config.emit_prompts {
+        transforms.push(Box::new(PromptLine));
+    }
+    transforms.push(Box::new(PrologueEpilogue { config, values }));
+    transforms
+}
+
+pub fn apply_transforms<'a>(
+    transforms: &[Box<dyn DdlTransform + 'a>],
+    ddl: &str,
+    context: &DdlContext,
+) -> String {
+    transforms.iter().fold(ddl.to_string(), |ddl, transform| {
+        transform.apply(&ddl, context)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::config::{Config, Indent};
+    use crate::ddl_transform::*;
+    use crate::template::TemplateValues;
+
+    const CONTEXT: DdlContext = DdlContext {
+        object_type: "VIEW",
+        object_owner: "APP",
+        object_name: "V_SNAFU",
+    };
+
+    fn context(object_type: &'static str) -> DdlContext<'static> {
+        DdlContext {
+            object_type,
+            ..CONTEXT
+        }
+    }
+
+    struct MockPlsqlDevApi;
+
+    impl PlsqlDevApi for MockPlsqlDevApi {
+        fn ide_get_comments(
+            &self,
+            _object_owner: &str,
+            _object_name: &str,
+        ) -> Vec<(String, String)> {
+            vec![("ID".to_string(), "Primary key".to_string())]
+        }
+
+        fn ide_get_object_last_ddl_time(
+            &self,
+            object_type: &str,
+            _object_owner: &str,
+            _object_name: &str,
+        ) -> Option<String> {
+            match object_type {
+                "VIEW" => Some("2021-07-18 10:15:00".to_string()),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn owner_qualifier_should_qualify_name_and_drop_force() {
+        assert_eq!(
+            "create or replace NONEDITIONABLE view APP.V_SNAFU as select 1 from dual;\n",
+            OwnerQualifier.apply(
+                "CREATE OR REPLACE NONEDITIONABLE VIEW v_snafu AS select 1 from dual;\n",
+                &CONTEXT
+            )
+        );
+        assert_eq!(
+            "create or replace type APP.V_SNAFU as object (id number);\n",
+            OwnerQualifier.apply(
+                "create or replace type \"SCOTT\".\"V_SNAFU\" force as object (id number);\n",
+                &context("TYPE")
+            )
+        );
+        assert_eq!(
+            "create or replace trigger APP.V_SNAFU \nbefore insert on t for each row\n",
+            OwnerQualifier.apply(
+                "create or replace trigger v_snafu\nbefore insert on t for each row\n",
+                &context("TRIGGER")
+            )
+        );
+    }
+
+    #[test]
+    fn table_owner_qualifier_should_qualify_name() {
+        let ddl = indoc! {r#"
+            -- Create table
+            CREATE TABLE t_address
+            (
+              id NUMBER not null
+            );
+            -- Add comments to the table
+            comment on table T_ADDRESS
+              is 'Addresses';
+            comment on column "SCOTT".T_ADDRESS.ID
+              is 'Key';
+            -- Create/Recreate primary, unique and foreign key constraints
+            alter table T_ADDRESS
+              add constraint PK_ADDRESS primary key (ID);
+            alter table T_ADDRESS_2
+              add constraint PK_ADDRESS_2 primary key (ID);
+            -- Create/Recreate indexes
+            create unique index IX_ADDRESS on T_ADDRESS (ID);
+            create index SCOTT.IX_ADDRESS_2 on "T_ADDRESS" (ID, NAME);
+            -- Grant/Revoke object privileges
+            grant select, insert on T_ADDRESS to APP_ROLE;
+        "#};
+        let context = DdlContext {
+            object_type: "TABLE",
+            object_owner: "APP",
+            object_name: "T_ADDRESS",
+        };
+        assert_eq!(
+            indoc! {"
+                -- Create table
+                create table APP.T_ADDRESS
+                (
+                  id NUMBER not null
+                );
+                -- Add comments to the table
+                comment on table APP.T_ADDRESS
+                  is 'Addresses';
+                comment on column APP.T_ADDRESS.ID
+                  is 'Key';
+                -- Create/Recreate primary, unique and foreign key constraints
+                alter table APP.T_ADDRESS
+                  add constraint PK_ADDRESS primary key (ID);
+                alter table T_ADDRESS_2
+                  add constraint PK_ADDRESS_2 primary key (ID);
+                -- Create/Recreate indexes
+                create unique index APP.IX_ADDRESS on APP.T_ADDRESS (ID);
+                create index APP.IX_ADDRESS_2 on APP.T_ADDRESS (ID, NAME);
+                -- Grant/Revoke object privileges
+                grant select, insert on APP.T_ADDRESS to APP_ROLE;
+            "},
+            TableOwnerQualifier.apply(ddl, &context)
+        );
+        assert_eq!(
+            "create global temporary table APP.T_ADDRESS (id number) on commit delete rows;",
+            TableOwnerQualifier.apply(
+                "create Global  Temporary table \"SCOTT\".\"T_ADDRESS\" (id number) on commit delete rows;",
+                &context
+            )
+        );
+        assert_eq!(
+            "create table T_ADDRESS (id number);\nalter table T_ADDRESS add primary key (ID);\ncreate index IX_ADDRESS on T_ADDRESS (ID);\ngrant select on T_ADDRESS to APP_ROLE;\n",
+            OwnerPrefixPolicy {
+                unqualified_owners: vec!["app".to_string()]
+            }
+            .apply(
+                &TableOwnerQualifier.apply(
+                    "create table T_ADDRESS (id number);\nalter table T_ADDRESS add primary key (ID);\ncreate index IX_ADDRESS on T_ADDRESS (ID);\ngrant select on T_ADDRESS to APP_ROLE;\n",
+                    &context
+                ),
+                &context
+            )
+        );
+    }
+
+    #[test]
+    fn owner_prefix_policy_should_handle_names_ending_in_special_characters_and_comments() {
+        let context = DdlContext {
+            object_type: "PACKAGE",
+            object_owner: "APP",
+            object_name: "PKG$UTIL#",
+        };
+        let policy = OwnerPrefixPolicy {
+            unqualified_owners: vec!["APP".to_string()],
+        };
+        assert_eq!(
+            "create or replace package PKG$UTIL# is\nend;\n/\ncreate or replace package body PKG$UTIL#\nis\nend;\n/\n",
+            policy.apply(
+                "create or replace package APP.PKG$UTIL# is\nend;\n/\ncreate or replace package body APP.PKG$UTIL#\nis\nend;\n/\n",
+                &context
+            )
+        );
+        // another object whose name starts like this one keeps its owner
+        let ddl = "create or replace package APP.PKG$UTIL#2 is\nend;\n";
+        assert_eq!(ddl, policy.apply(ddl, &context));
+
+        let context = DdlContext {
+            object_type: "VIEW",
+            object_owner: "APP",
+            object_name: "V_SNAFU",
+        };
+        assert_eq!(
+            "comment on table V_SNAFU is 'Snafu';\ncomment on column V_SNAFU.ID is 'Primary key';\n",
+            policy.apply(
+                "comment on table APP.V_SNAFU is 'Snafu';\ncomment on column APP.V_SNAFU.ID is 'Primary key';\n",
+                &context
+            )
+        );
+    }
+
+    #[test]
+    fn materialized_view_owner_qualifier_should_keep_the_clauses_before_as() {
+        let ddl = "CREATE MATERIALIZED VIEW \"SCOTT\".\"MV_SALES\" (\"REGION\", \"TOTAL\")\n  BUILD IMMEDIATE\n  REFRESH FAST ON COMMIT\n  AS SELECT region, sum(amount) total FROM sales GROUP BY region;\n";
+        let context = DdlContext {
+            object_type: "MATERIALIZED VIEW",
+            object_owner: "APP",
+            object_name: "MV_SALES",
+        };
+        assert_eq!(
+            "create materialized view APP.MV_SALES (\"REGION\", \"TOTAL\")\n  BUILD IMMEDIATE\n  REFRESH FAST ON COMMIT\n  AS SELECT region, sum(amount) total FROM sales GROUP BY region;\n",
+            MaterializedViewOwnerQualifier.apply(ddl, &context)
+        );
+        assert_eq!(
+            "create materialized view MV_SALES\nrefresh complete on demand as select 1 x from dual;",
+            OwnerPrefixPolicy {
+                unqualified_owners: vec!["app".to_string()]
+            }
+            .apply(
+                "create materialized view APP.MV_SALES\nrefresh complete on demand as select 1 x from dual;",
+                &context
+            )
+        );
+    }
+
+    #[test]
+    fn ddl_transforms_should_drop_materialized_views_as_they_are_named() {
+        let ddl = "CREATE MATERIALIZED VIEW mv_sales\nrefresh complete on demand as select 1 x from dual;\n";
+        let context = DdlContext {
+            object_type: "MATERIALIZED VIEW",
+            object_owner: "APP",
+            object_name: "MV_SALES",
+        };
+        let config = Config {
+            unqualified_owners: vec!["APP".to_string()],
+            ..Config::default()
+        };
+        let migration = apply_transforms(
+            &ddl_transforms(&MockPlsqlDevApi, "MATERIALIZED VIEW", &config),
+            ddl,
+            &context,
+        );
+        assert!(migration.contains("  execute immediate 'drop materialized view MV_SALES';\n"));
+        assert!(migration.ends_with("end;\n/\n\ncreate materialized view MV_SALES\nrefresh complete on demand as select 1 x from dual;\n"));
+        // other DDL is left alone
+        let ddl = "create table T (id number);\n";
+        assert_eq!(ddl, MaterializedViewDropPrologue.apply(ddl, &context));
+    }
+
+    #[test]
+    fn edition_keyword_policy_should_write_keyword_in_lowercase() {
+        assert_eq!(
+            "create or replace noneditionable view APP.V_SNAFU as select 1 from dual;\n",
+            EditionKeywordPolicy.apply(
+                "create or replace NONEDITIONABLE view APP.V_SNAFU as select 1 from dual;\n",
+                &CONTEXT
+            )
+        );
+        let ddl = "create or replace view APP.V_SNAFU as select 1 from dual;\n";
+        assert_eq!(ddl, EditionKeywordPolicy.apply(ddl, &CONTEXT));
+    }
+
+    #[test]
+    fn force_keyword_policy_should_force_views_and_types_as_configured() {
+        let policy = ForceKeywordPolicy {
+            force_views: true,
+            force_types: false,
+        };
+        assert_eq!(
+            "create or replace editionable force editioning view APP.V_SNAFU as\n",
+            policy.apply(
+                "create or replace editionable editioning view APP.V_SNAFU as\n",
+                &CONTEXT
+            )
+        );
+        let ddl = "create or replace type APP.V_SNAFU as object (id number);\n";
+        assert_eq!(ddl, policy.apply(ddl, &context("TYPE")));
+        let policy = ForceKeywordPolicy {
+            force_views: false,
+            force_types: true,
+        };
+        assert_eq!(
+            "create or replace type APP.V_SNAFU force as object (id number);\n",
+            policy.apply(ddl, &context("TYPE"))
+        );
+        assert_eq!(
+            "create or replace view APP.V_SNAFU as\n",
+            policy.apply("create or replace view APP.V_SNAFU as\n", &CONTEXT)
+        );
+    }
+
+    #[test]
+    fn end_label_normalizer_should_keep_other_labels() {
+        let ddl = "create or replace package body APP.PKG_SNAFU is\n  procedure p is\n  begin\n    null;\n  end p;\nend pkg_snafu;\n";
+        assert_eq!(
+            "create or replace package body APP.PKG_SNAFU is\n  procedure p is\n  begin\n    null;\n  end p;\nend PKG_SNAFU;\n",
+            EndLabelNormalizer.apply(ddl, &DdlContext { object_name: "PKG_SNAFU", ..context("PACKAGE BODY") })
+        );
+    }
+
+    #[test]
+    fn whitespace_normalizer_should_use_configured_indent_unit() {
+        let ddl = "create or replace package body APP.PKG_SNAFU is\n    procedure p is\n    begin\n\tnull;\n  \n        x := f(1,\n          2);\n    end p;\nend PKG_SNAFU;\n";
+        assert_eq!(
+            "create or replace package body APP.PKG_SNAFU is\n  procedure p is\n  begin\n  null;\n\n    x := f(1,\n      2);\n  end p;\nend PKG_SNAFU;\n",
+            WhitespaceNormalizer { indent: Indent::Spaces(2) }.apply(ddl, &CONTEXT)
+        );
+        assert_eq!(
+            "create or replace package body APP.PKG_SNAFU is\n\tprocedure p is\n\tbegin\n\tnull;\n\n\t\tx := f(1,\n\t\t  2);\n\tend p;\nend PKG_SNAFU;\n",
+            WhitespaceNormalizer { indent: Indent::Tab }.apply(ddl, &CONTEXT)
+        );
+    }
+
+    #[test]
+    fn terminator_appender_should_trim_and_terminate() {
+        assert_eq!(
+            "create or replace package APP.PKG is\nend;\n/\n",
+            TerminatorAppender.apply("\ncreate or replace package APP.PKG is\nend;\n\n", &CONTEXT)
+        );
+    }
+
+    #[test]
+    fn comment_appender_should_append_comment_statements() {
+        assert_eq!(
+            "select 1 from dual;\n\ncomment on column APP.V_SNAFU.ID is 'Primary key';\n",
+            CommentAppender {
+                api: &MockPlsqlDevApi
+            }
+            .apply("select 1 from dual;\n", &CONTEXT)
+        );
+    }
+
+    #[test]
+    fn header_comment_should_record_last_ddl_time_if_available() {
+        let header_comment = HeaderComment {
+            api: &MockPlsqlDevApi,
+        };
+        assert_eq!(
+            "-- last_ddl_time: 2021-07-18 10:15:00\nselect 1 from dual;\n",
+            header_comment.apply("select 1 from dual;\n", &CONTEXT)
+        );
+        assert_eq!(
+            "select 1 from dual;\n",
+            header_comment.apply("select 1 from dual;\n", &context("PACKAGE"))
+        );
+    }
+
+    #[test]
+    fn prologue_epilogue_should_apply_templates() {
+        let config = Config {
+            header_template: Some("-- {name}\n".to_string()),
+            footer_template: Some("-- end\n".to_string()),
+            ..Config::default()
+        };
+        let values = TemplateValues {
+            name: "V_SNAFU".to_string(),
+            ..TemplateValues::default()
+        };
+        let prologue_epilogue = PrologueEpilogue {
+            config: &config,
+            values: &values,
+        };
+        assert_eq!(
+            "-- V_SNAFU\nselect 1 from dual;\n-- end\n",
+            prologue_epilogue.apply("select 1 from dual;\n", &CONTEXT)
+        );
+    }
+
+    #[test]
+    fn ddl_transforms_should_depend_on_object_type_and_config() {
+        let config = Config {
+            include_view_comments: true,
+            ..Config::default()
+        };
+        let api = MockPlsqlDevApi;
+        let transform = |object_type, ddl| {
+            apply_transforms(
+                &ddl_transforms(&api, object_type, &config),
+                ddl,
+                &context(object_type),
+            )
+        };
+        assert_eq!(
+            "create or replace force view APP.V_SNAFU as select 1 from dual;\n\n\
+             comment on column APP.V_SNAFU.ID is 'Primary key';\n",
+            transform(
+                "VIEW",
+                "create or replace view v_snafu as select 1 from dual;\n"
+            )
+        );
+        assert_eq!(
+            "create or replace package body APP.V_SNAFU is\nend v_snafu;\n/\n",
+            transform(
+                "PACKAGE BODY",
+                "create or replace package body v_snafu is\nend v_snafu;\n"
+            )
+        );
+        assert_eq!(
+            "create or replace procedure APP.V_SNAFU is\nbegin\n  null;\nend;\n",
+            transform(
+                "PROCEDURE",
+                "create or replace procedure v_snafu is\nbegin\n  null;\nend;\n"
+            )
+        );
+    }
+
+    #[test]
+    fn ddl_transforms_should_remove_the_owner_from_appended_comments() {
+        let config = Config {
+            include_view_comments: true,
+            unqualified_owners: vec!["app".to_string()],
+            ..Config::default()
+        };
+        assert_eq!(
+            "create or replace force view V_SNAFU as select 1 from dual;\n\n\
+             comment on column V_SNAFU.ID is 'Primary key';\n",
+            apply_transforms(
+                &ddl_transforms(&MockPlsqlDevApi, "VIEW", &config),
+                "create or replace view v_snafu as select 1 from dual;\n",
+                &context("VIEW"),
+            )
+        );
+    }
+
+    #[test]
+    fn migration_transforms_should_compose_header_comment_prompt_and_templates() {
+        let config = Config {
+            include_header_comment: true,
+            emit_prompts: true,
+            header_template: Some("-- {name}\n".to_string()),
+            ..Config::default()
+        };
+        let values = TemplateValues {
+            name: "V_SNAFU".to_string(),
+            ..TemplateValues::default()
+        };
+        let api = MockPlsqlDevApi;
+        let transform = |versioned| {
+            apply_transforms(
+                &migration_transforms(&api, &config, &values, versioned),
+                "select 1 from dual;\n",
+                &CONTEXT,
+            )
+        };
+        assert_eq!(
+            "-- V_SNAFU\nPROMPT Creating APP.V_SNAFU\n-- last_ddl_time: 2021-07-18 10:15:00\n\
+             select 1 from dual;\n",
+            transform(true)
+        );
+        assert_eq!(
+            "-- V_SNAFU\n-- last_ddl_time: 2021-07-18 10:15:00\nselect 1 from dual;\n",
+            transform(false)
+        );
+    }
+}
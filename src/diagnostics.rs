@@ -0,0 +1,198 @@
+// A report on how the plugin is wired into the IDE, for support requests: the callbacks the
+// IDE registered, the configuration in effect and where the log goes.
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::flyway_conf::detect_flyway_config;
+use crate::init_tasks::TaskState;
+use crate::logging::{log_path, target};
+use crate::plsqldev_api::{callback_name, PlsqlDevApi, CALLBACK_INDICES};
+use crate::ui::{clipboard, dialogs, MessageKind};
+
+const DIAGNOSTICS_CAPTION: &str = "Diagnostics";
+
+pub fn show_diagnostics(api: &dyn PlsqlDevApi, config: &Config, init_tasks: &[(&str, TaskState)]) {
+    let report = diagnostics_report(api, config, init_tasks, log_path());
+    // message boxes are hard to copy from, so the report goes to the clipboard, too
+    let note = match clipboard().copy_to_clipboard(&report, config.max_clipboard_bytes) {
+        Ok(_) => "The report was copied to the clipboard.",
+        Err(e) => {
+            warn!(target: target::MENU, "Could not copy the diagnostics report: {}", e);
+            "The report could not be copied to the clipboard."
+        }
+    };
+    dialogs().show_message(
+        &format!("{}\n{}", report, note),
+        DIAGNOSTICS_CAPTION,
+        MessageKind::Information,
+    );
+}
+
+pub fn diagnostics_report(
+    api: &dyn PlsqlDevApi,
+    config: &Config,
+    init_tasks: &[(&str, TaskState)],
+    log_path: Option<PathBuf>,
+) -> String {
+    let wired = api.wired_callbacks();
+    let mut report = format!(
+        "Callbacks ({} of {} wired):\n",
+        CALLBACK_INDICES
+            .iter()
+            .filter(|index| wired.contains(index))
+            .count(),
+        CALLBACK_INDICES.len()
+    );
+    for index in CALLBACK_INDICES {
+        let state = match wired.contains(&index) {
+            true => "wired  ",
+            false => "MISSING",
+        };
+        report.push_str(&format!(
+            "  {} {:>3} {}\n",
+            state,
+            index,
+            callback_name(index)
+        ));
+    }
+    report.push_str("\nStartup tasks:\n");
+    if init_tasks.is_empty() {
+        report.push_str("  none started\n");
+    }
+    for (name, state) in init_tasks {
+        report.push_str(&format!("  {}: {}\n", name, state));
+    }
+    report.push_str(&format!(
+        "\nFlyway config: {}\n",
+        detect_flyway_config(config)
+    ));
+    report.push_str("\nSettings changed from the defaults:\n");
+    let changed = changed_settings(config);
+    if changed.is_empty() {
+        report.push_str("  none\n");
+    }
+    for (name, value) in changed {
+        report.push_str(&format!("  {} = {}\n", name, value));
+    }
+    report.push('\n');
+    match log_path {
+        Some(path) => report.push_str(&format!("Log file: {}\n", path.display())),
+        None => report.push_str("Log file: logging has not been initialized\n"),
+    }
+    report
+}
+
+// (name, value) of the settings whose value differs from the default, in declaration order
+fn changed_settings(config: &Config) -> Vec<(String, String)> {
+    let defaults = settings(&Config::default());
+    settings(config)
+        .into_iter()
+        .filter(|setting| !defaults.contains(setting))
+        .collect()
+}
+
+// (name, value) of all settings, taken from the pretty-printed debug output of the config, with
+// each value on a single line
+fn settings(config: &Config) -> Vec<(String, String)> {
+    let mut settings: Vec<(String, String)> = vec![];
+    // the fields are indented by four spaces, their nested values by more
+    for line in format!("{:#?}", config).lines().skip(1) {
+        match line.strip_prefix("    ") {
+            Some(field) if !field.starts_with(' ') && field.contains(": ") => {
+                let (name, value) = field.split_once(": ").unwrap();
+                settings.push((name.to_string(), value.to_string()));
+            }
+            Some(_) => {
+                if let Some((_, value)) = settings.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            }
+            None => (),
+        }
+    }
+    settings
+        .into_iter()
+        .map(|(name, value)| (name, readable_value(&value)))
+        .collect()
+}
+
+// e.g. `Some("C:\\repo"),` as `"C:\repo"`, and `[ "APP", ],` as `["APP"]`
+fn readable_value(value: &str) -> String {
+    let value = value
+        .replace("[ ", "[")
+        .replace("( ", "(")
+        .replace("{ ", "{")
+        .replace(", ]", "]")
+        .replace(", )", ")")
+        .replace(", }", "}")
+        .replace("\\\\", "\\");
+    let value = value.trim_end_matches(',');
+    match value
+        .strip_prefix("Some(")
+        .and_then(|v| v.strip_suffix(')'))
+    {
+        Some(inner) => inner.to_string(),
+        None if value == "None" => "not set".to_string(),
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::c_void;
+    use std::path::PathBuf;
+
+    use crate::config::Config;
+    use crate::diagnostics::*;
+    use crate::init_tasks::TaskState;
+    use crate::plsqldev_api::NativePlsqlDevApi;
+
+    extern "C" fn dummy_callback() {}
+
+    #[test]
+    fn diagnostics_report_should_list_wired_callbacks_config_and_log_path() {
+        let mut api = NativePlsqlDevApi::new();
+        unsafe {
+            api.set_callback_from_address(1, dummy_callback as *mut c_void);
+            api.set_callback_from_address(31, dummy_callback as *mut c_void);
+        }
+        let init_tasks = [
+            ("Sweep", TaskState::Done("deleted 2 files".to_string())),
+            ("Slow", TaskState::Running),
+        ];
+        let report = diagnostics_report(
+            &api,
+            &Config::default(),
+            &init_tasks,
+            Some(PathBuf::from("rustplugin.log")),
+        );
+        assert!(report.starts_with("Callbacks (2 of 21 wired):\n"));
+        assert!(report.contains("  wired     1 SYS_Version\n"));
+        assert!(report.contains("  wired    31 IDE_GetSelectedText\n"));
+        assert!(report.contains("  MISSING  11 IDE_Connected\n"));
+        assert_eq!(2, report.matches("  wired  ").count());
+        assert!(report.contains(
+            "\nStartup tasks:\n  Sweep: done: deleted 2 files\n  Slow: running\n\nFlyway config: detection is switched off\n\nSettings changed from the defaults:\n  none\n\n"
+        ));
+        assert!(report.ends_with("Log file: rustplugin.log\n"));
+
+        let config = Config {
+            version_prefix: "M".to_string(),
+            repo_root: Some("C:\\repo".to_string()),
+            unqualified_owners: vec!["APP".to_string(), "CORE".to_string()],
+            ..Config::default()
+        };
+        let report = diagnostics_report(&api, &config, &[], None);
+        assert!(report.contains(
+            "\nSettings changed from the defaults:\n  \
+             unqualified_owners = [\"APP\", \"CORE\"]\n  \
+             repo_root = \"C:\\repo\"\n  \
+             version_prefix = \"M\"\n\n"
+        ));
+
+        let report = diagnostics_report(&api, &Config::default(), &[], None);
+        assert!(report.contains("\nStartup tasks:\n  none started\n"));
+        assert!(report.ends_with("Log file: logging has not been initialized\n"));
+    }
+}
@@ -0,0 +1,96 @@
+// Line based differences between two texts.
+//
+// Only the size of the difference is needed so far, which follows from the length of the
+// longest common subsequence of lines: every line of the old text not in it was removed,
+// every line of the new text not in it was added.
+
+// The number of lines added and removed to get from `old` to `new`
+#[derive(Debug, PartialEq)]
+pub struct LineChanges {
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl LineChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.removed == 0
+    }
+}
+
+// Line endings don't count as a difference
+pub fn line_changes(old: &str, new: &str) -> LineChanges {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let common = longest_common_subsequence(&old, &new);
+    LineChanges {
+        added: new.len() - common,
+        removed: old.len() - common,
+    }
+}
+
+// The classic dynamic program, keeping only one row
+fn longest_common_subsequence(old: &[&str], new: &[&str]) -> usize {
+    // the common prefix and suffix don't need the quadratic part
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+
+    let mut row = vec![0usize; new.len() + 1];
+    for old_line in old {
+        let mut diagonal = 0;
+        for (j, new_line) in new.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = match old_line == new_line {
+                true => diagonal + 1,
+                false => above.max(row[j]),
+            };
+            diagonal = above;
+        }
+    }
+    prefix + row[new.len()] + suffix
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::*;
+
+    #[test]
+    fn line_changes_should_count_added_and_removed_lines() {
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nc\nd\ne\nf\n";
+        assert_eq!(
+            LineChanges {
+                added: 2,
+                removed: 1
+            },
+            line_changes(old, new)
+        );
+        // a changed line is removed and added
+        assert_eq!(
+            LineChanges {
+                added: 1,
+                removed: 1
+            },
+            line_changes("x\ny\nz", "x\nY\nz")
+        );
+        assert_eq!(
+            LineChanges {
+                added: 0,
+                removed: 3
+            },
+            line_changes(old, "a\n")
+        );
+    }
+
+    #[test]
+    fn line_changes_should_ignore_line_endings() {
+        assert!(line_changes("a\r\nb\r\n", "a\nb").is_empty());
+        assert!(line_changes("", "").is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+// Export schema documentation: one Markdown file per selected object, containing a title,
+// a metadata table and the object's source, in a subfolder per object type.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+
+use crate::config::Config;
+use crate::flyway::{
+    fetch_object_source, NO_OBJECT_SELECTED_CAPTION, NO_OBJECT_SELECTED_MESSAGE,
+    SUPPORTED_OBJECT_TYPES,
+};
+use crate::logging::target;
+use crate::paths;
+use crate::plsqldev_api::{PlsqlDevApi, SelectedObject};
+use crate::string_utils::sanitize_file_name;
+use crate::ui::{dialogs, MessageKind};
+
+const DOCUMENTATION_CAPTION: &str = "Export documentation";
+const MIN_FENCE_LENGTH: usize = 3;
+
+pub fn create_documentation(api: &dyn PlsqlDevApi, config: &Config) {
+    let selected_objects = api.ide_selected_objects();
+    if selected_objects.is_empty() {
+        dialogs().show_message(
+            NO_OBJECT_SELECTED_MESSAGE,
+            NO_OBJECT_SELECTED_CAPTION,
+            MessageKind::Information,
+        );
+        return;
+    }
+
+    let folder_name = dialogs().get_save_folder_name();
+    if folder_name.is_empty() {
+        return;
+    }
+
+    let export_date = Utc::now().format("%Y-%m-%d").to_string();
+    let mut files_written = 0;
+    for selected_object in &selected_objects {
+        if !SUPPORTED_OBJECT_TYPES.contains(&selected_object.object_type.as_str()) {
+            debug!(target: target::FLYWAY, "Skipping documentation of {}", selected_object);
+            continue;
+        }
+        let source = fetch_object_source(api, selected_object, config);
+        match write_documentation(
+            Path::new(&folder_name),
+            selected_object,
+            &source,
+            &export_date,
+        ) {
+            Ok(path) => {
+                debug!(target: target::FLYWAY, "Wrote documentation {:?}", path);
+                files_written += 1;
+            }
+            Err(e) => warn!(target: target::FLYWAY,
+                "Could not write documentation of {}: {}",
+                selected_object, e
+            ),
+        }
+    }
+
+    if files_written > 0 {
+        let message = format!(
+            "Successfully exported documentation of {} objects to {}.",
+            files_written,
+            paths::display(config.repo_root.as_deref(), &folder_name)
+        );
+        dialogs().show_message(&message, DOCUMENTATION_CAPTION, MessageKind::Information);
+    } else {
+        let message = "No documentation was created!\nPlease make sure you have selected one or more supported\nobject types.";
+        dialogs().show_message(message, DOCUMENTATION_CAPTION, MessageKind::Error);
+    }
+}
+
+// Write <folder>\<type subfolder>\<NAME>.md, creating the subfolder if necessary
+pub fn write_documentation(
+    folder: &Path,
+    selected_object: &SelectedObject,
+    source: &str,
+    export_date: &str,
+) -> std::io::Result<PathBuf> {
+    let subfolder = folder.join(type_subfolder(&selected_object.object_type));
+    fs::create_dir_all(&subfolder)?;
+    let path = subfolder.join(documentation_file_name(&selected_object.object_name));
+    fs::write(
+        &path,
+        markdown_document(selected_object, source, export_date),
+    )?;
+    Ok(path)
+}
+
+// e.g. PACKAGE -> packages, TYPE -> types
+pub fn type_subfolder(object_type: &str) -> String {
+    format!("{}s", object_type.to_lowercase().replace(' ', "_"))
+}
+
+// <NAME>.md, with characters Windows doesn't allow in file names replaced by an underscore
+pub fn documentation_file_name(object_name: &str) -> String {
+    format!("{}.md", sanitize_file_name(&object_name.to_uppercase()))
+}
+
+// A code fence that is longer than any run of backticks inside the source,
+// so the source can't terminate the code block early
+pub fn code_fence(source: &str) -> String {
+    let longest_run = source.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat((longest_run + 1).max(MIN_FENCE_LENGTH))
+}
+
+fn escape_table_cell(value: &str) -> String {
+    value.replace('|', "\\|")
+}
+
+pub fn markdown_document(
+    selected_object: &SelectedObject,
+    source: &str,
+    export_date: &str,
+) -> String {
+    let fence = code_fence(source);
+    format!(
+        "# {owner}.{name}\n\
+         \n\
+         | Property | Value |\n\
+         |---|---|\n\
+         | Owner | {owner_cell} |\n\
+         | Type | {object_type} |\n\
+         | Export date | {export_date} |\n\
+         \n\
+         {fence}sql\n\
+         {source}\n\
+         {fence}\n",
+        owner = selected_object.object_owner,
+        name = selected_object.object_name,
+        owner_cell = escape_table_cell(&selected_object.object_owner),
+        object_type = escape_table_cell(&selected_object.object_type),
+        export_date = export_date,
+        fence = fence,
+        source = source.trim_end(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::documentation::*;
+    use crate::plsqldev_api::SelectedObject;
+
+    #[test]
+    fn markdown_document_should_contain_title_metadata_and_source() {
+        let selected_object = SelectedObject::new("PACKAGE", "APP", "PKG_SNAFU", "");
+        let source = "create or replace package APP.PKG_SNAFU is\nend;\n/\n";
+        assert_eq!(
+            "# APP.PKG_SNAFU\n\
+             \n\
+             | Property | Value |\n\
+             |---|---|\n\
+             | Owner | APP |\n\
+             | Type | PACKAGE |\n\
+             | Export date | 2021-07-18 |\n\
+             \n\
+             ```sql\n\
+             create or replace package APP.PKG_SNAFU is\nend;\n/\n\
+             ```\n",
+            markdown_document(&selected_object, source, "2021-07-18")
+        );
+    }
+
+    #[test]
+    fn code_fence_should_be_longer_than_backticks_in_source() {
+        assert_eq!("```", code_fence("select 1 from dual"));
+        assert_eq!("```", code_fence("-- `quoted` and ``double``"));
+        assert_eq!("````", code_fence("-- ```sql inside a comment"));
+        assert_eq!("``````", code_fence("-- `````"));
+    }
+
+    #[test]
+    fn documentation_file_name_should_replace_invalid_characters() {
+        assert_eq!("PKG_SNAFU.md", documentation_file_name("pkg_snafu"));
+        assert_eq!("MY_VIEW_1_.md", documentation_file_name("My/View:1?"));
+    }
+
+    #[test]
+    fn write_documentation_should_use_type_subfolder() {
+        let folder = std::env::temp_dir().join(format!(
+            "xanthidae_test_documentation_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&folder);
+        let selected_object = SelectedObject::new("VIEW", "APP", "V_ALL_OBJECTS", "");
+
+        let path = write_documentation(
+            &folder,
+            &selected_object,
+            "select 1 from dual;\n",
+            "2021-07-18",
+        )
+        .unwrap();
+
+        assert_eq!(folder.join("views").join("V_ALL_OBJECTS.md"), path);
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("```sql\nselect 1 from dual;\n```\n"));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+// Minimal support for `.editorconfig` files in the export folder.
+//
+// Only the `end_of_line` setting of sections applying to *.sql files is evaluated;
+// everything else in the file is ignored.
+use std::fs;
+use std::path::Path;
+
+use crate::config::LineEnding;
+
+const EDITORCONFIG_FILE_NAME: &str = ".editorconfig";
+// how many parent folders of the export folder are searched for an .editorconfig
+const MAX_PARENT_LEVELS: usize = 3;
+
+// Find the line ending configured for SQL files by the closest .editorconfig
+// in `folder` or one of its parents
+pub fn find_end_of_line(folder: &Path) -> Option<LineEnding> {
+    // canonicalize so we can walk up from relative folders, too
+    let folder = folder
+        .canonicalize()
+        .unwrap_or_else(|_| folder.to_path_buf());
+    folder
+        .ancestors()
+        .take(MAX_PARENT_LEVELS + 1)
+        .filter_map(|dir| fs::read_to_string(dir.join(EDITORCONFIG_FILE_NAME)).ok())
+        .find_map(|content| parse_end_of_line(&content))
+}
+
+// Extract the `end_of_line` value for SQL files; later sections override earlier ones,
+// just like editorconfig itself handles it
+pub fn parse_end_of_line(content: &str) -> Option<LineEnding> {
+    let mut section_applies = false;
+    let mut result = None;
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            section_applies = section_applies_to_sql(&line[1..line.len() - 1]);
+            continue;
+        }
+        if !section_applies {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim().eq_ignore_ascii_case("end_of_line") {
+                result = match value.trim().to_lowercase().as_str() {
+                    "lf" => Some(LineEnding::Lf),
+                    "crlf" => Some(LineEnding::CrLf),
+                    _ => result,
+                };
+            }
+        }
+    }
+    result
+}
+
+// We don't implement the full glob syntax, but support the patterns commonly used for SQL files:
+// `*`, `**`, `*.sql`, `**.sql`, `**/*.sql` and brace lists like `*.{sql,pks,pkb}`
+fn section_applies_to_sql(pattern: &str) -> bool {
+    let pattern = pattern
+        .trim()
+        .trim_start_matches("**/")
+        .trim_start_matches('*');
+    if pattern.is_empty() {
+        return true;
+    }
+    match pattern.strip_prefix('.') {
+        Some(extension) => extension
+            .trim_start_matches('{')
+            .trim_end_matches('}')
+            .split(',')
+            .any(|e| e.trim().eq_ignore_ascii_case("sql")),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::LineEnding;
+    use crate::editorconfig::*;
+
+    #[test]
+    fn parse_end_of_line_should_read_global_section() {
+        let content = "root = true\n\n[*]\nindent_style = space\nend_of_line = crlf\n";
+        assert_eq!(Some(LineEnding::CrLf), parse_end_of_line(content));
+    }
+
+    #[test]
+    fn parse_end_of_line_should_prefer_later_sql_section() {
+        let content = "[*]\nend_of_line = crlf\n\n[*.{sql,pkb}]\nend_of_line = lf\n";
+        assert_eq!(Some(LineEnding::Lf), parse_end_of_line(content));
+    }
+
+    #[test]
+    fn parse_end_of_line_should_ignore_other_sections() {
+        let content = "[*.md]\nend_of_line = crlf\n\n[Makefile]\nend_of_line = lf\n";
+        assert_eq!(None, parse_end_of_line(content));
+    }
+
+    #[test]
+    fn parse_end_of_line_should_ignore_unsupported_values() {
+        let content = "[**.sql]\nend_of_line = cr\n";
+        assert_eq!(None, parse_end_of_line(content));
+    }
+}
@@ -1,26 +1,58 @@
 use std::ffi::CStr;
+use std::fmt::{Display, Formatter};
 //use std::fs::File;
 //use std::os::raw::{c_char, c_ushort};
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
 use std::os::raw::c_char;
-use std::sync::RwLock;
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, RwLockWriteGuard};
+
+use chrono::{DateTime, Local};
+use regex::Regex;
 //use std::ffi::OsString;
 //use std::os::windows::prelude::*;
 
 //use std::os::raw::c_int;
 //use std::os::raw::c_void;
-use winapi::um::winuser::MB_ICONINFORMATION;
-use winapi::um::winuser::MB_OK;
 
-use crate::clipboard::copy_to_clipboard;
-use crate::windows_api::show_message_box;
+use crate::column_selection::{choose_columns, retain_columns, COLUMN_SELECTIONS};
+use crate::config::{
+    Config, ExportFormat, ExportTarget, MaskingStrategy, DEFAULT_LOB_PLACEHOLDERS,
+};
+use crate::logging::target;
+use crate::masking::{mask_data, masked_columns};
+use crate::prelude::{api, config, read_config};
+use crate::string_utils::{escape_html, escape_wiki_pipe, json_string, wrap_text};
+use crate::ui::{clipboard, dialogs, ClipboardError, MessageKind, SaveDialogOutcome};
+use crate::verified_write::write_verified;
 
 const EXPORT_TO_CLIPBOARD_AS_WIKI: &[u8] = b"Export to clipboard in Wiki syntax (Rust)\0";
+const EXPORT_TO_CLIPBOARD_AS_MARKDOWN: &[u8] = b"Export to clipboard as Markdown (Rust)\0";
+const EXPORT_TO_CLIPBOARD_AS_CSV: &[u8] = b"Export to clipboard as CSV (Rust)\0";
+const EXPORT_TO_CLIPBOARD_AS_JSON: &[u8] = b"Export to clipboard as JSON (Rust)\0";
+const EXPORT_TO_CLIPBOARD_AS_HTML: &[u8] = b"Export to clipboard as HTML (Rust)\0";
+// readable defaults for HTML exports, inline so the table looks the same wherever it's pasted
+const HTML_STYLE: &str = "<style>\n\
+    table { border-collapse: collapse; font-family: sans-serif; }\n\
+    th, td { border: 1px solid #999; padding: 2px 6px; text-align: left; }\n\
+    th { background-color: #eee; }\n\
+    </style>\n";
+// rows reserved at least whenever the captured rows are full; the vector still grows by
+// doubling, this only skips its many small reallocations at the start
+const ROW_RESERVE_CHUNK: usize = 4096;
 
+#[derive(Clone)]
 pub struct ExportData {
     pub headers: Vec<String>,
-    pub data: Vec<Vec<String>>,
-    pub current_row: Vec<String>,
+    // None is a SQL NULL, as opposed to an empty string
+    pub data: Vec<Vec<Option<String>>>,
+    pub current_row: Vec<Option<String>>,
     pub prepared: bool,
+    // complete rows received after prepare, including a dropped repetition of the headers
+    pub rows_received: usize,
+    // between ExportInit and ExportFinished
+    pub active: bool,
 }
 
 impl ExportData {
@@ -30,6 +62,8 @@ impl ExportData {
             data: vec![],
             current_row: vec![],
             prepared: false,
+            rows_received: 0,
+            active: false,
         }
     }
 
@@ -38,146 +72,3135 @@ impl ExportData {
         self.data = vec![];
         self.current_row = vec![];
         self.prepared = false;
+        self.rows_received = 0;
+        self.active = true;
+    }
+
+    // The IDE has been seen calling the other export callbacks without ExportInit (e.g. after
+    // switching the export format), so a session is started if none is active. Otherwise the
+    // state of the previous export would remain, and the headers would end up in the rows.
+    pub fn ensure_session(&mut self, callback: &str) {
+        if !self.active {
+            warn!(target: target::EXPORT,
+                "{} called without ExportInit, starting a new export",
+                callback
+            );
+            self.init();
+        }
+    }
+
+    // After the headers, the number of columns is known: every row is allocated at its size
+    pub fn prepare(&mut self) {
+        self.prepared = true;
+        self.current_row.reserve_exact(self.num_columns());
+    }
+
+    // Values before prepare are headers, values after it fill the rows.
+    // The IDE sends NULL as an empty string (Oracle has no empty strings anyway), so an empty
+    // value is captured as NULL.
+    // Some IDE windows (e.g. the report window of PL/SQL Developer 15) send the headers again
+    // as the first row; if `drop_repeated_headers` is set, that row is dropped.
+    pub fn push_value(&mut self, value: String, drop_repeated_headers: bool) {
+        if !self.prepared {
+            self.headers.push(value);
+            return;
+        }
+        if self.current_row.capacity() == 0 {
+            self.current_row.reserve_exact(self.num_columns());
+        }
+        self.current_row
+            .push(Some(value).filter(|value| !value.is_empty()));
+        if self.current_row.len() < self.num_columns() {
+            return;
+        }
+        let row = std::mem::take(&mut self.current_row);
+        self.rows_received += 1;
+        if drop_repeated_headers && self.rows_received == 1 && self.repeats_headers(&row) {
+            warn!(target: target::EXPORT, "Dropping repeated header row {:?}", row);
+            return;
+        }
+        if self.data.len() == self.data.capacity() {
+            self.data.reserve(ROW_RESERVE_CHUNK);
+        }
+        self.data.push(row);
+    }
+
+    fn repeats_headers(&self, row: &[Option<String>]) -> bool {
+        row.len() == self.headers.len()
+            && row.iter().zip(&self.headers).all(|(cell, header)| {
+                cell.as_deref().unwrap_or_default().to_lowercase() == header.to_lowercase()
+            })
     }
 
     pub fn num_columns(self: &ExportData) -> usize {
-        return self.headers.len();
+        self.headers.len()
     }
 
-    /// convert to string (in Wiki syntax).
-    pub fn to_string(self: &ExportData) -> String {
+    /// convert to string (in Wiki syntax); NULL is written as `null_repr`.
+    pub fn to_string(self: &ExportData, null_repr: &str) -> String {
         // TODO: rewrite this in a more functional style, something like headers.join() + data.join() or map or ...
         let mut result: String = String::new();
-        result = result + "||";
+        result += "||";
         for h in &self.headers {
-            result = result + &h + "||";
+            result = result + &wiki_cell(h) + "||";
         }
-        result = result + "\n";
+        result += "\n";
         for d in &self.data {
-            result = result + "|";
+            result += "|";
             for cell in d {
-                result = result + cell + "|";
+                result = result + &wiki_cell(cell.as_deref().unwrap_or(null_repr)) + "|";
             }
-            result = result + "\n";
+            result += "\n";
+        }
+        result
+    }
+
+    /// convert to a GitHub-flavored Markdown table; NULL is written as `null_repr`.
+    pub fn to_markdown(self: &ExportData, null_repr: &str) -> String {
+        let row = |cells: Vec<&str>| {
+            let cells: Vec<String> = cells.into_iter().map(markdown_cell).collect();
+            format!("| {} |\n", cells.join(" | "))
+        };
+        let mut result = row(self.headers.iter().map(String::as_str).collect());
+        result += &format!("|{}\n", " --- |".repeat(self.num_columns()));
+        for d in &self.data {
+            result += &row(d
+                .iter()
+                .map(|cell| cell.as_deref().unwrap_or(null_repr))
+                .collect());
+        }
+        result
+    }
+
+    /// convert to a GitHub Flavored Markdown table as pasted into issues and wikis: pipes in the
+    /// cells are escaped, NULL is an empty cell whatever the configured null representation.
+    pub fn to_gfm_markdown(self: &ExportData) -> String {
+        self.to_markdown("")
+    }
+
+    /// convert to comma-separated values (RFC 4180), with the header as the first record
+    /// and CRLF line breaks; NULL is written as `null_repr`, an empty string as two qualifiers.
+    pub fn to_csv(self: &ExportData, delimiter: char, qualifier: char, null_repr: &str) -> String {
+        let separator = delimiter.to_string();
+        let record = |fields: Vec<String>| format!("{}\r\n", fields.join(&separator));
+        let mut result = record(
+            self.headers
+                .iter()
+                .map(|h| csv_field(h, delimiter, qualifier))
+                .collect(),
+        );
+        for d in &self.data {
+            result += &record(
+                d.iter()
+                    .map(|cell| match cell.as_deref() {
+                        None => null_repr.to_string(),
+                        Some("") => format!("{}{}", qualifier, qualifier),
+                        Some(value) => csv_field(value, delimiter, qualifier),
+                    })
+                    .collect(),
+            );
+        }
+        result
+    }
+
+    /// convert to an INSERT statement per row; numbers are written as they are, other values
+    /// as string literals, and NULL as NULL. Values of columns with a known type are literals
+    /// unless the type is numeric.
+    pub fn to_insert_statements(
+        self: &ExportData,
+        table_name: &str,
+        column_types: &[(String, String)],
+    ) -> Result<String, ExportError> {
+        let table_name = valid_table_name(table_name)?;
+        let columns: Vec<String> = self.headers.iter().map(|h| oracle_identifier(h)).collect();
+        let numeric: Vec<Option<bool>> = self
+            .headers
+            .iter()
+            .map(|header| {
+                column_types
+                    .iter()
+                    .find(|(name, _)| name == header)
+                    .map(|(_, column_type)| is_numeric_type(column_type))
+            })
+            .collect();
+        Ok(self
+            .data
+            .iter()
+            .map(|row| {
+                let values: Vec<String> = row
+                    .iter()
+                    .zip(&numeric)
+                    .map(|(cell, &numeric)| insert_value(cell, numeric))
+                    .collect();
+                format!(
+                    "INSERT INTO {} ({}) VALUES ({});\n",
+                    table_name,
+                    columns.join(", "),
+                    values.join(", ")
+                )
+            })
+            .collect())
+    }
+
+    /// convert to a JSON array with an object per row, keyed by the headers; the values are
+    /// strings, NULL is null. Pretty output is indented with a member per line,
+    /// otherwise the array is a single line.
+    pub fn to_json(self: &ExportData, pretty: bool) -> String {
+        let (colon, member_separator, object_separator) = match pretty {
+            true => (": ", ",\n    ", ",\n  "),
+            false => (":", ",", ","),
+        };
+        let keys: Vec<String> = self.headers.iter().map(|h| json_string(h)).collect();
+        let objects: Vec<String> = self
+            .data
+            .iter()
+            .map(|row| {
+                let members: Vec<String> = keys
+                    .iter()
+                    .zip(row)
+                    .map(|(key, cell)| match cell {
+                        None => format!("{}{}null", key, colon),
+                        Some(value) => format!("{}{}{}", key, colon, json_string(value)),
+                    })
+                    .collect();
+                match pretty && !members.is_empty() {
+                    true => format!("{{\n    {}\n  }}", members.join(member_separator)),
+                    false => format!("{{{}}}", members.join(member_separator)),
+                }
+            })
+            .collect();
+        match pretty && !objects.is_empty() {
+            true => format!("[\n  {}\n]\n", objects.join(object_separator)),
+            false => format!("[{}]\n", objects.join(object_separator)),
+        }
+    }
+
+    /// convert to an HTML table with a header row, optionally preceded by a <style> block;
+    /// NULL is an empty cell.
+    pub fn to_html(self: &ExportData, include_style: bool) -> String {
+        let row = |cells: Vec<&str>, tag: &str| {
+            let cells: Vec<String> = cells
+                .into_iter()
+                .map(|cell| format!("<{}>{}</{}>", tag, html_cell(cell), tag))
+                .collect();
+            format!("  <tr>{}</tr>\n", cells.concat())
+        };
+        let mut result = String::new();
+        if include_style {
+            result += HTML_STYLE;
+        }
+        result += "<table>\n";
+        result += &row(self.headers.iter().map(String::as_str).collect(), "th");
+        for d in &self.data {
+            result += &row(
+                d.iter().map(|cell| cell.as_deref().unwrap_or("")).collect(),
+                "td",
+            );
         }
-        return result;
+        result += "</table>\n";
+        result
     }
 }
 
-lazy_static! {
-  // See https://stackoverflow.com/questions/59679968/static-array-of-trait-objects
-  pub static ref EXPORT_DATA: RwLock<ExportData> = RwLock::new(ExportData::new());
+// A field of a CSV file: qualified if it contains the delimiter, the qualifier or a line break,
+// with the qualifiers in it doubled
+fn csv_field(value: &str, delimiter: char, qualifier: char) -> String {
+    match value.contains([delimiter, qualifier, '\r', '\n']) {
+        true => {
+            let doubled = format!("{}{}", qualifier, qualifier);
+            format!(
+                "{}{}{}",
+                qualifier,
+                value.replace(qualifier, &doubled),
+                qualifier
+            )
+        }
+        false => value.to_string(),
+    }
 }
 
-#[allow(non_snake_case)]
-#[no_mangle]
-pub extern "C" fn ExportInit() -> bool {
-    //let caption = CStr::from_bytes_with_nul(b"ExportInit\0").unwrap();
-    //show_message_box(&caption, &caption, MB_OK | MB_ICONINFORMATION);
-    let mut export_data = EXPORT_DATA.write().unwrap();
-    export_data.init();
-    return true;
+// A cell of a Wiki table: the two pipes around an empty cell would be taken for a header cell,
+// shifting the rest of the row to the left, and a line break would end the row, so it's written
+// as a forced one
+fn wiki_cell(value: &str) -> String {
+    match value {
+        "" => " ".to_string(),
+        _ => escape_wiki_pipe(value)
+            .replace("\r\n", "\\\\")
+            .replace(['\r', '\n'], "\\\\"),
+    }
 }
 
-#[allow(non_snake_case)]
-#[no_mangle]
-pub extern "C" fn ExportFinished() {
-    //let caption = CStr::from_bytes_with_nul(b"ExportFinished\0").unwrap();
-    //show_message_box(&caption, &caption, MB_OK | MB_ICONINFORMATION);
-    let export_data = EXPORT_DATA.read().unwrap();
-    let res = copy_to_clipboard(&export_data.to_string());
-    let caption = match res {
-        Ok(_) => CStr::from_bytes_with_nul(b"Results copied to clipboard\0"),
-        Err(_e) => CStr::from_bytes_with_nul(
-            b"An error occured. If this problem persists, please file a bug report.\0",
-        ),
+// A cell of a Markdown table: a pipe would end the cell and a line break the row, and an empty
+// cell is written as a single space, like in Wiki tables
+fn markdown_cell(value: &str) -> String {
+    match value {
+        "" => " ".to_string(),
+        _ => value
+            .replace('|', "\\|")
+            .replace("\r\n", "<br>")
+            .replace(['\r', '\n'], "<br>"),
     }
-    .unwrap();
-    show_message_box(&caption, &caption, MB_OK | MB_ICONINFORMATION);
 }
 
-/// One cell of data, this can be the column description or the actual data.
-#[allow(non_snake_case)]
-#[no_mangle]
-pub extern "C" fn ExportData(value: *const c_char) -> bool {
-    //let caption = CStr::from_bytes_with_nul(b"ExportData\0").unwrap();
-    //show_message_box(&caption, &caption, MB_OK | MB_ICONINFORMATION);
-    let mut export_data = EXPORT_DATA.write().unwrap();
-    // from https://doc.rust-lang.org/std/os/windows/ffi/index.html - this might work with some tweaking, but currently results in an access violation
-    //pub extern "C" fn ExportData(value: &[u16]) -> bool {
-    //let string = OsString::from_wide(value);
-    /*let str_buf: String = match string.into_string() {
-      Ok(s) => s,
-      Err(e) => "?".to_string()
-    };*/
+// A cell of an HTML table: escaped, with line breaks kept, which would be mere spaces otherwise
+fn html_cell(value: &str) -> String {
+    escape_html(value)
+        .replace("\r\n", "<br>")
+        .replace(['\r', '\n'], "<br>")
+}
 
-    let c_str: &CStr = unsafe { CStr::from_ptr(value) };
-    // to_str() fails for non UTF-8 input(e.g. Strings containing umlauts - presumably, they're UTF-16 encoded?);
-    //   in that case, we simply return a question mark for the whole string
-    let str_slice: &str = match c_str.to_str() {
-        Ok(s) => s,
-        Err(_) => "?",
+#[derive(Debug, PartialEq)]
+pub enum ExportError {
+    UnknownKeyColumn(String),
+    UnknownGroupColumn(String),
+    NoColumnsToUpdate,
+    // 1-based numbers of the rows with a NULL key
+    NullKey(Vec<usize>),
+    NoColumnsSelected,
+    InvalidTableName(String),
+}
+
+impl Display for ExportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            ExportError::UnknownKeyColumn(column) => {
+                format!("Key column {} is not part of the result set", column)
+            }
+            ExportError::UnknownGroupColumn(column) => {
+                format!("Group column {} is not part of the result set", column)
+            }
+            ExportError::NoColumnsToUpdate => {
+                "All columns are key columns, there is nothing to update".to_string()
+            }
+            ExportError::NullKey(rows) => format!(
+                "Refusing to create UPDATE statements, key is NULL in row(s) {}",
+                rows.iter()
+                    .map(|r| r.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            ExportError::NoColumnsSelected => "No columns were selected for export".to_string(),
+            ExportError::InvalidTableName(name) if name.trim().is_empty() => {
+                "The table name is empty".to_string()
+            }
+            ExportError::InvalidTableName(name) => format!("{} is not a valid table name", name),
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+// A table name as entered by the user, trimmed: optionally schema qualified, each part either a
+// plain identifier or one in double quotes
+pub fn valid_table_name(name: &str) -> Result<String, ExportError> {
+    lazy_static! {
+        static ref TABLE_NAME: Regex = Regex::new(
+            r#"^(?:[A-Za-z][A-Za-z0-9_$#]*|"[^"]+")(?:\.(?:[A-Za-z][A-Za-z0-9_$#]*|"[^"]+"))?$"#
+        )
+        .unwrap();
+    }
+    match TABLE_NAME.is_match(name.trim()) {
+        true => Ok(name.trim().to_string()),
+        false => Err(ExportError::InvalidTableName(name.to_string())),
+    }
+}
+
+// A column name for Oracle SQL: headers of unquoted columns come back in uppercase and are
+// written as they are, anything else (e.g. `COUNT(*)` or a mixed-case alias) in double quotes
+pub fn oracle_identifier(name: &str) -> String {
+    lazy_static! {
+        static ref PLAIN: Regex = Regex::new(r"^[A-Z][A-Z0-9_$#]*$").unwrap();
+    }
+    match PLAIN.is_match(name) {
+        true => name.to_string(),
+        false => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+/// Creates one UPDATE statement per row, using the key columns in the WHERE clause
+/// and all other columns in the SET list.
+pub struct UpdateFormatter {
+    pub table_name: String,
+    pub key_columns: Vec<String>,
+    // cells with these values are written as NULL, see `is_lob_placeholder`
+    pub lob_placeholders: Vec<String>,
+}
+
+impl UpdateFormatter {
+    pub fn new(table_name: &str, key_columns: &[&str]) -> UpdateFormatter {
+        UpdateFormatter {
+            table_name: table_name.to_string(),
+            key_columns: key_columns.iter().map(|c| c.trim().to_string()).collect(),
+            lob_placeholders: DEFAULT_LOB_PLACEHOLDERS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn format(&self, export_data: &ExportData) -> Result<String, ExportError> {
+        let table_name = valid_table_name(&self.table_name)?;
+        // resolve key columns to their index in the headers (case-insensitive)
+        let mut key_indices: Vec<usize> = vec![];
+        for key_column in &self.key_columns {
+            match export_data
+                .headers
+                .iter()
+                .position(|h| h.eq_ignore_ascii_case(key_column))
+            {
+                Some(index) => key_indices.push(index),
+                None => return Err(ExportError::UnknownKeyColumn(key_column.to_string())),
+            }
+        }
+        if key_indices.len() >= export_data.num_columns() {
+            return Err(ExportError::NoColumnsToUpdate);
+        }
+
+        // refuse to run if any key cell is NULL, otherwise we'd produce "WHERE key = NULL"
+        let null_key_rows: Vec<usize> = export_data
+            .data
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| {
+                key_indices.iter().any(|&k| match &row[k] {
+                    None => true,
+                    Some(value) => is_lob_placeholder(value, &self.lob_placeholders),
+                })
+            })
+            .map(|(i, _)| i + 1)
+            .collect();
+        if !null_key_rows.is_empty() {
+            return Err(ExportError::NullKey(null_key_rows));
+        }
+
+        let mut result = String::new();
+        for row in &export_data.data {
+            let mut lob_omitted = false;
+            let set_list: Vec<String> = export_data
+                .headers
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !key_indices.contains(i))
+                .map(|(i, h)| match &row[i] {
+                    Some(value) if is_lob_placeholder(value, &self.lob_placeholders) => {
+                        lob_omitted = true;
+                        format!("{} = NULL", oracle_identifier(h))
+                    }
+                    cell => format!("{} = {}", oracle_identifier(h), sql_literal(cell)),
+                })
+                .collect();
+            let where_clause: Vec<String> = key_indices
+                .iter()
+                .map(|&k| {
+                    format!(
+                        "{} = {}",
+                        oracle_identifier(&export_data.headers[k]),
+                        sql_literal(&row[k])
+                    )
+                })
+                .collect();
+            result = result
+                + &format!(
+                    "UPDATE {} SET {} WHERE {};{}\n",
+                    table_name,
+                    set_list.join(", "),
+                    where_clause.join(" AND "),
+                    lob_comment(lob_omitted)
+                );
+        }
+        Ok(result)
+    }
+}
+
+/// Creates a SQL*Loader control file and the matching comma separated data file.
+pub struct SqlLoaderFormatter {
+    pub table_name: String,
+    // name of the data file as referenced by INFILE in the control file
+    pub data_file_name: String,
+    // cells with these values are loaded as NULL, see `is_lob_placeholder`
+    pub lob_placeholders: Vec<String>,
+}
+
+/// The two parts of a SQL*Loader export
+#[derive(Debug, PartialEq)]
+pub struct SqlLoaderExport {
+    pub control_file: String,
+    pub data_file: String,
+}
+
+impl SqlLoaderFormatter {
+    pub fn new(table_name: &str, data_file_name: &str) -> SqlLoaderFormatter {
+        SqlLoaderFormatter {
+            table_name: table_name.trim().to_string(),
+            data_file_name: data_file_name.to_string(),
+            lob_placeholders: DEFAULT_LOB_PLACEHOLDERS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn format(&self, export_data: &ExportData) -> Result<SqlLoaderExport, ExportError> {
+        let infile = format!("'{}'", self.data_file_name.replace('\'', "''"));
+        Ok(SqlLoaderExport {
+            control_file: self.control_file(export_data, &infile)?,
+            data_file: self.data_file(export_data),
+        })
+    }
+
+    /// A single control file containing the data after BEGINDATA
+    pub fn format_inline(&self, export_data: &ExportData) -> Result<String, ExportError> {
+        Ok(self.control_file(export_data, "*")? + "BEGINDATA\n" + &self.data_file(export_data))
+    }
+
+    fn control_file(&self, export_data: &ExportData, infile: &str) -> Result<String, ExportError> {
+        let table_name = valid_table_name(&self.table_name)?;
+        let columns: Vec<String> = export_data
+            .headers
+            .iter()
+            .map(|h| format!("  {}", oracle_identifier(h)))
+            .collect();
+        // records holding line breaks need a terminator that can't be mistaken for one of them
+        let record_format = match has_line_breaks(export_data) {
+            true => format!(" \"str X'{}'\"", hex(SQL_LOADER_RECORD_TERMINATOR)),
+            false => String::new(),
+        };
+        Ok(format!(
+            "LOAD DATA\n\
+             INFILE {}{}\n\
+             APPEND\n\
+             INTO TABLE {}\n\
+             FIELDS TERMINATED BY ',' OPTIONALLY ENCLOSED BY '\"'\n\
+             TRAILING NULLCOLS\n\
+             (\n\
+             {}\n\
+             )\n",
+            infile,
+            record_format,
+            table_name,
+            columns.join(",\n")
+        ))
+    }
+
+    fn data_file(&self, export_data: &ExportData) -> String {
+        let terminator = match has_line_breaks(export_data) {
+            true => SQL_LOADER_RECORD_TERMINATOR,
+            false => "\n",
+        };
+        let mut data_file = String::new();
+        for row in &export_data.data {
+            let fields: Vec<String> = row
+                .iter()
+                .map(|cell| match cell {
+                    Some(value) if is_lob_placeholder(value, &self.lob_placeholders) => {
+                        String::new()
+                    }
+                    cell => sql_loader_field(cell),
+                })
+                .collect();
+            data_file = data_file + &fields.join(",") + terminator;
+        }
+        data_file
+    }
+}
+
+// ends the records of a SQL*Loader data file with line breaks inside values: the ASCII record
+// separator, followed by a line break to keep the file readable
+const SQL_LOADER_RECORD_TERMINATOR: &str = "\u{1e}\n";
+
+fn has_line_breaks(export_data: &ExportData) -> bool {
+    export_data
+        .data
+        .iter()
+        .flatten()
+        .flatten()
+        .any(|value| value.contains(['\n', '\r']))
+}
+
+fn hex(text: &str) -> String {
+    text.bytes().map(|b| format!("{:02X}", b)).collect()
+}
+
+// Enclose a cell value in double quotes, doubling embedded quotes; NULL is written as empty
+// field
+fn sql_loader_field(cell: &Option<String>) -> String {
+    match cell {
+        None => String::new(),
+        Some(value) => format!("\"{}\"", value.replace('"', "\"\"")),
+    }
+}
+
+/// Creates a PostgreSQL `COPY ... FROM stdin` block, with the rows in text format.
+pub struct PostgresCopyFormatter {
+    pub table_name: String,
+    // cells with these values are loaded as NULL, see `is_lob_placeholder`
+    pub lob_placeholders: Vec<String>,
+}
+
+impl PostgresCopyFormatter {
+    pub fn new(table_name: &str) -> PostgresCopyFormatter {
+        PostgresCopyFormatter {
+            table_name: table_name.trim().to_string(),
+            lob_placeholders: DEFAULT_LOB_PLACEHOLDERS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn format(&self, export_data: &ExportData) -> Result<String, ExportError> {
+        lazy_static! {
+            // a quoted part may contain dots itself
+            static ref NAME_PART: Regex = Regex::new(r#""[^"]+"|[^.]+"#).unwrap();
+        }
+        let table_name = valid_table_name(&self.table_name)?;
+        let table_name: Vec<String> = NAME_PART
+            .find_iter(&table_name)
+            .map(|part| part.as_str())
+            .map(|part| match part.starts_with('"') {
+                true => part.to_string(),
+                // PostgreSQL folds unquoted names to lowercase
+                false => postgres_identifier(&part.to_lowercase()),
+            })
+            .collect();
+        // plain (uppercase) Oracle column names map to plain (lowercase) PostgreSQL ones
+        let columns: Vec<String> = export_data
+            .headers
+            .iter()
+            .map(|h| match oracle_identifier(h).starts_with('"') {
+                true => postgres_identifier(h),
+                false => postgres_identifier(&h.to_lowercase()),
+            })
+            .collect();
+        let mut result = format!(
+            "COPY {} ({}) FROM stdin;\n",
+            table_name.join("."),
+            columns.join(", ")
+        );
+        for row in &export_data.data {
+            let fields: Vec<String> = row
+                .iter()
+                .map(|cell| match cell {
+                    Some(value) if !is_lob_placeholder(value, &self.lob_placeholders) => {
+                        postgres_copy_field(value)
+                    }
+                    _ => POSTGRES_NULL.to_string(),
+                })
+                .collect();
+            result = result + &fields.join("\t") + "\n";
+        }
+        Ok(result + "\\.\n")
+    }
+}
+
+// A name in double quotes, so PostgreSQL keeps it as it is even if it's a keyword there
+fn postgres_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+const POSTGRES_NULL: &str = "\\N";
+
+// Escape a cell value per the rules of COPY's text format, where NULL is \N
+fn postgres_copy_field(value: &str) -> String {
+    let mut field = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => field.push_str("\\\\"),
+            '\t' => field.push_str("\\t"),
+            '\n' => field.push_str("\\n"),
+            '\r' => field.push_str("\\r"),
+            c => field.push(c),
+        }
+    }
+    field
+}
+
+const LOB_OMITTED_COMMENT: &str = " -- TODO: LOB value omitted";
+
+// Check whether the cell holds a placeholder for an unfetched LOB / LONG value instead of data
+pub fn is_lob_placeholder(value: &str, placeholders: &[String]) -> bool {
+    placeholders
+        .iter()
+        .any(|p| p.eq_ignore_ascii_case(value.trim()))
+}
+
+// Number of LOB placeholder cells, i.e. the values the SQL formats export as NULL
+pub fn count_lob_placeholders(export_data: &ExportData, placeholders: &[String]) -> usize {
+    export_data
+        .data
+        .iter()
+        .flatten()
+        .flatten()
+        .filter(|value| is_lob_placeholder(value, placeholders))
+        .count()
+}
+
+// Comment appended to statements in which LOB values were replaced by NULL
+fn lob_comment(lob_omitted: bool) -> &'static str {
+    match lob_omitted {
+        true => LOB_OMITTED_COMMENT,
+        false => "",
+    }
+}
+
+// Quote a cell value as SQL string literal; NULL is returned as NULL keyword
+fn sql_literal(cell: &Option<String>) -> String {
+    match cell {
+        None => "NULL".to_string(),
+        Some(value) => format!("'{}'", value.replace('\'', "''")),
+    }
+}
+
+// A value of an INSERT statement: numbers bare, except with leading zeros (e.g. a postal code
+// like 0815), which a number column would drop; with the column type known, only the values of
+// numeric columns
+fn insert_value(cell: &Option<String>, numeric: Option<bool>) -> String {
+    let value = cell.as_deref().unwrap_or_default();
+    let digits = value.trim_start_matches(['+', '-']);
+    let leading_zero = digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.");
+    let bare = match numeric {
+        Some(numeric) => numeric,
+        None => !leading_zero,
     };
-    let str_buf: String = str_slice.to_owned();
-    // still in header part? append to header vec
-    if !export_data.prepared {
-        export_data.headers.push(str_buf);
+    match is_plain_number(value) && bare {
+        true => value.to_string(),
+        false => sql_literal(cell),
     }
-    // otherwise: append to current row, and start a new row if necessary
-    else {
-        export_data.current_row.push(str_buf);
-        if export_data.current_row.len() == export_data.num_columns() {
-            let current_row = export_data.current_row.clone();
-            export_data.data.push(current_row);
-            export_data.current_row = vec![];
+}
+
+// Whether values of the SQL data type (e.g. `NUMBER(10,2)`) are numbers
+fn is_numeric_type(column_type: &str) -> bool {
+    let base = column_type
+        .split('(')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_uppercase();
+    matches!(
+        base.as_str(),
+        "NUMBER"
+            | "INTEGER"
+            | "INT"
+            | "SMALLINT"
+            | "DECIMAL"
+            | "NUMERIC"
+            | "FLOAT"
+            | "REAL"
+            | "DOUBLE PRECISION"
+            | "BINARY_FLOAT"
+            | "BINARY_DOUBLE"
+            | "PLS_INTEGER"
+            | "BINARY_INTEGER"
+    )
+}
+
+// Set the cells holding a LOB placeholder to NULL
+fn null_lob_placeholders(export_data: &ExportData, lob_placeholders: &[String]) -> ExportData {
+    let mut result = export_data.clone();
+    for cell in result.data.iter_mut().flatten() {
+        if matches!(cell, Some(value) if is_lob_placeholder(value, lob_placeholders)) {
+            *cell = None;
         }
     }
-    return true;
+    result
 }
 
-// This function allows you to prepare for the actual data
-// All values received with Exportdata before this function is called are column headers,
-// and all values received after ExportPrepare is data.
-#[allow(non_snake_case)]
-#[no_mangle]
-pub extern "C" fn ExportPrepare() -> bool {
-    //let caption = CStr::from_bytes_with_nul(b"ExportPrepare\0").unwrap();
-    //show_message_box(&caption, &caption, MB_OK | MB_ICONINFORMATION);
-    let mut export_data = EXPORT_DATA.write().unwrap();
-    export_data.prepared = true;
-    return true;
+// Whether numeric cells are written with the configured decimal separator in this format;
+// SQL output needs the decimal point, regardless of the locale
+fn uses_decimal_separator(format: ExportFormat) -> bool {
+    match format {
+        ExportFormat::Wiki
+        | ExportFormat::Markdown
+        | ExportFormat::MarkdownCodeBlock
+        | ExportFormat::Csv
+        | ExportFormat::Html => true,
+        ExportFormat::Update
+        | ExportFormat::Insert
+        | ExportFormat::Json
+        | ExportFormat::SqlLoader
+        | ExportFormat::PostgresCopy => false,
+    }
 }
 
-#[allow(non_snake_case)]
-#[no_mangle]
-pub extern "C" fn RegisterExport() -> *mut c_char {
-    return EXPORT_TO_CLIPBOARD_AS_WIKI.as_ptr() as *mut c_char;
+// What a line break inside a cell is replaced with in this format, if anything: Wiki tables
+// can't hold one, Markdown and CSV only replace it if configured
+fn newline_replacement(format: ExportFormat, configured: Option<&str>) -> Option<&str> {
+    match format {
+        ExportFormat::Wiki => Some(configured.unwrap_or(" ")),
+        // Markdown writes line breaks as <br>, and CSV quotes them
+        ExportFormat::Markdown | ExportFormat::Csv => configured,
+        // the code block aligns the lines of a cell itself, the SQL formats and JSON quote line
+        // breaks, and HTML writes them as <br>
+        ExportFormat::MarkdownCodeBlock
+        | ExportFormat::Json
+        | ExportFormat::Html
+        | ExportFormat::Update
+        | ExportFormat::Insert
+        | ExportFormat::SqlLoader
+        | ExportFormat::PostgresCopy => None,
+    }
 }
 
-#[cfg(test)]
-mod tests {
+/// Replace the line breaks (CRLF, CR or LF) inside the cells with the replacement
+pub fn replace_newlines(export_data: &ExportData, replacement: &str) -> ExportData {
+    let mut replaced = export_data.clone();
+    for cell in replaced.data.iter_mut().flatten().flatten() {
+        if cell.contains(['\r', '\n']) {
+            *cell = cell
+                .replace("\r\n", "\n")
+                .replace(['\r', '\n'], replacement);
+        }
+    }
+    replaced
+}
 
-    use crate::export::*;
+fn is_plain_number(value: &str) -> bool {
+    lazy_static! {
+        // no thousands grouping - a value like 1,234.5 must not be turned into 1,234,5
+        static ref PLAIN_NUMBER: Regex =
+            Regex::new(r"^[+-]?(\d+\.?\d*|\.\d+)([eE][+-]?\d+)?$").unwrap();
+    }
+    PLAIN_NUMBER.is_match(value)
+}
+
+const REDACTED_VALUE: &str = "***";
 
-    // Create a vector from string literals, i.e. vec_of_strings!["a", "b", "c"]
-    macro_rules! vec_of_strings {
-      ($($x:expr),*) => (vec![$($x.to_string()),*]);
+/// Replace all values (including NULL) of the given columns (matched case-insensitively)
+/// with ***
+pub fn redact_columns(export_data: &ExportData, columns: &[String]) -> ExportData {
+    let redacted: Vec<bool> = export_data
+        .headers
+        .iter()
+        .map(|h| columns.iter().any(|c| c.trim().eq_ignore_ascii_case(h)))
+        .collect();
+    let mut result = export_data.clone();
+    for row in &mut result.data {
+        for (cell, _) in row.iter_mut().zip(&redacted).filter(|(_, &r)| r) {
+            *cell = Some(REDACTED_VALUE.to_string());
+        }
     }
+    result
+}
 
-    #[test]
-    fn to_string_should_return_wiki_syntax() {
-        let export_data = ExportData {
-            headers: vec_of_strings!["h1", "h2", "h3"],
-            data: vec![
-                vec_of_strings!["d11", "d12", "d13"],
-                vec_of_strings!["d21", "d22", "d23"],
-            ],
-            current_row: vec![],
-            prepared: true,
+/// Replace the decimal point in numeric columns with the given separator.
+/// A column is considered numeric if it has at least one value, and all of its values are plain
+/// numbers; other columns (including numbers with thousands grouping) are left untouched.
+pub fn localize_decimal_separator(export_data: &ExportData, separator: char) -> ExportData {
+    let numeric_columns: Vec<bool> = (0..export_data.num_columns())
+        .map(|i| {
+            let mut values = export_data
+                .data
+                .iter()
+                .filter_map(|row| row[i].as_deref())
+                .filter(|value| !value.is_empty())
+                .peekable();
+            values.peek().is_some() && values.all(is_plain_number)
+        })
+        .collect();
+
+    let mut result = ExportData::new();
+    result.headers = export_data.headers.clone();
+    result.prepared = export_data.prepared;
+    result.data = export_data
+        .data
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(numeric_columns.iter())
+                .map(|(cell, &numeric)| match numeric {
+                    true => cell
+                        .as_ref()
+                        .map(|value| value.replace('.', &separator.to_string())),
+                    false => cell.clone(),
+                })
+                .collect()
+        })
+        .collect();
+    result
+}
+
+// label of the group containing the rows where the group column is NULL
+const NULL_GROUP_LABEL: &str = "(null)";
+
+/// Split the rows into groups by the value of the given column.
+/// Groups are returned in the order of their first appearance, the NULL group always comes last.
+/// Optionally, the group column is removed from the grouped data.
+pub fn partition_by_column(
+    export_data: &ExportData,
+    column: &str,
+    drop_column: bool,
+) -> Result<Vec<(Option<String>, ExportData)>, ExportError> {
+    let column_index = export_data
+        .headers
+        .iter()
+        .position(|h| h.eq_ignore_ascii_case(column))
+        .ok_or_else(|| ExportError::UnknownGroupColumn(column.to_string()))?;
+
+    fn without_column<T: Clone>(row: &[T], column_index: Option<usize>) -> Vec<T> {
+        row.iter()
+            .enumerate()
+            .filter(|(i, _)| Some(*i) != column_index)
+            .map(|(_, cell)| cell.clone())
+            .collect()
+    }
+    let dropped_index = Some(column_index).filter(|_| drop_column);
+    let new_group = || {
+        let mut group = ExportData::new();
+        group.headers = without_column(&export_data.headers, dropped_index);
+        group.prepared = true;
+        group
+    };
+
+    let mut groups: Vec<(Option<String>, ExportData)> = vec![];
+    let mut null_group: Option<ExportData> = None;
+    for row in &export_data.data {
+        let group = match &row[column_index] {
+            None => null_group.get_or_insert_with(new_group),
+            value => match groups.iter().position(|(v, _)| v == value) {
+                Some(position) => &mut groups[position].1,
+                None => {
+                    groups.push((value.clone(), new_group()));
+                    &mut groups.last_mut().unwrap().1
+                }
+            },
         };
-        assert_eq!(
-            "||h1||h2||h3||\n|d11|d12|d13|\n|d21|d22|d23|\n",
-            export_data.to_string()
+        group.data.push(without_column(row, dropped_index));
+    }
+    if let Some(group) = null_group {
+        groups.push((None, group));
+    }
+    Ok(groups)
+}
+
+/// Wrap the data cells longer than the width, joining their lines with the line break
+fn wrap_cells(export_data: &ExportData, width: usize, line_break: &str) -> ExportData {
+    let mut wrapped = export_data.clone();
+    for cell in wrapped.data.iter_mut().flatten().flatten() {
+        *cell = wrap_text(cell, width).join(line_break);
+    }
+    wrapped
+}
+
+/// Pair the headers with the column types the IDE reported; empty if the types don't match
+/// the columns
+pub fn header_types(headers: &[String], types: &[String]) -> Vec<(String, String)> {
+    if types.len() != headers.len() {
+        if !types.is_empty() {
+            warn!(target: target::EXPORT,
+                "Got {} column types for {} columns, leaving them out",
+                types.len(),
+                headers.len()
+            );
+        }
+        return vec![];
+    }
+    headers.iter().cloned().zip(types.iter().cloned()).collect()
+}
+
+/// The `(type)` annotation of each header, looked up by name, so it survives column selection
+/// and grouping; None without column types
+fn type_annotations(headers: &[String], column_types: &[(String, String)]) -> Option<Vec<String>> {
+    if column_types.is_empty() {
+        return None;
+    }
+    let annotation = |header: &String| {
+        column_types
+            .iter()
+            .find(|(name, _)| name == header)
+            .map(|(_, column_type)| format!("({})", column_type))
+            .unwrap_or_default()
+    };
+    Some(headers.iter().map(annotation).collect())
+}
+
+/// The data in Wiki syntax, with a second header row holding the column types if known
+fn wiki_table(
+    export_data: &ExportData,
+    column_types: &[(String, String)],
+    null_repr: &str,
+) -> String {
+    let table = export_data.to_string(null_repr);
+    match type_annotations(&export_data.headers, column_types) {
+        Some(annotations) => {
+            // a Wiki table cell can't be empty
+            let cells: Vec<&str> = annotations
+                .iter()
+                .map(|a| if a.is_empty() { " " } else { a.as_str() })
+                .collect();
+            let (header_row, rows) = table.split_at(table.find('\n').map_or(0, |i| i + 1));
+            format!("{}||{}||\n{}", header_row, cells.join("||"), rows)
+        }
+        None => table,
+    }
+}
+
+/// The data as a table with aligned columns, for monospaced display;
+/// data cells longer than the wrap width continue on the following lines,
+/// the column types (if known) go on a line below the headers, NULL is written as `null_repr`
+pub fn fixed_width_table(
+    export_data: &ExportData,
+    wrap_width: Option<usize>,
+    column_types: &[(String, String)],
+    null_repr: &str,
+) -> String {
+    // line breaks inside cells would break the alignment
+    let one_line = |cell: &str| cell.replace("\r\n", " ").replace(['\r', '\n'], " ");
+    let headers: Vec<String> = export_data.headers.iter().map(|h| one_line(h)).collect();
+    let annotations = type_annotations(&headers, column_types);
+    let rows: Vec<Vec<Vec<String>>> = export_data
+        .data
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| cell.as_deref().unwrap_or(null_repr))
+                .map(|cell| match wrap_width {
+                    Some(width) => wrap_text(&one_line(cell), width),
+                    None => vec![one_line(cell)],
+                })
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .flatten()
+                .chain(std::iter::once(&headers[i]))
+                .chain(annotations.iter().map(|a| &a[i]))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        padded.join(" | ").trim_end().to_string() + "\n"
+    };
+    let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    let mut result = line(&headers);
+    if let Some(annotations) = &annotations {
+        result += &line(annotations);
+    }
+    result += &separator.join("-+-");
+    result += "\n";
+    for row in &rows {
+        let height = row.iter().map(Vec::len).max().unwrap_or(1);
+        for i in 0..height {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|lines| lines.get(i).cloned().unwrap_or_default())
+                .collect();
+            result += &line(&cells);
+        }
+    }
+    result
+}
+
+/// The data as a Markdown table; a table has only one header row, so the column types (if
+/// known) are appended to the headers
+fn markdown_table(
+    export_data: &ExportData,
+    column_types: &[(String, String)],
+    null_repr: &str,
+) -> String {
+    // without a null representation, as copied by ExportFinishedMarkdown
+    let render = |data: &ExportData| match null_repr.is_empty() {
+        true => data.to_gfm_markdown(),
+        false => data.to_markdown(null_repr),
+    };
+    match type_annotations(&export_data.headers, column_types) {
+        Some(annotations) => {
+            let mut annotated = export_data.clone();
+            for (header, annotation) in annotated.headers.iter_mut().zip(annotations) {
+                if !annotation.is_empty() {
+                    *header = format!("{} {}", header, annotation);
+                }
+            }
+            render(&annotated)
+        }
+        None => render(export_data),
+    }
+}
+
+/// The fixed-width table in a fenced Markdown code block with an optional language tag
+pub fn markdown_code_block(
+    export_data: &ExportData,
+    language: Option<&str>,
+    wrap_width: Option<usize>,
+    column_types: &[(String, String)],
+    null_repr: &str,
+) -> String {
+    let table = fixed_width_table(export_data, wrap_width, column_types, null_repr);
+    // the fence must be longer than any run of backticks in the data
+    let longest_run = table.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(std::cmp::max(3, longest_run + 1));
+    format!("{}{}\n{}{}\n", fence, language.unwrap_or(""), table, fence)
+}
+
+/// Render the caption preceding a group in the given format
+pub fn group_caption(format: ExportFormat, value: &Option<String>) -> String {
+    let label = value.as_deref().unwrap_or(NULL_GROUP_LABEL);
+    match format {
+        ExportFormat::Wiki => format!("h3. {}\n", label),
+        ExportFormat::Markdown | ExportFormat::MarkdownCodeBlock => format!("### {}\n", label),
+        ExportFormat::Html => format!("<h3>{}</h3>\n", escape_html(label)),
+        ExportFormat::Update
+        | ExportFormat::Insert
+        | ExportFormat::SqlLoader
+        | ExportFormat::PostgresCopy => format!("-- {}\n", label),
+        // CSV and JSON output isn't grouped
+        ExportFormat::Csv | ExportFormat::Json => format!("{}\n", label),
+    }
+}
+
+fn render_groups<F>(
+    groups: &[(Option<String>, ExportData)],
+    format: ExportFormat,
+    render: F,
+) -> Result<String, ExportError>
+where
+    F: Fn(&ExportData) -> Result<String, ExportError>,
+{
+    let mut result = String::new();
+    for (value, group) in groups {
+        result = result + &group_caption(format, value) + &render(group)? + "\n";
+    }
+    Ok(result)
+}
+
+/// Everything besides the data and the format that determines the rendered export:
+/// the export settings of the config, plus the answers to the questions some formats ask
+pub struct RenderOptions {
+    pub group_by: Option<String>,
+    pub drop_group_column: bool,
+    pub decimal_separator: char,
+    pub lob_placeholders: Vec<String>,
+    pub redact_columns: Vec<String>,
+    pub masking_rules: Vec<(String, MaskingStrategy)>,
+    // target table of the UPDATE statements / the SQL*Loader control file / the COPY block
+    pub table_name: String,
+    // key columns of the UPDATE statements
+    pub key_columns: Vec<String>,
+    pub markdown_codeblock_language: Option<String>,
+    pub csv_delimiter: char,
+    pub csv_qualifier: char,
+    pub csv_null: String,
+    pub json_pretty: bool,
+    pub html_style: bool,
+    // NULL in the table formats (Wiki, Markdown, code block); the SQL, CSV, JSON and HTML
+    // formats have their own way of writing it
+    pub null_representation: String,
+    pub newline_replacement: Option<String>,
+    pub wrap_width: Option<usize>,
+    // (header, SQL data type) of the columns, empty if unknown
+    pub column_types: Vec<(String, String)>,
+}
+
+impl RenderOptions {
+    pub fn from_config(config: &Config) -> RenderOptions {
+        RenderOptions {
+            group_by: config.group_by.clone(),
+            drop_group_column: config.group_by_drop_column,
+            decimal_separator: config.decimal_separator,
+            lob_placeholders: config.lob_placeholders.clone(),
+            redact_columns: config.redact_columns.clone(),
+            masking_rules: config.masking_rules.clone(),
+            table_name: String::new(),
+            key_columns: vec![],
+            markdown_codeblock_language: config.markdown_codeblock_language.clone(),
+            csv_delimiter: config.csv_delimiter,
+            csv_qualifier: config.csv_qualifier,
+            csv_null: config.csv_null.clone(),
+            json_pretty: config.json_pretty,
+            html_style: config.html_style,
+            null_representation: config.null_representation.clone(),
+            newline_replacement: config.newline_replacement.clone(),
+            wrap_width: config.wrap_width,
+            column_types: vec![],
+        }
+    }
+}
+
+/// The data as it is serialized: redacted, and with localized numbers where the format allows
+pub fn prepare_export_data(
+    export_data: &ExportData,
+    format: ExportFormat,
+    options: &RenderOptions,
+) -> ExportData {
+    // redact first, so no formatter ever sees the real values
+    let redacted = redact_columns(export_data, &options.redact_columns);
+    let (mut prepared, _) = mask_data(&redacted, &options.masking_rules);
+    if options.decimal_separator != '.' && uses_decimal_separator(format) {
+        prepared = localize_decimal_separator(&prepared, options.decimal_separator);
+    }
+    if let Some(replacement) = newline_replacement(format, options.newline_replacement.as_deref()) {
+        prepared = replace_newlines(&prepared, replacement);
+    }
+    prepared
+}
+
+/// Render the export in the given format, without any FFI or user interaction.
+/// SQL*Loader output is a single control file with the data inlined after BEGINDATA.
+pub fn render_export(
+    export_data: &ExportData,
+    format: ExportFormat,
+    options: &RenderOptions,
+) -> Result<String, ExportError> {
+    let export_data = prepare_export_data(export_data, format, options);
+    // a control file can only load into one table, so SQL*Loader output isn't grouped,
+    // and neither are CSV, which is one table to a spreadsheet, and JSON, which is one array
+    let group_by = options.group_by.as_ref().filter(|_| {
+        !matches!(
+            format,
+            ExportFormat::SqlLoader | ExportFormat::Csv | ExportFormat::Json
+        )
+    });
+    let render = |data: &ExportData| match format {
+        ExportFormat::Wiki => match options.wrap_width {
+            // the line breaks become forced ones, see wiki_cell
+            Some(width) => Ok(wiki_table(
+                &wrap_cells(data, width, "\n"),
+                &options.column_types,
+                &options.null_representation,
+            )),
+            None => Ok(wiki_table(
+                data,
+                &options.column_types,
+                &options.null_representation,
+            )),
+        },
+        ExportFormat::Markdown => match options.wrap_width {
+            Some(width) => Ok(markdown_table(
+                &wrap_cells(data, width, "<br>"),
+                &options.column_types,
+                &options.null_representation,
+            )),
+            None => Ok(markdown_table(
+                data,
+                &options.column_types,
+                &options.null_representation,
+            )),
+        },
+        ExportFormat::Update => {
+            let key_columns: Vec<&str> = options.key_columns.iter().map(String::as_str).collect();
+            let mut formatter = UpdateFormatter::new(&options.table_name, &key_columns);
+            formatter.lob_placeholders = options.lob_placeholders.clone();
+            formatter.format(data)
+        }
+        ExportFormat::SqlLoader => {
+            let mut formatter = SqlLoaderFormatter::new(&options.table_name, "");
+            formatter.lob_placeholders = options.lob_placeholders.clone();
+            formatter.format_inline(data)
+        }
+        ExportFormat::PostgresCopy => {
+            let mut formatter = PostgresCopyFormatter::new(&options.table_name);
+            formatter.lob_placeholders = options.lob_placeholders.clone();
+            formatter.format(data)
+        }
+        ExportFormat::Csv => Ok(data.to_csv(
+            options.csv_delimiter,
+            options.csv_qualifier,
+            &options.csv_null,
+        )),
+        ExportFormat::Json => Ok(data.to_json(options.json_pretty)),
+        // the tables of the groups share a single style block
+        ExportFormat::Html => Ok(data.to_html(options.html_style && group_by.is_none())),
+        ExportFormat::Insert => null_lob_placeholders(data, &options.lob_placeholders)
+            .to_insert_statements(&options.table_name, &options.column_types),
+        ExportFormat::MarkdownCodeBlock => Ok(markdown_code_block(
+            data,
+            options.markdown_codeblock_language.as_deref(),
+            options.wrap_width,
+            &options.column_types,
+            &options.null_representation,
+        )),
+    };
+    match group_by {
+        Some(column) => {
+            let groups = partition_by_column(&export_data, column, options.drop_group_column)?;
+            let rendered = render_groups(&groups, format, render)?;
+            match format == ExportFormat::Html && options.html_style {
+                true => Ok(format!("{}{}", HTML_STYLE, rendered)),
+                false => Ok(rendered),
+            }
+        }
+        None => render(&export_data),
+    }
+}
+
+lazy_static! {
+  // See https://stackoverflow.com/questions/59679968/static-array-of-trait-objects
+  pub static ref EXPORT_DATA: RwLock<ExportData> = RwLock::new(ExportData::new());
+}
+
+// Lock the export data for writing. A panic while it was locked (which must not take down the
+// IDE) poisons the lock; the half written data is then discarded and the export starts over,
+// or, with `salvage`, only the incomplete row is dropped.
+fn lock_export_data(lock: &RwLock<ExportData>, salvage: bool) -> RwLockWriteGuard<'_, ExportData> {
+    match lock.write() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            let mut guard = poisoned.into_inner();
+            match salvage {
+                true => {
+                    warn!(target: target::EXPORT,
+                        "Export data lock was poisoned, keeping the {} complete rows",
+                        guard.data.len()
+                    );
+                    guard.current_row.clear();
+                }
+                false => {
+                    warn!(target: target::EXPORT, "Export data lock was poisoned, discarding the export data");
+                    *guard = ExportData::new();
+                }
+            }
+            lock.clear_poison();
+            guard
+        }
+    }
+}
+
+// End the export session, taking the captured data; the next session starts empty anyway
+fn finish_session(lock: &RwLock<ExportData>, salvage: bool) -> ExportData {
+    let mut export_data = lock_export_data(lock, salvage);
+    export_data.ensure_session("ExportFinished");
+    export_data.active = false;
+    std::mem::replace(&mut *export_data, ExportData::new())
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn ExportInit() -> bool {
+    //let caption = CStr::from_bytes_with_nul(b"ExportInit\0").unwrap();
+    //show_message_box(&caption, &caption, MB_OK | MB_ICONINFORMATION);
+    export_init();
+    true
+}
+
+pub fn export_init() {
+    // a new export, nothing to salvage
+    let mut export_data = lock_export_data(&EXPORT_DATA, false);
+    export_data.init();
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn ExportFinished() {
+    //let caption = CStr::from_bytes_with_nul(b"ExportFinished\0").unwrap();
+    //show_message_box(&caption, &caption, MB_OK | MB_ICONINFORMATION);
+    export_finished();
+}
+
+pub fn export_finished() {
+    let export_format = read_config().export_format;
+    export_finished_as(export_format);
+}
+
+// Like export_finished, in the given format instead of the configured one
+pub fn export_finished_as(export_format: ExportFormat) {
+    export_finished_with(Config {
+        export_format,
+        ..config()
+    });
+}
+
+// Like export_finished, with the given config; a copy, so the lock isn't held while dialogs
+// are shown
+fn export_finished_with(config: Config) {
+    let captured_data = finish_session(&EXPORT_DATA, config.salvage_poisoned_export);
+    if captured_data.data.is_empty() {
+        dialogs().show_message(
+            "No rows were captured, so there is nothing to copy.",
+            "Nothing to export",
+            MessageKind::Information,
+        );
+        return;
+    }
+    let column_types = match config.export_column_types {
+        true => api().ide_get_export_column_types(),
+        false => vec![],
+    };
+    export_as_configured(&captured_data, &column_types, &config);
+}
+
+/// Render the data in the configured format and copy it to the configured destination, asking
+/// for the columns and the format's details as configured
+pub fn export_as_configured(captured_data: &ExportData, column_types: &[String], config: &Config) {
+    let export_format = config.export_format;
+    let mut options = RenderOptions::from_config(config);
+    options.column_types = header_types(&captured_data.headers, column_types);
+
+    let mut export_data: &ExportData = captured_data;
+    let selected;
+    if config.ask_for_columns {
+        let selection = match choose_columns(&COLUMN_SELECTIONS, &export_data.headers, |headers| {
+            dialogs().select_columns(headers)
+        }) {
+            Some(selection) => selection,
+            // user cancelled
+            None => return,
+        };
+        selected = match retain_columns(export_data, &selection) {
+            Ok(data) => data,
+            Err(e) => {
+                dialogs().show_message(&format!("{}", e), "Export failed", MessageKind::Error);
+                return;
+            }
+        };
+        export_data = &selected;
+    }
+    match export_format {
+        ExportFormat::SqlLoader => {
+            // two files can't go to the clipboard, so they are written next to each other
+            let prepared = prepare_export_data(export_data, export_format, &options);
+            export_to_sql_loader_files(
+                &prepared,
+                options.lob_placeholders,
+                config.verify_written_files,
+            );
+            return;
+        }
+        ExportFormat::Update => match ask_for_update_target() {
+            Some((table_name, key_columns)) => {
+                options.table_name = table_name;
+                options.key_columns = key_columns;
+            }
+            // user cancelled
+            None => return,
+        },
+        ExportFormat::PostgresCopy => {
+            match dialogs().get_user_input("Table name for the COPY block") {
+                Ok(table_name) => options.table_name = table_name,
+                // user cancelled
+                Err(_) => return,
+            }
+        }
+        ExportFormat::Insert => {
+            match dialogs().get_user_input("Table name for the INSERT statements") {
+                Ok(table_name) => options.table_name = table_name,
+                // user cancelled
+                Err(_) => return,
+            }
+        }
+        ExportFormat::Wiki
+        | ExportFormat::Markdown
+        | ExportFormat::MarkdownCodeBlock
+        | ExportFormat::Csv
+        | ExportFormat::Json
+        | ExportFormat::Html => (),
+    }
+    let text = match render_export(export_data, export_format, &options) {
+        Ok(text) => text,
+        Err(e) => {
+            dialogs().show_message(&format!("{}", e), "Export failed", MessageKind::Error);
+            return;
+        }
+    };
+    let omitted_lobs = match export_format {
+        ExportFormat::Update | ExportFormat::Insert | ExportFormat::PostgresCopy => {
+            count_lob_placeholders(
+                &prepare_export_data(export_data, export_format, &options),
+                &options.lob_placeholders,
+            )
+        }
+        _ => 0,
+    };
+    let mut notes = match omitted_lobs {
+        0 => String::new(),
+        n => format!(
+            "\n\n{} LOB value(s) could not be exported and were replaced by NULL.",
+            n
+        ),
+    };
+    let masked = masked_columns(&export_data.headers, &options.masking_rules);
+    if !masked.is_empty() {
+        notes.push_str(&format!("\n\nMasked columns: {}", masked.join(", ")));
+    }
+    if config.export_target == ExportTarget::TempFile {
+        export_to_temp_file(&std::env::temp_dir(), &text, &notes, export_format, config);
+        return;
+    }
+    let copied = match export_format {
+        // pasted as a formatted table where the markup isn't wanted
+        ExportFormat::Html => clipboard().copy_html_to_clipboard(&text, config.max_clipboard_bytes),
+        _ => clipboard().copy_to_clipboard(&text, config.max_clipboard_bytes),
+    };
+    match copied {
+        Ok(_) => {
+            let caption = "Results copied to clipboard";
+            dialogs().show_message(
+                &format!("{}{}", caption, notes),
+                caption,
+                MessageKind::Information,
+            );
+        }
+        // too large, or the allocation failed: offer a file instead
+        Err(e @ ClipboardError::TooLarge(..)) | Err(e @ ClipboardError::AllocationFailed(..)) => {
+            dialogs().show_message(
+                &format!("{}", e),
+                "Export to file",
+                MessageKind::Information,
+            );
+            export_to_file(&text, &notes, config.verify_written_files);
+        }
+        Err(e) => dialogs().show_message(
+            &format!("{}\nIf this problem persists, please file a bug report.", e),
+            "An error occured",
+            MessageKind::Error,
+        ),
+    }
+}
+
+// Ask for the file to export to; None if the user cancelled, or the dialog failed (which
+// is reported)
+fn choose_export_file() -> Option<PathBuf> {
+    let message = match dialogs().get_save_file_name() {
+        SaveDialogOutcome::Chosen(folder, file_name) => return Some(folder.join(file_name)),
+        SaveDialogOutcome::Cancelled => return None,
+        SaveDialogOutcome::EmptyName => "No file name was entered.".to_string(),
+        SaveDialogOutcome::Failed(e) => format!("Could not ask for the file name: {}", e),
+    };
+    dialogs().show_message(&message, "Export failed", MessageKind::Error);
+    None
+}
+
+// Write the rendered export to a file chosen by the user
+pub fn export_to_file(text: &str, notes: &str, verify: bool) {
+    let path = match choose_export_file() {
+        Some(path) => path,
+        None => return,
+    };
+    match write_verified(&path, text.as_bytes(), verify) {
+        Ok(_) => dialogs().show_message(
+            &format!("Results written to {}{}", path.display(), notes),
+            "Results exported",
+            MessageKind::Information,
+        ),
+        Err(e) => dialogs().show_message(&format!("{}", e), "Export failed", MessageKind::Error),
+    }
+}
+
+// The extension of a file containing an export in the format
+fn file_extension(format: ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Wiki => "txt",
+        ExportFormat::Markdown => "md",
+        ExportFormat::Update => "sql",
+        ExportFormat::SqlLoader => "ctl",
+        ExportFormat::MarkdownCodeBlock => "md",
+        ExportFormat::PostgresCopy => "sql",
+        ExportFormat::Csv => "csv",
+        ExportFormat::Json => "json",
+        ExportFormat::Insert => "sql",
+        ExportFormat::Html => "html",
+    }
+}
+
+// Write the export to a new file in the folder, named after the time of the export;
+// a counter is appended if another export got the same name
+fn write_temp_export(
+    dir: &Path,
+    text: &str,
+    extension: &str,
+    now: DateTime<Local>,
+    verify: bool,
+) -> std::io::Result<PathBuf> {
+    let stem = format!("xanthidae_export_{}", now.format("%Y%m%d_%H%M%S_%3f"));
+    let mut attempt = 0;
+    loop {
+        let file_name = match attempt {
+            0 => format!("{}.{}", stem, extension),
+            n => format!("{}_{}.{}", stem, n, extension),
+        };
+        let path = dir.join(file_name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => {
+                write_verified(&path, text.as_bytes(), verify)?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Write the rendered export to a new file in the folder, and copy the file's path to the
+// clipboard, so it can be opened right away
+fn export_to_temp_file(dir: &Path, text: &str, notes: &str, format: ExportFormat, config: &Config) {
+    let path = match write_temp_export(
+        dir,
+        text,
+        file_extension(format),
+        Local::now(),
+        config.verify_written_files,
+    ) {
+        Ok(path) => path,
+        Err(e) => {
+            dialogs().show_message(&format!("{}", e), "Export failed", MessageKind::Error);
+            return;
+        }
+    };
+    let shown = path.display().to_string();
+    let message = match clipboard().copy_to_clipboard(&shown, config.max_clipboard_bytes) {
+        Ok(_) => format!(
+            "Results written to {}, the path was copied to the clipboard{}",
+            shown, notes
+        ),
+        Err(e) => format!(
+            "Results written to {}, but the path could not be copied: {}{}",
+            shown, e, notes
+        ),
+    };
+    dialogs().show_message(&message, "Results exported", MessageKind::Information);
+}
+
+// Ask for the table name and the control file, then write <name>.ctl and <name>.dat
+fn export_to_sql_loader_files(
+    export_data: &ExportData,
+    lob_placeholders: Vec<String>,
+    verify: bool,
+) {
+    let table_name = match dialogs().get_user_input("Table name for SQL*Loader") {
+        Ok(table_name) => table_name,
+        // user cancelled
+        Err(_) => return,
+    };
+    if let Err(e) = valid_table_name(&table_name) {
+        dialogs().show_message(&format!("{}", e), "Export failed", MessageKind::Error);
+        return;
+    }
+    let control_path = match choose_export_file() {
+        Some(path) => path.with_extension("ctl"),
+        None => return,
+    };
+    let data_path = control_path.with_extension("dat");
+    // the data file is referenced relative to the control file
+    let data_file_name = data_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut formatter = SqlLoaderFormatter::new(&table_name, &data_file_name);
+    formatter.lob_placeholders = lob_placeholders;
+    let export = match formatter.format(export_data) {
+        Ok(export) => export,
+        Err(e) => {
+            dialogs().show_message(&format!("{}", e), "Export failed", MessageKind::Error);
+            return;
+        }
+    };
+    let res = write_verified(&control_path, export.control_file.as_bytes(), verify)
+        .and_then(|_| write_verified(&data_path, export.data_file.as_bytes(), verify));
+    match res {
+        Ok(_) => dialogs().show_message(
+            &format!(
+                "Written {} and {}",
+                control_path.display(),
+                data_path.display()
+            ),
+            "Results exported",
+            MessageKind::Information,
+        ),
+        Err(e) => dialogs().show_message(&format!("{}", e), "Export failed", MessageKind::Error),
+    }
+}
+
+// Ask the user for the table name and key columns of the UPDATE statements;
+// returns None if the user cancelled
+fn ask_for_update_target() -> Option<(String, Vec<String>)> {
+    let table_name = dialogs()
+        .get_user_input("Table name for the UPDATE statements")
+        .ok()?;
+    let key_columns = dialogs()
+        .get_user_input("Key column(s), separated by commas")
+        .ok()?;
+    let key_columns = key_columns.split(',').map(|c| c.to_string()).collect();
+    Some((table_name, key_columns))
+}
+
+/// One cell of data, this can be the column description or the actual data.
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn ExportData(value: *const c_char) -> bool {
+    //let caption = CStr::from_bytes_with_nul(b"ExportData\0").unwrap();
+    //show_message_box(&caption, &caption, MB_OK | MB_ICONINFORMATION);
+    // from https://doc.rust-lang.org/std/os/windows/ffi/index.html - this might work with some tweaking, but currently results in an access violation
+    //pub extern "C" fn ExportData(value: &[u16]) -> bool {
+    //let string = OsString::from_wide(value);
+    /*let str_buf: String = match string.into_string() {
+      Ok(s) => s,
+      Err(e) => "?".to_string()
+    };*/
+
+    // NULL is sent as an empty string, but a null pointer would crash the IDE
+    if value.is_null() {
+        export_value(String::new());
+        return true;
+    }
+    let c_str: &CStr = unsafe { CStr::from_ptr(value) };
+    // to_str() fails for non UTF-8 input(e.g. Strings containing umlauts - presumably, they're UTF-16 encoded?);
+    //   in that case, we simply return a question mark for the whole string
+    let str_slice: &str = match c_str.to_str() {
+        Ok(s) => s,
+        Err(_) => "?",
+    };
+    export_value(str_slice.to_owned());
+    true
+}
+
+pub fn export_value(str_buf: String) {
+    // read first, so the config and the data are never locked at the same time
+    let (drop_repeated_headers, salvage) = {
+        let config = read_config();
+        (config.drop_repeated_headers, config.salvage_poisoned_export)
+    };
+    let mut export_data = lock_export_data(&EXPORT_DATA, salvage);
+    export_data.ensure_session("ExportData");
+    export_data.push_value(str_buf, drop_repeated_headers);
+}
+
+// This function allows you to prepare for the actual data
+// All values received with Exportdata before this function is called are column headers,
+// and all values received after ExportPrepare is data.
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn ExportPrepare() -> bool {
+    //let caption = CStr::from_bytes_with_nul(b"ExportPrepare\0").unwrap();
+    //show_message_box(&caption, &caption, MB_OK | MB_ICONINFORMATION);
+    export_prepare();
+    true
+}
+
+pub fn export_prepare() {
+    let salvage = read_config().salvage_poisoned_export;
+    let mut export_data = lock_export_data(&EXPORT_DATA, salvage);
+    export_data.ensure_session("ExportPrepare");
+    export_data.prepare();
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn RegisterExport() -> *mut c_char {
+    register_export().as_ptr() as *mut c_char
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn RegisterExportMarkdown() -> *mut c_char {
+    EXPORT_TO_CLIPBOARD_AS_MARKDOWN.as_ptr() as *mut c_char
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn ExportFinishedMarkdown() {
+    // GitHub Flavored Markdown, see to_gfm_markdown
+    export_finished_with(Config {
+        export_format: ExportFormat::Markdown,
+        null_representation: String::new(),
+        ..config()
+    });
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn RegisterExportCsv() -> *mut c_char {
+    EXPORT_TO_CLIPBOARD_AS_CSV.as_ptr() as *mut c_char
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn ExportFinishedCsv() {
+    export_finished_as(ExportFormat::Csv);
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn RegisterExportJson() -> *mut c_char {
+    EXPORT_TO_CLIPBOARD_AS_JSON.as_ptr() as *mut c_char
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn ExportFinishedJson() {
+    export_finished_as(ExportFormat::Json);
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn RegisterExportHtml() -> *mut c_char {
+    EXPORT_TO_CLIPBOARD_AS_HTML.as_ptr() as *mut c_char
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn ExportFinishedHtml() {
+    export_finished_as(ExportFormat::Html);
+}
+
+// The IDE registers one export per plugin, so its name follows the configured format; the
+// formats with their own entry points (e.g. RegisterExportMarkdown) are there regardless
+pub fn register_export() -> &'static [u8] {
+    match read_config().export_format {
+        ExportFormat::Markdown => EXPORT_TO_CLIPBOARD_AS_MARKDOWN,
+        ExportFormat::Csv => EXPORT_TO_CLIPBOARD_AS_CSV,
+        ExportFormat::Json => EXPORT_TO_CLIPBOARD_AS_JSON,
+        ExportFormat::Html => EXPORT_TO_CLIPBOARD_AS_HTML,
+        _ => EXPORT_TO_CLIPBOARD_AS_WIKI,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chrono::TimeZone;
+    use indoc::indoc;
+
+    use crate::export::*;
+
+    fn push_values(values: &[&str], drop_repeated_headers: bool) -> ExportData {
+        let mut export_data = ExportData::new();
+        for value in &values[..2] {
+            export_data.push_value(value.to_string(), drop_repeated_headers);
+        }
+        export_data.prepared = true;
+        for value in &values[2..] {
+            export_data.push_value(value.to_string(), drop_repeated_headers);
+        }
+        export_data
+    }
+
+    #[test]
+    fn push_value_should_drop_headers_repeated_as_first_row() {
+        let export_data = push_values(&["ID", "NAME", "id", "Name", "1", "Snafu"], true);
+        assert_eq!(vec_of_strings!["ID", "NAME"], export_data.headers);
+        assert_eq!(vec![vec_of_cells!["1", "Snafu"]], export_data.data);
+        assert_eq!(2, export_data.rows_received);
+    }
+
+    #[test]
+    fn push_value_should_keep_legitimate_rows_looking_like_headers() {
+        // only the very first row is checked
+        let export_data = push_values(&["ID", "NAME", "1", "Snafu", "ID", "NAME"], true);
+        assert_eq!(
+            vec![vec_of_cells!["1", "Snafu"], vec_of_cells!["ID", "NAME"]],
+            export_data.data
+        );
+        // and not at all if disabled
+        let export_data = push_values(&["ID", "NAME", "ID", "NAME", "1", "Snafu"], false);
+        assert_eq!(
+            vec![vec_of_cells!["ID", "NAME"], vec_of_cells!["1", "Snafu"]],
+            export_data.data
+        );
+        // a dropped row doesn't make the next one the first
+        let export_data = push_values(&["ID", "NAME", "ID", "NAME", "ID", "NAME"], true);
+        assert_eq!(vec![vec_of_cells!["ID", "NAME"]], export_data.data);
+    }
+
+    #[test]
+    fn push_value_should_capture_large_exports_row_by_row() {
+        let headers = vec_of_strings!["ID", "NAME", "CITY", "NOTES"];
+        let cell = |row: usize, column: usize| match column {
+            // NULLs and the headers' text must not confuse the row boundaries
+            2 if row.is_multiple_of(7) => String::new(),
+            3 if row == 1 => "NOTES".to_string(),
+            _ => format!("{}-{}", row, column),
+        };
+        let rows = 3 * ROW_RESERVE_CHUNK + 5;
+
+        let mut captured = ExportData::new();
+        captured.init();
+        for header in &headers {
+            captured.push_value(header.clone(), true);
+        }
+        captured.prepare();
+        for row in 0..rows {
+            for column in 0..headers.len() {
+                captured.push_value(cell(row, column), true);
+            }
+        }
+
+        let expected = ExportData {
+            headers: headers.clone(),
+            data: (0..rows)
+                .map(|row| {
+                    (0..headers.len())
+                        .map(|column| Some(cell(row, column)).filter(|c| !c.is_empty()))
+                        .collect()
+                })
+                .collect(),
+            current_row: vec![],
+            prepared: true,
+            rows_received: rows,
+            active: true,
+        };
+        assert_eq!(rows, captured.rows_received);
+        assert!(captured.current_row.is_empty());
+        assert_eq!(expected.data, captured.data);
+        assert_eq!(expected.to_string(" "), captured.to_string(" "));
+        let options = RenderOptions::from_config(&Config::default());
+        assert_eq!(
+            render_export(&expected, ExportFormat::MarkdownCodeBlock, &options).unwrap(),
+            render_export(&captured, ExportFormat::MarkdownCodeBlock, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn to_string_should_return_wiki_syntax() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["h1", "h2", "h3"],
+            data: vec![
+                vec_of_cells!["d11", "d12", "d13"],
+                vec_of_cells!["d21", "d22", "d23"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        assert_eq!(
+            "||h1||h2||h3||\n|d11|d12|d13|\n|d21|d22|d23|\n",
+            export_data.to_string(" ")
+        );
+    }
+
+    #[test]
+    fn to_string_should_write_nulls_as_a_space() {
+        // select 1 a, null b, '2a' c from dual union all ...
+        let export_data = ExportData {
+            headers: vec_of_strings!["A", "", "C"],
+            data: vec![
+                vec_of_cells!["1", "", "2a"],
+                vec_of_cells!["2", "2b", ""],
+                vec_of_cells!["", "3b", "3c"],
+                vec_of_cells!["", "", ""],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        assert_eq!(
+            "||A|| ||C||\n|1| |2a|\n|2|2b| |\n| |3b|3c|\n| | | |\n",
+            export_data.to_string(" ")
+        );
+    }
+
+    #[test]
+    fn render_export_should_write_nulls_as_configured() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["A", "B"],
+            data: vec![vec![None, Some(String::new())]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let mut options = RenderOptions::from_config(&Config::default());
+        let render =
+            |format, options: &RenderOptions| render_export(&export_data, format, options).unwrap();
+        // by default, NULL and an empty string look the same in the tables
+        assert_eq!("||A||B||\n| | |\n", render(ExportFormat::Wiki, &options));
+        options.null_representation = "NULL".to_string();
+        assert_eq!("||A||B||\n|NULL| |\n", render(ExportFormat::Wiki, &options));
+        assert_eq!(
+            "| A | B |\n| --- | --- |\n| NULL |   |\n",
+            render(ExportFormat::Markdown, &options)
+        );
+        assert_eq!(
+            "```\nA    | B\n-----+--\nNULL |\n```\n",
+            render(ExportFormat::MarkdownCodeBlock, &options)
+        );
+        // the other formats tell them apart by themselves
+        assert_eq!("A,B\r\n,\"\"\r\n", render(ExportFormat::Csv, &options));
+        assert_eq!("[{\"A\":null,\"B\":\"\"}]\n", export_data.to_json(false));
+        options.table_name = "T".to_string();
+        assert_eq!(
+            "INSERT INTO T (A, B) VALUES (NULL, '');\n",
+            render(ExportFormat::Insert, &options)
+        );
+    }
+
+    #[test]
+    fn to_string_should_escape_pipes() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["A|B", "C"],
+            data: vec![vec_of_cells!["x||y", "|"], vec_of_cells!["|start", "end|"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        assert_eq!(
+            "||A\\|B||C||\n|x\\|\\|y|\\||\n|\\|start|end\\||\n",
+            export_data.to_string(" ")
+        );
+    }
+
+    #[test]
+    fn to_csv_should_quote_as_rfc_4180() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "NAME", "NOTES"],
+            data: vec![
+                vec_of_cells!["1", "O'Brien, Pat", "says \"hi\""],
+                vec_of_cells!["2", "Müller; Hans", "line 1\nline 2"],
+                vec_of_cells!["3", "", "1,5"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        assert_eq!(
+            "ID,NAME,NOTES\r\n\
+             1,\"O'Brien, Pat\",\"says \"\"hi\"\"\"\r\n\
+             2,Müller; Hans,\"line 1\nline 2\"\r\n\
+             3,,\"1,5\"\r\n",
+            export_data.to_csv(',', '"', "")
+        );
+        assert_eq!(
+            "ID;NAME;NOTES\r\n\
+             1;O'Brien, Pat;\"says \"\"hi\"\"\"\r\n\
+             2;\"Müller; Hans\";\"line 1\nline 2\"\r\n\
+             3;;1,5\r\n",
+            export_data.to_csv(';', '"', "")
+        );
+    }
+
+    #[test]
+    fn to_csv_should_use_qualifier_and_null_representation() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "NAME"],
+            data: vec![
+                vec_of_cells!["1", "it's, \"quoted\""],
+                vec_of_cells!["2", ""],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        assert_eq!(
+            "ID,NAME\r\n1,'it''s, \"quoted\"'\r\n2,NULL\r\n",
+            export_data.to_csv(',', '\'', "NULL")
+        );
+        assert_eq!(
+            "ID\tNAME\r\n1\t\"it's, \"\"quoted\"\"\"\r\n2\t\\N\r\n",
+            export_data.to_csv('\t', '"', "\\N")
+        );
+    }
+
+    #[test]
+    fn render_export_should_replace_newlines_in_single_line_formats() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "NOTES"],
+            data: vec![vec_of_cells!["1", "line 1\r\nline 2\nline 3"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let mut options = RenderOptions::from_config(&Config::default());
+        options.csv_delimiter = '\t';
+        // by default, only Wiki tables lose the line breaks
+        assert_eq!(
+            "ID\tNOTES\r\n1\t\"line 1\r\nline 2\nline 3\"\r\n",
+            render_export(&export_data, ExportFormat::Csv, &options).unwrap()
+        );
+        assert!(
+            render_export(&export_data, ExportFormat::Markdown, &options)
+                .unwrap()
+                .ends_with("| 1 | line 1<br>line 2<br>line 3 |\n")
+        );
+        assert!(render_export(&export_data, ExportFormat::Wiki, &options)
+            .unwrap()
+            .ends_with("|1|line 1 line 2 line 3|\n"));
+        options.newline_replacement = Some("\\n".to_string());
+        assert_eq!(
+            "ID\tNOTES\r\n1\tline 1\\nline 2\\nline 3\r\n",
+            render_export(&export_data, ExportFormat::Csv, &options).unwrap()
+        );
+        options.newline_replacement = Some("<br>".to_string());
+        assert!(render_export(&export_data, ExportFormat::Wiki, &options)
+            .unwrap()
+            .ends_with("|1|line 1<br>line 2<br>line 3|\n"));
+    }
+
+    #[test]
+    fn to_insert_statements_should_quote_all_but_numbers() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "NAME", "AMOUNT", "ZIP"],
+            data: vec![
+                vec_of_cells!["1", "O'Brien", "-1.5e3", "0815"],
+                vec_of_cells!["2", "", "0.5", "1,234"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        assert_eq!(
+            indoc! {"
+                INSERT INTO PERSONS (ID, NAME, AMOUNT, ZIP) VALUES (1, 'O''Brien', -1.5e3, '0815');
+                INSERT INTO PERSONS (ID, NAME, AMOUNT, ZIP) VALUES (2, NULL, 0.5, '1,234');
+            "},
+            export_data.to_insert_statements("PERSONS", &[]).unwrap()
+        );
+    }
+
+    #[test]
+    fn to_insert_statements_should_quote_non_simple_identifiers() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "COUNT(*)", "Name"],
+            data: vec![vec_of_cells!["1", "2", "Ada"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        assert_eq!(
+            "INSERT INTO hr.\"Persons\" (ID, \"COUNT(*)\", \"Name\") VALUES (1, 2, 'Ada');\n",
+            export_data
+                .to_insert_statements(" hr.\"Persons\" ", &[])
+                .unwrap()
+        );
+        assert_eq!(
+            Err(ExportError::InvalidTableName("".to_string())),
+            export_data.to_insert_statements("", &[])
+        );
+        assert_eq!(
+            Err(ExportError::InvalidTableName("PERSONS; DROP".to_string())),
+            export_data.to_insert_statements("PERSONS; DROP", &[])
+        );
+    }
+
+    #[test]
+    fn to_insert_statements_should_follow_the_column_types() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "CODE", "ZIP", "AMOUNT"],
+            data: vec![vec_of_cells!["1", "123", "0815", "007"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let column_types = header_types(
+            &export_data.headers,
+            &[
+                "NUMBER(10)".to_string(),
+                "VARCHAR2(10)".to_string(),
+                "CHAR(4)".to_string(),
+                "number".to_string(),
+            ],
+        );
+        assert_eq!(
+            "INSERT INTO T (ID, CODE, ZIP, AMOUNT) VALUES (1, '123', '0815', 007);\n",
+            export_data
+                .to_insert_statements("T", &column_types)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn render_insert_should_write_lob_placeholders_as_null() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "DOC"],
+            data: vec![vec_of_cells!["1", "<CLOB>"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let mut options = RenderOptions::from_config(&Config::default());
+        options.table_name = "DOCS".to_string();
+        assert_eq!(
+            "INSERT INTO DOCS (ID, DOC) VALUES (1, NULL);\n",
+            render_export(&export_data, ExportFormat::Insert, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn to_json_should_return_array_of_objects() {
+        let mut export_data = ExportData {
+            headers: vec_of_strings!["PK", "VAL1", "VAL \"2\""],
+            data: vec![
+                vec_of_cells!["1", "1a", ""],
+                vec_of_cells!["2", "C:\\temp\n\u{7}", "\"2b\""],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        assert_eq!(
+            indoc! {r#"
+                [
+                  {
+                    "PK": "1",
+                    "VAL1": "1a",
+                    "VAL \"2\"": null
+                  },
+                  {
+                    "PK": "2",
+                    "VAL1": "C:\\temp\n\u0007",
+                    "VAL \"2\"": "\"2b\""
+                  }
+                ]
+            "#},
+            export_data.to_json(true)
+        );
+        assert_eq!(
+            concat!(
+                r#"[{"PK":"1","VAL1":"1a","VAL \"2\"":null},"#,
+                r#"{"PK":"2","VAL1":"C:\\temp\n\u0007","VAL \"2\"":"\"2b\""}]"#,
+                "\n"
+            ),
+            export_data.to_json(false)
+        );
+        export_data.data.clear();
+        assert_eq!("[]\n", export_data.to_json(true));
+        assert_eq!("[]\n", export_data.to_json(false));
+    }
+
+    #[test]
+    fn to_html_should_escape_cells_and_leave_nulls_empty() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "<NAME>"],
+            data: vec![
+                vec_of_cells!["1", "Tom & \"Jerry\"'s"],
+                vec_of_cells!["", "line 1\r\nline 2"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let table = indoc! {r#"
+            <table>
+              <tr><th>ID</th><th>&lt;NAME&gt;</th></tr>
+              <tr><td>1</td><td>Tom &amp; &quot;Jerry&quot;&#39;s</td></tr>
+              <tr><td></td><td>line 1<br>line 2</td></tr>
+            </table>
+        "#};
+        assert_eq!(table, export_data.to_html(false));
+        assert_eq!(
+            format!("{}{}", HTML_STYLE, table),
+            export_data.to_html(true)
+        );
+    }
+
+    #[test]
+    fn render_html_should_style_grouped_tables_once() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["DEPT", "NAME"],
+            data: vec![vec_of_cells!["10", "KING"], vec_of_cells!["20", "SCOTT"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let mut options = RenderOptions::from_config(&Config::default());
+        options.group_by = Some("DEPT".to_string());
+        options.drop_group_column = true;
+        let rendered = render_export(&export_data, ExportFormat::Html, &options).unwrap();
+        assert!(rendered.starts_with(HTML_STYLE));
+        assert_eq!(1, rendered.matches("<style>").count());
+        assert_eq!(2, rendered.matches("<table>").count());
+        assert!(rendered.contains("<h3>20</h3>\n<table>\n  <tr><th>NAME</th></tr>\n"));
+    }
+
+    #[test]
+    fn to_gfm_markdown_should_escape_pipes_and_leave_nulls_empty() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "NAME|ALIAS", "COMMENTS"],
+            data: vec![
+                vec_of_cells!["1", "a|b|c", ""],
+                vec![Some("2".to_string()), Some("NULL".to_string()), None],
+            ],
+            ..ExportData::new()
+        };
+        assert_eq!(
+            "| ID | NAME\\|ALIAS | COMMENTS |\n\
+             | --- | --- | --- |\n\
+             | 1 | a\\|b\\|c |   |\n\
+             | 2 | NULL |   |\n",
+            export_data.to_gfm_markdown()
+        );
+    }
+
+    #[test]
+    fn to_markdown_should_return_markdown_table() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["h1", "h2", "h3"],
+            data: vec![
+                vec_of_cells!["d11", "a|b", ""],
+                vec_of_cells!["d21", "line 1\r\nline 2", "d23"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        assert_eq!(
+            "| h1 | h2 | h3 |\n\
+             | --- | --- | --- |\n\
+             | d11 | a\\|b |   |\n\
+             | d21 | line 1<br>line 2 | d23 |\n",
+            export_data.to_markdown(" ")
+        );
+        let mut options = RenderOptions::from_config(&Config::default());
+        options.column_types = vec![
+            ("h1".to_string(), "NUMBER".to_string()),
+            ("h2".to_string(), "VARCHAR2(10)".to_string()),
+        ];
+        assert!(
+            render_export(&export_data, ExportFormat::Markdown, &options)
+                .unwrap()
+                .starts_with("| h1 (NUMBER) | h2 (VARCHAR2(10)) | h3 |\n")
+        );
+    }
+
+    #[test]
+    fn markdown_code_block_should_fence_aligned_table() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "NAME", "CITY"],
+            data: vec![
+                vec_of_cells!["1", "Ada Lovelace", "London"],
+                vec_of_cells!["42", "Jörg", ""],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let text = markdown_code_block(&export_data, Some("text"), None, &[], " ");
+        assert!(text.starts_with("```text\n"));
+        assert!(text.trim_end().ends_with("```"));
+        assert!(text.contains(
+            "ID | NAME         | CITY\n\
+             ---+--------------+-------\n\
+             1  | Ada Lovelace | London\n\
+             42 | Jörg         |\n"
+        ));
+        let options = RenderOptions::from_config(&Config::default());
+        assert_eq!(
+            "```\n",
+            &render_export(&export_data, ExportFormat::MarkdownCodeBlock, &options).unwrap()[..4]
+        );
+    }
+
+    #[test]
+    fn markdown_code_block_should_outrun_backticks_in_data() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["SNIPPET"],
+            data: vec![vec_of_cells!["```sql"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        assert_eq!(
+            "````\nSNIPPET\n-------\n```sql\n````\n",
+            markdown_code_block(&export_data, None, None, &[], " ")
+        );
+    }
+
+    #[test]
+    fn render_export_should_wrap_long_cells_if_configured() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "REMARK"],
+            data: vec![vec_of_cells!["1", "moved to Berlin in 2021"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let options = RenderOptions {
+            wrap_width: Some(10),
+            ..RenderOptions::from_config(&Config::default())
+        };
+        assert_eq!(
+            "||ID||REMARK||\n|1|moved to\\\\Berlin in\\\\2021|\n",
+            render_export(&export_data, ExportFormat::Wiki, &options).unwrap()
+        );
+        assert_eq!(
+            "```\nID | REMARK\n---+----------\n1  | moved to\n   | Berlin in\n   | 2021\n```\n",
+            render_export(&export_data, ExportFormat::MarkdownCodeBlock, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn render_export_should_annotate_headers_with_column_types() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "NAME"],
+            data: vec![vec_of_cells!["1", "Ada"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let options = RenderOptions {
+            column_types: header_types(
+                &export_data.headers,
+                &["NUMBER".to_string(), "VARCHAR2(30)".to_string()],
+            ),
+            ..RenderOptions::from_config(&Config::default())
+        };
+        assert_eq!(
+            "||ID||NAME||\n||(NUMBER)||(VARCHAR2(30))||\n|1|Ada|\n",
+            render_export(&export_data, ExportFormat::Wiki, &options).unwrap()
+        );
+        assert_eq!(
+            "```\n\
+             ID       | NAME\n\
+             (NUMBER) | (VARCHAR2(30))\n\
+             ---------+---------------\n\
+             1        | Ada\n\
+             ```\n",
+            render_export(&export_data, ExportFormat::MarkdownCodeBlock, &options).unwrap()
+        );
+        // types that don't fit the columns are left out
+        assert!(header_types(&export_data.headers, &["NUMBER".to_string()]).is_empty());
+    }
+
+    fn create_update_test_data() -> ExportData {
+        ExportData {
+            headers: vec_of_strings!["ID", "VERSION", "NAME", "CITY"],
+            data: vec![
+                vec_of_cells!["1", "1", "O'Brien", "Dublin"],
+                vec_of_cells!["1", "2", "", "Cork"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        }
+    }
+
+    #[test]
+    fn update_formatter_should_support_composite_keys() {
+        let formatter = UpdateFormatter::new("PERSONS", &["id", " version"]);
+        assert_eq!(
+            Ok("UPDATE PERSONS SET NAME = 'O''Brien', CITY = 'Dublin' WHERE ID = '1' AND VERSION = '1';\n\
+                UPDATE PERSONS SET NAME = NULL, CITY = 'Cork' WHERE ID = '1' AND VERSION = '2';\n"
+                .to_string()),
+            formatter.format(&create_update_test_data())
+        );
+    }
+
+    #[test]
+    fn update_formatter_should_render_null_values_in_set_list() {
+        let formatter = UpdateFormatter::new("PERSONS", &["ID", "VERSION", "CITY"]);
+        let got = formatter.format(&create_update_test_data()).unwrap();
+        assert!(got.contains(
+            "UPDATE PERSONS SET NAME = NULL WHERE ID = '1' AND VERSION = '2' AND CITY = 'Cork';"
+        ));
+    }
+
+    #[test]
+    fn update_formatter_should_reject_null_keys() {
+        let formatter = UpdateFormatter::new("PERSONS", &["NAME"]);
+        assert_eq!(
+            Err(ExportError::NullKey(vec![2])),
+            formatter.format(&create_update_test_data())
+        );
+    }
+
+    #[test]
+    fn update_formatter_should_replace_lob_placeholders_with_null() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "DOCUMENT", "NAME"],
+            data: vec![
+                vec_of_cells!["1", "<CLOB>", "<clob> is not a placeholder here"],
+                vec_of_cells!["2", "plain text", "x"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let formatter = UpdateFormatter::new("DOCS", &["ID"]);
+        assert_eq!(
+            Ok("UPDATE DOCS SET DOCUMENT = NULL, NAME = '<clob> is not a placeholder here' WHERE ID = '1'; -- TODO: LOB value omitted\n\
+                UPDATE DOCS SET DOCUMENT = 'plain text', NAME = 'x' WHERE ID = '2';\n"
+                .to_string()),
+            formatter.format(&export_data)
+        );
+        assert_eq!(
+            1,
+            count_lob_placeholders(&export_data, &formatter.lob_placeholders)
+        );
+    }
+
+    #[test]
+    fn update_formatter_should_use_configured_lob_placeholders() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "PICTURE"],
+            data: vec![vec_of_cells!["1", "<BLOB>"], vec_of_cells!["2", "[blob]"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let mut formatter = UpdateFormatter::new("PICS", &["ID"]);
+        formatter.lob_placeholders = vec_of_strings!["[blob]"];
+        assert_eq!(
+            Ok("UPDATE PICS SET PICTURE = '<BLOB>' WHERE ID = '1';\n\
+                UPDATE PICS SET PICTURE = NULL WHERE ID = '2'; -- TODO: LOB value omitted\n"
+                .to_string()),
+            formatter.format(&export_data)
+        );
+    }
+
+    #[test]
+    fn update_formatter_should_reject_lob_placeholder_in_key() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "NAME"],
+            data: vec![vec_of_cells!["<RAW>", "x"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let formatter = UpdateFormatter::new("T", &["ID"]);
+        assert_eq!(
+            Err(ExportError::NullKey(vec![1])),
+            formatter.format(&export_data)
+        );
+    }
+
+    #[test]
+    fn is_lob_placeholder_should_match_default_placeholders_case_insensitively() {
+        let placeholders: Vec<String> = DEFAULT_LOB_PLACEHOLDERS
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        assert!(is_lob_placeholder("<LONG>", &placeholders));
+        assert!(is_lob_placeholder("<Blob>", &placeholders));
+        assert!(!is_lob_placeholder("<CLOB> and more", &placeholders));
+        assert!(!is_lob_placeholder("", &placeholders));
+    }
+
+    fn create_group_test_data() -> ExportData {
+        ExportData {
+            headers: vec_of_strings!["NAME", "DEPARTMENT"],
+            data: vec![
+                vec_of_cells!["Anne", "Sales"],
+                vec_of_cells!["Frank", ""],
+                vec_of_cells!["Marco", "IT"],
+                vec_of_cells!["Bob", "Sales"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        }
+    }
+
+    #[test]
+    fn partition_by_column_should_keep_order_of_first_appearance() {
+        let groups = partition_by_column(&create_group_test_data(), "department", true).unwrap();
+        let values: Vec<Option<String>> = groups.iter().map(|(v, _)| v.clone()).collect();
+        assert_eq!(
+            vec![Some("Sales".to_string()), Some("IT".to_string()), None],
+            values
+        );
+        assert_eq!(vec_of_strings!["NAME"], groups[0].1.headers);
+        assert_eq!(
+            vec![vec_of_cells!["Anne"], vec_of_cells!["Bob"]],
+            groups[0].1.data
+        );
+        assert_eq!(vec![vec_of_cells!["Frank"]], groups[2].1.data);
+    }
+
+    #[test]
+    fn partition_by_column_should_optionally_keep_group_column() {
+        let groups = partition_by_column(&create_group_test_data(), "DEPARTMENT", false).unwrap();
+        assert_eq!(vec_of_strings!["NAME", "DEPARTMENT"], groups[1].1.headers);
+        assert_eq!(vec![vec_of_cells!["Marco", "IT"]], groups[1].1.data);
+    }
+
+    #[test]
+    fn partition_by_column_should_reject_missing_column() {
+        assert_eq!(
+            Err(ExportError::UnknownGroupColumn("TEAM".to_string())),
+            partition_by_column(&create_group_test_data(), "TEAM", true).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn group_caption_should_depend_on_format() {
+        let it = Some("IT".to_string());
+        assert_eq!("h3. IT\n", group_caption(ExportFormat::Wiki, &it));
+        assert_eq!("-- IT\n", group_caption(ExportFormat::Update, &it));
+        assert_eq!("h3. (null)\n", group_caption(ExportFormat::Wiki, &None));
+    }
+
+    #[test]
+    fn render_groups_should_render_one_captioned_table_per_group() {
+        let groups = partition_by_column(&create_group_test_data(), "DEPARTMENT", true).unwrap();
+        let got = render_groups(&groups, ExportFormat::Wiki, |d| Ok(d.to_string(" "))).unwrap();
+        assert_eq!(
+            "h3. Sales\n||NAME||\n|Anne|\n|Bob|\n\n\
+             h3. IT\n||NAME||\n|Marco|\n\n\
+             h3. (null)\n||NAME||\n|Frank|\n\n",
+            got
+        );
+    }
+
+    #[test]
+    fn postgres_copy_formatter_should_create_copy_block() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "NAME", "NOTES"],
+            data: vec![
+                vec_of_cells!["1", "O'Brien", "tab\there\nC:\\temp"],
+                vec_of_cells!["2", "", "<CLOB>"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        assert_eq!(
+            Ok("COPY \"emp\" (\"id\", \"name\", \"notes\") FROM stdin;\n\
+             1\tO'Brien\ttab\\there\\nC:\\\\temp\n\
+             2\t\\N\t\\N\n\
+             \\.\n"
+                .to_string()),
+            PostgresCopyFormatter::new(" emp ").format(&export_data)
+        );
+    }
+
+    #[test]
+    fn postgres_copy_formatter_should_quote_identifiers_and_reject_invalid_table_names() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ORDER", "Total \"net\""],
+            data: vec![vec_of_cells!["1", "42"]],
+            ..ExportData::new()
+        };
+        assert_eq!(
+            Ok(
+                "COPY \"app\".\"My.Table\" (\"order\", \"Total \"\"net\"\"\") FROM stdin;\n\
+             1\t42\n\
+             \\.\n"
+                    .to_string()
+            ),
+            PostgresCopyFormatter::new("APP.\"My.Table\"").format(&export_data)
+        );
+        assert_eq!(
+            Err(ExportError::InvalidTableName("".to_string())),
+            PostgresCopyFormatter::new(" ").format(&export_data)
+        );
+        assert_eq!(
+            Err(ExportError::InvalidTableName(
+                "emp; drop table emp".to_string()
+            )),
+            PostgresCopyFormatter::new("emp; drop table emp").format(&export_data)
+        );
+    }
+
+    #[test]
+    fn sql_loader_formatter_should_create_control_and_data_file() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "NAME", "NOTES"],
+            data: vec![
+                vec_of_cells!["1", "O'Brien", "says \"hi\""],
+                vec_of_cells!["2", "", "<CLOB>"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let export = SqlLoaderFormatter::new("EMP", "emp.dat")
+            .format(&export_data)
+            .unwrap();
+        assert_eq!(
+            "LOAD DATA\n\
+             INFILE 'emp.dat'\n\
+             APPEND\n\
+             INTO TABLE EMP\n\
+             FIELDS TERMINATED BY ',' OPTIONALLY ENCLOSED BY '\"'\n\
+             TRAILING NULLCOLS\n\
+             (\n  \
+             ID,\n  \
+             NAME,\n  \
+             NOTES\n\
+             )\n",
+            export.control_file
+        );
+        assert_eq!(export_data.data.len(), export.data_file.lines().count());
+        assert_eq!(
+            "\"1\",\"O'Brien\",\"says \"\"hi\"\"\"\n\"2\",,\n",
+            export.data_file
+        );
+    }
+
+    #[test]
+    fn sql_loader_formatter_should_terminate_records_holding_line_breaks() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "Note", "COUNT(*)"],
+            data: vec![
+                vec_of_cells!["1", "line 1\r\nline 2", "3"],
+                vec_of_cells!["2", "", "1"],
+            ],
+            ..ExportData::new()
+        };
+        let export = SqlLoaderFormatter::new("app.emp", "emp.dat")
+            .format(&export_data)
+            .unwrap();
+        assert!(export.control_file.starts_with(
+            "LOAD DATA\nINFILE 'emp.dat' \"str X'1E0A'\"\nAPPEND\nINTO TABLE app.emp\n"
+        ));
+        assert!(export
+            .control_file
+            .ends_with("(\n  ID,\n  \"Note\",\n  \"COUNT(*)\"\n)\n"));
+        assert_eq!(
+            "\"1\",\"line 1\r\nline 2\",\"3\"\u{1e}\n\"2\",,\"1\"\u{1e}\n",
+            export.data_file
+        );
+    }
+
+    #[test]
+    fn valid_table_name_should_reject_empty_and_malformed_names() {
+        assert_eq!(Ok("EMP".to_string()), valid_table_name(" EMP "));
+        assert_eq!(
+            Ok("app.\"My Table\"".to_string()),
+            valid_table_name("app.\"My Table\"")
+        );
+        for name in ["", "  ", "my table", "emp; drop table emp", "a.b.c", "1EMP"] {
+            assert_eq!(
+                Err(ExportError::InvalidTableName(name.to_string())),
+                valid_table_name(name),
+                "{:?}",
+                name
+            );
+        }
+        let mut options = RenderOptions::from_config(&Config::default());
+        options.table_name = " ".to_string();
+        let error = render_export(
+            &create_update_test_data(),
+            ExportFormat::SqlLoader,
+            &options,
+        );
+        assert_eq!("The table name is empty", format!("{}", error.unwrap_err()));
+    }
+
+    #[test]
+    fn update_formatter_should_quote_non_simple_identifiers_and_validate_the_table() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["Id", "First Name", "CITY"],
+            data: vec![vec_of_cells!["1", "Ada", "London"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let formatter = UpdateFormatter::new(" hr.\"Persons\" ", &["id"]);
+        assert_eq!(
+            Ok("UPDATE hr.\"Persons\" SET \"First Name\" = 'Ada', CITY = 'London' WHERE \"Id\" = '1';\n"
+                .to_string()),
+            formatter.format(&export_data)
+        );
+        for table_name in ["", "PERSONS; DROP"] {
+            assert_eq!(
+                Err(ExportError::InvalidTableName(table_name.to_string())),
+                UpdateFormatter::new(table_name, &["ID"]).format(&export_data)
+            );
+        }
+    }
+
+    #[test]
+    fn update_formatter_should_reject_unknown_key_columns() {
+        let formatter = UpdateFormatter::new("PERSONS", &["ZIP"]);
+        assert_eq!(
+            Err(ExportError::UnknownKeyColumn("ZIP".to_string())),
+            formatter.format(&create_update_test_data())
+        );
+    }
+
+    #[test]
+    fn localize_decimal_separator_should_replace_point_in_numeric_columns() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["NAME", "VALUE", "VERSION"],
+            data: vec![
+                vec_of_cells!["pi", "3.14", "1.2.3"],
+                vec_of_cells!["e", "", "v2.0"],
+                vec_of_cells!["tiny", "-1.5E-10", "2.0"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let localized = localize_decimal_separator(&export_data, ',');
+        assert_eq!(vec_of_cells!["pi", "3,14", "1.2.3"], localized.data[0]);
+        assert_eq!(vec_of_cells!["e", "", "v2.0"], localized.data[1]);
+        assert_eq!(vec_of_cells!["tiny", "-1,5E-10", "2.0"], localized.data[2]);
+    }
+
+    #[test]
+    fn localize_decimal_separator_should_not_touch_thousands_grouping() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["AMOUNT"],
+            data: vec![vec_of_cells!["1,234.5"], vec_of_cells!["12.5"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let localized = localize_decimal_separator(&export_data, ',');
+        assert_eq!(export_data.data, localized.data);
+    }
+
+    #[test]
+    fn redact_columns_should_replace_values_of_matching_columns() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "EMAIL", "CITY"],
+            data: vec![
+                vec_of_cells!["1", "jdoe@example.com", "Dublin"],
+                vec_of_cells!["2", "", "Cork"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let redacted = redact_columns(&export_data, &["email".to_string()]);
+        assert_eq!(export_data.headers, redacted.headers);
+        assert_eq!(
+            vec![
+                vec_of_cells!["1", "***", "Dublin"],
+                vec_of_cells!["2", "***", "Cork"]
+            ],
+            redacted.data
+        );
+        assert!(!redacted.to_string(" ").contains("jdoe@example.com"));
+    }
+
+    #[test]
+    fn render_export_should_render_same_data_in_different_formats() {
+        let export_data = create_update_test_data();
+        let mut options = RenderOptions::from_config(&Config::default());
+        assert_eq!(
+            "||ID||VERSION||NAME||CITY||\n|1|1|O'Brien|Dublin|\n|1|2| |Cork|\n",
+            render_export(&export_data, ExportFormat::Wiki, &options).unwrap()
+        );
+
+        options.table_name = "PERSON".to_string();
+        options.key_columns = vec_of_strings!["ID", "VERSION"];
+        assert_eq!(
+            "UPDATE PERSON SET NAME = 'O''Brien', CITY = 'Dublin' WHERE ID = '1' AND VERSION = '1';\n\
+             UPDATE PERSON SET NAME = NULL, CITY = 'Cork' WHERE ID = '1' AND VERSION = '2';\n",
+            render_export(&export_data, ExportFormat::Update, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn render_export_should_redact_and_inline_sql_loader_data() {
+        let export_data = create_update_test_data();
+        let mut options = RenderOptions::from_config(&Config::default());
+        options.table_name = "PERSON".to_string();
+        options.redact_columns = vec_of_strings!["name"];
+        let rendered = render_export(&export_data, ExportFormat::SqlLoader, &options).unwrap();
+        assert!(rendered.starts_with("LOAD DATA\nINFILE *\n"));
+        assert!(rendered.ends_with(
+            "BEGINDATA\n\"1\",\"1\",\"***\",\"Dublin\"\n\"1\",\"2\",\"***\",\"Cork\"\n"
+        ));
+    }
+
+    #[test]
+    fn lock_export_data_should_recover_poisoned_lock() {
+        let lock = std::sync::Arc::new(RwLock::new(ExportData::new()));
+        lock.write().unwrap().headers = vec_of_strings!["ID"];
+        let poisoner = lock.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.write().unwrap();
+            panic!("poisoning the export data lock");
+        })
+        .join();
+        assert!(lock.is_poisoned());
+
+        assert!(finish_session(&lock, false).headers.is_empty());
+        assert!(!lock.is_poisoned());
+        lock_export_data(&lock, false).push_value("NAME".to_string(), true);
+        assert_eq!(vec_of_strings!["NAME"], lock.read().unwrap().headers);
+    }
+
+    #[test]
+    fn lock_export_data_should_salvage_complete_rows_if_configured() {
+        let lock = std::sync::Arc::new(RwLock::new(ExportData::new()));
+        lock.write().unwrap().init();
+        for value in ["ID", "NAME"] {
+            lock.write().unwrap().push_value(value.to_string(), true);
+        }
+        lock.write().unwrap().prepared = true;
+        let poisoner = lock.clone();
+        let _ = std::thread::spawn(move || {
+            let mut guard = poisoner.write().unwrap();
+            for value in ["1", "Ada", "2"] {
+                guard.push_value(value.to_string(), true);
+            }
+            panic!("poisoning the export data lock");
+        })
+        .join();
+
+        let salvaged = finish_session(&lock, true);
+        assert!(!lock.is_poisoned());
+        assert_eq!(vec![vec_of_cells!["1", "Ada"]], salvaged.data);
+        assert!(salvaged.current_row.is_empty());
+    }
+
+    #[test]
+    fn render_export_should_mask_before_formatting() {
+        let export_data = create_update_test_data();
+        let mut options = RenderOptions::from_config(&Config::default());
+        options.masking_rules = vec![("*ame".to_string(), MaskingStrategy::KeepFirst(1))];
+        assert_eq!(
+            "||ID||VERSION||NAME||CITY||\n|1|1|O***|Dublin|\n|1|2| |Cork|\n",
+            render_export(&export_data, ExportFormat::Wiki, &options).unwrap()
+        );
+    }
+
+    // runs the complete FFI export sequence against the headless clipboard
+    #[cfg(not(all(windows, feature = "windows-ui")))]
+    #[test]
+    fn export_finished_should_copy_wiki_table_to_headless_clipboard() {
+        use crate::ui::headless::{CLIPBOARD_TEST_LOCK, CLIPBOARD_TEXT};
+        use std::ffi::CString;
+
+        let _lock = CLIPBOARD_TEST_LOCK.lock().unwrap();
+
+        assert!(ExportInit());
+        for value in &["h1", "h2", "d11", "d12"] {
+            if *value == "d11" {
+                assert!(ExportPrepare());
+            }
+            let value = CString::new(*value).unwrap();
+            assert!(ExportData(value.as_ptr()));
+        }
+        ExportFinished();
+        assert_eq!("||h1||h2||\n|d11|d12|\n", *CLIPBOARD_TEXT.lock().unwrap());
+    }
+
+    #[cfg(not(all(windows, feature = "windows-ui")))]
+    #[test]
+    fn export_finished_markdown_should_copy_markdown_regardless_of_the_configured_format() {
+        use crate::prelude::{API_TEST_LOCK, CONFIG};
+        use crate::ui::headless::{CLIPBOARD_TEST_LOCK, CLIPBOARD_TEXT};
+
+        let _api_lock = API_TEST_LOCK.lock().unwrap();
+        let _lock = CLIPBOARD_TEST_LOCK.lock().unwrap();
+        *CONFIG.write().unwrap() = Config {
+            null_representation: "NULL".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(
+            "Export to clipboard as Markdown (Rust)",
+            unsafe { CStr::from_ptr(RegisterExportMarkdown()) }
+                .to_str()
+                .unwrap()
+        );
+
+        export_init();
+        send_export_values(&["h1", "h2"], &["d11", "d12", "a|b", ""]);
+        ExportFinishedMarkdown();
+        // GitHub Flavored Markdown, with NULL as an empty cell despite the null representation
+        assert_eq!(
+            "| h1 | h2 |\n| --- | --- |\n| d11 | d12 |\n| a\\|b |   |\n",
+            *CLIPBOARD_TEXT.lock().unwrap()
+        );
+        *CONFIG.write().unwrap() = Config::default();
+    }
+
+    #[cfg(not(all(windows, feature = "windows-ui")))]
+    #[test]
+    fn export_finished_csv_json_and_html_should_copy_their_format() {
+        use crate::ui::cf_html;
+        use crate::ui::headless::{CLIPBOARD_HTML, CLIPBOARD_TEST_LOCK, CLIPBOARD_TEXT};
+
+        let _lock = CLIPBOARD_TEST_LOCK.lock().unwrap();
+        let name = |register: extern "C" fn() -> *mut c_char| {
+            unsafe { CStr::from_ptr(register()) }
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!("Export to clipboard as CSV (Rust)", name(RegisterExportCsv));
+        assert_eq!(
+            "Export to clipboard as JSON (Rust)",
+            name(RegisterExportJson)
+        );
+        assert_eq!(
+            "Export to clipboard as HTML (Rust)",
+            name(RegisterExportHtml)
+        );
+
+        export_init();
+        send_export_values(&["h1", "h2"], &["d11", "d12"]);
+        ExportFinishedCsv();
+        assert_eq!("h1,h2\r\nd11,d12\r\n", *CLIPBOARD_TEXT.lock().unwrap());
+
+        export_init();
+        send_export_values(&["h1", "h2"], &["d11", "d12"]);
+        ExportFinishedJson();
+        assert_eq!(
+            "[\n  {\n    \"h1\": \"d11\",\n    \"h2\": \"d12\"\n  }\n]\n",
+            *CLIPBOARD_TEXT.lock().unwrap()
+        );
+
+        export_init();
+        send_export_values(&["h1", "h2"], &["d11", "d12"]);
+        ExportFinishedHtml();
+        let html = CLIPBOARD_TEXT.lock().unwrap().clone();
+        assert!(html.contains("<tr><th>h1</th><th>h2</th></tr>"));
+        assert!(html.contains("<tr><td>d11</td><td>d12</td></tr>"));
+        assert_eq!(cf_html(&html), *CLIPBOARD_HTML.lock().unwrap());
+    }
+
+    #[cfg(not(all(windows, feature = "windows-ui")))]
+    #[test]
+    fn export_as_configured_should_copy_html_as_html_format() {
+        use crate::ui::cf_html;
+        use crate::ui::headless::{CLIPBOARD_HTML, CLIPBOARD_TEST_LOCK, CLIPBOARD_TEXT};
+
+        let _lock = CLIPBOARD_TEST_LOCK.lock().unwrap();
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID"],
+            data: vec![vec_of_cells!["1"]],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 0,
+            ..ExportData::new()
+        };
+        let config = Config {
+            export_format: ExportFormat::Html,
+            html_style: false,
+            ..Config::default()
+        };
+        export_as_configured(&export_data, &[], &config);
+        let html = "<table>\n  <tr><th>ID</th></tr>\n  <tr><td>1</td></tr>\n</table>\n";
+        assert_eq!(html, *CLIPBOARD_TEXT.lock().unwrap());
+        assert_eq!(cf_html(html), *CLIPBOARD_HTML.lock().unwrap());
+    }
+
+    // sends the headers and rows the way the IDE does, but without ExportInit
+    #[cfg(not(all(windows, feature = "windows-ui")))]
+    fn send_export_values(headers: &[&str], values: &[&str]) {
+        for header in headers {
+            export_value(header.to_string());
+        }
+        export_prepare();
+        for value in values {
+            export_value(value.to_string());
+        }
+    }
+
+    #[cfg(not(all(windows, feature = "windows-ui")))]
+    #[test]
+    fn export_callbacks_should_start_a_session_without_export_init() {
+        use crate::ui::headless::{CLIPBOARD_TEST_LOCK, CLIPBOARD_TEXT};
+
+        let _lock = CLIPBOARD_TEST_LOCK.lock().unwrap();
+
+        // a finished export leaves prepared == true behind
+        export_init();
+        send_export_values(&["h1"], &["d11"]);
+        export_finished();
+        assert_eq!("||h1||\n|d11|\n", *CLIPBOARD_TEXT.lock().unwrap());
+
+        // the next export starts without ExportInit
+        send_export_values(&["h2", "h3"], &["d21", "d22"]);
+        export_finished();
+        assert_eq!("||h2||h3||\n|d21|d22|\n", *CLIPBOARD_TEXT.lock().unwrap());
+    }
+
+    #[cfg(not(all(windows, feature = "windows-ui")))]
+    #[test]
+    fn export_should_work_again_after_the_export_data_lock_was_poisoned() {
+        use crate::ui::headless::{CLIPBOARD_TEST_LOCK, CLIPBOARD_TEXT};
+
+        let _lock = CLIPBOARD_TEST_LOCK.lock().unwrap();
+        let _ = std::thread::spawn(|| {
+            let _guard = EXPORT_DATA.write().unwrap();
+            panic!("poisoning the export data lock");
+        })
+        .join();
+        assert!(EXPORT_DATA.is_poisoned());
+
+        export_init();
+        assert!(!EXPORT_DATA.is_poisoned());
+        send_export_values(&["h1"], &["d11"]);
+        export_finished();
+        assert_eq!("||h1||\n|d11|\n", *CLIPBOARD_TEXT.lock().unwrap());
+    }
+
+    #[cfg(not(all(windows, feature = "windows-ui")))]
+    #[test]
+    fn export_finished_without_data_should_not_copy_an_empty_table() {
+        use crate::ui::headless::{CLIPBOARD_TEST_LOCK, CLIPBOARD_TEXT};
+
+        let _lock = CLIPBOARD_TEST_LOCK.lock().unwrap();
+        *CLIPBOARD_TEXT.lock().unwrap() = "previous".to_string();
+
+        // only ExportFinished
+        export_finished();
+        assert_eq!("previous", *CLIPBOARD_TEXT.lock().unwrap());
+
+        // headers, but no rows
+        export_init();
+        send_export_values(&["h1"], &[]);
+        export_finished();
+        assert_eq!("previous", *CLIPBOARD_TEXT.lock().unwrap());
+        assert!(!EXPORT_DATA.read().unwrap().active);
+    }
+
+    #[cfg(not(all(windows, feature = "windows-ui")))]
+    #[test]
+    fn export_to_temp_file_should_copy_the_path_of_a_new_file() {
+        use crate::ui::headless::{CLIPBOARD_TEST_LOCK, CLIPBOARD_TEXT};
+        use std::fs;
+
+        let _lock = CLIPBOARD_TEST_LOCK.lock().unwrap();
+        let dir = std::env::temp_dir().join("xanthidae_temp_export_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let config = Config::default();
+        for _ in 0..2 {
+            export_to_temp_file(&dir, "||h1||\n|d11|\n", "", ExportFormat::Wiki, &config);
+        }
+        let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+        assert_eq!(2, files.len());
+        assert!(files.iter().all(|f| f.extension().unwrap() == "txt"));
+        let copied = PathBuf::from(CLIPBOARD_TEXT.lock().unwrap().clone());
+        assert!(files.contains(&copied));
+        assert_eq!("||h1||\n|d11|\n", fs::read_to_string(&copied).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_temp_export_should_not_reuse_names() {
+        let dir = std::env::temp_dir().join("xanthidae_temp_export_names_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let now = Local.with_ymd_and_hms(2021, 7, 18, 10, 15, 0).unwrap();
+        let first = write_temp_export(&dir, "a", "md", now, true).unwrap();
+        let second = write_temp_export(&dir, "b", "md", now, true).unwrap();
+        assert_eq!(
+            "xanthidae_export_20210718_101500_000.md",
+            first.file_name().unwrap()
+        );
+        assert_eq!(
+            "xanthidae_export_20210718_101500_000_1.md",
+            second.file_name().unwrap()
         );
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
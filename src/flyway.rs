@@ -1,18 +1,39 @@
-use std::ffi::{CStr, CString};
 use std::fmt::{Display, Formatter};
-use std::fs::File;
+use std::fs;
+use std::fs::{File, OpenOptions};
 use std::io::{Error, ErrorKind, Write};
-use std::path::PathBuf;
-use std::sync::RwLockReadGuard;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 
-use chrono::Utc;
+use chrono::format::{Fixed, Item, Numeric, Pad, StrftimeItems};
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDateTime, TimeZone, Utc};
 use indoc::indoc;
-use regex::{Captures, Regex, RegexBuilder};
-use winapi::um::winuser::{MB_ICONERROR, MB_ICONINFORMATION, MB_OK};
-
-use crate::config::Config;
+use regex::Regex;
+
+use crate::bulk::{run_bulk, BulkSummary, SystemClock, BULK_CANCEL};
+use crate::config::{
+    Config, EmptySelection, LineEnding, PackageParts, TimestampSource, Versioning,
+};
+use crate::ddl_transform::{
+    apply_transforms, ddl_transforms, migration_transforms, owner_transforms, DdlContext,
+    DdlTransform, PromptLine,
+};
+use crate::editorconfig::find_end_of_line;
+use crate::flyway_help::{help_footer, MigrationKind};
+use crate::logging::target;
+use crate::paths;
 use crate::plsqldev_api::{PlsqlDevApi, SelectedObject};
-use crate::windows_api::{get_save_file_name, get_save_folder_name, show_message_box};
+use crate::project_config::config_for_folder;
+use crate::retry::{retry_while_empty, ThreadSleeper};
+use crate::schema_history::remember_migration;
+use crate::string_utils::{
+    convert_line_endings, decode_ide_text, same_content_ignoring_bom, sanitize_file_name,
+    DecodingPath,
+};
+use crate::template::{apply_templates, TemplateValues};
+use crate::ui::{dialogs, DialogError, MessageKind, Progress, SaveDialogOutcome};
+use crate::verified_write::write_verified;
 
 const COWARDLY_REFUSING_TO_CREATE_EMPTY_MIGRATION: &str = indoc! { "
   Cowardly refusing to create an empty migration.
@@ -21,11 +42,23 @@ const COWARDLY_REFUSING_TO_CREATE_EMPTY_MIGRATION: &str = indoc! { "
 
 const EMPTY_FILE_NAME: &str = "Please enter a file name!";
 
+const PROBE_FILE_NAME: &str = ".xanthidae_probe";
+
+const USE_WHOLE_TEXT_QUESTION: &str = indoc! { "
+  Nothing is selected.
+  Do you want to create the migration from the whole text of the window?
+  "};
+
 #[derive(Debug)]
-enum FlywayError {
+pub enum FlywayError {
     EmptySelectionError,
     EmptyFileName,
+    // folder and reason
+    FolderNotWritable(String, String),
+    DialogFailed(DialogError),
     IOError(String),
+    // the file name of an undo migration that would be overwritten
+    UndoMigrationExists(String),
 }
 
 impl Display for FlywayError {
@@ -35,7 +68,15 @@ impl Display for FlywayError {
                 COWARDLY_REFUSING_TO_CREATE_EMPTY_MIGRATION.to_string()
             }
             FlywayError::EmptyFileName => EMPTY_FILE_NAME.to_string(),
+            FlywayError::FolderNotWritable(folder, reason) => {
+                format!("Can't write to the folder {}: {}", folder, reason)
+            }
+            FlywayError::DialogFailed(e) => format!("Could not ask for the file name: {}", e),
             FlywayError::IOError(s) => format!("I/O error: {}", s),
+            FlywayError::UndoMigrationExists(file_name) => format!(
+                "The undo migration {} already exists, no migration was written.",
+                file_name
+            ),
         };
         write!(f, "{}", msg)
     }
@@ -50,167 +91,1062 @@ impl From<std::io::Error> for FlywayError {
 // Create a versioned migration for Flyway
 //
 // Extracts the currently selected text, asks user for base filename, and writes the
-// text to a file whose name is automatically generated as V<timestamp>__<basename>.sql
-pub fn create_versioned_migration(
-    api: &RwLockReadGuard<Box<dyn PlsqlDevApi + Send + Sync>>,
-    config: &Config,
-) {
-    let result = create_versioned_migration_impl(&api, config, get_save_file_name);
+// text to a file whose name is automatically generated as V<timestamp>__<basename>.sql;
+// `with_undo` adds an undo migration stub U<timestamp>__<basename>.sql of the same version
+pub fn create_versioned_migration(api: &dyn PlsqlDevApi, config: &Config, with_undo: bool) {
+    let versioned_folder = configured_folder(config, config.versioned_folder.as_deref());
+    let result = create_versioned_migration_impl(
+        api,
+        config,
+        || match &versioned_folder {
+            Some(folder) => ask_for_description(folder),
+            None => dialogs().get_save_file_name(),
+        },
+        |question| dialogs().ask_yes_no(question, "Versioned migration"),
+        with_undo,
+    );
 
     if let Err(e) = result {
-        let caption = CString::new("Error").unwrap();
-        let message = CString::new(format!("{}", e)).unwrap();
-        show_message_box(&message, &caption, MB_OK | MB_ICONERROR);
+        dialogs().show_message(&format!("{}", e), "Error", MessageKind::Error);
+    }
+}
+
+// The configured folder, resolved against the repository root
+pub fn configured_folder(config: &Config, folder: Option<&str>) -> Option<String> {
+    let folder = folder?;
+    Some(match &config.repo_root {
+        Some(root) => paths::resolve(root, folder),
+        None => folder.to_string(),
+    })
+}
+
+// With a configured folder, only the description of a versioned migration is asked for
+fn ask_for_description(folder: &str) -> SaveDialogOutcome {
+    match dialogs().get_user_input("Description of the versioned migration") {
+        Ok(description) if description.trim().is_empty() => SaveDialogOutcome::EmptyName,
+        Ok(description) => {
+            SaveDialogOutcome::Chosen(PathBuf::from(folder), description.trim().to_string())
+        }
+        // user cancelled
+        Err(_) => SaveDialogOutcome::Cancelled,
     }
 }
 
 fn create_versioned_migration_impl(
-    api: &RwLockReadGuard<Box<dyn PlsqlDevApi + Send + Sync>>,
+    api: &dyn PlsqlDevApi,
     config: &Config,
-    get_save_file_name: fn() -> Result<String, &'static str>,
+    get_save_file_name: impl Fn() -> SaveDialogOutcome,
+    confirm: fn(&str) -> bool,
+    with_undo: bool,
 ) -> std::result::Result<(), FlywayError> {
-    let ddl = api.ide_get_selected_text();
-    // bail out if current selection is empty
-    if ddl.len() == 0 {
+    let mut ddl = api.ide_get_selected_text();
+    if !ddl.is_empty() {
+        let threshold = config.whole_buffer_confirmation_threshold;
+        if is_unintended_whole_buffer(&ddl, &api.ide_get_text(), threshold)
+            && !config
+                .whole_buffer_prompt
+                .answer(|| confirm(&whole_buffer_question(&ddl)))
+        {
+            return Ok(());
+        }
+    } else {
+        let use_whole_text = match config.empty_selection {
+            EmptySelection::Error => false,
+            EmptySelection::WholeText => true,
+            EmptySelection::Prompt => {
+                if !confirm(USE_WHOLE_TEXT_QUESTION) {
+                    return Ok(());
+                }
+                true
+            }
+        };
+        if use_whole_text {
+            ddl = api.ide_get_text();
+        }
+    }
+    // bail out if there's still nothing to write
+    if ddl.is_empty() {
         return Err(FlywayError::EmptySelectionError);
     }
-    // get basename from user, and construct versioned file name
-    let basename = get_save_file_name();
+    // get folder and basename from user, and construct versioned file name
+    let (folder, basename) = match get_save_file_name() {
+        SaveDialogOutcome::Chosen(folder, basename) => (folder, basename),
+        SaveDialogOutcome::Cancelled => return Ok(()),
+        SaveDialogOutcome::EmptyName => return Err(FlywayError::EmptyFileName),
+        SaveDialogOutcome::Failed(e) => return Err(FlywayError::DialogFailed(e)),
+    };
+
+    if let Err(e) = probe_writable(&folder) {
+        return Err(FlywayError::FolderNotWritable(
+            paths::display(config.repo_root.as_deref(), &folder.to_string_lossy()),
+            format!("{}", e),
+        ));
+    }
+    // the conventions of the target repository apply to this migration
+    let config = &config_for_folder(config, &folder);
+    ensure_gitattributes(&folder, config);
+    // computed once, so the undo migration gets exactly the same version
+    let version = next_version(api, config, &folder);
+    let filename = migration_file_name(&config.version_prefix, &version, &basename, config);
+    let mut template_values = TemplateValues::new("", "", "", Utc::now());
+    template_values.version = version.clone();
+    // write DDL to output file
+    let ddl = apply_templates(&ddl, &template_values, config);
+    let path = folder.join(&filename);
+    let io_error = |e: std::io::Error| FlywayError::IOError(format!("{}", e));
+    // both files are written, or neither; an undo migration may have been edited already
+    let undo_filename = migration_file_name(&config.undo_prefix, &version, &basename, config);
+    let undo_path = folder.join(&undo_filename);
+    if with_undo && undo_path.exists() {
+        return Err(FlywayError::UndoMigrationExists(undo_filename));
+    }
+    let written = write_migration(&path, &ddl, config).map_err(io_error)?;
+    if let WriteOutcome::Written(bytes) = written {
+        if config.confirm_small_migrations
+            && bytes < config.min_migration_bytes
+            && !confirm(&small_file_question(&filename, bytes))
+        {
+            return fs::remove_file(&path).map_err(io_error);
+        }
+    }
+    remember_migration(&path);
+    if with_undo {
+        let stub = format!(
+            "-- TODO: write undo for {}\n",
+            basename.trim_end_matches(".sql")
+        );
+        if let Err(e) = write_migration(&undo_path, &stub, config) {
+            // an unchanged versioned migration was there before
+            if let WriteOutcome::Written(_) = written {
+                if let Err(e) = fs::remove_file(&path) {
+                    warn!(target: target::FLYWAY, "Could not delete {:?}: {}", path, e);
+                }
+            }
+            return Err(io_error(e));
+        }
+        remember_migration(&undo_path);
+    }
+    Ok(())
+}
+
+// Some windows return their whole text as selection if nothing is selected; a long
+// "selection" identical to the whole text is most likely such a case
+fn is_unintended_whole_buffer(selected_text: &str, whole_text: &str, threshold: usize) -> bool {
+    selected_text == whole_text && selected_text.chars().count() > threshold
+}
+
+fn whole_buffer_question(text: &str) -> String {
+    format!(
+        "No explicit selection detected - export the entire window contents ({} lines)?",
+        text.lines().count()
+    )
+}
+
+// Write a migration file, converting the line endings as configured; returns its size in bytes.
+// If the config says to keep the line endings, an .editorconfig in the target folder
+// (or one of its parents) gets the final say.
+pub fn write_migration(
+    path: &Path,
+    content: &str,
+    config: &Config,
+) -> std::io::Result<WriteOutcome> {
+    let content = convert_line_endings(content, line_ending_for(path, config));
+    // keep unchanged files untouched, even if the existing one starts with a BOM
+    if let Ok(existing) = fs::read(path) {
+        if same_content_ignoring_bom(&existing, content.as_bytes()) {
+            debug!(target: target::FLYWAY, "{:?} is unchanged", path);
+            return Ok(WriteOutcome::Unchanged(existing.len()));
+        }
+    }
+    write_verified(path, content.as_bytes(), config.verify_written_files)?;
+    Ok(WriteOutcome::Written(content.len()))
+}
+
+// The configured line ending of a migration file, or the one of the .editorconfig for its folder
+fn line_ending_for(path: &Path, config: &Config) -> LineEnding {
+    match config.line_ending {
+        LineEnding::Keep => {
+            let folder = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            find_end_of_line(folder).unwrap_or(LineEnding::Keep)
+        }
+        line_ending => line_ending,
+    }
+}
+
+// What write_migration did, with the size of the file in bytes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WriteOutcome {
+    Written(usize),
+    // the file already had this content and was left untouched
+    Unchanged(usize),
+}
+
+impl WriteOutcome {
+    pub fn bytes(&self) -> usize {
+        match self {
+            WriteOutcome::Written(bytes) | WriteOutcome::Unchanged(bytes) => *bytes,
+        }
+    }
+}
+
+const DATABASE_TIMESTAMP_QUERY: &str =
+    "select to_char(sys_extract_utc(systimestamp), 'YYYY MM DD HH24 MI SS FF3') from dual";
+
+// The output of DATABASE_TIMESTAMP_QUERY, the database's time in UTC
+fn parse_database_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value.trim(), "%Y %m %d %H %M %S %3f")
+        .ok()
+        .map(|timestamp| Utc.from_utc_datetime(&timestamp))
+}
+
+// The timestamp of versioned migrations created now, from the configured clock
+pub fn migration_timestamp(api: &dyn PlsqlDevApi, config: &Config) -> DateTime<Utc> {
+    if config.timestamp_source == TimestampSource::Workstation {
+        return Utc::now();
+    }
+    let value = api.ide_query_value(DATABASE_TIMESTAMP_QUERY);
+    match value.as_deref().and_then(parse_database_timestamp) {
+        Some(timestamp) => timestamp,
+        None => {
+            warn!(target: target::FLYWAY,
+                "Could not get the database time (got {:?}), using the workstation's",
+                value
+            );
+            Utc::now()
+        }
+    }
+}
+
+// The version of a versioned migration created now in the folder
+fn next_version(api: &dyn PlsqlDevApi, config: &Config, folder: &Path) -> String {
+    match config.versioning {
+        Versioning::Timestamp => timestamp_version(config, migration_timestamp(api, config)),
+        Versioning::Sequential => next_sequential_version(folder, config).to_string(),
+    }
+}
+
+// <prefix><version>__<basename>.sql; a .sql suffix of the basename is removed, so we don't get
+// file names with suffix .sql.sql
+fn migration_file_name(prefix: &str, version: &str, basename: &str, config: &Config) -> String {
+    format!(
+        "{}{}{}{}.sql",
+        prefix,
+        version,
+        config.version_description_separator,
+        basename.trim_end_matches(".sql")
+    )
+}
+
+// The version part of a versioned migration's file name, e.g. 42 for V42__PKG_SNAFU.sql.
+// The description separator may also occur within a timestamp version (e.g. with `_` between
+// the fields and as separator), so the separator following a version of the configured shape is
+// looked for first, and the first separator only if the version has a different shape.
+pub fn version_of<'a>(file_name: &'a str, config: &Config) -> &'a str {
+    let rest = match file_name.strip_prefix(&config.version_prefix) {
+        Some(rest) => rest,
+        None => return "",
+    };
+    let length = match config.versioning {
+        Versioning::Timestamp => {
+            let sample = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+            version_shape_length(rest, &timestamp_version(config, sample))
+        }
+        Versioning::Sequential => {
+            Some(rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len())
+        }
+    };
+    match length {
+        Some(length)
+            if length > 0 && rest[length..].starts_with(&config.version_description_separator) =>
+        {
+            &rest[..length]
+        }
+        _ => rest
+            .split_once(&config.version_description_separator)
+            .map_or("", |(version, _)| version),
+    }
+}
 
-    if let Err(message) = basename {
-        return match message {
-            "Cancelled" => Ok({}),
-            "Empty name" => Err(FlywayError::EmptyFileName),
-            _ => Err(FlywayError::IOError(message.to_string())),
+// The length of the version at the start of `text` if it has the shape of `sample`: digits where
+// the sample has digits, and the same characters everywhere else
+fn version_shape_length(text: &str, sample: &str) -> Option<usize> {
+    let mut chars = text.chars();
+    for expected in sample.chars() {
+        let c = chars.next()?;
+        let matches = match expected.is_ascii_digit() {
+            true => c.is_ascii_digit(),
+            false => c == expected,
         };
+        if !matches {
+            return None;
+        }
     }
+    Some(text.len() - chars.as_str().len())
+}
 
-    let filename = get_versioned_filename(config, &basename.unwrap());
-    // write DDL to output file
-    let file = File::create(filename);
-    let res = match file {
-        Ok(mut f) => f.write_all(ddl.as_bytes()),
-        Err(e) => Err(e),
+pub fn get_sequential_filename(config: &Config, version: u64, basename: &str) -> String {
+    migration_file_name(
+        &config.version_prefix,
+        &version.to_string(),
+        basename,
+        config,
+    )
+}
+
+// Determine the next sequential version by looking for the highest V<number>__ migration in the folder
+pub fn next_sequential_version(folder: &Path, config: &Config) -> u64 {
+    lazy_static! {
+        static ref LEADING_NUMBER: Regex = Regex::new(r"^\d+").unwrap();
+    }
+    // <prefix><number><separator>...
+    let sequential_version = |file_name: &str| -> Option<u64> {
+        let rest = file_name.strip_prefix(&config.version_prefix)?;
+        let number = LEADING_NUMBER.find(rest)?;
+        if !rest[number.end()..].starts_with(&config.version_description_separator) {
+            return None;
+        }
+        number.as_str().parse().ok()
+    };
+
+    let highest_version = match fs::read_dir(folder) {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|entry| sequential_version(&entry.file_name().to_string_lossy()))
+            .max()
+            .unwrap_or(0),
+        Err(_) => 0,
     };
-    // convert from Result<(), std::io::Error> to Result<(), FlywayError>
-    return res.map_err(|e| FlywayError::IOError(format!("{}", e)));
+    highest_version + 1
+}
+
+// `count` timestamps one second (or millisecond) apart and ending at `now`, so the versions of
+// migrations created together are strictly increasing, sort in the order they were created and
+// never lie in the future
+pub fn offset_timestamps(
+    now: DateTime<Utc>,
+    count: usize,
+    use_millisecond_precision: bool,
+) -> Vec<DateTime<Utc>> {
+    (0..count)
+        .rev()
+        .map(|i| match use_millisecond_precision {
+            true => now - ChronoDuration::milliseconds(i as i64),
+            false => now - ChronoDuration::seconds(i as i64),
+        })
+        .collect()
+}
+
+// The versioned migrations of objects exported together, in export order:
+// V<version>__<description>_<basename>.sql, or V<version>__<basename>.sql without a description
+fn versioned_file_names(
+    config: &Config,
+    folder: &Path,
+    selected_objects: &[SelectedObject],
+    description: &str,
+    now: DateTime<Utc>,
+) -> Vec<String> {
+    let description = sanitize_file_name(description.trim()).replace(char::is_whitespace, "_");
+    let basenames = selected_objects.iter().map(|o| {
+        let basename = migration_basename(o, config);
+        match description.is_empty() {
+            true => basename,
+            false => format!("{}_{}", description, basename),
+        }
+    });
+    match config.versioning {
+        Versioning::Timestamp => offset_timestamps(
+            now,
+            selected_objects.len(),
+            config.use_millisecond_precision,
+        )
+        .into_iter()
+        .zip(basenames)
+        .map(|(timestamp, basename)| get_versioned_filename_impl(config, timestamp, &basename))
+        .collect(),
+        Versioning::Sequential => {
+            let first_version = next_sequential_version(folder, config);
+            basenames
+                .enumerate()
+                .map(|(i, basename)| {
+                    get_sequential_filename(config, first_version + i as u64, &basename)
+                })
+                .collect()
+        }
+    }
+}
+
+// The content of one versioned migration combining the sources of several objects, in the
+// given order: each source (already owner-qualified, see fetch_object_source) terminated by a
+// line with `/`, and separated from the next one by the configured number of blank lines
+fn combine_sources(objects: &[(SelectedObject, String)], config: &Config) -> String {
+    let terminated: Vec<String> = objects
+        .iter()
+        .map(|(selected_object, source)| {
+            debug!(target: target::FLYWAY, "Combining {}", selected_object);
+            let source = match config.emit_prompts {
+                true => PromptLine.apply(source, &DdlContext::of(selected_object)),
+                false => source.to_string(),
+            };
+            let source = source.trim_end();
+            match source.lines().last().map(str::trim) {
+                Some("/") => format!("{}\n", source),
+                _ => format!("{}\n/\n", source),
+            }
+        })
+        .collect();
+    terminated.join(&"\n".repeat(config.object_separator_blank_lines as usize))
+}
+
+// Check a configured version format. Formatting with an unsupported specifier (e.g. %.2f)
+// panics with 'a Display implementation returned an error unexpectedly', so it must be
+// rejected before it's used. Flyway applies migrations in the order of their versions, so the
+// timestamps must sort like the times they stand for, and the version must end where the
+// description separator starts.
+pub fn validate_version_format(format: &str, description_separator: &str) -> Result<(), String> {
+    if format.trim().is_empty() {
+        return Err("the format is empty".to_string());
+    }
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(format!("{:?} is not a valid timestamp format", format));
+    }
+    if let Some(i) = format.find(|c| "<>:\"/\\|?*".contains(c)) {
+        return Err(format!(
+            "{:?} would put {:?} into file names",
+            format,
+            &format[i..i + 1]
+        ));
+    }
+    if !description_separator.is_empty() && format.contains(description_separator) {
+        return Err(format!(
+            "{:?} contains the description separator {:?}",
+            format, description_separator
+        ));
+    }
+    match sorts_chronologically(format) {
+        true => Ok(()),
+        false => Err(format!(
+            "{:?} does not sort chronologically, the fields must go from the year down",
+            format
+        )),
+    }
+}
+
+// Whether versions in the format sort like their timestamps: the fields go from the most to the
+// least significant one, starting with the year (or the seconds since the epoch), and all but the
+// first have a fixed width
+fn sorts_chronologically(format: &str) -> bool {
+    let mut last_rank = None;
+    for item in StrftimeItems::new(format) {
+        let (rank, pad) = match item {
+            Item::Numeric(numeric, pad) => match numeric {
+                Numeric::YearDiv100 => (0, pad),
+                Numeric::Year | Numeric::YearMod100 | Numeric::Timestamp => (1, pad),
+                Numeric::Month => (2, pad),
+                Numeric::Day | Numeric::Ordinal => (3, pad),
+                Numeric::Hour => (4, pad),
+                Numeric::Minute => (5, pad),
+                Numeric::Second => (6, pad),
+                Numeric::Nanosecond => (7, pad),
+                // weekdays, weeks, 12 hour clocks, ...
+                _ => return false,
+            },
+            Item::Fixed(Fixed::Nanosecond)
+            | Item::Fixed(Fixed::Nanosecond3)
+            | Item::Fixed(Fixed::Nanosecond6)
+            | Item::Fixed(Fixed::Nanosecond9)
+            // %3f, %6f and %9f
+            | Item::Fixed(Fixed::Internal(_)) => (7, Pad::Zero),
+            // names of months and weekdays, time zones, ...
+            Item::Fixed(_) => return false,
+            _ => continue,
+        };
+        let in_order = match last_rank {
+            None => rank <= 1,
+            Some(last_rank) => rank > last_rank && pad == Pad::Zero,
+        };
+        if !in_order {
+            return false;
+        }
+        last_rank = Some(rank);
+    }
+    last_rank.is_some()
 }
 
-fn get_versioned_filename(config: &Config, basename: &str) -> String {
-    let now = Utc::now();
-    get_versioned_filename_impl(config, now, basename)
+// The fields of the timestamp, joined by the separator
+fn default_version_format(config: &Config) -> String {
+    // a % in the separator would be taken for a format specifier
+    let separator = config.timestamp_field_separator.replace('%', "%%");
+    ["%Y", "%m", "%d", "%H", "%M", "%S"].join(&separator)
 }
 
-fn get_versioned_filename_impl(
+pub fn get_versioned_filename_impl(
     config: &Config,
     timestamp: chrono::DateTime<chrono::Utc>,
     basename: &str,
 ) -> String {
     // construct filename: V<timestamp>_<basename>.sql
-    // if basename already contains a .sql suffix, it is removed so we don't get filenams with suffix .sql.sql
-    // the user can opt in to include milliseconds in the timestamp to avoid collisions if two developers create migrations
-    // at the exact same second
-    // CAUTION: only 3f and 6f are supported - trying to use eg 2f causes an External Exception /
-    //          thread 'main' panicked at 'a Display implementation return an error unexpectedly: Error'
-    //          at runtime!
-    let version = match config.use_millisecond_precision {
-        true => timestamp.format("V%Y_%m_%d_%H_%M_%S%.3f__"),
-        false => timestamp.format("V%Y_%m_%d_%H_%M_%S__"),
+    let version = timestamp_version(config, timestamp);
+    migration_file_name(&config.version_prefix, &version, basename, config)
+}
+
+// The timestamp as version, in the configured format
+// the user can opt in to include milliseconds in the timestamp to avoid collisions if two developers create migrations
+// at the exact same second
+fn timestamp_version(config: &Config, timestamp: DateTime<Utc>) -> String {
+    let mut format = match &config.version_format {
+        Some(format) => {
+            match validate_version_format(format, &config.version_description_separator) {
+                Ok(()) => format.clone(),
+                Err(e) => {
+                    warn!(target: target::FLYWAY, "Ignoring the configured version format: {}", e);
+                    default_version_format(config)
+                }
+            }
+        }
+        None => default_version_format(config),
     };
-    let result = format!("{}{}.sql", version, basename.trim_end_matches(".sql"));
-    result
+    if config.use_millisecond_precision {
+        format.push_str("%.3f");
+    }
+    timestamp.format(&format).to_string()
 }
 
-const NO_OBJECT_SELECTED_MESSAGE: &[u8] = b"Please select an object in the object browser first!\0";
-const NO_OBJECT_SELECTED_CAPTION: &[u8] = b"Nothing selected\0";
+pub const NO_OBJECT_SELECTED_MESSAGE: &str = "Please select an object in the object browser first!";
+pub const NO_OBJECT_SELECTED_CAPTION: &str = "Nothing selected";
+
+pub fn create_repeatable_migration(api: &dyn PlsqlDevApi, config: &Config, export_versioned: bool) {
+    let selected_objects = api.ide_selected_objects();
+    if !selected_objects.is_empty() {
+        export_repeatable_migrations(api, config, export_versioned, selected_objects);
+    } else {
+        dialogs().show_message(
+            NO_OBJECT_SELECTED_MESSAGE,
+            NO_OBJECT_SELECTED_CAPTION,
+            MessageKind::Information,
+        );
+    }
+}
 
-pub fn create_repeatable_migration(
-    api: &RwLockReadGuard<Box<dyn PlsqlDevApi + Send + Sync>>,
+// Ask for the target folder and export the objects as repeatable (+ versioned) migrations
+pub fn export_repeatable_migrations(
+    api: &dyn PlsqlDevApi,
     config: &Config,
     export_versioned: bool,
+    mut selected_objects: Vec<SelectedObject>,
 ) {
-    if let Some(selected_object) = api.ide_first_selected_object() {
-        // ME 2021-07-18: #48, do not support multi-export with versioned migration
-        if export_versioned && api.ide_next_selected_object().is_some() {
-            let message = CString::new("Exporting multiple selected objects as versioned and repeatable migrations is not supported!").unwrap();
-            let caption = CString::new("Information").unwrap();
-            show_message_box(&message, &caption, MB_OK | MB_ICONINFORMATION);
+    if !export_versioned && selected_objects.iter().any(is_versioned_only) {
+        let (versioned_only, others): (Vec<_>, Vec<_>) =
+            selected_objects.into_iter().partition(is_versioned_only);
+        let names: Vec<String> = versioned_only
+            .iter()
+            .take(MAX_SUMMARY_OBJECTS)
+            .map(|o| format!("{}.{}", o.object_owner, o.object_name))
+            .collect();
+        dialogs().show_message(
+            &format!(
+                "{}, please use \"Repeatable + versioned migration\" for them.\nSkipped: {}",
+                versioned_only_reason(&versioned_only[0].object_type),
+                names.join(", ")
+            ),
+            "Repeatable migration",
+            MessageKind::Information,
+        );
+        if others.is_empty() {
             return;
         }
+        selected_objects = others;
+    }
+    // ME 2021-07-18: #48, do not support multi-export with versioned migration
+    // (sequential versions can't collide, so they're fine; neither can offset timestamps or a
+    // single combined migration)
+    if export_versioned
+        && selected_objects.len() > 1
+        && config.versioning != Versioning::Sequential
+        && !config.multi_object_versioned
+        && !config.combine_versioned
+    {
+        dialogs().show_message(
+            "Exporting multiple selected objects as versioned and repeatable migrations is not supported!",
+            "Information",
+            MessageKind::Information,
+        );
+        return;
+    }
 
-        debug!("Selected object: {}", selected_object);
+    let folder_name = match configured_folder(config, config.repeatable_folder.as_deref()) {
+        Some(folder) => folder,
+        None => dialogs().get_save_folder_name(),
+    };
+    debug!(target: target::FLYWAY, "Selected folder: {:?}", folder_name);
+    // the conventions of the target repository apply to this export
+    let config = &match folder_name.is_empty() {
+        true => config.clone(),
+        false => config_for_folder(config, Path::new(&folder_name)),
+    };
 
-        let folder_name = get_save_folder_name();
-        debug!("Selected folder: {:?}", folder_name);
+    // nothing is written into the folder before the user confirmed the dry run
+    if config.dry_run {
+        let planned = plan_repeatable_migrations(&folder_name, &selected_objects, config);
+        let question = format!("{}\n\nExport now?", dry_run_report(&planned));
+        if !dialogs().ask_yes_no(&question, "Dry run") {
+            return;
+        }
+    }
 
-        let mut objects_exported = 0;
+    if !folder_name.is_empty() {
+        // fail before fetching the sources of all objects, not on the first one written
+        if let Err(e) = probe_writable(Path::new(&folder_name)) {
+            let error = FlywayError::FolderNotWritable(
+                paths::display(config.repo_root.as_deref(), &folder_name),
+                format!("{}", e),
+            );
+            dialogs().show_message(&format!("{}", error), "Error", MessageKind::Error);
+            return;
+        }
+        ensure_gitattributes(Path::new(&folder_name), config);
+    }
 
-        if export_object_as_repeatable_migration(
-            &api,
+    let description =
+        match export_versioned && (config.multi_object_versioned || config.combine_versioned) {
+            true => match dialogs().get_user_input("Description of the versioned migrations") {
+                Ok(description) => description,
+                // user cancelled
+                Err(_) => return,
+            },
+            false => String::new(),
+        };
+    let versioned = match export_versioned {
+        true => Some(description.as_str()),
+        false => None,
+    };
+
+    if config.write_callback {
+        if let Err(e) = write_callback(&folder_name, config) {
+            warn!(target: target::FLYWAY, "Could not write Flyway callback: {}", e);
+        }
+    }
+
+    let caption = "Repeatable migration";
+    let (summary, mut exported, failures) = {
+        let mut progress = dialogs().start_progress(caption, &BULK_CANCEL);
+        export_objects_as_repeatable_migrations(
+            api,
             &folder_name,
-            &selected_object,
+            &mut selected_objects,
             config,
-            export_versioned,
+            versioned,
+            &BULK_CANCEL,
+            progress.as_mut(),
         )
-        .is_ok()
-        {
-            objects_exported += 1
+    };
+    let discarded = match config.confirm_small_migrations {
+        true => discard_unconfirmed_small_files(
+            Path::new(&folder_name),
+            &mut exported,
+            config.min_migration_bytes,
+            |question| dialogs().ask_yes_no(question, caption),
+        ),
+        false => vec![],
+    };
+    // with versioned migrations, which file belongs to which object isn't obvious;
+    // suspiciously small files must be seen
+    let any_small = exported
+        .iter()
+        .flat_map(|object| &object.files)
+        .any(|file| file.is_suspiciously_small(config.min_migration_bytes));
+    let mut files = match export_versioned || any_small {
+        true => format!(
+            "\n\n{}",
+            exported_files_summary(&exported, MAX_SUMMARY_OBJECTS, config.min_migration_bytes)
+        ),
+        false => String::new(),
+    };
+    if !discarded.is_empty() {
+        files.push_str(&format!("\n\nDeleted: {}", discarded.join(", ")));
+    }
+    if config.show_help_links {
+        // both kinds are explained by the naming in general
+        files.push_str(&help_footer(match export_versioned {
+            true => None,
+            false => Some(MigrationKind::Repeatable),
+        }));
+    }
+
+    let timeout = config.bulk_timeout_secs.map(Duration::from_secs);
+    if let Some(interruption) = summary.interruption(timeout) {
+        let message = format!(
+            "{}\nExported {} objects as repeatable migration(s) to {}.{}",
+            interruption,
+            summary.succeeded,
+            paths::display(config.repo_root.as_deref(), &folder_name),
+            files
+        );
+        dialogs().show_message(&message, caption, MessageKind::Information);
+    } else if summary.succeeded > 0 {
+        let message = format!(
+            "Successfully exported {} objects as repeatable migration(s) to {}.{}",
+            summary.succeeded,
+            paths::display(config.repo_root.as_deref(), &folder_name),
+            files
+        );
+        dialogs().show_message(&message, caption, MessageKind::Information);
+    } else {
+        let message = "No repeatable migrations were created!\nPlease make sure you have selected one or more supported\nobject types.";
+        dialogs().show_message(message, caption, MessageKind::Error);
+    }
+    if !failures.is_empty() {
+        let mut lines: Vec<&str> = failures
+            .iter()
+            .take(MAX_SUMMARY_OBJECTS)
+            .map(String::as_str)
+            .collect();
+        let more = format!(
+            "... and {} more",
+            failures.len().saturating_sub(lines.len())
+        );
+        if failures.len() > lines.len() {
+            lines.push(&more);
         }
+        let message = format!("These objects could not be exported:\n{}", lines.join("\n"));
+        dialogs().show_message(&message, caption, MessageKind::Error);
+    }
+}
 
-        while let Some(selected_object) = api.ide_next_selected_object() {
-            debug!("Selected object: {}", selected_object);
+// objects listed in the final message of an export, so it still fits on the screen
+const MAX_SUMMARY_OBJECTS: usize = 25;
 
-            if export_object_as_repeatable_migration(
-                &api,
-                &folder_name,
-                &selected_object,
-                config,
-                export_versioned,
-            )
-            .is_ok()
-            {
-                objects_exported += 1
-            }
+// A migration file written by an export
+#[derive(Debug, PartialEq)]
+pub struct WrittenFile {
+    pub name: String,
+    pub bytes: usize,
+    // the file already existed with the same content, nothing was written
+    pub unchanged: bool,
+}
+
+impl WrittenFile {
+    // e.g. a truncated object source
+    pub fn is_suspiciously_small(&self, min_bytes: usize) -> bool {
+        self.bytes < min_bytes
+    }
+}
+
+// An object written by a bulk export, with the files written for it
+#[derive(Debug, PartialEq)]
+pub struct ExportedObject {
+    pub name: String,
+    pub object_type: String,
+    pub files: Vec<WrittenFile>,
+}
+
+// One line per object: OWNER.NAME: V..._NAME.sql (1234 bytes), R__NAME.sql (1234 bytes)
+fn exported_files_summary(
+    exported: &[ExportedObject],
+    max_objects: usize,
+    min_bytes: usize,
+) -> String {
+    let describe = |file: &WrittenFile| match file.is_suspiciously_small(min_bytes) {
+        _ if file.unchanged => format!("{} (unchanged)", file.name),
+        true => format!(
+            "{} ({} bytes, suspiciously small - please verify)",
+            file.name, file.bytes
+        ),
+        false => format!("{} ({} bytes)", file.name, file.bytes),
+    };
+    let mut lines: Vec<String> = exported
+        .iter()
+        .take(max_objects)
+        .map(|object| {
+            let files: Vec<String> = object.files.iter().map(describe).collect();
+            format!("{}: {}", object.name, files.join(", "))
+        })
+        .collect();
+    if exported.len() > max_objects {
+        lines.push(format!("... and {} more", exported.len() - max_objects));
+    }
+    lines.join("\n")
+}
+
+// Write the configured Flyway callback (e.g. afterEachMigrate__recompile.sql) into the folder.
+// An existing callback file is left untouched, as it may have been edited by hand.
+fn write_callback(folder_name: &str, config: &Config) -> std::io::Result<()> {
+    let path: PathBuf = [folder_name, &config.callback_file_name].iter().collect();
+    if path.exists() {
+        debug!(target: target::FLYWAY, "Flyway callback {:?} already exists", path);
+        return Ok(());
+    }
+    write_migration(&path, &config.callback_body, config).map(|_| ())
+}
+
+const GITATTRIBUTES_FILE_NAME: &str = ".gitattributes";
+
+// Write a .gitattributes telling git to keep the configured line endings of SQL files.
+// An existing file is never touched. Returns whether the file was created.
+fn write_gitattributes(folder: &Path, config: &Config) -> std::io::Result<bool> {
+    let eol = match config.line_ending {
+        LineEnding::Keep => find_end_of_line(folder).unwrap_or(LineEnding::Lf),
+        line_ending => line_ending,
+    };
+    let eol = match eol {
+        LineEnding::CrLf => "crlf",
+        _ => "lf",
+    };
+    let path = folder.join(GITATTRIBUTES_FILE_NAME);
+    // create_new fails if the file exists, so there's no window to overwrite it
+    match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(mut file) => {
+            file.write_all(format!("*.sql text eol={}\n", eol).as_bytes())?;
+            Ok(true)
         }
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            debug!(target: target::FLYWAY, "{:?} already exists", path);
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
 
-        let caption = CString::new("Repeatable migration").unwrap();
-        if objects_exported > 0 {
-            let message = CString::new(format!(
-                "Successfully exported {} objects as repeatable migration(s).",
-                objects_exported
-            ))
-            .unwrap();
-            show_message_box(&message, &caption, MB_OK | MB_ICONINFORMATION);
-        } else {
-            let message = CString::new("No repeatable migrations were created!\nPlease make sure you have selected one or more supported\nobject types.").unwrap();
-            show_message_box(&message, &caption, MB_OK | MB_ICONERROR);
+// Check that files can be created in the folder, by creating and deleting a probe file
+pub fn probe_writable(folder: &Path) -> std::io::Result<()> {
+    let probe = folder.join(PROBE_FILE_NAME);
+    File::create(&probe)?;
+    fs::remove_file(&probe)
+}
+
+pub fn ensure_gitattributes(folder: &Path, config: &Config) {
+    if config.manage_gitattributes {
+        if let Err(e) = write_gitattributes(folder, config) {
+            warn!(target: target::FLYWAY, "Could not write .gitattributes: {}", e);
+        }
+    }
+}
+
+// Ask whether each suspiciously small file written by this export should be kept, and delete
+// those that shouldn't; returns the names of the deleted files
+fn discard_unconfirmed_small_files<F>(
+    folder: &Path,
+    exported: &mut [ExportedObject],
+    min_bytes: usize,
+    mut confirm: F,
+) -> Vec<String>
+where
+    F: FnMut(&str) -> bool,
+{
+    let mut discarded = vec![];
+    for object in exported.iter_mut() {
+        object.files.retain(|file| {
+            // an unchanged file was there before, and isn't this export's to delete
+            if file.unchanged || !file.is_suspiciously_small(min_bytes) {
+                return true;
+            }
+            let question =
+                small_file_question(&format!("{} of {}", file.name, object.name), file.bytes);
+            if confirm(&question) {
+                return true;
+            }
+            if let Err(e) = fs::remove_file(folder.join(&file.name)) {
+                warn!(target: target::FLYWAY, "Could not delete {}: {}", file.name, e);
+                return true;
+            }
+            discarded.push(file.name.clone());
+            false
+        });
+    }
+    discarded
+}
+
+fn small_file_question(file: &str, bytes: usize) -> String {
+    format!(
+        "{} is only {} bytes, which is suspiciously small.\nKeep it?",
+        file, bytes
+    )
+}
+
+// Export all objects, ordered by the configured object type weight (and by selection order
+// within the same weight), so e.g. types get lower sequential versions than packages using them.
+// With `versioned` (the description of the versioned migrations), each object also gets a
+// versioned migration, with versions increasing in that order; or, with several objects and
+// combine_versioned, they all share one versioned migration combining their sources in that order.
+// Stops between objects when `cancel` is set or the configured time limit is reached.
+// Returns the exported objects, and why objects of supported types could not be exported.
+pub fn export_objects_as_repeatable_migrations(
+    api: &dyn PlsqlDevApi,
+    folder_name: &str,
+    selected_objects: &mut [SelectedObject],
+    config: &Config,
+    versioned: Option<&str>,
+    cancel: &AtomicBool,
+    progress: &mut dyn Progress,
+) -> (BulkSummary, Vec<ExportedObject>, Vec<String>) {
+    // sort_by_key is stable, so the selection order is kept for objects of the same weight
+    selected_objects.sort_by_key(|o| config.object_type_weight(&o.object_type));
+
+    let combined_file_name = match versioned {
+        Some(description) if config.combine_versioned && selected_objects.len() > 1 => Some(
+            combined_file_name(api, config, Path::new(folder_name), description),
+        ),
+        _ => None,
+    };
+    let mut combined = vec![];
+    // named up front, as the timestamps must not depend on how long each export takes
+    let versioned_file_names = match versioned {
+        Some(description) if combined_file_name.is_none() => versioned_file_names(
+            config,
+            Path::new(folder_name),
+            selected_objects,
+            description,
+            migration_timestamp(api, config),
+        ),
+        _ => vec![],
+    };
+    let total = selected_objects.len();
+    let timeout = config.bulk_timeout_secs.map(Duration::from_secs);
+    let mut exported = vec![];
+    let mut failures = vec![];
+    let summary = run_bulk(
+        selected_objects,
+        cancel,
+        timeout,
+        &SystemClock,
+        |index, selected_object| {
+            debug!(target: target::FLYWAY, "Selected object: {}", selected_object);
+            progress.update(&format!(
+                "Exporting {}.{} ({} of {})",
+                selected_object.object_owner,
+                selected_object.object_name,
+                index + 1,
+                total
+            ));
+            match export_object_as_repeatable_migration(
+                api,
+                folder_name,
+                selected_object,
+                config,
+                versioned_file_names.get(index).map(String::as_str),
+                combined_file_name.as_ref().map(|_| &mut combined),
+            ) {
+                Ok(files) => {
+                    exported.push(ExportedObject {
+                        name: format!(
+                            "{}.{}",
+                            selected_object.object_owner, selected_object.object_name
+                        ),
+                        object_type: selected_object.object_type.clone(),
+                        files,
+                    });
+                    true
+                }
+                // unsupported object types are expected in a mixed selection
+                Err(e) if e.kind() == ErrorKind::InvalidInput => false,
+                Err(e) => {
+                    warn!(target: target::FLYWAY, "Could not export {}: {}", selected_object, e);
+                    failures.push(format!(
+                        "{}.{}: {}",
+                        selected_object.object_owner, selected_object.object_name, e
+                    ));
+                    false
+                }
+            }
+        },
+    );
+    if let Some(file_name) = combined_file_name {
+        if !combined.is_empty() {
+            match write_combined_migration(folder_name, &file_name, &combined, config) {
+                Ok(file) => exported.push(ExportedObject {
+                    name: format!("{} objects combined", combined.len()),
+                    object_type: String::new(),
+                    files: vec![file],
+                }),
+                Err(e) => {
+                    warn!(target: target::FLYWAY, "Could not write {}: {}", file_name, e);
+                    failures.push(format!("{}: {}", file_name, e));
+                }
+            }
         }
-    } else {
-        let message = CStr::from_bytes_with_nul(NO_OBJECT_SELECTED_MESSAGE).unwrap();
-        let caption = CStr::from_bytes_with_nul(NO_OBJECT_SELECTED_CAPTION).unwrap();
-        show_message_box(message, caption, MB_OK | MB_ICONINFORMATION);
     }
+    (summary, exported, failures)
+}
+
+// V<version>__<description>.sql, the versioned migration combining the objects exported together
+fn combined_file_name(
+    api: &dyn PlsqlDevApi,
+    config: &Config,
+    folder: &Path,
+    description: &str,
+) -> String {
+    let description = sanitize_file_name(description.trim()).replace(char::is_whitespace, "_");
+    let basename = match description.is_empty() {
+        true => "combined",
+        false => &description,
+    };
+    migration_file_name(
+        &config.version_prefix,
+        &next_version(api, config, folder),
+        basename,
+        config,
+    )
+}
+
+// Write the versioned migration combining the (owner-qualified) sources of the objects
+fn write_combined_migration(
+    folder_name: &str,
+    file_name: &str,
+    combined: &[(SelectedObject, String)],
+    config: &Config,
+) -> std::io::Result<WrittenFile> {
+    let path: PathBuf = [folder_name, file_name].iter().collect();
+    let outcome = write_migration(&path, &combine_sources(combined, config), config)?;
+    remember_migration(&path);
+    Ok(WrittenFile {
+        name: file_name.to_string(),
+        bytes: outcome.bytes(),
+        unchanged: matches!(outcome, WriteOutcome::Unchanged(_)),
+    })
 }
 
-const SUPPORTED_OBJECT_TYPES: [&str; 6] = [
+pub const SUPPORTED_OBJECT_TYPES: [&str; 7] = [
     "FUNCTION",
     "PROCEDURE",
     "PACKAGE",
     "TYPE",
     "VIEW",
+    "MATERIALIZED VIEW",
     "TRIGGER",
 ];
 
+// object types that can't be created again and again, so they have versioned migrations only
+pub const VERSIONED_ONLY_OBJECT_TYPES: [&str; 1] = ["TABLE"];
+
+fn is_versioned_only(selected_object: &SelectedObject) -> bool {
+    VERSIONED_ONLY_OBJECT_TYPES.contains(&selected_object.object_type.as_str())
+}
+
+// e.g. "tables can only be exported as versioned migrations"
+fn versioned_only_reason(object_type: &str) -> String {
+    format!(
+        "{}s can only be exported as versioned migrations",
+        object_type.to_lowercase()
+    )
+}
+
 // not sure we actually need the sub_object from above
+// Writes the versioned migration too if its file name is given; returns the files written.
+// With `combined`, the source is added to the ones of the combined versioned migration instead,
+// which is written once all objects are exported.
 fn export_object_as_repeatable_migration(
-    api: &RwLockReadGuard<Box<dyn PlsqlDevApi + Send + Sync>>,
+    api: &dyn PlsqlDevApi,
     folder_name: &str,
     selected_object: &SelectedObject,
     config: &Config,
-    export_versioned: bool,
-) -> std::io::Result<()> {
+    versioned_file_name: Option<&str>,
+    combined: Option<&mut Vec<(SelectedObject, String)>>,
+) -> std::io::Result<Vec<WrittenFile>> {
     // check for supported object type
-    if !SUPPORTED_OBJECT_TYPES.contains(&selected_object.object_type.as_str()) {
+    let versioned_only = is_versioned_only(selected_object);
+    if !versioned_only && !SUPPORTED_OBJECT_TYPES.contains(&selected_object.object_type.as_str()) {
         return Err(Error::new(
             ErrorKind::InvalidInput,
             format!(
@@ -220,173 +1156,464 @@ fn export_object_as_repeatable_migration(
         ));
     }
 
-    let object_source = match selected_object.object_type.as_str() {
-        "PACKAGE" | "TYPE" => get_object_source_and_body(api, selected_object),
-        _ => get_object_source(api, selected_object),
-    };
+    if let Some(reason) = invalid_object_reason(selected_object) {
+        return Err(Error::new(ErrorKind::InvalidData, reason));
+    }
+    // explained before exports of repeatable migrations only, see export_repeatable_migrations
+    if versioned_only && versioned_file_name.is_none() && combined.is_none() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            versioned_only_reason(&selected_object.object_type),
+        ));
+    }
 
-    let basename = selected_object.object_name.to_uppercase();
-    if export_versioned {
-        let versioned_file_name = get_versioned_filename(config, &basename);
-        let path: PathBuf = [folder_name, &versioned_file_name].iter().collect();
-        // TODO I don't like the _ assignment - perhaps there's a more elegant way using and_then / map or similar?
-        let _ = match File::create(path) {
-            Ok(mut f) => f.write_all(object_source.as_bytes()),
-            Err(e) => return Err(e),
-        };
+    let object_source = migration_source(api, selected_object, config)?;
+    let context = DdlContext::of(selected_object);
+    if let Some(combined) = combined {
+        combined.push((selected_object.clone(), object_source.clone()));
+        if versioned_only {
+            return Ok(vec![]);
+        }
     }
-    let file_name = format!("R__{}.sql", basename);
-    let path: PathBuf = [folder_name, &file_name].iter().collect();
-    return match File::create(path) {
-        Ok(mut f) => f.write_all(object_source.as_bytes()),
-        Err(e) => Err(e),
-    };
-}
 
-// fetches the source of a package or type including its body
-fn get_object_source_and_body(
-    api: &RwLockReadGuard<Box<dyn PlsqlDevApi + Send + Sync>>,
-    selected_object: &SelectedObject,
-) -> String {
-    lazy_static! {
-        static ref OBJECT_BODY_NOT_AVAILABLE: Regex = Regex::new(
-            r#"/\* Source of (TYPE|PACKAGE) BODY [A-Za-z0-9$_"]+ is not available \*/.*"#
-        )
-        .unwrap();
-    }
-
-    let object_spec = api.ide_get_object_source(
+    let mut template_values = TemplateValues::new(
         &selected_object.object_type,
         &selected_object.object_owner,
         &selected_object.object_name,
+        Utc::now(),
+    );
+    let mut files = vec![];
+    if let Some(versioned_file_name) = versioned_file_name {
+        let path: PathBuf = [folder_name, versioned_file_name].iter().collect();
+        template_values.version = version_of(versioned_file_name, config).to_string();
+        let content = migration_content(
+            api,
+            &object_source,
+            &template_values,
+            &context,
+            config,
+            true,
+        );
+        let outcome = write_migration(&path, &content, config)?;
+        files.push(WrittenFile {
+            name: versioned_file_name.to_string(),
+            bytes: outcome.bytes(),
+            unchanged: matches!(outcome, WriteOutcome::Unchanged(_)),
+        });
+        // repeatable migrations have no version
+        template_values.version = String::new();
+    }
+    if versioned_only {
+        remember_migration(&Path::new(folder_name).join(&files[0].name));
+        return Ok(files);
+    }
+    let path = repeatable_migration_path(folder_name, selected_object, config);
+    let content = migration_content(
+        api,
+        &object_source,
+        &template_values,
+        &context,
+        config,
+        false,
     );
+    let outcome = write_migration(&path, &content, config)?;
+    files.push(WrittenFile {
+        name: repeatable_file_name(selected_object, config),
+        bytes: outcome.bytes(),
+        unchanged: matches!(outcome, WriteOutcome::Unchanged(_)),
+    });
+    // the versioned migration, if any, is the one applied by hand in an emergency
+    remember_migration(&Path::new(folder_name).join(&files[0].name));
+    Ok(files)
+}
 
-    let object_spec_incl_owner = ensure_owner_in_ddl(
-        &object_spec,
+// The content of the repeatable migration of the object, exactly as an export would write it
+// into the folder
+pub fn render_repeatable_migration(
+    api: &dyn PlsqlDevApi,
+    selected_object: &SelectedObject,
+    folder: &Path,
+    config: &Config,
+) -> std::io::Result<String> {
+    let object_source = migration_source(api, selected_object, config)?;
+    let template_values = TemplateValues::new(
         &selected_object.object_type,
         &selected_object.object_owner,
         &selected_object.object_name,
+        Utc::now(),
+    );
+    let content = migration_content(
+        api,
+        &object_source,
+        &template_values,
+        &DdlContext::of(selected_object),
+        config,
+        false,
     );
+    Ok(convert_line_endings(
+        &content,
+        line_ending_for(
+            &folder.join(repeatable_file_name(selected_object, config)),
+            config,
+        ),
+    ))
+}
 
-    let type_of_object_body = match selected_object.object_type.as_str() {
-        "PACKAGE" => "PACKAGE BODY",
-        "TYPE" => "TYPE BODY",
-        _ => "",
-    };
+// The source of the object, shared by its versioned and repeatable migration
+fn migration_source(
+    api: &dyn PlsqlDevApi,
+    selected_object: &SelectedObject,
+    config: &Config,
+) -> std::io::Result<String> {
+    let object_source = fetch_object_source(api, selected_object, config);
+    if object_source.trim().is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            SOURCE_NOT_AVAILABLE.to_string(),
+        ));
+    }
+    if let Some(error) = source_error(&object_source).filter(|_| !config.trust_source_verbatim) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("the IDE returned an error instead of the source: {}", error),
+        ));
+    }
+    Ok(object_source)
+}
 
-    let object_body = api.ide_get_object_source(
-        type_of_object_body,
-        &selected_object.object_owner,
-        &selected_object.object_name,
-    );
+// The content of the versioned or repeatable migration of an object with the given source
+fn migration_content(
+    api: &dyn PlsqlDevApi,
+    object_source: &str,
+    template_values: &TemplateValues,
+    context: &DdlContext,
+    config: &Config,
+    versioned: bool,
+) -> String {
+    apply_transforms(
+        &migration_transforms(api, config, template_values, versioned),
+        object_source,
+        context,
+    )
+}
 
-    let object_body_incl_owner = ensure_owner_in_ddl(
-        &object_body,
-        type_of_object_body,
-        &selected_object.object_owner,
-        &selected_object.object_name,
+// why an object whose source stayed empty is not exported
+const SOURCE_NOT_AVAILABLE: &str = "the source is not available";
+
+// The Oracle error the IDE returned in place of the source, e.g.
+// `ORA-04043: object APP.PKG_GONE does not exist`
+fn source_error(source: &str) -> Option<&str> {
+    lazy_static! {
+        static ref SOURCE_ERROR: Regex = Regex::new(r"^\s*((?:ORA|PLS)-\d{5}\b.*)").unwrap();
+    }
+    SOURCE_ERROR
+        .captures(source)
+        .and_then(|caps| caps.get(1))
+        .map(|error| error.as_str().trim_end())
+}
+
+// Why the DDL of the object would come out malformed, e.g. `create or replace package .BIN$...`
+// for a dropped object the IDE returned without owner
+fn invalid_object_reason(selected_object: &SelectedObject) -> Option<String> {
+    if selected_object.object_owner.trim().is_empty() {
+        return Some("the IDE returned the object without an owner".to_string());
+    }
+    if selected_object.object_name.trim().is_empty() {
+        return Some("the IDE returned the object without a name".to_string());
+    }
+    if selected_object
+        .object_name
+        .to_uppercase()
+        .starts_with("BIN$")
+    {
+        return Some("the object is in the recycle bin".to_string());
+    }
+    None
+}
+
+fn repeatable_migration_path(
+    folder_name: &str,
+    selected_object: &SelectedObject,
+    config: &Config,
+) -> PathBuf {
+    [folder_name, &repeatable_file_name(selected_object, config)]
+        .iter()
+        .collect()
+}
+
+// A file a repeatable export would write
+#[derive(Debug, PartialEq)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    // the export would overwrite it (unless its content is unchanged)
+    pub exists: bool,
+}
+
+// The repeatable migrations the export of the objects would write, without writing anything
+fn plan_repeatable_migrations(
+    folder_name: &str,
+    selected_objects: &[SelectedObject],
+    config: &Config,
+) -> Vec<PlannedFile> {
+    selected_objects
+        .iter()
+        .filter(|o| SUPPORTED_OBJECT_TYPES.contains(&o.object_type.as_str()))
+        .map(|o| {
+            let path = repeatable_migration_path(folder_name, o, config);
+            let exists = path.exists();
+            PlannedFile { path, exists }
+        })
+        .collect()
+}
+
+// The files that would be overwritten first, then the new ones
+fn dry_run_report(planned: &[PlannedFile]) -> String {
+    let (existing, new): (Vec<&PlannedFile>, Vec<&PlannedFile>) =
+        planned.iter().partition(|file| file.exists);
+    let mut report = format!(
+        "{} repeatable migration(s) would be written, {} of them would overwrite an existing file.\n",
+        planned.len(),
+        existing.len()
     );
+    for (label, files) in [("would overwrite", existing), ("new", new)] {
+        for file in files.iter().take(MAX_SUMMARY_OBJECTS) {
+            report.push_str(&format!(
+                "\n{}: {}",
+                label,
+                file.path.file_name().unwrap_or_default().to_string_lossy()
+            ));
+        }
+        if files.len() > MAX_SUMMARY_OBJECTS {
+            report.push_str(&format!(
+                "\n... and {} more",
+                files.len() - MAX_SUMMARY_OBJECTS
+            ));
+        }
+    }
+    report
+}
+
+// <NAME>, or <abbreviation>_<NAME> if the object type is mapped to a word; quoted names may
+// contain characters that aren't allowed in file names
+pub fn migration_basename(selected_object: &SelectedObject, config: &Config) -> String {
+    let name = sanitize_file_name(&selected_object.object_name.to_uppercase());
+    match config.object_type_prefix(&selected_object.object_type) {
+        Some(prefix) if !prefix.is_empty() => format!("{}_{}", prefix, name),
+        _ => name,
+    }
+}
+
+// R__<basename>.sql, or R__<weight>_<basename>.sql with the order prefix (with the configured
+// prefix and separator)
+pub fn repeatable_file_name(selected_object: &SelectedObject, config: &Config) -> String {
+    let basename = migration_basename(selected_object, config);
+    let weight = config.object_type_weight(&selected_object.object_type);
+    match config.repeatable_order_prefix && weight != u32::MAX {
+        true => format!(
+            "{}{}{:0width$}_{}.sql",
+            config.repeatable_prefix,
+            config.version_description_separator,
+            weight,
+            basename,
+            width = config.repeatable_order_width()
+        ),
+        false => format!(
+            "{}{}{}.sql",
+            config.repeatable_prefix, config.version_description_separator, basename
+        ),
+    }
+}
+
+// fetches the DDL of the object (including the body of packages and types)
+pub fn fetch_object_source(
+    api: &dyn PlsqlDevApi,
+    selected_object: &SelectedObject,
+    config: &Config,
+) -> String {
+    match selected_object.object_type.as_str() {
+        "PACKAGE" | "TYPE" => get_object_source_and_body(api, selected_object, config),
+        _ => get_object_source(api, selected_object, config),
+    }
+}
 
-    return match OBJECT_BODY_NOT_AVAILABLE.is_match(&object_body_incl_owner.trim()) {
-        true => format!("{}\n/\n", object_spec_incl_owner.trim()),
-        _ => format!(
-            "{}\n/\n{}\n/\n",
-            object_spec_incl_owner.trim(),
-            object_body_incl_owner.trim()
+// fetches the raw object source and decodes it as configured; an empty source is asked for
+// again as configured, and stays empty if it's not available
+fn read_object_source(
+    api: &dyn PlsqlDevApi,
+    object_type: &str,
+    object_owner: &str,
+    object_name: &str,
+    config: &Config,
+) -> String {
+    let bytes = retry_while_empty(
+        &format!("Source of {} {}.{}", object_type, object_owner, object_name),
+        config.source_retries,
+        Duration::from_millis(config.source_retry_delay_ms),
+        &ThreadSleeper,
+        || api.ide_get_object_source_bytes(object_type, object_owner, object_name),
+    )
+    .unwrap_or_default();
+    let (source, path) = decode_ide_text(&bytes, config.source_encoding);
+    match path {
+        DecodingPath::Utf8 => debug!(target: target::FLYWAY,
+            "Source of {} {}.{} is UTF-8",
+            object_type, object_owner, object_name
         ),
+        _ => info!(target: target::FLYWAY,
+            "Source of {} {}.{} decoded via {:?}",
+            object_type, object_owner, object_name, path
+        ),
+    }
+    source
+}
+
+// fetches the source of a package or type including its body
+fn get_object_source_and_body(
+    api: &dyn PlsqlDevApi,
+    selected_object: &SelectedObject,
+    config: &Config,
+) -> String {
+    lazy_static! {
+        static ref OBJECT_BODY_NOT_AVAILABLE: Regex = Regex::new(
+            r#"/\* Source of (TYPE|PACKAGE) BODY [A-Za-z0-9$_"]+ is not available \*/.*"#
+        )
+        .unwrap();
+    }
+
+    let object_spec =
+        || transformed_object_source(api, &selected_object.object_type, selected_object, config);
+    if config.package_parts == PackageParts::SpecOnly {
+        return object_spec();
+    }
+
+    let type_of_object_body = match selected_object.object_type.as_str() {
+        "PACKAGE" => "PACKAGE BODY",
+        "TYPE" => "TYPE BODY",
+        _ => "",
     };
+
+    let object_body = transformed_object_source(api, type_of_object_body, selected_object, config);
+
+    match (
+        OBJECT_BODY_NOT_AVAILABLE.is_match(object_body.trim()),
+        config.package_parts,
+    ) {
+        (true, PackageParts::BodyOnly) => {
+            // rather than an empty migration
+            warn!(target: target::FLYWAY,
+                "{} {}.{} has no body, exporting its spec",
+                selected_object.object_type,
+                selected_object.object_owner,
+                selected_object.object_name
+            );
+            object_spec()
+        }
+        (true, _) => object_spec(),
+        (false, PackageParts::BodyOnly) => object_body,
+        (false, _) => format!("{}{}", object_spec(), object_body),
+    }
 }
 
 // fetches the object source of views, triggers, functions and procedures
 fn get_object_source(
-    api: &RwLockReadGuard<Box<dyn PlsqlDevApi + Send + Sync>>,
+    api: &dyn PlsqlDevApi,
+    selected_object: &SelectedObject,
+    config: &Config,
+) -> String {
+    // TODO: append "/\n" at the end of functions and procedures
+    transformed_object_source(api, &selected_object.object_type, selected_object, config)
+}
+
+// reads the source of the object (part) of the given type and transforms it as configured
+fn transformed_object_source(
+    api: &dyn PlsqlDevApi,
+    object_type: &str,
     selected_object: &SelectedObject,
+    config: &Config,
 ) -> String {
-    let object_source = api.ide_get_object_source(
-        &selected_object.object_type,
+    let object_source = read_object_source(
+        api,
+        object_type,
         &selected_object.object_owner,
         &selected_object.object_name,
+        config,
     );
-
-    // TODO: append "/\n" at the end of functions and procedures
-    ensure_owner_in_ddl(
+    debug!(target: target::FLYWAY, "Object source: {}", object_source);
+    let context = DdlContext {
+        object_type,
+        object_owner: &selected_object.object_owner,
+        object_name: &selected_object.object_name,
+    };
+    let result = apply_transforms(
+        &ddl_transforms(api, object_type, config),
         &object_source,
-        &selected_object.object_type,
-        &selected_object.object_owner,
-        &selected_object.object_name,
-    )
+        &context,
+    );
+    debug!(target: target::FLYWAY, "Final DDL: {}", result);
+    result
 }
 
 // Replace the type name in the DDL with owner.type, and optionally enforce creation of the object type
-fn ensure_owner_in_ddl(
+// (the `force` keyword for views and types can be switched off via the config)
+pub fn ensure_owner_in_ddl(
     ddl: &str,
     object_type: &str,
     object_owner: &str,
     object_name: &str,
+    config: &Config,
 ) -> String {
-    lazy_static! {
-        static ref DDL: Regex = RegexBuilder::new(r#"create or replace (editionable|noneditionable)?\s*(package|type|view|trigger|function|procedure)\s*(body )?([a-z0-9_$"]+\.)?[a-z0-9_$"]+\s*(\([a-z0-9._$", ]+\))?\s*(force )?(is|as)?(.*)"#)
-                            .case_insensitive(true)
-                            .build()
-                            .unwrap();
-    }
-
-    debug!("Object source: {}", ddl);
-
-    // It's necessary to replace $ with $$ as it's used by the Regex crate for capture group references
-    // Update 2021-04-02: Seems no longer necessary for whatever reasons, maybe because of the lambda
-    let result = DDL.replace(ddl, |caps: &Captures| {
-        format!("create or replace {editionable}{force_view}{object_type} {body}{object_owner}.{object_name}{parameter_list}{force_type}{is_or_as}{rest_of_line}",
-                editionable = match (caps.get(1).map_or("", |m| m.as_str())).to_lowercase().as_str() {
-                    "editionable" => "editionable ",
-                    "noneditionable" => "noneditionable ",
-                    _ => ""
-                },
-                force_view = match object_type {
-                    "VIEW" => "force ",
-                    _ => ""
-                },
-                object_type = (caps.get(2).map_or("", |m| m.as_str())).to_lowercase(),
-                body = (caps.get(3).map_or("", |m| m.as_str())).to_lowercase(),
-                object_owner = object_owner,
-                object_name = object_name,
-                parameter_list = format!("{} ", caps.get(5).map_or("", |m| m.as_str())),
-                force_type = match object_type {
-                    "TYPE" => "force ",
-                    _ => ""
-                },
-                is_or_as = match object_type {
-                    "TRIGGER" => "\n".to_string(),
-                    _ => (caps.get(7).map_or("", |m| m.as_str())).to_lowercase()
-                }, // insert a line break for triggers
-                rest_of_line = caps.get(8).map_or("", |m| m.as_str())
-        )
-    });
-
-    debug!("Final DDL: {}", result);
-    result.to_owned().to_string()
+    debug!(target: target::FLYWAY, "Object source: {}", ddl);
+    let context = DdlContext {
+        object_type,
+        object_owner,
+        object_name,
+    };
+    let result = apply_transforms(&owner_transforms(object_type, config), ddl, &context);
+    debug!(target: target::FLYWAY, "Final DDL: {}", result);
+    result
 }
 
 #[cfg(test)]
 mod tests {
     use std::fs::File;
-    use std::io::Read;
+    use std::io::{ErrorKind, Read};
     use std::path::{Path, PathBuf};
-    use std::sync::RwLock;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
     use std::{env, fs};
 
-    use chrono::TimeZone;
+    use chrono::{TimeZone, Utc};
     // have to re-import here, otherwise I get stupid 'unused imports' warnings during `cargo build`
     use indoc::indoc;
 
-    use crate::config::Config;
-    use crate::flyway::{create_versioned_migration_impl, get_versioned_filename_impl};
+    use crate::bulk::BulkOutcome;
+    use crate::config::{
+        Config, EmptySelection, LineEnding, PackageParts, PromptPolicy, TimestampSource,
+        UnmappedObjectType, Versioning,
+    };
+    use crate::flyway::{
+        create_versioned_migration_impl, get_sequential_filename, get_versioned_filename_impl,
+        is_unintended_whole_buffer, migration_content, next_sequential_version,
+        validate_version_format, version_of, whole_buffer_question, DdlContext, WriteOutcome,
+    };
     use crate::plsqldev_api::{PlsqlDevApi, SelectedObject};
-
-    use super::export_object_as_repeatable_migration;
+    use crate::prelude::{api, config, API, API_TEST_LOCK, CONFIG};
+    use crate::template::TemplateValues;
+    use crate::ui::{DialogError, Progress, SaveDialogOutcome};
+
+    use super::{
+        combine_sources, configured_folder, discard_unconfirmed_small_files, dry_run_report,
+        ensure_owner_in_ddl, export_object_as_repeatable_migration,
+        export_objects_as_repeatable_migrations, export_repeatable_migrations,
+        exported_files_summary, fetch_object_source, migration_timestamp, offset_timestamps,
+        parse_database_timestamp, plan_repeatable_migrations, probe_writable,
+        render_repeatable_migration, repeatable_file_name, source_error, versioned_file_names,
+        write_callback, write_gitattributes, write_migration, ExportedObject, FlywayError,
+        WrittenFile, DATABASE_TIMESTAMP_QUERY, PROBE_FILE_NAME,
+    };
 
     lazy_static! {
-        static ref TMP_DIR: String = env::var("TMP").unwrap();
+        static ref TMP_DIR: String = env::temp_dir().to_string_lossy().into_owned();
     }
 
     const PACKAGE_SPEC: &str = indoc! { "
@@ -400,6 +1627,28 @@ mod tests {
     end pkg_noneditionable;
     " };
 
+    const TABLE: &str = indoc! { "
+    -- Create table
+    create table T_ADDRESS
+    (
+      id NUMBER not null
+    );
+    " };
+
+    const MATERIALIZED_VIEW: &str = indoc! { r#"
+    CREATE MATERIALIZED VIEW mv_order_totals
+      BUILD IMMEDIATE
+      REFRESH FAST ON COMMIT
+      WITH PRIMARY KEY
+      ENABLE QUERY REWRITE
+    AS
+    SELECT o.customer_id,
+           count(*) order_count,
+           sum(o.amount) total_amount
+      FROM orders o
+     GROUP BY o.customer_id;
+    "# };
+
     const VIEW: &str = indoc! { r#"
     create or replace view v_all_objects as
     select ao."OWNER",
@@ -431,138 +1680,1546 @@ mod tests {
       from all_objects ao;
     "# };
 
-    const PACKAGE_SPEC_WITH_UNICODE_CHARACTERS: &str = indoc! { r#"
-    create or replace package DEMO_USER.PKG_SNAFU is
-      CHARS constant varchar2(9 byte) := '€µψΨ';
-    end pkg_snafu;
-    /
-    "# };
+    const PACKAGE_SPEC_WITH_UNICODE_CHARACTERS: &str = indoc! { r#"
+    create or replace package DEMO_USER.PKG_SNAFU is
+      CHARS constant varchar2(9 byte) := '€µψΨ';
+    end pkg_snafu;
+    /
+    "# };
+
+    // Remembers the progress messages, and presses Cancel at the given message
+    #[derive(Default)]
+    struct RecordingProgress<'a> {
+        messages: Vec<String>,
+        cancel_at: Option<(usize, &'a AtomicBool)>,
+    }
+
+    impl Progress for RecordingProgress<'_> {
+        fn update(&mut self, message: &str) {
+            self.messages.push(message.to_string());
+            if let Some((at, cancel)) = self.cancel_at {
+                if at == self.messages.len() {
+                    cancel.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+    }
+
+    struct MockPlsqlDevApi {
+        test_type: String,
+    }
+
+    impl MockPlsqlDevApi {
+        fn new(test_type: &str) -> MockPlsqlDevApi {
+            MockPlsqlDevApi {
+                test_type: test_type.to_string(),
+            }
+        }
+    }
+
+    impl PlsqlDevApi for MockPlsqlDevApi {
+        fn ide_get_selected_text(&self) -> String {
+            match self.test_type.as_str() {
+                "versioned_migration_with_unicode_characters" => {
+                    PACKAGE_SPEC_WITH_UNICODE_CHARACTERS.to_string()
+                }
+                _ => "".to_string(),
+            }
+        }
+
+        fn ide_get_object_source(
+            &self,
+            object_type: &str,
+            _object_owner: &str,
+            _object_name: &str,
+        ) -> String {
+            match self.test_type.as_str() {
+                "noneditionable_package" => match object_type {
+                    "PACKAGE BODY" => PACKAGE_BODY.to_string(),
+                    _ => PACKAGE_SPEC.to_string(),
+                },
+                "view" => VIEW.to_string(),
+                "materialized_view" => MATERIALIZED_VIEW.to_string(),
+                "table" => TABLE.to_string(),
+                "missing_object" => "ORA-04043: object APP.V_GONE does not exist\n".to_string(),
+                _ => "".to_string(),
+            }
+        }
+    }
+
+    #[test]
+    fn create_repeatable_migration_for_noneditionable_package() {
+        let api = MockPlsqlDevApi::new("noneditionable_package");
+        let selected_object = SelectedObject::new("PACKAGE", "APP", "PKG_NONEDITIONABLE", "");
+
+        if let Err(e) = export_object_as_repeatable_migration(
+            &api,
+            &TMP_DIR,
+            &selected_object,
+            &Config::default(),
+            None,
+            None,
+        ) {
+            panic!("Exporting object failed, reason: {}", e);
+        }
+
+        let output_file: PathBuf = [&TMP_DIR, "R__PKG_NONEDITIONABLE.sql"].iter().collect();
+
+        let expected = indoc! { "
+               create or replace noneditionable package APP.PKG_NONEDITIONABLE is
+
+               end pkg_noneditionable;
+               /
+               create or replace noneditionable package body APP.PKG_NONEDITIONABLE is
+
+               end pkg_noneditionable;
+               /
+            "};
+
+        assert_eq!(expected, get_contents_of_file(&output_file));
+    }
+
+    #[test]
+    fn fetch_object_source_should_emit_the_configured_package_parts() {
+        let api = MockPlsqlDevApi::new("noneditionable_package");
+        let selected_object = SelectedObject::new("PACKAGE", "APP", "PKG_NONEDITIONABLE", "");
+        let fetch = |package_parts| {
+            let config = Config {
+                package_parts,
+                ..Config::default()
+            };
+            fetch_object_source(&api, &selected_object, &config)
+        };
+        let spec = "create or replace noneditionable package APP.PKG_NONEDITIONABLE is";
+        let body = "create or replace noneditionable package body APP.PKG_NONEDITIONABLE is";
+
+        let got = fetch(PackageParts::SpecOnly);
+        assert!(got.starts_with(spec));
+        assert!(!got.contains("package body"));
+        assert!(got.ends_with("end pkg_noneditionable;\n/\n"));
+
+        let got = fetch(PackageParts::BodyOnly);
+        assert!(got.starts_with(body));
+        assert!(!got.contains(spec));
+        assert!(got.ends_with("end pkg_noneditionable;\n/\n"));
+
+        let got = fetch(PackageParts::Both);
+        assert!(got.starts_with(spec));
+        assert!(got.contains(&format!("/\n{}", body)));
+    }
+
+    #[test]
+    fn create_repeatable_migration_from_materialized_view() {
+        let api = MockPlsqlDevApi::new("materialized_view");
+        let selected_object =
+            SelectedObject::new("MATERIALIZED VIEW", "APP", "MV_ORDER_TOTALS", "");
+
+        if let Err(e) = export_object_as_repeatable_migration(
+            &api,
+            &TMP_DIR,
+            &selected_object,
+            &Config::default(),
+            None,
+            None,
+        ) {
+            panic!("Exporting object failed, reason: {}", e);
+        }
+
+        let output_file: PathBuf = [&TMP_DIR, "R__MV_ORDER_TOTALS.sql"].iter().collect();
+
+        let expected = indoc! {r#"
+             declare
+               materialized_view_missing exception;
+               pragma exception_init(materialized_view_missing, -12003);
+             begin
+               execute immediate 'drop materialized view APP.MV_ORDER_TOTALS';
+             exception
+               when materialized_view_missing then
+                 null;
+             end;
+             /
+
+             create materialized view APP.MV_ORDER_TOTALS
+               BUILD IMMEDIATE
+               REFRESH FAST ON COMMIT
+               WITH PRIMARY KEY
+               ENABLE QUERY REWRITE
+             AS
+             SELECT o.customer_id,
+                    count(*) order_count,
+                    sum(o.amount) total_amount
+               FROM orders o
+              GROUP BY o.customer_id;
+            "#};
+
+        assert_eq!(expected, get_contents_of_file(&output_file));
+    }
+
+    #[test]
+    fn create_repeatable_migration_from_view() {
+        let api = MockPlsqlDevApi::new("view");
+        let selected_object = SelectedObject::new("VIEW", "APP", "V_ALL_OBJECTS", "");
+
+        if let Err(e) = export_object_as_repeatable_migration(
+            &api,
+            &TMP_DIR,
+            &selected_object,
+            &Config::default(),
+            None,
+            None,
+        ) {
+            panic!("Exporting object failed, reason: {}", e);
+        }
+
+        let output_file: PathBuf = [&TMP_DIR, "R__V_ALL_OBJECTS.sql"].iter().collect();
+
+        let expected = indoc! {r#"
+             create or replace force view APP.V_ALL_OBJECTS as
+             select ao."OWNER",
+                    ao."OBJECT_NAME",
+                    ao."SUBOBJECT_NAME",
+                    ao."OBJECT_ID",
+                    ao."DATA_OBJECT_ID",
+                    ao."OBJECT_TYPE",
+                    ao."CREATED",
+                    ao."LAST_DDL_TIME",
+                    ao."TIMESTAMP",
+                    ao."STATUS",
+                    ao."TEMPORARY",
+                    ao."GENERATED",
+                    ao."SECONDARY",
+                    ao."NAMESPACE",
+                    ao."EDITION_NAME",
+                    ao."SHARING",
+                    ao."EDITIONABLE",
+                    ao."ORACLE_MAINTAINED",
+                    ao."APPLICATION",
+                    ao."DEFAULT_COLLATION",
+                    ao."DUPLICATED",
+                    ao."SHARDED",
+                    ao."CREATED_APPID",
+                    ao."CREATED_VSNID",
+                    ao."MODIFIED_APPID",
+                    ao."MODIFIED_VSNID"
+               from all_objects ao;
+    "# };
+
+        assert_eq!(expected, get_contents_of_file(&output_file));
+    }
+
+    #[test]
+    fn create_repeatable_migration_should_honor_editorconfig_line_endings() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_editorconfig_crlf"].iter().collect();
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join(".editorconfig"), "[*]\nend_of_line = crlf\n").unwrap();
+
+        let api = MockPlsqlDevApi::new("view");
+        let selected_object = SelectedObject::new("VIEW", "APP", "V_ALL_OBJECTS", "");
+
+        if let Err(e) = export_object_as_repeatable_migration(
+            &api,
+            &folder.to_string_lossy(),
+            &selected_object,
+            &Config::default(),
+            None,
+            None,
+        ) {
+            panic!("Exporting object failed, reason: {}", e);
+        }
+
+        let contents = get_contents_of_file(&folder.join("R__V_ALL_OBJECTS.sql"));
+        assert!(contents.starts_with("create or replace force view APP.V_ALL_OBJECTS as\r\n"));
+        assert_eq!(
+            contents.matches('\n').count(),
+            contents.matches("\r\n").count()
+        );
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn write_callback_should_create_callback_with_configured_content() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_callback"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let config = Config {
+            callback_file_name: "afterMigrate__recompile.sql".to_string(),
+            callback_body: "exec dbms_utility.compile_schema(user);\n".to_string(),
+            ..Config::default()
+        };
+
+        write_callback(&folder.to_string_lossy(), &config).unwrap();
+
+        assert_eq!(
+            "exec dbms_utility.compile_schema(user);\n",
+            get_contents_of_file(&folder.join("afterMigrate__recompile.sql"))
+        );
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn write_migration_should_leave_file_with_bom_but_same_content_untouched() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_unchanged_bom"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("R__V_SNAFU.sql");
+        let config = Config {
+            line_ending: LineEnding::Lf,
+            ..Config::default()
+        };
+        fs::write(&path, b"\xEF\xBB\xBFselect 1 from dual;\n").unwrap();
+
+        assert_eq!(
+            WriteOutcome::Unchanged(23),
+            write_migration(&path, "select 1 from dual;\n", &config).unwrap()
+        );
+        assert_eq!(
+            b"\xEF\xBB\xBFselect 1 from dual;\n".to_vec(),
+            fs::read(&path).unwrap()
+        );
+
+        assert_eq!(
+            WriteOutcome::Written(20),
+            write_migration(&path, "select 2 from dual;\n", &config).unwrap()
+        );
+        assert_eq!("select 2 from dual;\n", get_contents_of_file(&path));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn probe_writable_should_leave_no_trace_in_writable_folder() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_probe_writable"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+
+        assert!(probe_writable(&folder).is_ok());
+        assert_eq!(0, fs::read_dir(&folder).unwrap().count());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn probe_writable_should_fail_for_nonexistent_folders_and_files() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_probe_missing"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+
+        // a file where the folder should be is not writable for anybody
+        let file_as_folder = folder.join("not_a_folder");
+        fs::write(&file_as_folder, "").unwrap();
+        assert!(probe_writable(&file_as_folder).is_err());
+
+        assert!(probe_writable(&folder.join("nonexistent")).is_err());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    // sysfs is read-only for everybody, including root, who ignores folder permissions
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn probe_writable_should_fail_for_read_only_folder() {
+        let folder = Path::new("/sys");
+        let error = probe_writable(folder).unwrap_err();
+        assert_eq!(ErrorKind::PermissionDenied, error.kind());
+        assert!(!folder.join(PROBE_FILE_NAME).exists());
+    }
+
+    #[test]
+    fn export_repeatable_migrations_should_leave_folder_alone_until_dry_run_is_confirmed() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_dry_run_untouched"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let config = Config {
+            dry_run: true,
+            manage_gitattributes: true,
+            write_callback: true,
+            repeatable_folder: Some(folder.to_string_lossy().into_owned()),
+            ..Config::default()
+        };
+        let api = MockPlsqlDevApi::new("view");
+
+        // the headless dialogs answer the dry run with no
+        export_repeatable_migrations(
+            &api,
+            &config,
+            false,
+            vec![SelectedObject::new("VIEW", "APP", "V_SNAFU", "")],
+        );
+        assert_eq!(0, fs::read_dir(&folder).unwrap().count());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn write_gitattributes_should_create_file_only_if_missing() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_gitattributes"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let config = Config {
+            line_ending: LineEnding::CrLf,
+            ..Config::default()
+        };
+
+        assert!(write_gitattributes(&folder, &config).unwrap());
+        assert_eq!(
+            "*.sql text eol=crlf\n",
+            get_contents_of_file(&folder.join(".gitattributes"))
+        );
+
+        fs::write(folder.join(".gitattributes"), "* -text\n").unwrap();
+        assert!(!write_gitattributes(&folder, &Config::default()).unwrap());
+        assert_eq!(
+            "* -text\n",
+            get_contents_of_file(&folder.join(".gitattributes"))
+        );
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn sequential_versions_should_follow_object_type_weight() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_sequential"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let api = MockPlsqlDevApi::new("noneditionable_package");
+        // the package is selected before the type it depends on
+        let mut selected_objects = vec![
+            SelectedObject::new("PACKAGE", "APP", "PKG_NONEDITIONABLE", ""),
+            SelectedObject::new("TYPE", "APP", "T_ADDRESS", ""),
+        ];
+        let config = Config {
+            versioning: Versioning::Sequential,
+            ..Config::default()
+        };
+
+        let cancel = AtomicBool::new(false);
+        let (summary, _, _) = export_objects_as_repeatable_migrations(
+            &api,
+            &folder.to_string_lossy(),
+            &mut selected_objects,
+            &config,
+            Some(""),
+            &cancel,
+            &mut RecordingProgress::default(),
+        );
+
+        assert_eq!(2, summary.succeeded);
+        assert!(folder.join("V1__T_ADDRESS.sql").exists());
+        assert!(folder.join("V2__PKG_NONEDITIONABLE.sql").exists());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn offset_timestamps_should_be_strictly_increasing_up_to_now() {
+        let now = chrono::Utc
+            .with_ymd_and_hms(2021, 7, 18, 10, 15, 58)
+            .unwrap()
+            + chrono::Duration::microseconds(999_999);
+        let config = Config::default();
+        let names: Vec<String> = offset_timestamps(now, 3, false)
+            .into_iter()
+            .map(|timestamp| get_versioned_filename_impl(&config, timestamp, "x"))
+            .collect();
+        assert_eq!(
+            vec![
+                "V2021_07_18_10_15_56__x.sql",
+                "V2021_07_18_10_15_57__x.sql",
+                "V2021_07_18_10_15_58__x.sql",
+            ],
+            names
+        );
+
+        // the sub-millisecond part of now must not make two versions equal
+        let config = Config {
+            use_millisecond_precision: true,
+            ..Config::default()
+        };
+        let names: Vec<String> = offset_timestamps(now, 3, true)
+            .into_iter()
+            .map(|timestamp| get_versioned_filename_impl(&config, timestamp, "x"))
+            .collect();
+        assert_eq!(
+            vec![
+                "V2021_07_18_10_15_58.997__x.sql",
+                "V2021_07_18_10_15_58.998__x.sql",
+                "V2021_07_18_10_15_58.999__x.sql",
+            ],
+            names
+        );
+        assert!(offset_timestamps(now, 0, true).is_empty());
+    }
+
+    #[test]
+    fn versioned_file_names_should_sort_in_export_order() {
+        let now = chrono::Utc
+            .with_ymd_and_hms(2021, 7, 18, 10, 15, 0)
+            .unwrap();
+        // more than ten objects, so a version mismatch in the tens would show
+        let selected_objects: Vec<SelectedObject> = (0..12)
+            .map(|i| SelectedObject::new("PACKAGE", "APP", &format!("PKG_{}", 12 - i), ""))
+            .collect();
+        let config = Config::default();
+        let names = versioned_file_names(
+            &config,
+            Path::new(&*TMP_DIR),
+            &selected_objects,
+            " Release 42\tfix/cleanup ",
+            now,
+        );
+        assert_eq!(
+            "V2021_07_18_10_14_49__Release_42_fix_cleanup_PKG_12.sql",
+            names[0]
+        );
+        assert_eq!(
+            "V2021_07_18_10_15_00__Release_42_fix_cleanup_PKG_1.sql",
+            names[11]
+        );
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(sorted, names);
+
+        let names = versioned_file_names(
+            &config,
+            Path::new(&*TMP_DIR),
+            &selected_objects[..1],
+            "",
+            now,
+        );
+        assert_eq!(vec!["V2021_07_18_10_15_00__PKG_12.sql"], names);
+    }
+
+    // Answers queries like a SQL cursor returning the given value
+    struct MockQueryPlsqlDevApi {
+        value: Option<&'static str>,
+    }
+
+    impl PlsqlDevApi for MockQueryPlsqlDevApi {
+        fn ide_query_value(&self, query: &str) -> Option<String> {
+            assert_eq!(DATABASE_TIMESTAMP_QUERY, query);
+            self.value.map(str::to_string)
+        }
+    }
+
+    #[test]
+    fn parse_database_timestamp_should_read_the_query_output() {
+        assert_eq!(
+            Some(
+                Utc.with_ymd_and_hms(2021, 7, 18, 10, 15, 0).unwrap()
+                    + chrono::Duration::milliseconds(123)
+            ),
+            parse_database_timestamp("2021 07 18 10 15 00 123\n")
+        );
+        assert_eq!(None, parse_database_timestamp("18.07.2021 10:15:00"));
+        assert_eq!(None, parse_database_timestamp("2021 07 18 25 15 00 123"));
+        assert_eq!(None, parse_database_timestamp(""));
+    }
+
+    #[test]
+    fn migration_timestamp_should_use_the_database_clock_if_configured() {
+        let config = Config {
+            timestamp_source: TimestampSource::DatabaseSysdate,
+            ..Config::default()
+        };
+        let api = MockQueryPlsqlDevApi {
+            value: Some("2021 07 18 10 15 00 123"),
+        };
+        assert_eq!(
+            "V2021_07_18_10_15_00__PKG.sql",
+            get_versioned_filename_impl(&config, migration_timestamp(&api, &config), "PKG")
+        );
+
+        // falls back to the workstation's clock
+        let before = Utc::now();
+        for value in [None, Some("ORA-00942: table or view does not exist")] {
+            let timestamp = migration_timestamp(&MockQueryPlsqlDevApi { value }, &config);
+            assert!(timestamp >= before && timestamp <= Utc::now());
+        }
+        let timestamp = migration_timestamp(&api, &Config::default());
+        assert!(timestamp >= before && timestamp <= Utc::now());
+    }
+
+    #[test]
+    fn combine_sources_should_terminate_and_separate_sources_in_order() {
+        let objects = vec![
+            (
+                SelectedObject::new("VIEW", "APP", "V_ADDRESS", ""),
+                "create or replace force view APP.V_ADDRESS as\nselect * from address;\n"
+                    .to_string(),
+            ),
+            (
+                SelectedObject::new("PACKAGE", "APP", "PKG_ADDRESS", ""),
+                "create or replace package APP.PKG_ADDRESS is\nend pkg_address;\n/\n".to_string(),
+            ),
+            (
+                SelectedObject::new("PROCEDURE", "APP", "DO_IT", ""),
+                "create or replace procedure APP.DO_IT is\nbegin\n  null;\nend do_it;\n\n"
+                    .to_string(),
+            ),
+        ];
+        let expected = indoc! { "
+            create or replace force view APP.V_ADDRESS as
+            select * from address;
+            /
+
+            create or replace package APP.PKG_ADDRESS is
+            end pkg_address;
+            /
+
+            create or replace procedure APP.DO_IT is
+            begin
+              null;
+            end do_it;
+            /
+        " };
+        assert_eq!(expected, combine_sources(&objects, &Config::default()));
+    }
+
+    #[test]
+    fn combine_sources_should_separate_sources_by_configured_blank_lines() {
+        let objects = vec![
+            (
+                SelectedObject::new("VIEW", "APP", "V_ADDRESS", ""),
+                "create or replace force view APP.V_ADDRESS as\nselect * from address;\n/\n"
+                    .to_string(),
+            ),
+            (
+                SelectedObject::new("VIEW", "APP", "V_CITY", ""),
+                "create or replace force view APP.V_CITY as\nselect * from city;\n/\n".to_string(),
+            ),
+        ];
+        let config = Config {
+            object_separator_blank_lines: 2,
+            ..Config::default()
+        };
+        assert_eq!(
+            "create or replace force view APP.V_ADDRESS as\nselect * from address;\n/\n\n\n\
+             create or replace force view APP.V_CITY as\nselect * from city;\n/\n",
+            combine_sources(&objects, &config)
+        );
+    }
+
+    #[test]
+    fn combine_sources_should_prompt_before_each_object_if_configured() {
+        let objects = vec![
+            (
+                SelectedObject::new("VIEW", "APP", "V_ADDRESS", ""),
+                "create or replace force view APP.V_ADDRESS as\nselect * from address;\n"
+                    .to_string(),
+            ),
+            (
+                SelectedObject::new("PACKAGE", "APP", "PKG_X", ""),
+                "create or replace package APP.PKG_X is\nend pkg_x;\n/\n".to_string(),
+            ),
+        ];
+        let config = Config {
+            emit_prompts: true,
+            ..Config::default()
+        };
+        let expected = indoc! { "
+            PROMPT Creating APP.V_ADDRESS
+            create or replace force view APP.V_ADDRESS as
+            select * from address;
+            /
+
+            PROMPT Creating APP.PKG_X
+            create or replace package APP.PKG_X is
+            end pkg_x;
+            /
+        " };
+        assert_eq!(expected, combine_sources(&objects, &config));
+    }
+
+    #[test]
+    fn multi_object_versioned_export_should_pair_objects_with_their_files() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_multi_versioned"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let api = MockPlsqlDevApi::new("noneditionable_package");
+        let mut selected_objects = vec![
+            SelectedObject::new("PACKAGE", "APP", "PKG_NONEDITIONABLE", ""),
+            SelectedObject::new("TYPE", "APP", "T_ADDRESS", ""),
+        ];
+        let config = Config {
+            multi_object_versioned: true,
+            ..Config::default()
+        };
+
+        let (summary, exported, failures) = export_objects_as_repeatable_migrations(
+            &api,
+            &folder.to_string_lossy(),
+            &mut selected_objects,
+            &config,
+            Some("release"),
+            &AtomicBool::new(false),
+            &mut RecordingProgress::default(),
+        );
+
+        assert_eq!(2, summary.succeeded);
+        assert!(failures.is_empty());
+        assert_eq!(
+            vec!["APP.T_ADDRESS", "APP.PKG_NONEDITIONABLE"],
+            exported.iter().map(|o| o.name.as_str()).collect::<Vec<_>>()
+        );
+        let (type_files, package_files) = (&exported[0].files, &exported[1].files);
+        assert!(type_files[0].name.ends_with("__release_T_ADDRESS.sql"));
+        assert_eq!("R__T_ADDRESS.sql", type_files[1].name);
+        assert!(package_files[0]
+            .name
+            .ends_with("__release_PKG_NONEDITIONABLE.sql"));
+        assert_eq!("R__PKG_NONEDITIONABLE.sql", package_files[1].name);
+        // the type is exported first, so its version is lower
+        assert!(type_files[0].name < package_files[0].name);
+        for file in type_files.iter().chain(package_files) {
+            assert!(folder.join(&file.name).exists(), "{} is missing", file.name);
+        }
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn combined_versioned_export_should_write_one_versioned_migration() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_combined_versioned"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let api = MockPlsqlDevApi::new("noneditionable_package");
+        let mut selected_objects = vec![
+            SelectedObject::new("PACKAGE", "APP", "PKG_NONEDITIONABLE", ""),
+            SelectedObject::new("TYPE", "APP", "T_ADDRESS", ""),
+        ];
+        let config = Config {
+            combine_versioned: true,
+            object_separator_blank_lines: 2,
+            emit_prompts: true,
+            ..Config::default()
+        };
+
+        let (summary, exported, failures) = export_objects_as_repeatable_migrations(
+            &api,
+            &folder.to_string_lossy(),
+            &mut selected_objects,
+            &config,
+            Some("release 42"),
+            &AtomicBool::new(false),
+            &mut RecordingProgress::default(),
+        );
+
+        assert_eq!(2, summary.succeeded);
+        assert!(failures.is_empty());
+        assert_eq!(
+            vec![
+                "APP.T_ADDRESS",
+                "APP.PKG_NONEDITIONABLE",
+                "2 objects combined"
+            ],
+            exported.iter().map(|o| o.name.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!("R__T_ADDRESS.sql", exported[0].files[0].name);
+        assert_eq!("R__PKG_NONEDITIONABLE.sql", exported[1].files[0].name);
+        let combined = &exported[2].files[0].name;
+        assert!(combined.starts_with('V'));
+        assert!(combined.ends_with("__release_42.sql"));
+        let content = fs::read_to_string(folder.join(combined)).unwrap();
+        // in export order, each announced, separated by the configured blank lines
+        let type_start = content.find("PROMPT Creating APP.T_ADDRESS\n").unwrap();
+        let package_start = content
+            .find("/\n\n\nPROMPT Creating APP.PKG_NONEDITIONABLE\n")
+            .unwrap();
+        assert_eq!(0, type_start);
+        assert!(package_start > type_start);
+        assert!(content.ends_with("/\n"));
+        let versioned = fs::read_dir(&folder)
+            .unwrap()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with('V'))
+            .count();
+        assert_eq!(1, versioned);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn exported_files_summary_should_list_objects_up_to_maximum() {
+        let exported: Vec<ExportedObject> = (1..=3)
+            .map(|i| ExportedObject {
+                object_type: "PACKAGE".to_string(),
+                name: format!("APP.PKG_{}", i),
+                files: vec![
+                    WrittenFile {
+                        name: format!("V{}__PKG_{}.sql", i, i),
+                        bytes: 100 * i,
+                        unchanged: false,
+                    },
+                    WrittenFile {
+                        name: format!("R__PKG_{}.sql", i),
+                        bytes: 100 * i,
+                        unchanged: false,
+                    },
+                ],
+            })
+            .collect();
+        assert_eq!(
+            "APP.PKG_1: V1__PKG_1.sql (100 bytes), R__PKG_1.sql (100 bytes)\n\
+             APP.PKG_2: V2__PKG_2.sql (200 bytes), R__PKG_2.sql (200 bytes)\n\
+             ... and 1 more",
+            exported_files_summary(&exported, 2, 50)
+        );
+    }
+
+    #[test]
+    fn exported_files_summary_should_tell_unchanged_files_apart() {
+        let exported = vec![ExportedObject {
+            object_type: "VIEW".to_string(),
+            name: "APP.V_SNAFU".to_string(),
+            files: vec![WrittenFile {
+                name: "R__V_SNAFU.sql".to_string(),
+                bytes: 10,
+                unchanged: true,
+            }],
+        }];
+        assert_eq!(
+            "APP.V_SNAFU: R__V_SNAFU.sql (unchanged)",
+            exported_files_summary(&exported, 25, 50)
+        );
+    }
+
+    fn small_files_test_objects() -> Vec<ExportedObject> {
+        [49, 50, 14]
+            .iter()
+            .map(|bytes| ExportedObject {
+                object_type: "VIEW".to_string(),
+                name: format!("APP.V_{}", bytes),
+                files: vec![WrittenFile {
+                    name: format!("R__V_{}.sql", bytes),
+                    bytes: *bytes,
+                    unchanged: false,
+                }],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exported_files_summary_should_flag_files_below_minimum_size() {
+        let exported = small_files_test_objects();
+        assert_eq!(
+            "APP.V_49: R__V_49.sql (49 bytes, suspiciously small - please verify)\n\
+             APP.V_50: R__V_50.sql (50 bytes)\n\
+             APP.V_14: R__V_14.sql (14 bytes, suspiciously small - please verify)",
+            exported_files_summary(&exported, 25, Config::default().min_migration_bytes)
+        );
+        // the minimum is configurable, 0 switches the check off
+        assert!(!exported_files_summary(&exported, 25, 14).contains("R__V_14.sql (14 bytes, s"));
+        assert!(exported_files_summary(&exported, 25, 15).contains("R__V_14.sql (14 bytes, s"));
+        assert!(!exported_files_summary(&exported, 25, 0).contains("suspiciously"));
+    }
+
+    #[test]
+    fn discard_unconfirmed_small_files_should_delete_rejected_files() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_small_files"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let mut exported = small_files_test_objects();
+        for file in exported.iter().flat_map(|o| &o.files) {
+            fs::write(folder.join(&file.name), "x".repeat(file.bytes)).unwrap();
+        }
+
+        let mut questions = vec![];
+        // keep the first one asked about
+        let discarded = discard_unconfirmed_small_files(&folder, &mut exported, 50, |question| {
+            questions.push(question.to_string());
+            questions.len() == 1
+        });
+
+        assert_eq!(2, questions.len());
+        assert!(questions[1].starts_with("R__V_14.sql of APP.V_14 is only 14 bytes"));
+        assert_eq!(vec!["R__V_14.sql"], discarded);
+        assert!(folder.join("R__V_49.sql").exists());
+        assert!(folder.join("R__V_50.sql").exists());
+        assert!(!folder.join("R__V_14.sql").exists());
+        assert!(exported[2].files.is_empty());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn discard_unconfirmed_small_files_should_keep_unchanged_files() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_small_unchanged"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let mut exported = small_files_test_objects();
+        for object in exported.iter_mut() {
+            for file in object.files.iter_mut() {
+                fs::write(folder.join(&file.name), "x".repeat(file.bytes)).unwrap();
+                file.unchanged = true;
+            }
+        }
+
+        let discarded = discard_unconfirmed_small_files(&folder, &mut exported, 50, |question| {
+            panic!("unexpected question {}", question);
+        });
+
+        assert!(discarded.is_empty());
+        assert!(folder.join("R__V_14.sql").exists());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn export_object_should_report_the_size_of_written_files() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_written_size"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let api = MockPlsqlDevApi::new("view");
+        let selected_object = SelectedObject::new("VIEW", "APP", "V_ALL_OBJECTS", "");
+
+        let files = export_object_as_repeatable_migration(
+            &api,
+            &folder.to_string_lossy(),
+            &selected_object,
+            &Config::default(),
+            None,
+            None,
+        )
+        .unwrap();
+
+        let written = fs::metadata(folder.join("R__V_ALL_OBJECTS.sql"))
+            .unwrap()
+            .len();
+        assert_eq!("R__V_ALL_OBJECTS.sql", files[0].name);
+        assert_eq!(written as usize, files[0].bytes);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn export_object_should_reject_recycle_bin_objects_and_empty_fields() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_invalid_objects"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let api = MockPlsqlDevApi::new("package");
+        let mut selected_objects = vec![
+            SelectedObject::new("PACKAGE", "", "BIN$xyz==$0", ""),
+            SelectedObject::new("PACKAGE", "APP", "BIN$xyz==$0", ""),
+            SelectedObject::new("PACKAGE", "APP", " ", ""),
+        ];
+
+        let (summary, exported, failures) = export_objects_as_repeatable_migrations(
+            &api,
+            &folder.to_string_lossy(),
+            &mut selected_objects,
+            &Config::default(),
+            None,
+            &AtomicBool::new(false),
+            &mut RecordingProgress::default(),
+        );
+
+        assert_eq!(0, summary.succeeded);
+        assert!(exported.is_empty());
+        assert_eq!(
+            vec![
+                ".BIN$xyz==$0: the IDE returned the object without an owner",
+                "APP.BIN$xyz==$0: the object is in the recycle bin",
+                "APP. : the IDE returned the object without a name",
+            ],
+            failures
+        );
+        assert_eq!(0, fs::read_dir(&folder).unwrap().count());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn dry_run_should_report_existing_targets_without_writing() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_dry_run"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("R__PKG_EXISTING.sql"), "-- mine\n").unwrap();
+        let selected_objects = vec![
+            SelectedObject::new("PACKAGE", "APP", "PKG_NEW", ""),
+            SelectedObject::new("PACKAGE", "APP", "PKG_EXISTING", ""),
+            // not exported at all
+            SelectedObject::new("TABLE", "APP", "T_EXISTING", ""),
+        ];
+
+        let planned = plan_repeatable_migrations(
+            &folder.to_string_lossy(),
+            &selected_objects,
+            &Config::default(),
+        );
+
+        assert_eq!(
+            vec![
+                (folder.join("R__PKG_NEW.sql"), false),
+                (folder.join("R__PKG_EXISTING.sql"), true),
+            ],
+            planned
+                .iter()
+                .map(|file| (file.path.clone(), file.exists))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "2 repeatable migration(s) would be written, 1 of them would overwrite an existing file.\n\
+             \nwould overwrite: R__PKG_EXISTING.sql\
+             \nnew: R__PKG_NEW.sql",
+            dry_run_report(&planned)
+        );
+        // nothing was written or changed
+        assert!(!folder.join("R__PKG_NEW.sql").exists());
+        assert_eq!(
+            "-- mine\n",
+            fs::read_to_string(folder.join("R__PKG_EXISTING.sql")).unwrap()
+        );
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn export_object_should_write_tables_as_versioned_migration_only() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_table"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let api = MockPlsqlDevApi::new("table");
+        let table = SelectedObject::new("TABLE", "APP", "T_ADDRESS", "");
+
+        let error = export_object_as_repeatable_migration(
+            &api,
+            &folder.to_string_lossy(),
+            &table,
+            &Config::default(),
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(ErrorKind::InvalidInput, error.kind());
+        assert_eq!(
+            "tables can only be exported as versioned migrations",
+            format!("{}", error)
+        );
+
+        let files = export_object_as_repeatable_migration(
+            &api,
+            &folder.to_string_lossy(),
+            &table,
+            &Config::default(),
+            Some("V1__T_ADDRESS.sql"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["V1__T_ADDRESS.sql"],
+            files.iter().map(|f| f.name.as_str()).collect::<Vec<_>>()
+        );
+        assert!(!folder.join("R__T_ADDRESS.sql").exists());
+        assert!(get_contents_of_file(&folder.join("V1__T_ADDRESS.sql"))
+            .contains("create table APP.T_ADDRESS\n(\n  id NUMBER not null\n);"));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn export_object_should_fail_if_the_source_stays_empty() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_empty_source"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        // returns no source at all
+        let api = MockPlsqlDevApi::new("package");
+        let mut selected_objects = vec![SelectedObject::new("VIEW", "APP", "V_GONE", "")];
+        let config = Config {
+            source_retry_delay_ms: 0,
+            ..Config::default()
+        };
+
+        let (summary, exported, failures) = export_objects_as_repeatable_migrations(
+            &api,
+            &folder.to_string_lossy(),
+            &mut selected_objects,
+            &config,
+            None,
+            &AtomicBool::new(false),
+            &mut RecordingProgress::default(),
+        );
+
+        assert_eq!(0, summary.succeeded);
+        assert!(exported.is_empty());
+        assert_eq!(vec!["APP.V_GONE: the source is not available"], failures);
+        assert!(!folder.join("R__V_GONE.sql").exists());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn export_object_should_skip_sources_that_are_oracle_errors() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_error_source"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let api = MockPlsqlDevApi::new("missing_object");
+        let export = |config: &Config| {
+            export_objects_as_repeatable_migrations(
+                &api,
+                &folder.to_string_lossy(),
+                &mut [SelectedObject::new("VIEW", "APP", "V_GONE", "")],
+                config,
+                None,
+                &AtomicBool::new(false),
+                &mut RecordingProgress::default(),
+            )
+        };
+
+        let (summary, exported, failures) = export(&Config::default());
+        assert_eq!(0, summary.succeeded);
+        assert!(exported.is_empty());
+        assert_eq!(
+            vec![
+                "APP.V_GONE: the IDE returned an error instead of the source: \
+                  ORA-04043: object APP.V_GONE does not exist"
+            ],
+            failures
+        );
+        assert!(!folder.join("R__V_GONE.sql").exists());
+
+        let config = Config {
+            trust_source_verbatim: true,
+            ..Config::default()
+        };
+        let (summary, _, failures) = export(&config);
+        assert_eq!(1, summary.succeeded);
+        assert!(failures.is_empty());
+        assert!(get_contents_of_file(&folder.join("R__V_GONE.sql")).contains("ORA-04043"));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn source_error_should_only_match_leading_errors() {
+        assert_eq!(
+            Some("PLS-00201: identifier 'X' must be declared"),
+            source_error("\n  PLS-00201: identifier 'X' must be declared\r\nmore")
+        );
+        assert_eq!(None, source_error("ORA-1: too short"));
+        assert_eq!(
+            None,
+            source_error("create or replace view v as\nselect 'ORA-04043: x' from dual;")
+        );
+    }
+
+    #[test]
+    fn bulk_export_should_stop_after_cancel() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_bulk_cancel"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let api = MockPlsqlDevApi::new("noneditionable_package");
+        let mut selected_objects = vec![
+            SelectedObject::new("TYPE", "APP", "T_ADDRESS", ""),
+            SelectedObject::new("PACKAGE", "APP", "PKG_NONEDITIONABLE", ""),
+        ];
+        let cancel = AtomicBool::new(false);
+        // Cancel is pressed while the first object is exported
+        let mut progress = RecordingProgress {
+            messages: vec![],
+            cancel_at: Some((1, &cancel)),
+        };
+
+        let (summary, _, _) = export_objects_as_repeatable_migrations(
+            &api,
+            &folder.to_string_lossy(),
+            &mut selected_objects,
+            &Config::default(),
+            None,
+            &cancel,
+            &mut progress,
+        );
+
+        assert_eq!(BulkOutcome::Cancelled, summary.outcome);
+        assert_eq!(
+            (1, 1, 2),
+            (summary.processed, summary.succeeded, summary.total)
+        );
+        assert_eq!(vec!["Exporting APP.T_ADDRESS (1 of 2)"], progress.messages);
+        assert!(folder.join("R__T_ADDRESS.sql").exists());
+        assert!(!folder.join("R__PKG_NONEDITIONABLE.sql").exists());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn export_should_apply_header_and_footer_templates() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_templates"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let api = MockPlsqlDevApi::new("view");
+        let selected_object = SelectedObject::new("VIEW", "APP", "V_ALL_OBJECTS", "");
+        let config = Config {
+            versioning: Versioning::Sequential,
+            header_template: Some("-- {object_type} {owner}.{name} V{version}\n".to_string()),
+            footer_template: Some("-- {unknown}\n".to_string()),
+            ..Config::default()
+        };
+
+        export_object_as_repeatable_migration(
+            &api,
+            &folder.to_string_lossy(),
+            &selected_object,
+            &config,
+            Some("V1__V_ALL_OBJECTS.sql"),
+            None,
+        )
+        .unwrap();
+
+        let versioned = get_contents_of_file(&folder.join("V1__V_ALL_OBJECTS.sql"));
+        assert!(versioned.starts_with("-- VIEW APP.V_ALL_OBJECTS V1\ncreate or replace force view"));
+        assert!(versioned.ends_with("from all_objects ao;\n-- {unknown}\n"));
+        let repeatable = get_contents_of_file(&folder.join("R__V_ALL_OBJECTS.sql"));
+        assert!(repeatable.starts_with("-- VIEW APP.V_ALL_OBJECTS V\ncreate or replace force view"));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn ensure_owner_in_ddl_should_force_views_if_configured() {
+        let config = Config {
+            force_views: true,
+            ..Config::default()
+        };
+        let got = ensure_owner_in_ddl(VIEW, "VIEW", "APP", "V_ALL_OBJECTS", &config);
+        assert!(got.starts_with("create or replace force view APP.V_ALL_OBJECTS as\n"));
+    }
+
+    #[test]
+    fn ensure_owner_in_ddl_should_not_force_views_if_disabled() {
+        let config = Config {
+            force_views: false,
+            ..Config::default()
+        };
+        let got = ensure_owner_in_ddl(VIEW, "VIEW", "APP", "V_ALL_OBJECTS", &config);
+        assert!(got.starts_with("create or replace view APP.V_ALL_OBJECTS as\n"));
+    }
+
+    #[test]
+    fn ensure_owner_in_ddl_should_not_qualify_unqualified_owners() {
+        let config = Config {
+            unqualified_owners: vec!["scott".to_string(), " app ".to_string()],
+            ..Config::default()
+        };
+        let got = ensure_owner_in_ddl(VIEW, "VIEW", "APP", "V_ALL_OBJECTS", &config);
+        assert!(got.starts_with("create or replace force view V_ALL_OBJECTS as\n"));
+        assert!(!got.contains("APP."));
+        let ddl = "create or replace type t_address as object (city varchar2(100));\n";
+        let got = ensure_owner_in_ddl(ddl, "TYPE", "APP", "T_ADDRESS", &config);
+        assert_eq!(
+            "create or replace type T_ADDRESS force as object (city varchar2(100));\n",
+            got
+        );
+
+        let config = Config {
+            unqualified_owners: vec!["SCOTT".to_string()],
+            ..Config::default()
+        };
+        let got = ensure_owner_in_ddl(VIEW, "VIEW", "APP", "V_ALL_OBJECTS", &config);
+        assert!(got.starts_with("create or replace force view APP.V_ALL_OBJECTS as\n"));
+    }
+
+    #[test]
+    fn ensure_owner_in_ddl_should_qualify_editioning_views() {
+        let ddl = "create or replace editioning view t_address as\nselect * from address_tab;\n";
+        let got = ensure_owner_in_ddl(ddl, "VIEW", "APP", "T_ADDRESS", &Config::default());
+        assert_eq!(
+            "create or replace force editioning view APP.T_ADDRESS as\nselect * from address_tab;\n",
+            got
+        );
+        let ddl = "CREATE OR REPLACE EDITIONABLE EDITIONING VIEW \"APP\".\"T_ADDRESS\" AS\nselect * from address_tab;\n";
+        let config = Config {
+            force_views: false,
+            ..Config::default()
+        };
+        let got = ensure_owner_in_ddl(ddl, "VIEW", "APP", "T_ADDRESS", &config);
+        assert_eq!(
+            "create or replace editionable editioning view APP.T_ADDRESS as\nselect * from address_tab;\n",
+            got
+        );
+    }
+
+    struct MockCommentsPlsqlDevApi;
+
+    impl PlsqlDevApi for MockCommentsPlsqlDevApi {
+        fn ide_get_object_source(
+            &self,
+            _object_type: &str,
+            _object_owner: &str,
+            _object_name: &str,
+        ) -> String {
+            "create or replace view v_address as\nselect id, city from address;\n".to_string()
+        }
+
+        fn ide_get_comments(
+            &self,
+            _object_owner: &str,
+            _object_name: &str,
+        ) -> Vec<(String, String)> {
+            vec![
+                (String::new(), "All addresses".to_string()),
+                ("ID".to_string(), "Primary key".to_string()),
+                ("city".to_string(), "The city's name".to_string()),
+            ]
+        }
+    }
+
+    #[test]
+    fn fetch_object_source_should_append_view_comments_if_configured() {
+        let selected_object = SelectedObject::new("VIEW", "APP", "V_ADDRESS", "");
+        let config = Config {
+            include_view_comments: true,
+            ..Config::default()
+        };
+        let expected = indoc! { r#"
+            create or replace force view APP.V_ADDRESS as
+            select id, city from address;
+
+            comment on table APP.V_ADDRESS is 'All addresses';
+            comment on column APP.V_ADDRESS.ID is 'Primary key';
+            comment on column APP.V_ADDRESS."city" is 'The city''s name';
+        "# };
+        assert_eq!(
+            expected,
+            fetch_object_source(&MockCommentsPlsqlDevApi, &selected_object, &config)
+        );
+        assert!(!fetch_object_source(
+            &MockCommentsPlsqlDevApi,
+            &selected_object,
+            &Config::default()
+        )
+        .contains("comment on"));
+    }
+
+    #[test]
+    fn ensure_owner_in_ddl_should_normalize_end_label_if_configured() {
+        let config = Config {
+            normalize_end_label: true,
+            ..Config::default()
+        };
+        let got = ensure_owner_in_ddl(
+            PACKAGE_BODY,
+            "PACKAGE BODY",
+            "APP",
+            "PKG_NONEDITIONABLE",
+            &config,
+        );
+        assert!(got.starts_with(
+            "create or replace noneditionable package body APP.PKG_NONEDITIONABLE is\n"
+        ));
+        assert!(got.ends_with("end PKG_NONEDITIONABLE;\n"));
+
+        let got = ensure_owner_in_ddl(
+            PACKAGE_BODY,
+            "PACKAGE BODY",
+            "APP",
+            "PKG_NONEDITIONABLE",
+            &Config::default(),
+        );
+        assert!(got.ends_with("end pkg_noneditionable;\n"));
+    }
 
-    struct MockPlsqlDevApi {
-        test_type: String,
+    struct MockLastDdlTimePlsqlDevApi {
+        last_ddl_time: Option<&'static str>,
     }
 
-    impl MockPlsqlDevApi {
-        fn new(test_type: &str) -> MockPlsqlDevApi {
-            MockPlsqlDevApi {
-                test_type: test_type.to_string(),
-            }
+    impl PlsqlDevApi for MockLastDdlTimePlsqlDevApi {
+        fn ide_get_object_last_ddl_time(
+            &self,
+            _object_type: &str,
+            _object_owner: &str,
+            _object_name: &str,
+        ) -> Option<String> {
+            self.last_ddl_time.map(str::to_string)
         }
     }
 
-    impl PlsqlDevApi for MockPlsqlDevApi {
-        fn ide_get_selected_text(&self) -> String {
-            match self.test_type.as_str() {
-                "versioned_migration_with_unicode_characters" => {
-                    PACKAGE_SPEC_WITH_UNICODE_CHARACTERS.to_string()
-                }
-                _ => "".to_string(),
-            }
-        }
+    struct MockViewWithLastDdlTimePlsqlDevApi;
 
+    impl PlsqlDevApi for MockViewWithLastDdlTimePlsqlDevApi {
         fn ide_get_object_source(
             &self,
-            object_type: &str,
+            _object_type: &str,
             _object_owner: &str,
             _object_name: &str,
         ) -> String {
-            match self.test_type.as_str() {
-                "noneditionable_package" => match object_type {
-                    "PACKAGE BODY" => PACKAGE_BODY.to_string(),
-                    _ => PACKAGE_SPEC.to_string(),
-                },
-                "view" => VIEW.to_string(),
-                _ => "".to_string(),
-            }
+            VIEW.to_string()
         }
-    }
 
-    fn create_rwlock(test_type: &str) -> RwLock<Box<dyn PlsqlDevApi + Send + Sync>> {
-        RwLock::new(Box::new(MockPlsqlDevApi::new(test_type)))
+        fn ide_get_object_last_ddl_time(
+            &self,
+            _object_type: &str,
+            _object_owner: &str,
+            _object_name: &str,
+        ) -> Option<String> {
+            Some("2021-07-18 10:15:00".to_string())
+        }
     }
 
     #[test]
-    fn create_repeatable_migration_for_noneditionable_package() {
-        let api = create_rwlock("noneditionable_package");
-        let guard = api.read().unwrap();
-        let selected_object = SelectedObject::new("PACKAGE", "APP", "PKG_NONEDITIONABLE", "");
+    fn render_repeatable_migration_should_match_the_exported_file() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_render_repeatable"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let selected_object = SelectedObject::new("VIEW", "APP", "V_SNAFU", "");
+        let config = Config {
+            include_header_comment: true,
+            header_template: Some("-- {owner}.{name}\n".to_string()),
+            footer_template: Some("-- end of {object_type}\n".to_string()),
+            line_ending: LineEnding::CrLf,
+            ..Config::default()
+        };
+        let api = MockViewWithLastDdlTimePlsqlDevApi;
 
-        if let Err(e) = export_object_as_repeatable_migration(
-            &guard,
-            &TMP_DIR,
+        let files = export_object_as_repeatable_migration(
+            &api,
+            &folder.to_string_lossy(),
             &selected_object,
-            &Config::default(),
-            false,
-        ) {
-            panic!("Exporting object failed, reason: {}", e);
-        }
-
-        let output_file: PathBuf = [&TMP_DIR, "R__PKG_NONEDITIONABLE.sql"].iter().collect();
-
-        let expected = indoc! { "
-               create or replace noneditionable package APP.PKG_NONEDITIONABLE is
-
-               end pkg_noneditionable;
-               /
-               create or replace noneditionable package body APP.PKG_NONEDITIONABLE is
+            &config,
+            None,
+            None,
+        )
+        .unwrap();
+        let rendered = render_repeatable_migration(&api, &selected_object, &folder, &config);
 
-               end pkg_noneditionable;
-               /
-            "};
+        let written = get_contents_of_file(&folder.join(&files[0].name));
+        assert!(written.starts_with("-- APP.V_SNAFU\r\n-- last_ddl_time: 2021-07-18 10:15:00\r\n"));
+        assert_eq!(written, rendered.unwrap());
+        fs::remove_dir_all(&folder).unwrap();
+    }
 
-        assert_eq!(expected, get_contents_of_file(&output_file));
+    #[test]
+    fn migration_content_should_record_last_ddl_time_if_available() {
+        let context = DdlContext {
+            object_type: "VIEW",
+            object_owner: "APP",
+            object_name: "V_SNAFU",
+        };
+        let config = Config {
+            include_header_comment: true,
+            ..Config::default()
+        };
+        let values = TemplateValues::default();
+        let api = MockLastDdlTimePlsqlDevApi {
+            last_ddl_time: Some("2021-07-18 10:15:00"),
+        };
+        assert_eq!(
+            "-- last_ddl_time: 2021-07-18 10:15:00\ncreate view V_SNAFU as select 1 from dual;\n",
+            migration_content(
+                &api,
+                "create view V_SNAFU as select 1 from dual;\n",
+                &values,
+                &context,
+                &config,
+                false
+            )
+        );
+        assert_eq!(
+            "select 1 from dual;\n",
+            migration_content(
+                &api,
+                "select 1 from dual;\n",
+                &values,
+                &context,
+                &Config::default(),
+                false
+            )
+        );
     }
 
     #[test]
-    fn create_repeatable_migration_from_view() {
-        let api = create_rwlock("view");
-        let guard = api.read().unwrap();
-        let selected_object = SelectedObject::new("VIEW", "APP", "V_ALL_OBJECTS", "");
+    fn migration_content_should_omit_header_comment_without_last_ddl_time() {
+        let context = DdlContext {
+            object_type: "VIEW",
+            object_owner: "APP",
+            object_name: "V_SNAFU",
+        };
+        let config = Config {
+            include_header_comment: true,
+            ..Config::default()
+        };
+        let api = MockLastDdlTimePlsqlDevApi {
+            last_ddl_time: None,
+        };
+        assert_eq!(
+            "select 1 from dual;\n",
+            migration_content(
+                &api,
+                "select 1 from dual;\n",
+                &TemplateValues::default(),
+                &context,
+                &config,
+                false
+            )
+        );
+    }
 
-        if let Err(e) = export_object_as_repeatable_migration(
-            &guard,
-            &TMP_DIR,
-            &selected_object,
-            &Config::default(),
-            false,
-        ) {
-            panic!("Exporting object failed, reason: {}", e);
-        }
+    #[test]
+    fn repeatable_file_name_should_use_object_type_abbreviation() {
+        let procedure = SelectedObject::new("PROCEDURE", "APP", "my_proc", "");
+        let view = SelectedObject::new("VIEW", "APP", "V_SNAFU", "");
+        let mut config = Config {
+            object_type_abbreviations: vec![("PROCEDURE".to_string(), "proc".to_string())],
+            ..Config::default()
+        };
+        assert_eq!(
+            "R__proc_MY_PROC.sql",
+            repeatable_file_name(&procedure, &config)
+        );
+        assert_eq!("R__V_SNAFU.sql", repeatable_file_name(&view, &config));
+
+        config.unmapped_object_type = UnmappedObjectType::RawType;
+        assert_eq!("R__VIEW_V_SNAFU.sql", repeatable_file_name(&view, &config));
+        assert_eq!(
+            "R__MY_PROC.sql",
+            repeatable_file_name(&procedure, &Config::default())
+        );
+        let quoted = SelectedObject::new("VIEW", "APP", "V_A/B:C", "");
+        assert_eq!(
+            "R__V_A_B_C.sql",
+            repeatable_file_name(&quoted, &Config::default())
+        );
+    }
 
-        let output_file: PathBuf = [&TMP_DIR, "R__V_ALL_OBJECTS.sql"].iter().collect();
+    #[test]
+    fn repeatable_file_name_should_start_with_the_order_prefix_if_configured() {
+        let view = SelectedObject::new("VIEW", "APP", "V_ALL_OBJECTS", "");
+        let table = SelectedObject::new("TABLE", "APP", "ADDRESS", "");
+        let mut config = Config {
+            repeatable_order_prefix: true,
+            object_type_weights: vec![("VIEW".to_string(), 1)],
+            ..Config::default()
+        };
+        assert_eq!(
+            "R__001_V_ALL_OBJECTS.sql",
+            repeatable_file_name(&view, &config)
+        );
+        // unlisted types come last
+        assert_eq!("R__ADDRESS.sql", repeatable_file_name(&table, &config));
+        config.repeatable_order_digits = 1;
+        config.object_type_weights = Config::default().object_type_weights;
+        assert_eq!(
+            "R__20_V_ALL_OBJECTS.sql",
+            repeatable_file_name(&view, &config)
+        );
+        // widened to the largest weight, so R__005_ still sorts before R__100_
+        config.object_type_weights = vec![("VIEW".to_string(), 5), ("TABLE".to_string(), 100)];
+        assert_eq!(
+            "R__005_V_ALL_OBJECTS.sql",
+            repeatable_file_name(&view, &config)
+        );
+        assert_eq!("R__100_ADDRESS.sql", repeatable_file_name(&table, &config));
+    }
 
-        let expected = indoc! {r#"
-             create or replace force view APP.V_ALL_OBJECTS as
-             select ao."OWNER",
-                    ao."OBJECT_NAME",
-                    ao."SUBOBJECT_NAME",
-                    ao."OBJECT_ID",
-                    ao."DATA_OBJECT_ID",
-                    ao."OBJECT_TYPE",
-                    ao."CREATED",
-                    ao."LAST_DDL_TIME",
-                    ao."TIMESTAMP",
-                    ao."STATUS",
-                    ao."TEMPORARY",
-                    ao."GENERATED",
-                    ao."SECONDARY",
-                    ao."NAMESPACE",
-                    ao."EDITION_NAME",
-                    ao."SHARING",
-                    ao."EDITIONABLE",
-                    ao."ORACLE_MAINTAINED",
-                    ao."APPLICATION",
-                    ao."DEFAULT_COLLATION",
-                    ao."DUPLICATED",
-                    ao."SHARDED",
-                    ao."CREATED_APPID",
-                    ao."CREATED_VSNID",
-                    ao."MODIFIED_APPID",
-                    ao."MODIFIED_VSNID"
-               from all_objects ao;
-    "# };
+    #[test]
+    fn repeatable_file_name_should_use_the_configured_prefix_and_separator() {
+        let view = SelectedObject::new("VIEW", "APP", "V_ALL_OBJECTS", "");
+        let config = Config {
+            repeatable_prefix: "RM".to_string(),
+            version_description_separator: "-".to_string(),
+            ..Config::default()
+        };
+        assert_eq!("RM-V_ALL_OBJECTS.sql", repeatable_file_name(&view, &config));
+    }
 
-        assert_eq!(expected, get_contents_of_file(&output_file));
+    #[test]
+    fn configured_folder_should_resolve_against_the_repository_root() {
+        let mut config = Config::default();
+        assert_eq!(None, configured_folder(&config, None));
+        assert_eq!(
+            Some("sql/repeatable".to_string()),
+            configured_folder(&config, Some("sql/repeatable"))
+        );
+        config.repo_root = Some("C:\\work\\repo".to_string());
+        assert_eq!(
+            Some("C:\\work\\repo\\sql\\repeatable".to_string()),
+            configured_folder(&config, Some("sql/repeatable"))
+        );
+        assert_eq!(
+            Some("D:\\shared".to_string()),
+            configured_folder(&config, Some("D:\\shared"))
+        );
     }
 
     #[test]
@@ -574,10 +3231,15 @@ mod tests {
            /
            "# };
 
-        let api = create_rwlock("versioned_migration_with_unicode_characters");
-        let guard = api.read().unwrap();
-        let res = create_versioned_migration_impl(&guard, &Config::default(), get_save_file_name);
-        assert_eq!(true, res.is_ok());
+        let api = MockPlsqlDevApi::new("versioned_migration_with_unicode_characters");
+        let res = create_versioned_migration_impl(
+            &api,
+            &Config::default(),
+            get_save_file_name,
+            answer_no,
+            false,
+        );
+        assert!(res.is_ok());
         // now find the output file
         // search in current directory for now as get_versioned_filename() does not work correctly
         let files = fs::read_dir(&*TMP_DIR).unwrap();
@@ -615,12 +3277,16 @@ mod tests {
         }
     }
 
-    fn get_save_file_name() -> Result<String, &'static str> {
-        // TODO instead of relying on the path that SaveFileDialog set as a side effect, we should use the PathBuf approach
-        /* let path: PathBuf = [&TMP_DIR, "PKG_SNAFU.sql"].iter().collect();
-        return CString::new(path.into_os_string().to_string_lossy().into_owned()).unwrap();*/
-        assert!(env::set_current_dir(Path::new(&*TMP_DIR)).is_ok());
-        Ok("PKG_SNAFU.sql".to_string())
+    fn get_save_file_name() -> SaveDialogOutcome {
+        SaveDialogOutcome::Chosen(PathBuf::from(&*TMP_DIR), "PKG_SNAFU.sql".to_string())
+    }
+
+    fn answer_no(_question: &str) -> bool {
+        false
+    }
+
+    fn answer_yes(_question: &str) -> bool {
+        true
     }
 
     struct MockEmptySelectedTextPlsqlDevApi {}
@@ -635,26 +3301,371 @@ mod tests {
         fn ide_get_selected_text(&self) -> String {
             "".to_string()
         }
+
+        fn ide_get_text(&self) -> String {
+            "create table t_whole_text(id number);\n".to_string()
+        }
+    }
+
+    // Create a versioned migration from an empty selection, and return the contents of the
+    // files written (which are removed again)
+    fn create_versioned_migration_from_empty_selection(
+        empty_selection: EmptySelection,
+        confirm_whole_text: fn(&str) -> bool,
+    ) -> (bool, Vec<String>) {
+        fn get_save_file_name() -> SaveDialogOutcome {
+            SaveDialogOutcome::Chosen(PathBuf::from(&*TMP_DIR), "WHOLE_TEXT.sql".to_string())
+        }
+        let config = Config {
+            empty_selection,
+            ..Config::default()
+        };
+        let res = create_versioned_migration_impl(
+            &MockEmptySelectedTextPlsqlDevApi::new(),
+            &config,
+            get_save_file_name,
+            confirm_whole_text,
+            false,
+        );
+
+        let mut contents = vec![];
+        for file in fs::read_dir(&*TMP_DIR).unwrap().flatten() {
+            if file.file_name().to_string_lossy().contains("WHOLE_TEXT") {
+                contents.push(get_contents_of_file(&file.path()));
+                fs::remove_file(file.path()).unwrap();
+            }
+        }
+        (res.is_ok(), contents)
+    }
+
+    #[test]
+    fn create_versioned_migration_with_empty_selection_should_follow_configured_mode() {
+        let whole_text = "create table t_whole_text(id number);\n".to_string();
+        assert_eq!(
+            (false, vec![]),
+            create_versioned_migration_from_empty_selection(EmptySelection::Error, answer_yes)
+        );
+        assert_eq!(
+            (true, vec![whole_text.clone()]),
+            create_versioned_migration_from_empty_selection(EmptySelection::WholeText, answer_no)
+        );
+        assert_eq!(
+            (true, vec![whole_text]),
+            create_versioned_migration_from_empty_selection(EmptySelection::Prompt, answer_yes)
+        );
+        assert_eq!(
+            (true, vec![]),
+            create_versioned_migration_from_empty_selection(EmptySelection::Prompt, answer_no)
+        );
+    }
+
+    #[test]
+    fn create_versioned_migration_should_delete_an_unconfirmed_small_file() {
+        fn get_save_file_name() -> SaveDialogOutcome {
+            SaveDialogOutcome::Chosen(PathBuf::from(&*TMP_DIR), "SMALL_FILE.sql".to_string())
+        }
+        fn reject_small_file(question: &str) -> bool {
+            assert!(
+                question.contains("SMALL_FILE.sql is only 38 bytes"),
+                "{}",
+                question
+            );
+            false
+        }
+        let config = Config {
+            empty_selection: EmptySelection::WholeText,
+            confirm_small_migrations: true,
+            ..Config::default()
+        };
+        let res = create_versioned_migration_impl(
+            &MockEmptySelectedTextPlsqlDevApi::new(),
+            &config,
+            get_save_file_name,
+            reject_small_file,
+            false,
+        );
+        assert!(res.is_ok());
+        let written = fs::read_dir(&*TMP_DIR)
+            .unwrap()
+            .flatten()
+            .filter(|file| file.file_name().to_string_lossy().contains("SMALL_FILE"))
+            .count();
+        assert_eq!(0, written);
+    }
+
+    #[test]
+    fn is_unintended_whole_buffer_should_need_identical_text_above_threshold() {
+        let text = "select 1 from dual;\n".repeat(10);
+        assert!(is_unintended_whole_buffer(&text, &text, 199));
+        assert!(!is_unintended_whole_buffer(&text, &text, 200));
+        assert!(!is_unintended_whole_buffer(&text[1..], &text, 100));
+        assert_eq!(
+            "No explicit selection detected - export the entire window contents (10 lines)?",
+            whole_buffer_question(&text)
+        );
+    }
+
+    // Returns the same (long) text as selection and as whole text
+    struct MockWholeBufferPlsqlDevApi {}
+
+    impl PlsqlDevApi for MockWholeBufferPlsqlDevApi {
+        fn ide_get_selected_text(&self) -> String {
+            "select 1 from dual;\n".repeat(200)
+        }
+
+        fn ide_get_text(&self) -> String {
+            "select 1 from dual;\n".repeat(200)
+        }
+    }
+
+    #[test]
+    fn create_versioned_migration_should_confirm_whole_buffer_selection() {
+        fn get_save_file_name_unreachable() -> SaveDialogOutcome {
+            panic!("the file name must not be asked for after the user declined");
+        }
+        let res = create_versioned_migration_impl(
+            &MockWholeBufferPlsqlDevApi {},
+            &Config::default(),
+            get_save_file_name_unreachable,
+            answer_no,
+            false,
+        );
+        assert!(res.is_ok());
+
+        // below the threshold, there's no question
+        let config = Config {
+            whole_buffer_confirmation_threshold: 10_000,
+            ..Config::default()
+        };
+        fn get_save_file_name_cancelled() -> SaveDialogOutcome {
+            SaveDialogOutcome::Cancelled
+        }
+        fn no_question(question: &str) -> bool {
+            panic!("unexpected question {}", question);
+        }
+        let res = create_versioned_migration_impl(
+            &MockWholeBufferPlsqlDevApi {},
+            &config,
+            get_save_file_name_cancelled,
+            no_question,
+            false,
+        );
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn whole_buffer_prompt_policy_should_predefine_the_answer() {
+        fn get_save_file_name_unreachable() -> SaveDialogOutcome {
+            panic!("the file name must not be asked for after the predefined no");
+        }
+        fn get_save_file_name_cancelled() -> SaveDialogOutcome {
+            SaveDialogOutcome::Cancelled
+        }
+        fn no_question(question: &str) -> bool {
+            panic!("unexpected question {}", question);
+        }
+        let config = |whole_buffer_prompt| Config {
+            whole_buffer_prompt,
+            ..Config::default()
+        };
+        let res = create_versioned_migration_impl(
+            &MockWholeBufferPlsqlDevApi {},
+            &config(PromptPolicy::AlwaysNo),
+            get_save_file_name_unreachable,
+            no_question,
+            false,
+        );
+        assert!(res.is_ok());
+        // a predefined yes goes on to the save dialog without asking
+        let res = create_versioned_migration_impl(
+            &MockWholeBufferPlsqlDevApi {},
+            &config(PromptPolicy::AlwaysYes),
+            get_save_file_name_cancelled,
+            no_question,
+            false,
+        );
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn create_versioned_migration_with_undo_should_share_the_version() {
+        fn get_save_file_name_undo() -> SaveDialogOutcome {
+            SaveDialogOutcome::Chosen(PathBuf::from(&*TMP_DIR), "PKG_UNDO.sql".to_string())
+        }
+        fn get_save_file_name_cancelled() -> SaveDialogOutcome {
+            SaveDialogOutcome::Cancelled
+        }
+        let migration_files = || -> Vec<PathBuf> {
+            let mut files: Vec<PathBuf> = fs::read_dir(&*TMP_DIR)
+                .unwrap()
+                .flatten()
+                .filter(|file| file.file_name().to_string_lossy().contains("PKG_UNDO"))
+                .map(|file| file.path())
+                .collect();
+            files.sort();
+            files
+        };
+        let api = MockPlsqlDevApi::new("versioned_migration_with_unicode_characters");
+        let config = Config {
+            use_millisecond_precision: true,
+            ..Config::default()
+        };
+
+        let res = create_versioned_migration_impl(
+            &api,
+            &config,
+            get_save_file_name_cancelled,
+            answer_no,
+            true,
+        );
+        assert!(res.is_ok());
+        assert!(migration_files().is_empty());
+
+        let res = create_versioned_migration_impl(
+            &api,
+            &config,
+            get_save_file_name_undo,
+            answer_no,
+            true,
+        );
+        assert!(res.is_ok());
+        let files = migration_files();
+        let names: Vec<String> = files
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        let undo_contents = files.first().map(|path| get_contents_of_file(path));
+        for path in &files {
+            fs::remove_file(path).unwrap();
+        }
+        assert_eq!(2, names.len(), "{:?}", names);
+        let (undo, versioned) = (&names[0], &names[1]);
+        assert!(
+            versioned.starts_with('V') && undo.starts_with('U'),
+            "{:?}",
+            names
+        );
+        assert_eq!(versioned[1..], undo[1..]);
+        // with milliseconds, e.g. V2024_01_02_03_04_05.678__PKG_UNDO.sql
+        assert_eq!(Some('.'), version_of(versioned, &config).chars().nth(19));
+        assert_eq!(
+            Some("-- TODO: write undo for PKG_UNDO\n".to_string()),
+            undo_contents
+        );
     }
 
-    fn create_rwlock_mockemptyselectedtext() -> RwLock<Box<dyn PlsqlDevApi + Send + Sync>> {
-        RwLock::new(Box::new(MockEmptySelectedTextPlsqlDevApi::new()))
+    #[test]
+    fn create_versioned_migration_should_write_nothing_if_the_undo_migration_exists() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_undo_exists"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("U1__PKG_UNDO.sql"), "-- edited by hand\n").unwrap();
+        let config = Config {
+            versioning: Versioning::Sequential,
+            ..Config::default()
+        };
+
+        let res = create_versioned_migration_impl(
+            &MockPlsqlDevApi::new("versioned_migration_with_unicode_characters"),
+            &config,
+            || SaveDialogOutcome::Chosen(folder.clone(), "PKG_UNDO.sql".to_string()),
+            answer_no,
+            true,
+        );
+
+        match res {
+            Err(FlywayError::UndoMigrationExists(file_name)) => {
+                assert_eq!("U1__PKG_UNDO.sql", file_name)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(!folder.join("V1__PKG_UNDO.sql").exists());
+        assert_eq!(
+            "-- edited by hand\n",
+            get_contents_of_file(&folder.join("U1__PKG_UNDO.sql"))
+        );
+        fs::remove_dir_all(&folder).unwrap();
     }
 
     #[test]
     fn create_versioned_migration_with_empty_selection_should_return_error() {
-        let api = create_rwlock_mockemptyselectedtext();
-        let guard = api.read().unwrap();
-        let res = create_versioned_migration_impl(&guard, &Config::default(), get_save_file_name);
+        let api = MockEmptySelectedTextPlsqlDevApi::new();
+        let res = create_versioned_migration_impl(
+            &api,
+            &Config::default(),
+            get_save_file_name,
+            answer_no,
+            false,
+        );
+        assert!(res.is_err(), "This should have returned an error");
+    }
+
+    #[test]
+    fn create_versioned_migration_should_tell_empty_names_from_failed_dialogs() {
+        fn get_save_file_name_empty() -> SaveDialogOutcome {
+            SaveDialogOutcome::EmptyName
+        }
+        fn get_save_file_name_failed() -> SaveDialogOutcome {
+            SaveDialogOutcome::Failed(DialogError(0x3002))
+        }
+        let api = MockPlsqlDevApi::new("versioned_migration_with_unicode_characters");
+        let res = create_versioned_migration_impl(
+            &api,
+            &Config::default(),
+            get_save_file_name_empty,
+            answer_no,
+            false,
+        );
+        assert!(matches!(res, Err(FlywayError::EmptyFileName)));
+
+        let res = create_versioned_migration_impl(
+            &api,
+            &Config::default(),
+            get_save_file_name_failed,
+            answer_no,
+            false,
+        );
         match res {
-            Ok(_) => panic!("This should have returned an error"),
-            Err(_) => (),
+            Err(e @ FlywayError::DialogFailed(DialogError(0x3002))) => assert_eq!(
+                "Could not ask for the file name: the file name is invalid (error code 0x3002)",
+                format!("{}", e)
+            ),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn create_versioned_migration_should_not_hold_locks_while_dialog_is_shown() {
+        let _lock = API_TEST_LOCK.lock().unwrap();
+        *API.write().unwrap() = Arc::new(MockPlsqlDevApi::new(
+            "versioned_migration_with_unicode_characters",
+        ));
+
+        // the IDE re-entering the plugin (e.g. RegisterCallback) needs the write locks
+        fn get_save_file_name_reentering() -> SaveDialogOutcome {
+            assert!(
+                API.try_write().is_ok(),
+                "API is locked while dialog is shown"
+            );
+            assert!(
+                CONFIG.try_write().is_ok(),
+                "config is locked while dialog is shown"
+            );
+            SaveDialogOutcome::Cancelled
         }
+        let res = create_versioned_migration_impl(
+            &*api(),
+            &config(),
+            get_save_file_name_reentering,
+            answer_no,
+            false,
+        );
+        assert!(res.is_ok());
     }
 
     #[test]
     fn get_versioned_filename_impl_should_use_provided_timestamp() {
-        let timestamp = chrono::Utc.ymd(1970, 1, 2).and_hms(3, 4, 5);
+        let timestamp = chrono::Utc.with_ymd_and_hms(1970, 1, 2, 3, 4, 5).unwrap();
         let basename = "do_it.sql";
         let got = get_versioned_filename_impl(&Config::default(), timestamp, basename);
         assert_eq!("V1970_01_02_03_04_05__do_it.sql", got);
@@ -662,7 +3673,7 @@ mod tests {
 
     #[test]
     fn get_versioned_filename_impl_should_add_sql_suffix() {
-        let timestamp = chrono::Utc.ymd(1970, 1, 2).and_hms(3, 4, 5);
+        let timestamp = chrono::Utc.with_ymd_and_hms(1970, 1, 2, 3, 4, 5).unwrap();
         let basename = "do_it";
         let got = get_versioned_filename_impl(&Config::default(), timestamp, basename);
         assert_eq!("V1970_01_02_03_04_05__do_it.sql", got);
@@ -670,10 +3681,151 @@ mod tests {
 
     #[test]
     fn get_versioned_filename_impl_should_take_config_into_account() {
-        let timestamp = chrono::Utc.ymd(1970, 1, 2).and_hms_micro(3, 4, 5, 678000);
+        let timestamp = chrono::Utc.with_ymd_and_hms(1970, 1, 2, 3, 4, 5).unwrap()
+            + chrono::Duration::microseconds(678000);
         let basename = "do_it";
         let config = Config::new(true);
         let got = get_versioned_filename_impl(&config, timestamp, basename);
         assert_eq!("V1970_01_02_03_04_05.678__do_it.sql", got);
     }
+
+    #[test]
+    fn get_versioned_filename_impl_should_use_the_configured_version_format() {
+        let timestamp = chrono::Utc.with_ymd_and_hms(1970, 1, 2, 3, 4, 5).unwrap()
+            + chrono::Duration::microseconds(678000);
+        let mut config = Config::default();
+        assert_eq!(
+            "V1970_01_02_03_04_05__do_it.sql",
+            get_versioned_filename_impl(&config, timestamp, "do_it")
+        );
+
+        config.version_format = Some("%Y%m%d%H%M%S".to_string());
+        assert_eq!(
+            "V19700102030405__do_it.sql",
+            get_versioned_filename_impl(&config, timestamp, "do_it")
+        );
+        config.version_format = Some("%s".to_string());
+        assert_eq!(
+            "V97445__do_it.sql",
+            get_versioned_filename_impl(&config, timestamp, "do_it")
+        );
+        config.use_millisecond_precision = true;
+        assert_eq!(
+            "V97445.678__do_it.sql",
+            get_versioned_filename_impl(&config, timestamp, "do_it")
+        );
+
+        // rejected formats fall back to the default instead of panicking
+        for format in ["%Y%m%d%H%M%S%.2f", "%Y/%m/%d", " "] {
+            assert!(validate_version_format(format, "__").is_err(), "{}", format);
+            config.version_format = Some(format.to_string());
+            assert_eq!(
+                "V1970_01_02_03_04_05.678__do_it.sql",
+                get_versioned_filename_impl(&config, timestamp, "do_it")
+            );
+        }
+    }
+
+    #[test]
+    fn validate_version_format_should_reject_formats_that_do_not_sort() {
+        for format in [
+            "%Y%m%d%H%M%S",
+            "%Y.%m.%d.%H%M",
+            "%s",
+            "%Y%j%H%M%S%3f",
+            "%y%m%d",
+        ] {
+            assert_eq!(Ok(()), validate_version_format(format, "__"), "{}", format);
+        }
+        for format in [
+            "%d_%m_%Y",
+            "%m%d%H%M",
+            "%Y%-m%d",
+            "%Y%b%d",
+            "%Y_%W_%u",
+            "%Y%m%d%I%M",
+            "%Y%m%d%H%M%S%z",
+            "V",
+        ] {
+            assert!(
+                validate_version_format(format, "__")
+                    .unwrap_err()
+                    .contains("does not sort chronologically"),
+                "{}",
+                format
+            );
+        }
+        assert_eq!(
+            Err("\"%Y__%m\" contains the description separator \"__\"".to_string()),
+            validate_version_format("%Y__%m", "__")
+        );
+        assert_eq!(Ok(()), validate_version_format("%Y__%m", "--"));
+    }
+
+    #[test]
+    fn version_of_should_split_at_the_separator_after_the_version() {
+        let timestamp = chrono::Utc.with_ymd_and_hms(1970, 1, 2, 3, 4, 5).unwrap();
+        let mut config = Config {
+            timestamp_field_separator: "__".to_string(),
+            version_description_separator: "__".to_string(),
+            ..Config::default()
+        };
+        let got = get_versioned_filename_impl(&config, timestamp, "do_it");
+        assert_eq!("V1970__01__02__03__04__05__do_it.sql", got);
+        assert_eq!("1970__01__02__03__04__05", version_of(&got, &config));
+
+        config.timestamp_field_separator = "_".to_string();
+        config.version_description_separator = "_".to_string();
+        config.use_millisecond_precision = true;
+        let got = get_versioned_filename_impl(&config, timestamp, "do_it");
+        assert_eq!("1970_01_02_03_04_05.000", version_of(&got, &config));
+        // a version of another shape, e.g. from before the format was changed
+        assert_eq!("2021", version_of("V2021_do_it.sql", &config));
+
+        config.versioning = Versioning::Sequential;
+        assert_eq!("42", version_of("V42_do_it.sql", &config));
+        assert_eq!("", version_of("R_do_it.sql", &config));
+    }
+
+    #[test]
+    fn versioned_file_names_should_use_configured_separators() {
+        let timestamp = chrono::Utc.with_ymd_and_hms(1970, 1, 2, 3, 4, 5).unwrap();
+        let mut config = Config {
+            timestamp_field_separator: "-".to_string(),
+            version_description_separator: "--".to_string(),
+            ..Config::default()
+        };
+        let got = get_versioned_filename_impl(&config, timestamp, "do_it");
+        assert_eq!("V1970-01-02-03-04-05--do_it.sql", got);
+        assert_eq!("1970-01-02-03-04-05", version_of(&got, &config));
+        assert_eq!(
+            "V7--do_it.sql",
+            get_sequential_filename(&config, 7, "do_it")
+        );
+
+        config.timestamp_field_separator = "%".to_string();
+        config.version_description_separator = "_".to_string();
+        let got = get_versioned_filename_impl(&config, timestamp, "do_it");
+        assert_eq!("V1970%01%02%03%04%05_do_it.sql", got);
+    }
+
+    #[test]
+    fn next_sequential_version_should_use_configured_separator() {
+        let folder: PathBuf = [&TMP_DIR, "xanthidae_separator"].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("V3__three.sql"), "").unwrap();
+        fs::write(folder.join("V2_two.sql"), "").unwrap();
+        let mut config = Config {
+            version_description_separator: "_".to_string(),
+            ..Config::default()
+        };
+        // V3__three also has a single underscore after its version
+        assert_eq!(4, next_sequential_version(&folder, &config));
+        config.version_description_separator = "__".to_string();
+        assert_eq!(4, next_sequential_version(&folder, &config));
+        fs::remove_file(folder.join("V3__three.sql")).unwrap();
+        assert_eq!(1, next_sequential_version(&folder, &config));
+        fs::remove_dir_all(&folder).unwrap();
+    }
 }
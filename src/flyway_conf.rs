@@ -0,0 +1,538 @@
+// The repository's own Flyway configuration, read from a flyway.toml or flyway.conf.
+//
+// Where migrations go and how they are named is written down in the Flyway configuration
+// already, so with `detect_flyway_config` it is read for every export instead of repeating it in
+// the plugin's settings. Only the filesystem locations, the prefixes and the separator are read,
+// everything else is ignored. flyway.conf is a Java properties file (`key=value` lines, `#` and
+// `!` comments, lines continued by a trailing backslash); of the TOML format of Flyway 10, the
+// `[flyway]` table is read.
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use toml::value::{Table, Value};
+
+use crate::config::Config;
+use crate::logging::target;
+
+// looked for in the repository root, then in its conf folder (where the Flyway CLI keeps it);
+// Flyway prefers the TOML format since version 10
+const FILE_NAMES: [&str; 2] = ["flyway.toml", "flyway.conf"];
+const CONF_FOLDER: &str = "conf";
+const FILESYSTEM_PREFIX: &str = "filesystem:";
+
+// The settings read from a Flyway configuration, None if not set there
+#[derive(Debug, Default, PartialEq)]
+pub struct FlywaySettings {
+    // with their prefixes, e.g. filesystem:sql
+    pub locations: Vec<String>,
+    pub sql_migration_prefix: Option<String>,
+    pub repeatable_sql_migration_prefix: Option<String>,
+    pub sql_migration_separator: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FlywayConfError {
+    // why the TOML can't be parsed
+    Syntax(String),
+    // key, value
+    InvalidValue(String, String),
+}
+
+impl Display for FlywayConfError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            FlywayConfError::Syntax(e) => format!("Cannot parse the Flyway config: {}", e),
+            FlywayConfError::InvalidValue(key, value) => {
+                format!("Invalid value {} for {}", value, key)
+            }
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+// What was found in the repository, for the diagnostics report
+#[derive(Debug, PartialEq)]
+pub enum FlywayDetection {
+    Disabled,
+    NoRepoRoot,
+    // the repository root searched
+    NotFound(String),
+    // the file, and why it couldn't be read
+    Unreadable(PathBuf, String),
+    // the file, its settings and the problems found in it
+    Found(PathBuf, FlywaySettings, Vec<FlywayConfError>),
+}
+
+impl Display for FlywayDetection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            FlywayDetection::Disabled => "detection is switched off".to_string(),
+            FlywayDetection::NoRepoRoot => "no repository root is configured".to_string(),
+            FlywayDetection::NotFound(root) => format!(
+                "no flyway.toml or flyway.conf in {} or its conf folder",
+                root
+            ),
+            FlywayDetection::Unreadable(path, e) => {
+                format!("could not read {}: {}", path.display(), e)
+            }
+            FlywayDetection::Found(path, settings, errors) => {
+                let shown = |value: &Option<String>| match value {
+                    Some(value) => format!("{:?}", value),
+                    None => "not set".to_string(),
+                };
+                let mut msg = format!(
+                    "read {}\n  locations: {}\n  sqlMigrationPrefix: {}\n  repeatableSqlMigrationPrefix: {}\n  sqlMigrationSeparator: {}",
+                    path.display(),
+                    match settings.locations.is_empty() {
+                        true => "not set".to_string(),
+                        false => settings.locations.join(", "),
+                    },
+                    shown(&settings.sql_migration_prefix),
+                    shown(&settings.repeatable_sql_migration_prefix),
+                    shown(&settings.sql_migration_separator)
+                );
+                for e in errors {
+                    msg.push_str(&format!("\n  {}", e));
+                }
+                msg
+            }
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+// The Flyway configuration of the repository, if any
+pub fn find_flyway_config(repo_root: &Path) -> Option<PathBuf> {
+    [repo_root.to_path_buf(), repo_root.join(CONF_FOLDER)]
+        .iter()
+        .flat_map(|folder| FILE_NAMES.iter().map(move |name| folder.join(name)))
+        .find(|path| path.is_file())
+}
+
+pub fn detect_flyway_config(config: &Config) -> FlywayDetection {
+    if !config.detect_flyway_config {
+        return FlywayDetection::Disabled;
+    }
+    let repo_root = match &config.repo_root {
+        Some(repo_root) => repo_root,
+        None => return FlywayDetection::NoRepoRoot,
+    };
+    let path = match find_flyway_config(Path::new(repo_root)) {
+        Some(path) => path,
+        None => return FlywayDetection::NotFound(repo_root.to_string()),
+    };
+    match read_flyway_config(&path) {
+        Ok((settings, errors)) => FlywayDetection::Found(path, settings, errors),
+        Err(e) => FlywayDetection::Unreadable(path, format!("{}", e)),
+    }
+}
+
+fn read_flyway_config(path: &Path) -> io::Result<(FlywaySettings, Vec<FlywayConfError>)> {
+    let content = fs::read_to_string(path)?;
+    let is_toml = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"));
+    Ok(match is_toml {
+        true => parse_flyway_toml(&content),
+        false => (parse_flyway_conf(&content), vec![]),
+    })
+}
+
+// The config for an export: the given one, overlaid with the settings of the repository's Flyway
+// config if detection is switched on. Problems in the Flyway config are logged, the settings that
+// could be read still apply.
+pub fn config_with_flyway_settings(config: &Config) -> Config {
+    let mut result = config.clone();
+    match detect_flyway_config(config) {
+        FlywayDetection::Found(path, settings, errors) => {
+            debug!(target: target::CONFIG, "Using Flyway config {:?}", path);
+            for e in errors {
+                warn!(target: target::CONFIG, "{:?}: {}", path, e);
+            }
+            apply_flyway_settings(&mut result, &settings);
+        }
+        detection @ FlywayDetection::Unreadable(..) => {
+            warn!(target: target::CONFIG, "Flyway config: {}", detection)
+        }
+        _ => (),
+    }
+    result
+}
+
+// Overlay the settings on the config. Flyway has one list of locations for all kinds of
+// migrations, which one a folder is meant for can't be told, so both go to the first one that
+// can be written to.
+pub fn apply_flyway_settings(config: &mut Config, settings: &FlywaySettings) {
+    if let Some(folder) = filesystem_folders(&settings.locations).first() {
+        config.versioned_folder = Some(folder.clone());
+        config.repeatable_folder = Some(folder.clone());
+    }
+    let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+    if let Some(prefix) = non_empty(&settings.sql_migration_prefix) {
+        config.version_prefix = prefix;
+    }
+    if let Some(prefix) = non_empty(&settings.repeatable_sql_migration_prefix) {
+        config.repeatable_prefix = prefix;
+    }
+    if let Some(separator) = non_empty(&settings.sql_migration_separator) {
+        config.version_description_separator = separator;
+    }
+}
+
+// The folders of the filesystem locations; classpath and cloud locations, and locations with
+// wildcards, can't be written to
+fn filesystem_folders(locations: &[String]) -> Vec<String> {
+    locations
+        .iter()
+        .filter_map(|location| location.trim().strip_prefix(FILESYSTEM_PREFIX))
+        .map(|folder| folder.trim().trim_end_matches(['/', '\\']))
+        .filter(|folder| !folder.is_empty() && !folder.contains(['*', '?']))
+        .map(str::to_string)
+        .collect()
+}
+
+fn set(settings: &mut FlywaySettings, key: &str, value: String) {
+    match key {
+        "flyway.locations" => {
+            settings.locations.extend(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|location| !location.is_empty())
+                    .map(str::to_string),
+            );
+        }
+        "flyway.sqlMigrationPrefix" => settings.sql_migration_prefix = Some(value),
+        "flyway.repeatableSqlMigrationPrefix" => {
+            settings.repeatable_sql_migration_prefix = Some(value)
+        }
+        "flyway.sqlMigrationSeparator" => settings.sql_migration_separator = Some(value),
+        _ => (),
+    }
+}
+
+fn is_known_key(key: &str) -> bool {
+    matches!(
+        key,
+        "flyway.locations"
+            | "flyway.sqlMigrationPrefix"
+            | "flyway.repeatableSqlMigrationPrefix"
+            | "flyway.sqlMigrationSeparator"
+    )
+}
+
+// Read a flyway.conf. Like any properties file, it can't be malformed: a line without a
+// separator is a key with an empty value.
+pub fn parse_flyway_conf(content: &str) -> FlywaySettings {
+    let mut settings = FlywaySettings::default();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let mut line = line.trim_start().to_string();
+        if line.is_empty() || line.starts_with(['#', '!']) {
+            continue;
+        }
+        // an odd number of trailing backslashes continues the line
+        while (line.len() - line.trim_end_matches('\\').len()) % 2 == 1 {
+            line.pop();
+            match lines.next() {
+                Some(next) => line.push_str(next.trim_start()),
+                None => break,
+            }
+        }
+        // the key ends at the first '=', ':' or whitespace, which may be followed by '=' or ':'
+        let (key, value) = match line.find(|c: char| c == '=' || c == ':' || c.is_whitespace()) {
+            Some(end) => {
+                let value = line[end..].trim_start();
+                let value = match value.starts_with(['=', ':']) {
+                    true => &value[1..],
+                    false => value,
+                };
+                (&line[..end], value.trim())
+            }
+            None => (line.as_str(), ""),
+        };
+        set(&mut settings, key, unescape_property(value));
+    }
+    settings
+}
+
+// Resolve the escapes of a properties value, e.g. \\ in Windows paths
+fn unescape_property(raw: &str) -> String {
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('r') => value.push('\r'),
+                Some(c) => value.push(c),
+                None => (),
+            },
+            c => value.push(c),
+        }
+    }
+    value
+}
+
+// Read a flyway.toml; returns the settings that could be read, and the problems found
+pub fn parse_flyway_toml(content: &str) -> (FlywaySettings, Vec<FlywayConfError>) {
+    let mut settings = FlywaySettings::default();
+    let document: Table = match toml::from_str(content) {
+        Ok(document) => document,
+        Err(e) => return (settings, vec![FlywayConfError::Syntax(format!("{}", e))]),
+    };
+    // the settings of environments, e.g. [environments.development.flyway], aren't read
+    let flyway = match document.get("flyway") {
+        Some(Value::Table(flyway)) => flyway,
+        _ => return (settings, vec![]),
+    };
+    let mut errors = vec![];
+    for (key, value) in flyway {
+        let key = format!("flyway.{}", key);
+        if !is_known_key(&key) {
+            continue;
+        }
+        match value {
+            Value::String(value) => set(&mut settings, &key, value.clone()),
+            Value::Array(locations) if key == "flyway.locations" => {
+                for location in locations {
+                    match location {
+                        Value::String(location) => settings.locations.push(location.clone()),
+                        value => errors.push(FlywayConfError::InvalidValue(
+                            key.clone(),
+                            value.to_string(),
+                        )),
+                    }
+                }
+            }
+            value => errors.push(FlywayConfError::InvalidValue(key, value.to_string())),
+        }
+    }
+    (settings, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use indoc::indoc;
+
+    use crate::config::Config;
+    use crate::flyway_conf::*;
+
+    #[test]
+    fn parse_flyway_conf_should_read_locations_and_naming() {
+        // as generated by the Flyway CLI, abridged
+        let content = indoc! { r"
+            # More information on the parameters can be found here: https://flywaydb.org/documentation/configfiles
+
+            # JDBC url to use to connect to the database
+            flyway.url=jdbc:oracle:thin:@//localhost:1521/XEPDB1
+            flyway.user=APP
+            ! Comma-separated list of locations to scan recursively for migrations.
+            flyway.locations=filesystem:sql/versioned, \
+                             filesystem:sql/repeatable,classpath:db/migration
+            flyway.sqlMigrationPrefix = M
+            flyway.repeatableSqlMigrationPrefix: RM
+            flyway.sqlMigrationSeparator=__
+            flyway.placeholders.owner=APP
+            flyway.validateMigrationNaming
+        " };
+
+        let settings = parse_flyway_conf(content);
+
+        assert_eq!(
+            FlywaySettings {
+                locations: vec![
+                    "filesystem:sql/versioned".to_string(),
+                    "filesystem:sql/repeatable".to_string(),
+                    "classpath:db/migration".to_string(),
+                ],
+                sql_migration_prefix: Some("M".to_string()),
+                repeatable_sql_migration_prefix: Some("RM".to_string()),
+                sql_migration_separator: Some("__".to_string()),
+            },
+            settings
+        );
+    }
+
+    #[test]
+    fn parse_flyway_conf_should_read_keys_without_value_and_whitespace_separators() {
+        let settings = parse_flyway_conf(
+            "flyway.sqlMigrationPrefix\nflyway.sqlMigrationSeparator  --\nflyway.locations : filesystem:sql\n",
+        );
+        assert_eq!(Some(String::new()), settings.sql_migration_prefix);
+        assert_eq!(Some("--".to_string()), settings.sql_migration_separator);
+        assert_eq!(vec!["filesystem:sql".to_string()], settings.locations);
+    }
+
+    #[test]
+    fn parse_flyway_conf_should_unescape_windows_paths() {
+        let settings = parse_flyway_conf("flyway.locations=filesystem:C:\\\\repo\\\\sql\n");
+        assert_eq!(
+            vec!["filesystem:C:\\repo\\sql".to_string()],
+            settings.locations
+        );
+    }
+
+    #[test]
+    fn parse_flyway_toml_should_read_the_flyway_table() {
+        // as generated by Flyway 10, abridged
+        let content = indoc! { r#"
+            databaseType = "Oracle"
+            id = "0e3fc4f2-3e5d-4b83-8b2b-6fd2c6d1f5b4"
+            name = "app"
+
+            [environments.development]
+            url = "jdbc:oracle:thin:@//localhost:1521/XEPDB1"
+            schemas = [
+              "APP",
+              "APP_AUDIT", # audit tables
+            ]
+
+            [flyway]
+            # both kinds of migrations
+            locations = [
+              "filesystem:migrations/versioned",
+              'filesystem:migrations/repeatable', # views and packages
+            ]
+            mixed = true
+            sqlMigrationPrefix = "V"
+            sqlMigrationSeparator = "--"  # instead of __
+            repeatableSqlMigrationPrefix = 1
+
+            [flyway.placeholders]
+            sqlMigrationPrefix = "not a setting"
+
+            [environments.development.flyway]
+            locations = ["filesystem:elsewhere"]
+        "# };
+
+        let (settings, errors) = parse_flyway_toml(content);
+
+        assert_eq!(
+            FlywaySettings {
+                locations: vec![
+                    "filesystem:migrations/versioned".to_string(),
+                    "filesystem:migrations/repeatable".to_string(),
+                ],
+                sql_migration_prefix: Some("V".to_string()),
+                repeatable_sql_migration_prefix: None,
+                sql_migration_separator: Some("--".to_string()),
+            },
+            settings
+        );
+        assert_eq!(
+            vec![FlywayConfError::InvalidValue(
+                "flyway.repeatableSqlMigrationPrefix".to_string(),
+                "1".to_string()
+            )],
+            errors
+        );
+    }
+
+    #[test]
+    fn parse_flyway_toml_should_report_syntax_errors() {
+        let (settings, errors) = parse_flyway_toml(
+            "[flyway]\nlocations = [\"filesystem:sql\"\nsqlMigrationPrefix = \"M\"\n",
+        );
+        assert_eq!(FlywaySettings::default(), settings);
+        assert!(matches!(errors.as_slice(), [FlywayConfError::Syntax(_)]));
+    }
+
+    #[test]
+    fn parse_flyway_toml_should_read_dotted_keys() {
+        let (settings, errors) = parse_flyway_toml(
+            "flyway.locations = [\"filesystem:sql\"]\nflyway.sqlMigrationPrefix = 'M'\n",
+        );
+        assert!(errors.is_empty());
+        assert_eq!(vec!["filesystem:sql".to_string()], settings.locations);
+        assert_eq!(Some("M".to_string()), settings.sql_migration_prefix);
+    }
+
+    #[test]
+    fn apply_flyway_settings_should_map_locations_to_folders() {
+        let mut config = Config::default();
+        let settings = FlywaySettings {
+            locations: vec![
+                "classpath:db/migration".to_string(),
+                "filesystem:sql/**/*.sql".to_string(),
+                "filesystem:sql/repeatable/".to_string(),
+                "filesystem:sql/versioned".to_string(),
+            ],
+            sql_migration_prefix: Some("M".to_string()),
+            repeatable_sql_migration_prefix: Some(String::new()),
+            sql_migration_separator: None,
+        };
+        apply_flyway_settings(&mut config, &settings);
+        // the first folder that can be written to takes both kinds
+        assert_eq!(Some("sql/repeatable".to_string()), config.versioned_folder);
+        assert_eq!(Some("sql/repeatable".to_string()), config.repeatable_folder);
+        assert_eq!("M", config.version_prefix);
+        assert_eq!("R", config.repeatable_prefix);
+        assert_eq!("__", config.version_description_separator);
+
+        // without a filesystem location, the configured folders stay
+        let mut config = Config {
+            versioned_folder: Some("db".to_string()),
+            ..Config::default()
+        };
+        let settings = FlywaySettings {
+            locations: vec!["classpath:db/migration".to_string()],
+            ..FlywaySettings::default()
+        };
+        apply_flyway_settings(&mut config, &settings);
+        assert_eq!(Some("db".to_string()), config.versioned_folder);
+        assert_eq!(None, config.repeatable_folder);
+    }
+
+    #[test]
+    fn detect_flyway_config_should_prefer_toml_in_the_repository_root() {
+        let repo: PathBuf = std::env::temp_dir().join("xanthidae_flyway_conf_test");
+        let _ = fs::remove_dir_all(&repo);
+        fs::create_dir_all(repo.join("conf")).unwrap();
+        let mut config = Config {
+            repo_root: Some(repo.to_string_lossy().to_string()),
+            ..Config::default()
+        };
+        assert_eq!(FlywayDetection::Disabled, detect_flyway_config(&config));
+        config.detect_flyway_config = true;
+        assert!(matches!(
+            detect_flyway_config(&config),
+            FlywayDetection::NotFound(_)
+        ));
+
+        fs::write(
+            repo.join("conf").join("flyway.conf"),
+            "flyway.locations=filesystem:sql\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Some(repo.join("conf").join("flyway.conf")),
+            find_flyway_config(&repo)
+        );
+        fs::write(
+            repo.join("flyway.toml"),
+            "[flyway]\nlocations = [\"filesystem:db\"]\n",
+        )
+        .unwrap();
+        match detect_flyway_config(&config) {
+            FlywayDetection::Found(path, settings, errors) => {
+                assert_eq!(repo.join("flyway.toml"), path);
+                assert_eq!(vec!["filesystem:db".to_string()], settings.locations);
+                assert!(errors.is_empty());
+            }
+            detection => panic!("unexpected {:?}", detection),
+        }
+        assert_eq!(
+            Some("db".to_string()),
+            config_with_flyway_settings(&config).repeatable_folder
+        );
+        assert_eq!(None, config.repeatable_folder);
+        fs::remove_dir_all(&repo).unwrap();
+    }
+}
@@ -0,0 +1,74 @@
+// Links to the Flyway documentation of the migration kinds, for colleagues new to Flyway's
+// naming (what are V, R and U files?).
+use crate::ui::{dialogs, MessageKind};
+
+const HELP_CAPTION: &str = "Flyway naming help";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MigrationKind {
+    Versioned,
+    Repeatable,
+    Undo,
+}
+
+// The documentation of each kind, None being the naming of migrations in general; kept in one
+// table so translated documentation can be linked in one place
+const HELP_URLS: [(Option<MigrationKind>, &str); 4] = [
+    (
+        None,
+        "https://flywaydb.org/documentation/concepts/migrations#naming",
+    ),
+    (
+        Some(MigrationKind::Versioned),
+        "https://flywaydb.org/documentation/concepts/migrations#versioned-migrations",
+    ),
+    (
+        Some(MigrationKind::Repeatable),
+        "https://flywaydb.org/documentation/concepts/migrations#repeatable-migrations",
+    ),
+    (
+        Some(MigrationKind::Undo),
+        "https://flywaydb.org/documentation/concepts/migrations#undo-migrations",
+    ),
+];
+
+// The documentation of the kind, or of the naming in general
+pub fn help_url(kind: Option<MigrationKind>) -> &'static str {
+    HELP_URLS
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map(|(_, url)| *url)
+        .unwrap_or(HELP_URLS[0].1)
+}
+
+// The footer of summary messages, pointing to the documentation of what was created
+pub fn help_footer(kind: Option<MigrationKind>) -> String {
+    format!("\n\nWhat are these files? {}", help_url(kind))
+}
+
+// Open the documentation of the naming in the default browser
+pub fn show_naming_help() {
+    let url = help_url(None);
+    if let Err(e) = dialogs().open_url(url) {
+        dialogs().show_message(
+            &format!("Could not open {}: {}", url, e),
+            HELP_CAPTION,
+            MessageKind::Error,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flyway_help::*;
+
+    #[test]
+    fn help_url_should_link_the_documentation_of_the_kind() {
+        assert!(help_url(None).ends_with("#naming"));
+        assert!(help_url(Some(MigrationKind::Versioned)).ends_with("#versioned-migrations"));
+        assert!(help_url(Some(MigrationKind::Repeatable)).ends_with("#repeatable-migrations"));
+        assert!(help_url(Some(MigrationKind::Undo)).ends_with("#undo-migrations"));
+        assert!(help_footer(Some(MigrationKind::Repeatable))
+            .ends_with(help_url(Some(MigrationKind::Repeatable))));
+    }
+}
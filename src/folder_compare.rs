@@ -0,0 +1,259 @@
+// Compare two folders of repeatable migrations, e.g. the working copies of two branches before
+// merging: which migrations only one of them has, and which differ, in a Markdown report.
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::diff::{line_changes, LineChanges};
+use crate::export::export_to_file;
+use crate::paths;
+use crate::ui::{clipboard, dialogs, ClipboardError, MessageKind};
+
+const COMPARE_CAPTION: &str = "Compare repeatable folders";
+
+#[derive(Debug, PartialEq)]
+pub struct FolderComparison {
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    // the name of the file in A, and what changed from A to B
+    pub differing: Vec<(String, LineChanges)>,
+}
+
+pub fn compare_repeatable_folders(config: &Config) {
+    // the folder picker returns an empty string if cancelled
+    let folder_a = dialogs().get_save_folder_name();
+    if folder_a.is_empty() {
+        return;
+    }
+    let folder_b = dialogs().get_save_folder_name();
+    if folder_b.is_empty() {
+        return;
+    }
+    let comparison = match compare_folders(Path::new(&folder_a), Path::new(&folder_b)) {
+        Ok(comparison) => comparison,
+        Err(e) => {
+            dialogs().show_message(
+                &format!("Could not compare the folders: {}", e),
+                COMPARE_CAPTION,
+                MessageKind::Error,
+            );
+            return;
+        }
+    };
+    let report = comparison_report(
+        &paths::display(config.repo_root.as_deref(), &folder_a),
+        &paths::display(config.repo_root.as_deref(), &folder_b),
+        &comparison,
+    );
+    match clipboard().copy_to_clipboard(&report, config.max_clipboard_bytes) {
+        Ok(_) => dialogs().show_message(
+            "The comparison report was copied to the clipboard.",
+            COMPARE_CAPTION,
+            MessageKind::Information,
+        ),
+        Err(e @ ClipboardError::TooLarge(..)) | Err(e @ ClipboardError::AllocationFailed(..)) => {
+            dialogs().show_message(&format!("{}", e), COMPARE_CAPTION, MessageKind::Information);
+            export_to_file(&report, "", config.verify_written_files);
+        }
+        Err(e) => dialogs().show_message(&format!("{}", e), COMPARE_CAPTION, MessageKind::Error),
+    }
+}
+
+// The .sql files of the folder by their lowercase name, as Windows ignores the case
+fn sql_files(folder: &Path) -> io::Result<BTreeMap<String, (String, PathBuf)>> {
+    let mut files = BTreeMap::new();
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        let is_sql = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("sql"));
+        if !is_sql || !path.is_file() {
+            continue;
+        }
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        files.insert(name.to_lowercase(), (name, path));
+    }
+    Ok(files)
+}
+
+// Match the .sql files of the folders by name; files differing only in their line endings
+// count as equal
+pub fn compare_folders(a: &Path, b: &Path) -> io::Result<FolderComparison> {
+    let files_a = sql_files(a)?;
+    let files_b = sql_files(b)?;
+    let mut comparison = FolderComparison {
+        only_in_a: vec![],
+        only_in_b: vec![],
+        differing: vec![],
+    };
+    for (key, (name, path_a)) in &files_a {
+        let path_b = match files_b.get(key) {
+            Some((_, path_b)) => path_b,
+            None => {
+                comparison.only_in_a.push(name.clone());
+                continue;
+            }
+        };
+        let content_a = fs::read(path_a)?;
+        let content_b = fs::read(path_b)?;
+        if content_a == content_b {
+            continue;
+        }
+        let changes = line_changes(
+            &String::from_utf8_lossy(&content_a),
+            &String::from_utf8_lossy(&content_b),
+        );
+        if !changes.is_empty() {
+            comparison.differing.push((name.clone(), changes));
+        }
+    }
+    comparison.only_in_b = files_b
+        .iter()
+        .filter(|(key, _)| !files_a.contains_key(*key))
+        .map(|(_, (name, _))| name.clone())
+        .collect();
+    Ok(comparison)
+}
+
+// The comparison as Markdown, with a section per kind of difference
+pub fn comparison_report(label_a: &str, label_b: &str, comparison: &FolderComparison) -> String {
+    let mut report = format!(
+        "## Comparison of repeatable migrations\n\n- A: {}\n- B: {}\n",
+        label_a, label_b
+    );
+    for (title, names) in [
+        ("Only in A", &comparison.only_in_a),
+        ("Only in B", &comparison.only_in_b),
+    ] {
+        report.push_str(&format!("\n### {} ({})\n\n", title, names.len()));
+        if names.is_empty() {
+            report.push_str("None.\n");
+            continue;
+        }
+        report.push_str("| File |\n|---|\n");
+        for name in names {
+            report.push_str(&format!("| {} |\n", name));
+        }
+    }
+    report.push_str(&format!(
+        "\n### Differing ({})\n\n",
+        comparison.differing.len()
+    ));
+    if comparison.differing.is_empty() {
+        report.push_str("None.\n");
+    } else {
+        report.push_str("| File | Lines added | Lines removed |\n|---|---:|---:|\n");
+        for (name, changes) in &comparison.differing {
+            report.push_str(&format!(
+                "| {} | {} | {} |\n",
+                name, changes.added, changes.removed
+            ));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use indoc::indoc;
+
+    use crate::diff::LineChanges;
+    use crate::folder_compare::*;
+
+    fn folder(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let folder: PathBuf = [std::env::temp_dir(), PathBuf::from(name)].iter().collect();
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        for (name, content) in files {
+            fs::write(folder.join(name), content).unwrap();
+        }
+        folder
+    }
+
+    #[test]
+    fn compare_folders_should_match_files_by_name() {
+        let a = folder(
+            "xanthidae_compare_a",
+            &[
+                ("R__PKG_A.sql", "create package a\nis\nend;\n"),
+                ("R__V_ONLY_A.sql", "view"),
+                ("R__V_SAME.sql", "select 1\r\nfrom dual\r\n"),
+                ("notes.txt", "not a migration"),
+            ],
+        );
+        let b = folder(
+            "xanthidae_compare_b",
+            &[
+                ("R__pkg_a.sql", "create package a\nas\n  x number;\nend;\n"),
+                ("R__V_ONLY_B.sql", "view"),
+                ("R__V_SAME.sql", "select 1\nfrom dual\n"),
+            ],
+        );
+
+        let comparison = compare_folders(&a, &b).unwrap();
+
+        assert_eq!(
+            FolderComparison {
+                only_in_a: vec!["R__V_ONLY_A.sql".to_string()],
+                only_in_b: vec!["R__V_ONLY_B.sql".to_string()],
+                differing: vec![(
+                    "R__PKG_A.sql".to_string(),
+                    LineChanges {
+                        added: 2,
+                        removed: 1
+                    }
+                )],
+            },
+            comparison
+        );
+        fs::remove_dir_all(&a).unwrap();
+        fs::remove_dir_all(&b).unwrap();
+    }
+
+    #[test]
+    fn comparison_report_should_render_the_three_sections() {
+        let comparison = FolderComparison {
+            only_in_a: vec!["R__V_A.sql".to_string(), "R__V_B.sql".to_string()],
+            only_in_b: vec![],
+            differing: vec![(
+                "R__PKG_A.sql".to_string(),
+                LineChanges {
+                    added: 2,
+                    removed: 1,
+                },
+            )],
+        };
+        let expected = indoc! { "
+            ## Comparison of repeatable migrations
+
+            - A: feature\\repeatable
+            - B: main\\repeatable
+
+            ### Only in A (2)
+
+            | File |
+            |---|
+            | R__V_A.sql |
+            | R__V_B.sql |
+
+            ### Only in B (0)
+
+            None.
+
+            ### Differing (1)
+
+            | File | Lines added | Lines removed |
+            |---|---:|---:|
+            | R__PKG_A.sql | 2 | 1 |
+        " };
+        assert_eq!(
+            expected,
+            comparison_report("feature\\repeatable", "main\\repeatable", &comparison)
+        );
+    }
+}
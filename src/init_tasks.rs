@@ -0,0 +1,174 @@
+// Startup work that must not delay the IDE: AfterStart only registers the menu and hands
+// everything else to InitTasks, which runs it on background threads and remembers how it went
+// (shown in the diagnostics report).
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::logging::target;
+
+// A startup task; returns a short summary of what it did, or why it failed
+pub type InitTask = Box<dyn FnOnce() -> Result<String, String> + Send>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TaskState {
+    // scheduled, but its thread hasn't started it yet
+    Pending,
+    Running,
+    Done(String),
+    Failed(String),
+}
+
+impl Display for TaskState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskState::Pending => write!(f, "pending"),
+            TaskState::Running => write!(f, "running"),
+            TaskState::Done(summary) => write!(f, "done: {}", summary),
+            TaskState::Failed(e) => write!(f, "FAILED: {}", e),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InitTasks {
+    states: Mutex<Vec<(&'static str, TaskState)>>,
+}
+
+impl InitTasks {
+    pub fn new() -> Arc<InitTasks> {
+        Arc::new(InitTasks::default())
+    }
+
+    // Run the task on a thread of its own; returns at once
+    pub fn spawn(self: &Arc<Self>, name: &'static str, task: InitTask) {
+        let index = {
+            let mut states = self.states.lock().unwrap();
+            states.push((name, TaskState::Pending));
+            states.len() - 1
+        };
+        let tasks = Arc::clone(self);
+        let spawned = thread::Builder::new()
+            .name(format!("xanthidae: {}", name))
+            .spawn(move || {
+                tasks.set_state(index, TaskState::Running);
+                // a panicking task must not stay Running forever
+                let result = catch_unwind(AssertUnwindSafe(task))
+                    .unwrap_or_else(|panic| Err(format!("panicked: {}", panic_message(&*panic))));
+                let state = match result {
+                    Ok(summary) => TaskState::Done(summary),
+                    Err(e) => {
+                        warn!(target: target::MENU, "Startup task '{}' failed: {}", name, e);
+                        TaskState::Failed(e)
+                    }
+                };
+                tasks.set_state(index, state);
+            });
+        if let Err(e) = spawned {
+            self.set_state(index, TaskState::Failed(format!("could not start: {}", e)));
+        }
+    }
+
+    fn set_state(&self, index: usize, state: TaskState) {
+        self.states.lock().unwrap()[index].1 = state;
+    }
+
+    // (name, state) of all tasks, in the order they were scheduled
+    pub fn states(&self) -> Vec<(&'static str, TaskState)> {
+        self.states.lock().unwrap().clone()
+    }
+}
+
+// The message panic! was called with, if it was called with one
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    match panic.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => panic
+            .downcast_ref::<String>()
+            .map_or("no message", String::as_str),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::init_tasks::*;
+
+    // Poll until the task is neither pending nor running; the deadline only keeps a broken
+    // task from hanging the tests
+    fn wait_for(tasks: &InitTasks, name: &str) -> TaskState {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let state = tasks
+                .states()
+                .into_iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, state)| state)
+                .unwrap();
+            match state {
+                TaskState::Pending | TaskState::Running if Instant::now() < deadline => {
+                    thread::sleep(Duration::from_millis(5))
+                }
+                state => return state,
+            }
+        }
+    }
+
+    #[test]
+    fn spawn_should_return_before_slow_task_finishes() {
+        let tasks = InitTasks::new();
+        // the task can only finish once the test lets it, so spawn must have returned
+        // before that, however slow the machine is
+        let (release, released) = mpsc::channel::<()>();
+        tasks.spawn(
+            "slow",
+            Box::new(move || match released.recv() {
+                Ok(()) => Ok("released".to_string()),
+                Err(_) => Err("never released".to_string()),
+            }),
+        );
+        tasks.spawn("failing", Box::new(|| Err("no such folder".to_string())));
+        assert!(matches!(
+            tasks.states()[0].1,
+            TaskState::Pending | TaskState::Running
+        ));
+        release.send(()).unwrap();
+
+        assert_eq!(
+            TaskState::Done("released".to_string()),
+            wait_for(&tasks, "slow")
+        );
+        let failed = wait_for(&tasks, "failing");
+        assert_eq!("FAILED: no such folder", failed.to_string());
+        assert_eq!(
+            vec!["slow", "failing"],
+            tasks
+                .states()
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn spawn_should_mark_panicking_tasks_failed() {
+        let tasks = InitTasks::new();
+        tasks.spawn("panicking", Box::new(|| panic!("config is broken")));
+        // a formatted message is a String rather than a &str
+        let what = "config";
+        tasks.spawn("formatted", Box::new(move || panic!("{} is broken", what)));
+        assert_eq!(
+            TaskState::Failed("panicked: config is broken".to_string()),
+            wait_for(&tasks, "panicking")
+        );
+        assert_eq!(
+            TaskState::Failed("panicked: config is broken".to_string()),
+            wait_for(&tasks, "formatted")
+        );
+    }
+}
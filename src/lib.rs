@@ -1,21 +1,65 @@
 extern crate chrono;
 extern crate core;
+extern crate crc32fast;
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
 extern crate log;
 extern crate indoc;
 extern crate regex;
+extern crate sha2;
 extern crate simplelog;
+extern crate toml;
+#[cfg(windows)]
 extern crate winapi;
 
 pub use self::prelude::*;
 
+#[cfg(test)]
+#[macro_use]
+mod test_macros;
+
+mod bulk;
+mod callback_skeletons;
+mod checksums;
+#[cfg(all(windows, feature = "windows-ui"))]
 mod clipboard;
+mod column_selection;
+mod companion_api;
 mod config;
+mod cursor_object;
+mod dbms_output;
+mod ddl_transform;
+mod diagnostics;
+mod diff;
+mod documentation;
+mod editorconfig;
 mod export;
 mod flyway;
+mod flyway_conf;
+mod flyway_help;
+mod folder_compare;
+mod init_tasks;
+mod logging;
+mod masking;
+mod menu;
+mod paths;
 mod plsqldev_api;
 mod prelude;
+mod project_config;
+mod retry;
+mod review_snippet;
+mod schema_history;
+mod selection_migration;
+mod self_test;
+mod statements;
 mod string_utils;
+mod temp_files;
+mod template;
+mod ui;
+mod verified_write;
+mod window_history;
+#[cfg(all(windows, feature = "windows-ui"))]
 mod windows_api;
+mod zip;
+mod zip_export;
@@ -0,0 +1,368 @@
+// The plugin log file.
+//
+// The logger can only be initialized once per process, so instead of handing the file itself to
+// WriteLogger, it gets a writer forwarding to the shared file. That way, the file can be cleared
+// while the IDE is running. Likewise, the levels are looked up on every record, so they can be
+// set once the config is known.
+//
+// Every IDE session appends to the log, starting with a banner, so the log of the last session
+// can be told apart from the ones before it.
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use simplelog::Config as LogConfig;
+use simplelog::WriteLogger;
+
+use crate::config::Config;
+
+// The areas of the plugin, as log targets; their levels can be configured separately
+pub mod target {
+    // migrations and the object sources they are made of
+    pub const FLYWAY: &str = "xanthidae::flyway";
+    // grid exports
+    pub const EXPORT: &str = "xanthidae::export";
+    // the plugin's menu and its startup
+    pub const MENU: &str = "xanthidae::menu";
+    // dialogs, the clipboard and the other Windows APIs
+    pub const WINAPI: &str = "xanthidae::winapi";
+    // project configs
+    pub const CONFIG: &str = "xanthidae::config";
+}
+
+// starts the first line of every session
+pub const SESSION_BANNER: &str = "===== Xanthidae session started";
+// a log larger than this is started afresh, rather than appended to
+const MAX_APPENDED_LOG_BYTES: u64 = 8 * 1024 * 1024;
+
+lazy_static! {
+    static ref LOG_FILE: Mutex<Option<(PathBuf, File)>> = Mutex::new(None);
+    static ref LOG_LEVELS: RwLock<LogLevels> = RwLock::new(LogLevels::default());
+}
+
+// The level of every target, see `level_for`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLevels {
+    pub default: LevelFilter,
+    // (target, level); a target includes the targets below it, e.g. xanthidae::flyway includes
+    // xanthidae::flyway::conf
+    pub targets: Vec<(String, LevelFilter)>,
+}
+
+impl Default for LogLevels {
+    fn default() -> LogLevels {
+        LogLevels {
+            default: LevelFilter::Debug,
+            targets: vec![],
+        }
+    }
+}
+
+impl LogLevels {
+    pub fn from_config(config: &Config) -> LogLevels {
+        LogLevels {
+            default: config.log_level,
+            targets: config.log_levels.clone(),
+        }
+    }
+
+    // The level of the most specific configured target containing the target, or the default
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(configured, _)| {
+                target == configured
+                    || target
+                        .strip_prefix(configured.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(configured, _)| configured.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    // The most verbose of the levels, so the log macros can skip anything beyond it
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, std::cmp::max)
+    }
+}
+
+// Hands the records of the levels currently configured for their targets to the file logger
+struct TargetFilter {
+    logger: Box<WriteLogger<LogWriter>>,
+}
+
+impl Log for TargetFilter {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= LOG_LEVELS.read().unwrap().level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            self.logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.logger.flush();
+    }
+}
+
+// Writes to the current log file; output is discarded if there is none
+struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut *LOG_FILE.lock().unwrap() {
+            Some((_, file)) => file.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut *LOG_FILE.lock().unwrap() {
+            Some((_, file)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn init_logging(path: &Path) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if file.metadata()?.len() > MAX_APPENDED_LOG_BYTES {
+        truncate_log_file(&mut file)?;
+    }
+    writeln!(
+        file,
+        "{} {}, version {} =====",
+        SESSION_BANNER,
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        env!("CARGO_PKG_VERSION")
+    )?;
+    *LOG_FILE.lock().unwrap() = Some((path.to_path_buf(), file));
+    let logger = TargetFilter {
+        logger: WriteLogger::new(LevelFilter::Trace, LogConfig::default(), LogWriter),
+    };
+    log::set_boxed_logger(Box::new(logger)).map_err(|e| std::io::Error::other(e.to_string()))?;
+    log::set_max_level(LOG_LEVELS.read().unwrap().max_level());
+    Ok(())
+}
+
+// Log the targets at the given levels from now on
+pub fn set_log_levels(levels: LogLevels) {
+    log::set_max_level(levels.max_level());
+    *LOG_LEVELS.write().unwrap() = levels;
+}
+
+// The path of the log file, if logging has been initialized
+pub fn log_path() -> Option<PathBuf> {
+    LOG_FILE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|(path, _)| path.clone())
+}
+
+// Empty the log file; logging continues at the start of the (now empty) file.
+// Returns the path of the log file.
+pub fn clear_log() -> std::io::Result<PathBuf> {
+    let mut log_file = LOG_FILE.lock().unwrap();
+    match &mut *log_file {
+        Some((path, file)) => {
+            // fails e.g. if another process (a log viewer, a virus scanner) has locked the file
+            truncate_log_file(file)?;
+            Ok(path.clone())
+        }
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Logging has not been initialized",
+        )),
+    }
+}
+
+// The log of the last session: everything from the last line starting with the session banner;
+// the whole log if there is none (e.g. after it was cleared)
+pub fn last_session(log: &str) -> &str {
+    let start = log
+        .match_indices(SESSION_BANNER)
+        .map(|(i, _)| i)
+        .filter(|&i| i == 0 || log[..i].ends_with('\n'))
+        .last()
+        .unwrap_or(0);
+    &log[start..]
+}
+
+// The log of the last session in the log file, truncated to about `max_bytes`
+pub fn read_last_session(path: &Path, max_bytes: usize) -> std::io::Result<String> {
+    let log = std::fs::read(path)?;
+    Ok(truncate_middle(
+        last_session(&String::from_utf8_lossy(&log)),
+        max_bytes,
+    ))
+}
+
+// The text if it takes at most `max_bytes`; otherwise its start and its end, taking half of
+// `max_bytes` each and cut at line breaks where possible, with a note on the bytes left out
+pub fn truncate_middle(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let half = max_bytes / 2;
+    let mut head_end = half;
+    while !text.is_char_boundary(head_end) {
+        head_end -= 1;
+    }
+    if let Some(i) = text[..head_end].rfind('\n') {
+        head_end = i + 1;
+    }
+    let mut tail_start = text.len() - half;
+    while !text.is_char_boundary(tail_start) {
+        tail_start += 1;
+    }
+    if let Some(i) = text[tail_start..].find('\n') {
+        if tail_start + i + 1 < text.len() {
+            tail_start += i + 1;
+        }
+    }
+    format!(
+        "{}[... {} bytes left out ...]\n{}",
+        &text[..head_end],
+        tail_start - head_end,
+        &text[tail_start..]
+    )
+}
+
+// Truncate an open file and continue writing at its start
+pub fn truncate_log_file(file: &mut File) -> std::io::Result<()> {
+    file.flush()?;
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    use crate::logging::*;
+
+    #[test]
+    fn level_for_should_use_the_most_specific_target() {
+        let levels = LogLevels {
+            default: LevelFilter::Info,
+            targets: vec![
+                ("xanthidae::flyway".to_string(), LevelFilter::Debug),
+                ("xanthidae::flyway::conf".to_string(), LevelFilter::Off),
+                ("xanthidae::winapi".to_string(), LevelFilter::Warn),
+            ],
+        };
+        assert_eq!(LevelFilter::Debug, levels.level_for(target::FLYWAY));
+        assert_eq!(
+            LevelFilter::Off,
+            levels.level_for("xanthidae::flyway::conf")
+        );
+        assert_eq!(LevelFilter::Debug, levels.level_for("xanthidae::flyway::x"));
+        assert_eq!(LevelFilter::Warn, levels.level_for(target::WINAPI));
+        // a prefix only counts at a path separator
+        assert_eq!(
+            LevelFilter::Info,
+            levels.level_for("xanthidae::flyway_conf")
+        );
+        assert_eq!(LevelFilter::Info, levels.level_for(target::EXPORT));
+        assert_eq!(LevelFilter::Debug, levels.max_level());
+    }
+
+    #[test]
+    fn last_session_should_start_at_the_last_banner() {
+        let log = format!(
+            "{banner} 2026-10-14 08:00:00, version 1.0.1 =====\n\
+             08:00:01 [DEBUG] first session\n\
+             08:00:02 [WARN] mentions {banner} in passing\n\
+             {banner} 2026-10-15 09:00:00, version 1.0.2 =====\n\
+             09:00:01 [DEBUG] second session\n\
+             {banner} 2026-10-16 10:00:00, version 1.0.2 =====\n\
+             10:00:01 [INFO] third session\n",
+            banner = SESSION_BANNER
+        );
+        let session = last_session(&log);
+        assert!(session.starts_with(&format!("{} 2026-10-16 10:00:00", SESSION_BANNER)));
+        assert!(session.ends_with("10:00:01 [INFO] third session\n"));
+        assert!(!session.contains("second session"));
+
+        // a banner in the middle of a line doesn't start a session
+        let log = &log[..log.find("09:00:01").unwrap()];
+        let log = &log[..log.rfind(SESSION_BANNER).unwrap()];
+        assert!(last_session(log).contains("in passing"));
+        assert!(last_session(log).contains("first session"));
+        // a cleared log has no banner
+        assert_eq!("10:00:01 [INFO] x\n", last_session("10:00:01 [INFO] x\n"));
+    }
+
+    #[test]
+    fn read_last_session_should_truncate_the_last_session_only() {
+        let path = std::env::temp_dir().join(format!(
+            "xanthidae_test_last_session_{}.log",
+            std::process::id()
+        ));
+        let session = |day: u32, lines: usize| -> String {
+            let mut log = format!("{} 2026-10-{} 08:00:00 =====\n", SESSION_BANNER, day);
+            for i in 0..lines {
+                log += &format!("08:00:01 [DEBUG] line {} of day {}\n", i, day);
+            }
+            log
+        };
+        fs::write(&path, session(14, 1000) + &session(15, 1000)).unwrap();
+
+        let copied = read_last_session(&path, 1024 * 1024).unwrap();
+        assert_eq!(session(15, 1000), copied);
+
+        let copied = read_last_session(&path, 1000).unwrap();
+        assert!(copied.len() <= 1000 + 40);
+        assert!(copied.starts_with(&format!("{} 2026-10-15", SESSION_BANNER)));
+        assert!(copied.contains(" bytes left out ...]\n"));
+        assert!(copied.ends_with("08:00:01 [DEBUG] line 999 of day 15\n"));
+        assert!(!copied.contains("day 14"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn truncate_middle_should_keep_head_and_tail_lines() {
+        let text: String = (1..=100).map(|i| format!("line {:03}\n", i)).collect();
+        assert_eq!(text, truncate_middle(&text, text.len()));
+
+        // lines of 9 bytes, 40 bytes each for head and tail
+        let truncated = truncate_middle(&text, 80);
+        assert!(truncated.starts_with("line 001\nline 002\nline 003\nline 004\n[... "));
+        assert!(truncated.ends_with(" ...]\nline 097\nline 098\nline 099\nline 100\n"));
+        assert!(truncated.contains(&format!("[... {} bytes left out ...]", 92 * 9)));
+
+        // no line breaks: cut at character boundaries
+        let truncated = truncate_middle("äöüäöüäöü", 7);
+        assert_eq!("ä[... 14 bytes left out ...]\nü", truncated);
+    }
+
+    #[test]
+    fn truncate_log_file_should_empty_file_and_rewind() {
+        let path = std::env::temp_dir().join(format!(
+            "xanthidae_test_truncate_{}.log",
+            std::process::id()
+        ));
+        fs::write(&path, "a lot of old log output\n").unwrap();
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.write_all(b"more output\n").unwrap();
+
+        truncate_log_file(&mut file).unwrap();
+        assert_eq!(0, fs::metadata(&path).unwrap().len());
+
+        file.write_all(b"fresh\n").unwrap();
+        assert_eq!("fresh\n", fs::read_to_string(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+}
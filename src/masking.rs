@@ -0,0 +1,214 @@
+// Masking (pseudonymisation) of personal data in grid exports, so query results can be pasted
+// into tickets.
+//
+// Columns are matched against the configured patterns (case-insensitive, `*` matches any
+// sequence of characters and `?` a single one); the first matching rule wins.
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use sha2::{Digest, Sha256};
+
+use crate::config::MaskingStrategy;
+use crate::export::ExportData;
+
+lazy_static! {
+    // salts the hashes, so a token can't be reversed by hashing guessed values (e.g. a list of
+    // email addresses); random per IDE session
+    static ref SESSION_SALT: [u8; 16] = random_salt();
+}
+
+// 16 bytes from the random keys of the standard library's hasher, which come from the OS
+fn random_salt() -> [u8; 16] {
+    let mut salt = [0u8; 16];
+    for chunk in salt.chunks_mut(8) {
+        let random = RandomState::new().build_hasher().finish();
+        chunk.copy_from_slice(&random.to_le_bytes());
+    }
+    salt
+}
+
+const MASK: &str = "***";
+
+// The data with all matching columns masked, and the names of these columns
+pub fn mask_data(
+    export_data: &ExportData,
+    rules: &[(String, MaskingStrategy)],
+) -> (ExportData, Vec<String>) {
+    let strategies: Vec<Option<&MaskingStrategy>> = export_data
+        .headers
+        .iter()
+        .map(|header| strategy_for(header, rules))
+        .collect();
+    let mut masked = export_data.clone();
+    for row in &mut masked.data {
+        // NULL stays NULL, so it can still be told apart
+        for (cell, strategy) in row.iter_mut().zip(&strategies) {
+            if let (Some(value), Some(strategy)) = (cell.as_mut(), strategy) {
+                *value = mask_value(value, strategy);
+            }
+        }
+    }
+    (masked, masked_columns(&export_data.headers, rules))
+}
+
+// The headers masked by the rules
+pub fn masked_columns(headers: &[String], rules: &[(String, MaskingStrategy)]) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|header| strategy_for(header, rules).is_some())
+        .cloned()
+        .collect()
+}
+
+fn strategy_for<'a>(
+    header: &str,
+    rules: &'a [(String, MaskingStrategy)],
+) -> Option<&'a MaskingStrategy> {
+    rules
+        .iter()
+        .find(|(pattern, _)| matches_pattern(pattern, header))
+        .map(|(_, strategy)| strategy)
+}
+
+// Empty values stay empty, so they can still be told apart
+pub fn mask_value(value: &str, strategy: &MaskingStrategy) -> String {
+    if value.is_empty() {
+        return String::new();
+    }
+    match strategy {
+        // the same value always gives the same token, so joins still line up
+        MaskingStrategy::Hash => pseudonym(value, &*SESSION_SALT),
+        MaskingStrategy::KeepFirst(n) => {
+            let kept: String = value.chars().take(*n).collect();
+            match kept.len() == value.len() {
+                true => kept,
+                false => format!("{}{}", kept, MASK),
+            }
+        }
+    }
+}
+
+// Glob match, ignoring case
+pub fn matches_pattern(pattern: &str, column: &str) -> bool {
+    let pattern: Vec<char> = pattern.trim().to_lowercase().chars().collect();
+    let column: Vec<char> = column.to_lowercase().chars().collect();
+    // classic wildcard matching, backtracking to the last `*`
+    let (mut p, mut c) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while c < column.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == column[c]) {
+            p += 1;
+            c += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            last_star = Some((p, c));
+            p += 1;
+        } else if let Some((star_p, star_c)) = last_star {
+            p = star_p + 1;
+            c = star_c + 1;
+            last_star = Some((star_p, star_c + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+// The first 8 hex digits of the SHA-256 of the salt followed by the value
+fn pseudonym(value: &str, salt: &[u8]) -> String {
+    let digest = Sha256::new()
+        .chain_update(salt)
+        .chain_update(value.as_bytes())
+        .finalize();
+    format!("{:x}", digest)[..8].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::masking::*;
+
+    #[test]
+    fn pseudonym_should_hash_salted_value() {
+        assert_eq!("fadf7b97", pseudonym("abc", b"pepper"));
+        // without the salt, it would be the start of the well-known SHA-256 of "abc"
+        assert_eq!("ba7816bf", pseudonym("abc", b""));
+        assert_ne!(pseudonym("abc", b"pepper"), pseudonym("abc", b"salt"));
+    }
+
+    #[test]
+    fn matches_pattern_should_support_wildcards_and_ignore_case() {
+        assert!(matches_pattern("email", "EMAIL"));
+        assert!(matches_pattern("*name", "LAST_NAME"));
+        assert!(matches_pattern("*_NAME*", "first_name_2"));
+        assert!(matches_pattern("phone?", "PHONE2"));
+        assert!(!matches_pattern("phone?", "PHONE"));
+        assert!(!matches_pattern("*name", "NAMESPACE"));
+        assert!(matches_pattern("*", "ANYTHING"));
+    }
+
+    #[test]
+    fn mask_value_should_apply_strategies() {
+        assert_eq!(
+            pseudonym("abc", &*SESSION_SALT),
+            mask_value("abc", &MaskingStrategy::Hash)
+        );
+        assert_ne!("ba7816bf", mask_value("abc", &MaskingStrategy::Hash));
+        assert_eq!(
+            mask_value("jdoe@example.com", &MaskingStrategy::Hash),
+            mask_value("jdoe@example.com", &MaskingStrategy::Hash)
+        );
+        assert_eq!("Jo***", mask_value("John", &MaskingStrategy::KeepFirst(2)));
+        assert_eq!("Jo", mask_value("Jo", &MaskingStrategy::KeepFirst(2)));
+        assert_eq!("", mask_value("", &MaskingStrategy::Hash));
+    }
+
+    #[test]
+    fn mask_data_should_mask_matching_columns_in_all_rows() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "EMAIL", "LAST_NAME"],
+            data: vec![
+                vec_of_cells!["1", "jdoe@example.com", "Doe"],
+                vec_of_cells!["2", "jdoe@example.com", "Roe"],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 2,
+            ..ExportData::new()
+        };
+        let rules = vec![
+            ("email".to_string(), MaskingStrategy::Hash),
+            ("*name".to_string(), MaskingStrategy::KeepFirst(1)),
+        ];
+
+        let (masked, columns) = mask_data(&export_data, &rules);
+
+        assert_eq!(vec_of_strings!["EMAIL", "LAST_NAME"], columns);
+        assert_eq!(masked.data[0][1], masked.data[1][1]);
+        assert_ne!(Some("jdoe@example.com"), masked.data[0][1].as_deref());
+        assert_eq!(
+            vec_of_cells!["1", "2"],
+            vec![masked.data[0][0].clone(), masked.data[1][0].clone()]
+        );
+        assert_eq!(Some("D***"), masked.data[0][2].as_deref());
+    }
+
+    #[test]
+    fn mask_data_should_keep_nulls_and_empty_strings() {
+        let export_data = ExportData {
+            headers: vec_of_strings!["ID", "LAST_NAME"],
+            data: vec![
+                vec![Some("1".to_string()), None],
+                vec![Some("2".to_string()), Some(String::new())],
+            ],
+            current_row: vec![],
+            prepared: true,
+            rows_received: 2,
+            ..ExportData::new()
+        };
+        let rules = vec![("*name".to_string(), MaskingStrategy::Hash)];
+
+        let (masked, _) = mask_data(&export_data, &rules);
+
+        assert_eq!(None, masked.data[0][1]);
+        assert_eq!(Some(""), masked.data[1][1].as_deref());
+    }
+}
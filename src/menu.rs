@@ -0,0 +1,391 @@
+// The items of the plugin's menu, their order and their accelerators.
+//
+// Items are identified by stable keys. The IDE only knows menu indices; these are assigned in
+// the configured order when the menu is created, and mapped back to the keys on a click, so
+// reordering the menu can't swap the handlers.
+use std::fmt::{Display, Formatter};
+use std::os::raw::c_int;
+
+use crate::logging::target;
+
+// index of the first item, the lower ones are taken by the tab and the group
+pub const FIRST_ITEM_INDEX: c_int = 11;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuKey {
+    RepeatableMigration,
+    VersionedMigration,
+    RepeatableAndVersionedMigration,
+    VersionedAndUndoMigration,
+    ExportDocumentation,
+    ExportObjectUnderCursor,
+    SelectionMigration,
+    DbmsOutputTable,
+    WindowHistory,
+    VerifyChecksums,
+    CompareFolders,
+    SchemaHistoryInsert,
+    ReviewSnippet,
+    CallbackSkeletons,
+    FlywayHelp,
+    PluginVersion,
+    ClearLog,
+    CopySessionLog,
+    Diagnostics,
+    SelfTest,
+    MillisecondPrecision,
+    ExportAsZip,
+}
+
+pub struct MenuItem {
+    pub key: MenuKey,
+    // the name used in the configured menu order
+    pub name: &'static str,
+    // the IDE's menu item definition; `&` marks the accelerator
+    pub definition: &'static [u8],
+    // shows a check mark reflecting a setting, which a click toggles
+    pub checkable: bool,
+}
+
+// in the default order, most used first
+pub const MENU_ITEMS: [MenuItem; 22] = [
+    MenuItem {
+        key: MenuKey::RepeatableMigration,
+        name: "repeatable_migration",
+        definition: b"ITEM=&Repeatable migration\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::VersionedMigration,
+        name: "versioned_migration",
+        definition: b"ITEM=&Versioned migration\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::RepeatableAndVersionedMigration,
+        name: "repeatable_and_versioned_migration",
+        definition: b"ITEM=R&epeatable + versioned migration\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::VersionedAndUndoMigration,
+        name: "versioned_and_undo_migration",
+        definition: b"ITEM=Versioned + &undo migration\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::ExportDocumentation,
+        name: "export_documentation",
+        definition: b"ITEM=Export &documentation...\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::ExportObjectUnderCursor,
+        name: "export_object_under_cursor",
+        definition: b"ITEM=Export object under &cursor\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::SelectionMigration,
+        name: "selection_migration",
+        definition: b"ITEM=Repeatable migration from selecti&on...\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::DbmsOutputTable,
+        name: "dbms_output_table",
+        definition: b"ITEM=Form&at DBMS_OUTPUT as table...\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::WindowHistory,
+        name: "window_history",
+        definition: b"ITEM=Versioned migrations from &window history...\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::VerifyChecksums,
+        name: "verify_checksums",
+        definition: b"ITEM=Verify c&hecksums...\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::CompareFolders,
+        name: "compare_folders",
+        definition: b"ITEM=Compare repeatable &folders...\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::SchemaHistoryInsert,
+        name: "schema_history_insert",
+        definition: b"ITEM=Copy schema-history &INSERT for migration...\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::ReviewSnippet,
+        name: "review_snippet",
+        definition: b"ITEM=Copy as review &snippet\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::CallbackSkeletons,
+        name: "callback_skeletons",
+        definition: b"ITEM=&Generate Flyway callback skeletons...\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::FlywayHelp,
+        name: "flyway_help",
+        definition: b"ITEM=Fl&yway naming help\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::PluginVersion,
+        name: "plugin_version",
+        definition: b"ITEM=&Plugin version\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::ClearLog,
+        name: "clear_log",
+        definition: b"ITEM=Clear &log\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::CopySessionLog,
+        name: "copy_session_log",
+        definition: b"ITEM=Copy last session log to clip&board\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::Diagnostics,
+        name: "diagnostics",
+        definition: b"ITEM=Diag&nostics\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::SelfTest,
+        name: "self_test",
+        definition: b"ITEM=Run self-&test\0",
+        checkable: false,
+    },
+    MenuItem {
+        key: MenuKey::MillisecondPrecision,
+        name: "millisecond_precision",
+        definition: b"ITEM=Use &millisecond precision in versions\0",
+        checkable: true,
+    },
+    MenuItem {
+        key: MenuKey::ExportAsZip,
+        name: "export_as_zip",
+        definition: b"ITEM=Export selection as &zip...\0",
+        checkable: false,
+    },
+];
+
+#[derive(Debug, PartialEq)]
+pub enum MenuError {
+    // the accelerator and the two items using it
+    DuplicateAccelerator(char, String, String),
+}
+
+impl Display for MenuError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            MenuError::DuplicateAccelerator(accelerator, first, second) => format!(
+                "The accelerator {} is used by both {} and {}",
+                accelerator, first, second
+            ),
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+// The menu items in the order they are shown
+pub struct Menu {
+    order: Vec<MenuKey>,
+}
+
+impl Menu {
+    pub fn new(configured_order: &[String]) -> Menu {
+        Menu {
+            order: resolve_order(configured_order),
+        }
+    }
+
+    pub fn index_of(&self, key: MenuKey) -> c_int {
+        let position = self.order.iter().position(|k| *k == key).unwrap();
+        FIRST_ITEM_INDEX + position as c_int
+    }
+
+    pub fn item_at(&self, index: c_int) -> Option<&'static MenuItem> {
+        let position = usize::try_from(index - FIRST_ITEM_INDEX).ok()?;
+        self.order.get(position).map(|key| menu_item(*key))
+    }
+}
+
+pub fn menu_item(key: MenuKey) -> &'static MenuItem {
+    MENU_ITEMS.iter().find(|item| item.key == key).unwrap()
+}
+
+// The configured items first (unknown names are ignored), then the others in default order
+pub fn resolve_order(configured_order: &[String]) -> Vec<MenuKey> {
+    let mut order = vec![];
+    for name in configured_order {
+        match MENU_ITEMS
+            .iter()
+            .find(|item| item.name.eq_ignore_ascii_case(name.trim()))
+        {
+            Some(item) if !order.contains(&item.key) => order.push(item.key),
+            Some(_) => (),
+            None => warn!(target: target::MENU, "Unknown menu item {:?} in the menu order", name),
+        }
+    }
+    for item in &MENU_ITEMS {
+        if !order.contains(&item.key) {
+            order.push(item.key);
+        }
+    }
+    order
+}
+
+// The character following a single `&`, in upper case; `&&` is a literal ampersand
+pub fn accelerator(caption: &str) -> Option<char> {
+    let mut chars = caption.chars();
+    while let Some(c) = chars.next() {
+        if c == '&' {
+            match chars.next() {
+                Some('&') => continue,
+                Some(accelerator) => return accelerator.to_uppercase().next(),
+                None => return None,
+            }
+        }
+    }
+    None
+}
+
+// The accelerators of the captions must be unique (ignoring case)
+pub fn validate_accelerators(captions: &[&str]) -> Result<(), MenuError> {
+    let mut seen: Vec<(char, &str)> = vec![];
+    for caption in captions {
+        if let Some(accelerator) = accelerator(caption) {
+            if let Some((_, first)) = seen.iter().find(|(a, _)| *a == accelerator) {
+                return Err(MenuError::DuplicateAccelerator(
+                    accelerator,
+                    first.to_string(),
+                    caption.to_string(),
+                ));
+            }
+            seen.push((accelerator, caption));
+        }
+    }
+    Ok(())
+}
+
+impl MenuItem {
+    // the caption, without the ITEM= prefix and the terminating NUL
+    pub fn caption(&self) -> &'static str {
+        let definition = std::str::from_utf8(self.definition).unwrap();
+        definition
+            .trim_start_matches("ITEM=")
+            .trim_end_matches('\0')
+    }
+
+    // The caption of a checkable item showing its state, for IDE versions without check marks
+    pub fn checked_caption(&self, checked: bool) -> String {
+        match checked {
+            true => format!("{} (on)", self.caption()),
+            false => format!("{} (off)", self.caption()),
+        }
+    }
+}
+
+// The captions of MENU_ITEMS
+pub fn menu_captions() -> Vec<&'static str> {
+    MENU_ITEMS.iter().map(MenuItem::caption).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::menu::*;
+
+    #[test]
+    fn resolve_order_should_append_missing_items_in_default_order() {
+        let order = resolve_order(
+            &[
+                "clear_log",
+                "no_such_item",
+                " Versioned_Migration ",
+                "clear_log",
+            ]
+            .map(String::from),
+        );
+        assert_eq!(MENU_ITEMS.len(), order.len());
+        assert_eq!(
+            vec![
+                MenuKey::ClearLog,
+                MenuKey::VersionedMigration,
+                MenuKey::RepeatableMigration,
+                MenuKey::RepeatableAndVersionedMigration,
+            ],
+            order[..4]
+        );
+        assert_eq!(MenuKey::ExportAsZip, order[MENU_ITEMS.len() - 1]);
+
+        let default_order: Vec<MenuKey> = MENU_ITEMS.iter().map(|item| item.key).collect();
+        assert_eq!(default_order, resolve_order(&[]));
+    }
+
+    #[test]
+    fn menu_should_map_indices_to_keys_in_configured_order() {
+        let menu = Menu::new(&["plugin_version".to_string()]);
+        assert_eq!(FIRST_ITEM_INDEX, menu.index_of(MenuKey::PluginVersion));
+        assert_eq!(
+            FIRST_ITEM_INDEX + 1,
+            menu.index_of(MenuKey::RepeatableMigration)
+        );
+        for item in &MENU_ITEMS {
+            assert_eq!(item.key, menu.item_at(menu.index_of(item.key)).unwrap().key);
+        }
+        assert!(menu.item_at(FIRST_ITEM_INDEX - 1).is_none());
+        assert!(menu
+            .item_at(FIRST_ITEM_INDEX + MENU_ITEMS.len() as c_int)
+            .is_none());
+    }
+
+    #[test]
+    fn accelerator_should_skip_literal_ampersands() {
+        assert_eq!(Some('R'), accelerator("&Repeatable migration"));
+        assert_eq!(Some('H'), accelerator("Verify c&hecksums..."));
+        assert_eq!(Some('B'), accelerator("Tom && Jerry &boom"));
+        assert_eq!(None, accelerator("Tom && Jerry"));
+        assert_eq!(None, accelerator("Trailing &"));
+    }
+
+    #[test]
+    fn checked_caption_should_show_the_state() {
+        let item = menu_item(MenuKey::MillisecondPrecision);
+        assert!(item.checkable);
+        assert_eq!(
+            "Use &millisecond precision in versions (on)",
+            item.checked_caption(true)
+        );
+        assert_eq!(
+            "Use &millisecond precision in versions (off)",
+            item.checked_caption(false)
+        );
+    }
+
+    #[test]
+    fn validate_accelerators_should_detect_duplicates() {
+        assert_eq!(Ok(()), validate_accelerators(&menu_captions()));
+        assert_eq!(
+            Err(MenuError::DuplicateAccelerator(
+                'R',
+                "&Repeatable migration".to_string(),
+                "&run self-test".to_string()
+            )),
+            validate_accelerators(&["&Repeatable migration", "Clear log", "&run self-test"])
+        );
+    }
+}
@@ -0,0 +1,290 @@
+// Helpers for displaying and storing paths relative to the configured repository root.
+//
+// Colleagues tend to have the same repository checked out under different drive letters
+// or network shares, so absolute paths are only used as a fallback. All helpers work on
+// plain strings using Windows semantics (drive letters, UNC shares, case-insensitive
+// comparison) so they behave identically regardless of the platform the tests run on.
+
+const SEPARATOR: char = '\\';
+const OUTSIDE_ROOT_NOTE: &str = "(outside repository root)";
+
+// The part of an absolute path that cannot be traversed with "..", i.e. a drive or a UNC share
+#[derive(Debug, PartialEq)]
+enum Prefix {
+    Drive(char),
+    Unc(String, String),
+}
+
+impl Prefix {
+    fn matches(&self, other: &Prefix) -> bool {
+        match (self, other) {
+            (Prefix::Drive(a), Prefix::Drive(b)) => a.eq_ignore_ascii_case(b),
+            (Prefix::Unc(server_a, share_a), Prefix::Unc(server_b, share_b)) => {
+                server_a.eq_ignore_ascii_case(server_b) && share_a.eq_ignore_ascii_case(share_b)
+            }
+            _ => false,
+        }
+    }
+}
+
+// An absolute path split into its prefix and its normalized components
+struct AbsolutePath {
+    prefix: Prefix,
+    components: Vec<String>,
+}
+
+impl AbsolutePath {
+    fn parse(path: &str) -> Option<AbsolutePath> {
+        let path = path.replace('/', "\\");
+        let (prefix, rest) = if let Some(unc) = path.strip_prefix("\\\\") {
+            let mut parts = unc.splitn(3, SEPARATOR);
+            let server = parts.next().filter(|s| !s.is_empty())?;
+            let share = parts.next().filter(|s| !s.is_empty())?;
+            (
+                Prefix::Unc(server.to_string(), share.to_string()),
+                parts.next().unwrap_or("").to_string(),
+            )
+        } else {
+            let mut chars = path.chars();
+            match (chars.next(), chars.next(), chars.next()) {
+                (Some(drive), Some(':'), Some(SEPARATOR)) if drive.is_ascii_alphabetic() => {
+                    (Prefix::Drive(drive), path[3..].to_string())
+                }
+                (Some(drive), Some(':'), None) if drive.is_ascii_alphabetic() => {
+                    (Prefix::Drive(drive), String::new())
+                }
+                _ => return None,
+            }
+        };
+        Some(AbsolutePath {
+            prefix,
+            components: normalize_components(&rest),
+        })
+    }
+}
+
+// Split a path into its components, dropping empty and "." components and applying ".."
+// (a ".." that would escape the start of the path is dropped, just like Windows does for roots)
+fn normalize_components(path: &str) -> Vec<String> {
+    let mut components: Vec<String> = vec![];
+    for component in path.split(['\\', '/']) {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component.to_string()),
+        }
+    }
+    components
+}
+
+// Check whether the given path is absolute, i.e. starts with a drive letter or a UNC share
+pub fn is_absolute(path: &str) -> bool {
+    AbsolutePath::parse(path).is_some()
+}
+
+// Compute the path relative to the repository root, using backslashes as separators.
+// Returns "." for the root itself, and None if the path is relative, on a different drive/share,
+// or outside of the root.
+pub fn relativize(root: &str, path: &str) -> Option<String> {
+    let root = AbsolutePath::parse(root)?;
+    let path = AbsolutePath::parse(path)?;
+
+    if !root.prefix.matches(&path.prefix) || root.components.len() > path.components.len() {
+        return None;
+    }
+
+    let is_below_root = root
+        .components
+        .iter()
+        .zip(path.components.iter())
+        .all(|(r, p)| r.to_lowercase() == p.to_lowercase());
+    if !is_below_root {
+        return None;
+    }
+
+    let relative = path.components[root.components.len()..].join("\\");
+    match relative.as_str() {
+        "" => Some(".".to_string()),
+        _ => Some(relative),
+    }
+}
+
+// Resolve a stored path against the repository root. Absolute paths are returned unchanged
+// (apart from separator normalization), relative paths are joined with the root.
+pub fn resolve(root: &str, stored: &str) -> String {
+    if is_absolute(stored) {
+        return stored.replace('/', "\\");
+    }
+    let mut result = root
+        .replace('/', "\\")
+        .trim_end_matches(SEPARATOR)
+        .to_string();
+    for component in normalize_components(stored) {
+        result.push(SEPARATOR);
+        result.push_str(&component);
+    }
+    result
+}
+
+// Format a path for display to the user: relative to the root if possible, absolute with a
+// note if the path is outside of the root. Without a configured root, the path is shown as is.
+pub fn display(repo_root: Option<&str>, path: &str) -> String {
+    match repo_root {
+        None => path.to_string(),
+        Some(root) => match relativize(root, path) {
+            Some(relative) => relative,
+            None => format!("{} {}", path, OUTSIDE_ROOT_NOTE),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::paths::*;
+
+    #[test]
+    fn relativize_should_strip_root() {
+        assert_eq!(
+            Some("db\\repeatable".to_string()),
+            relativize("C:\\work\\repo", "C:\\work\\repo\\db\\repeatable")
+        );
+    }
+
+    #[test]
+    fn relativize_should_return_dot_for_root_itself() {
+        assert_eq!(
+            Some(".".to_string()),
+            relativize("C:\\work\\repo", "C:\\work\\repo\\")
+        );
+    }
+
+    #[test]
+    fn relativize_should_ignore_case() {
+        assert_eq!(
+            Some("DB\\Repeatable".to_string()),
+            relativize("c:\\Work\\REPO", "C:\\work\\repo\\DB\\Repeatable")
+        );
+    }
+
+    #[test]
+    fn relativize_should_accept_forward_slashes_and_trailing_separators() {
+        assert_eq!(
+            Some("db\\versioned".to_string()),
+            relativize("C:/work/repo/", "C:\\work\\repo\\db/versioned\\")
+        );
+    }
+
+    #[test]
+    fn relativize_should_reject_different_drives() {
+        assert_eq!(None, relativize("C:\\work\\repo", "D:\\work\\repo\\db"));
+    }
+
+    #[test]
+    fn relativize_should_reject_paths_outside_root() {
+        assert_eq!(None, relativize("C:\\work\\repo", "C:\\work\\other\\db"));
+        assert_eq!(None, relativize("C:\\work\\repo", "C:\\work"));
+    }
+
+    #[test]
+    fn relativize_should_not_match_sibling_with_common_prefix() {
+        assert_eq!(
+            None,
+            relativize("C:\\work\\repo", "C:\\work\\repository\\db")
+        );
+    }
+
+    #[test]
+    fn relativize_should_apply_parent_components() {
+        assert_eq!(
+            Some("db".to_string()),
+            relativize("C:\\work\\repo", "C:\\work\\repo\\sql\\..\\db")
+        );
+        assert_eq!(
+            None,
+            relativize("C:\\work\\repo", "C:\\work\\repo\\..\\other")
+        );
+    }
+
+    #[test]
+    fn relativize_should_support_unc_paths() {
+        assert_eq!(
+            Some("db".to_string()),
+            relativize("\\\\server\\share\\repo", "\\\\SERVER\\Share\\repo\\db")
+        );
+    }
+
+    #[test]
+    fn relativize_should_reject_different_unc_shares() {
+        assert_eq!(
+            None,
+            relativize("\\\\server\\share\\repo", "\\\\server\\other\\repo\\db")
+        );
+        assert_eq!(
+            None,
+            relativize("\\\\server\\share\\repo", "\\\\other\\share\\repo\\db")
+        );
+    }
+
+    #[test]
+    fn relativize_should_reject_mixing_unc_and_drive_paths() {
+        assert_eq!(None, relativize("\\\\server\\share", "C:\\share\\db"));
+        assert_eq!(None, relativize("C:\\repo", "\\\\server\\share\\repo"));
+    }
+
+    #[test]
+    fn relativize_should_reject_relative_input() {
+        assert_eq!(None, relativize("C:\\work\\repo", "db\\repeatable"));
+        assert_eq!(None, relativize("work\\repo", "C:\\work\\repo\\db"));
+    }
+
+    #[test]
+    fn is_absolute_should_detect_drives_and_unc_paths() {
+        assert!(is_absolute("C:\\"));
+        assert!(is_absolute("c:"));
+        assert!(is_absolute("C:/work"));
+        assert!(is_absolute("\\\\server\\share"));
+        assert!(!is_absolute("\\\\server"));
+        assert!(!is_absolute("db\\repeatable"));
+        assert!(!is_absolute("C:relative"));
+    }
+
+    #[test]
+    fn resolve_should_join_relative_paths_with_root() {
+        assert_eq!(
+            "D:\\checkout\\repo\\db\\repeatable",
+            resolve("D:\\checkout\\repo\\", "db/repeatable")
+        );
+        assert_eq!("D:\\checkout\\repo", resolve("D:\\checkout\\repo", "."));
+    }
+
+    #[test]
+    fn resolve_should_keep_absolute_paths() {
+        assert_eq!(
+            "\\\\server\\share\\db",
+            resolve("C:\\work\\repo", "\\\\server\\share\\db")
+        );
+        assert_eq!("E:\\db", resolve("C:\\work\\repo", "E:/db"));
+    }
+
+    #[test]
+    fn stored_path_should_round_trip_across_drive_letters() {
+        let stored = relativize("C:\\work\\repo", "C:\\work\\repo\\db\\repeatable").unwrap();
+        assert_eq!("db\\repeatable", stored);
+        assert_eq!(
+            "D:\\checkout\\repo\\db\\repeatable",
+            resolve("D:\\checkout\\repo", &stored)
+        );
+    }
+
+    #[test]
+    fn display_should_add_note_for_paths_outside_root() {
+        assert_eq!("db", display(Some("C:\\work\\repo"), "C:\\work\\repo\\db"));
+        assert_eq!(
+            "E:\\db (outside repository root)",
+            display(Some("C:\\work\\repo"), "E:\\db")
+        );
+        assert_eq!("E:\\db", display(None, "E:\\db"));
+    }
+}
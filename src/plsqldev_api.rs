@@ -5,6 +5,105 @@ use std::mem::MaybeUninit;
 use std::os::raw::c_char;
 use std::os::raw::c_int;
 
+// index and name (as in the plugin documentation) of the callbacks the plugin uses, see
+// set_callback_from_address
+const CALLBACKS: [(c_int, &str); 21] = [
+    (1, "SYS_Version"),
+    (3, "SYS_RootDir"),
+    (11, "IDE_Connected"),
+    (12, "IDE_GetConnectionInfo"),
+    (30, "IDE_GetText"),
+    (31, "IDE_GetSelectedText"),
+    (32, "IDE_GetCursorWord"),
+    (40, "SQL_Execute"),
+    (42, "SQL_Eof"),
+    (43, "SQL_Next"),
+    (44, "SQL_Field"),
+    (54, "SQL_SetVariable"),
+    (56, "SQL_ClearVariables"),
+    (69, "IDE_CreatePopupItem"),
+    (77, "IDE_FirstSelectedObject"),
+    (78, "IDE_NextSelectedObject"),
+    (79, "IDE_GetObjectSource"),
+    (150, "IDE_SetMenuName"),
+    (151, "IDE_SetMenuCheck"),
+    (173, "IDE_DebugLog"),
+    (219, "IDE_PlugInSetting"),
+];
+
+pub const CALLBACK_INDICES: [c_int; CALLBACKS.len()] = callback_indices();
+
+const fn callback_indices() -> [c_int; CALLBACKS.len()] {
+    let mut indices = [0; CALLBACKS.len()];
+    let mut i = 0;
+    while i < CALLBACKS.len() {
+        indices[i] = CALLBACKS[i].0;
+        i += 1;
+    }
+    indices
+}
+
+// The name of the callback with this index
+pub fn callback_name(index: c_int) -> &'static str {
+    CALLBACKS
+        .iter()
+        .find(|(i, _)| *i == index)
+        .map_or("unknown", |(_, name)| name)
+}
+
+// A set of callback indices; the indices of the IDE's callbacks are all below 256
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct CallbackSet {
+    bits: [u64; 4],
+}
+
+impl CallbackSet {
+    // word and bit of the index, None if out of range
+    fn position(index: c_int) -> Option<(usize, u64)> {
+        let index = usize::try_from(index).ok().filter(|i| *i < 256)?;
+        Some((index / 64, 1 << (index % 64)))
+    }
+
+    pub fn insert(&mut self, index: c_int) {
+        if let Some((word, bit)) = CallbackSet::position(index) {
+            self.bits[word] |= bit;
+        }
+    }
+
+    pub fn remove(&mut self, index: c_int) {
+        if let Some((word, bit)) = CallbackSet::position(index) {
+            self.bits[word] &= !bit;
+        }
+    }
+
+    pub fn contains(&self, index: c_int) -> bool {
+        CallbackSet::position(index).is_some_and(|(word, bit)| self.bits[word] & bit != 0)
+    }
+
+    // the indices in the set, ascending
+    pub fn indices(&self) -> Vec<c_int> {
+        (0..256).filter(|index| self.contains(*index)).collect()
+    }
+}
+
+// the SQL_* callbacks needed to query the data dictionary
+const SQL_CALLBACK_INDICES: [c_int; 5] = [40, 42, 44, 54, 56];
+
+const LAST_DDL_TIME_QUERY: &str = "select to_char(last_ddl_time, 'YYYY-MM-DD HH24:MI:SS') \
+     from all_objects \
+     where object_type = :object_type and owner = :owner and object_name = :object_name";
+
+// the comment of the table or view (without a column name) first, then those of its columns
+const COMMENTS_QUERY: &str = "select null, comments, 0 from all_tab_comments \
+     where owner = :owner and table_name = :object_name and comments is not null \
+     union all \
+     select c.column_name, c.comments, t.column_id from all_col_comments c \
+     join all_tab_columns t \
+     on t.owner = c.owner and t.table_name = c.table_name and t.column_name = c.column_name \
+     where c.owner = :owner and c.table_name = :object_name and c.comments is not null \
+     order by 3";
+
+#[derive(Clone)]
 pub struct SelectedObject {
     pub object_type: String,
     pub object_owner: String,
@@ -62,12 +161,19 @@ pub trait PlsqlDevApi {
     fn ide_connected(&self) -> bool {
         false
     }
+    // (username, database) of the current connection
+    fn ide_get_connection_info(&self) -> (String, String) {
+        ("".to_string(), "".to_string())
+    }
     fn ide_get_text(&self) -> String {
         "".to_string()
     }
     fn ide_get_selected_text(&self) -> String {
         "".to_string()
     }
+    fn ide_get_cursor_word(&self) -> String {
+        "".to_string()
+    }
     fn ide_create_popup_item(&self, _id: i32, _index: i32, _name: &str, _object_type: &str) {}
     fn ide_first_selected_object(&self) -> Option<SelectedObject> {
         None
@@ -75,6 +181,17 @@ pub trait PlsqlDevApi {
     fn ide_next_selected_object(&self) -> Option<SelectedObject> {
         None
     }
+    // all objects currently selected in the object browser, in selection order
+    fn ide_selected_objects(&self) -> Vec<SelectedObject> {
+        let mut selected_objects = vec![];
+        if let Some(selected_object) = self.ide_first_selected_object() {
+            selected_objects.push(selected_object);
+            while let Some(selected_object) = self.ide_next_selected_object() {
+                selected_objects.push(selected_object);
+            }
+        }
+        selected_objects
+    }
     fn ide_get_object_source(
         &self,
         _object_type: &str,
@@ -83,17 +200,79 @@ pub trait PlsqlDevApi {
     ) -> String {
         "".to_string()
     }
+    // (column name, comment) of a table or view, the comment of the table or view itself with
+    // an empty column name
+    fn ide_get_comments(&self, _object_owner: &str, _object_name: &str) -> Vec<(String, String)> {
+        vec![]
+    }
+    // Change the caption of one of the plugin's menu items; false if the IDE didn't
+    fn ide_set_menu_name(&self, _id: i32, _index: i32, _name: &str) -> bool {
+        false
+    }
+    // Show or hide the check mark of one of the plugin's menu items; false if the IDE didn't
+    fn ide_set_menu_check(&self, _id: i32, _index: i32, _checked: bool) -> bool {
+        false
+    }
+    // The first column of the first row of the query, if it could be run
+    fn ide_query_value(&self, _query: &str) -> Option<String> {
+        None
+    }
+    // SQL data types of the columns of the current export, in header order. The export
+    // callbacks only pass header names and cell strings, so this stays empty until the IDE
+    // offers a way to get them.
+    fn ide_get_export_column_types(&self) -> Vec<String> {
+        vec![]
+    }
+    // LAST_DDL_TIME of the object as YYYY-MM-DD HH24:MI:SS, if it can be determined
+    fn ide_get_object_last_ddl_time(
+        &self,
+        _object_type: &str,
+        _object_owner: &str,
+        _object_name: &str,
+    ) -> Option<String> {
+        None
+    }
+    // the object source as delivered, for callers that decode it themselves
+    fn ide_get_object_source_bytes(
+        &self,
+        object_type: &str,
+        object_owner: &str,
+        object_name: &str,
+    ) -> Vec<u8> {
+        self.ide_get_object_source(object_type, object_owner, object_name)
+            .into_bytes()
+    }
     fn ide_debug_log(&self, _message: &str) {}
-    fn ide_plugin_setting(&self, _id: i32, _setting: &str, _value: &str) {}
+    // returns false if the IDE rejected the setting
+    fn ide_plugin_setting(&self, _id: i32, _setting: &str, _value: &str) -> bool {
+        true
+    }
     unsafe fn set_callback_from_address(&mut self, _index: c_int, _address: *mut c_void) {}
+    // indices of used callbacks the IDE didn't register (or registered as null)
+    fn missing_callbacks(&self) -> Vec<c_int> {
+        vec![]
+    }
+    // indices of the callbacks registered with a non-null address, ascending
+    fn wired_callbacks(&self) -> Vec<c_int> {
+        CALLBACK_INDICES.to_vec()
+    }
 }
 
+#[derive(Clone)]
 pub struct NativePlsqlDevApi {
     sys_version: MaybeUninit<extern "C" fn() -> c_int>,
     sys_root_dir: MaybeUninit<extern "C" fn() -> *mut c_char>,
     ide_connected: MaybeUninit<extern "C" fn() -> bool>,
+    ide_get_connection_info: MaybeUninit<
+        extern "C" fn(
+            username: *mut *mut c_char,
+            password: *mut *mut c_char,
+            database: *mut *mut c_char,
+        ) -> c_void,
+    >,
     ide_get_text: MaybeUninit<extern "C" fn() -> *mut c_char>,
     ide_get_selected_text: MaybeUninit<extern "C" fn() -> *mut c_char>,
+    ide_get_cursor_word: MaybeUninit<extern "C" fn() -> *mut c_char>,
     ide_create_popup_item: MaybeUninit<
         extern "C" fn(
             id: c_int,
@@ -125,26 +304,102 @@ pub struct NativePlsqlDevApi {
             object_name: *const c_char,
         ) -> *mut c_char,
     >,
+    sql_execute: MaybeUninit<extern "C" fn(sql: *const c_char) -> c_int>,
+    sql_eof: MaybeUninit<extern "C" fn() -> bool>,
+    sql_next: MaybeUninit<extern "C" fn() -> c_int>,
+    sql_field: MaybeUninit<extern "C" fn(field: c_int) -> *mut c_char>,
+    sql_set_variable: MaybeUninit<extern "C" fn(name: *const c_char, value: *const c_char)>,
+    sql_clear_variables: MaybeUninit<extern "C" fn()>,
+    ide_set_menu_name:
+        MaybeUninit<extern "C" fn(id: c_int, index: c_int, name: *const c_char) -> bool>,
+    ide_set_menu_check: MaybeUninit<extern "C" fn(id: c_int, index: c_int, enabled: bool) -> bool>,
     ide_debug_log: MaybeUninit<extern "C" fn(*const c_char) -> c_void>,
     ide_plugin_setting: MaybeUninit<
         extern "C" fn(plugin_id: c_int, setting: *const c_char, value: *const c_char) -> bool,
     >,
+    // the callbacks registered with a non-null address
+    registered_callbacks: CallbackSet,
 }
 
 impl NativePlsqlDevApi {
+    // The first `columns` fields of (at most `max_rows`) rows of the query, or None if it failed
+    // or the IDE doesn't offer the SQL_* callbacks needed
+    fn query(
+        &self,
+        query: &str,
+        variables: &[(&str, &str)],
+        columns: usize,
+        max_rows: usize,
+    ) -> Option<Vec<Vec<String>>> {
+        // older IDE versions may not offer all of them
+        let needs_next = max_rows > 1;
+        if SQL_CALLBACK_INDICES
+            .iter()
+            .chain(needs_next.then_some(&43))
+            .any(|index| !self.registered_callbacks.contains(*index))
+        {
+            return None;
+        }
+        unsafe {
+            let sql_clear_variables = self.sql_clear_variables.assume_init();
+            let sql_set_variable = self.sql_set_variable.assume_init();
+            let sql_execute = self.sql_execute.assume_init();
+            let sql_eof = self.sql_eof.assume_init();
+            let sql_field = self.sql_field.assume_init();
+
+            sql_clear_variables();
+            for (name, value) in variables {
+                let c_name = CString::new(*name).unwrap();
+                let c_value = CString::new(*value).ok()?;
+                sql_set_variable(c_name.as_ptr(), c_value.as_ptr());
+            }
+            let c_query = CString::new(query).unwrap();
+            if sql_execute(c_query.as_ptr()) != 0 {
+                return None;
+            }
+            let mut rows = vec![];
+            while !sql_eof() && rows.len() < max_rows {
+                rows.push(
+                    (0..columns)
+                        .map(|field| {
+                            CStr::from_ptr(sql_field(field as c_int))
+                                .to_string_lossy()
+                                .to_string()
+                        })
+                        .collect(),
+                );
+                if needs_next {
+                    self.sql_next.assume_init()();
+                }
+            }
+            Some(rows)
+        }
+    }
+
     pub fn new() -> NativePlsqlDevApi {
         NativePlsqlDevApi {
             sys_version: MaybeUninit::uninit(),
             sys_root_dir: MaybeUninit::uninit(),
             ide_connected: MaybeUninit::uninit(),
+            ide_get_connection_info: MaybeUninit::uninit(),
             ide_get_text: MaybeUninit::uninit(),
             ide_get_selected_text: MaybeUninit::uninit(),
+            ide_get_cursor_word: MaybeUninit::uninit(),
             ide_create_popup_item: MaybeUninit::uninit(),
             ide_first_selected_object: MaybeUninit::uninit(),
             ide_next_selected_object: MaybeUninit::uninit(),
             ide_get_object_source: MaybeUninit::uninit(),
+            sql_execute: MaybeUninit::uninit(),
+            sql_eof: MaybeUninit::uninit(),
+            sql_next: MaybeUninit::uninit(),
+            sql_field: MaybeUninit::uninit(),
+            sql_set_variable: MaybeUninit::uninit(),
+            sql_clear_variables: MaybeUninit::uninit(),
+            ide_set_menu_name: MaybeUninit::uninit(),
+            ide_set_menu_check: MaybeUninit::uninit(),
             ide_debug_log: MaybeUninit::uninit(),
             ide_plugin_setting: MaybeUninit::uninit(),
+            registered_callbacks: CallbackSet::default(),
         }
     }
 }
@@ -167,6 +422,31 @@ impl PlsqlDevApi for NativePlsqlDevApi {
         ide_connected()
     }
 
+    fn ide_get_connection_info(&self) -> (String, String) {
+        unsafe {
+            let ide_get_connection_info = self.ide_get_connection_info.assume_init();
+
+            let mut username = MaybeUninit::<*mut c_char>::uninit();
+            let mut password = MaybeUninit::<*mut c_char>::uninit();
+            let mut database = MaybeUninit::<*mut c_char>::uninit();
+
+            ide_get_connection_info(
+                username.as_mut_ptr(),
+                password.as_mut_ptr(),
+                database.as_mut_ptr(),
+            );
+            // the password is deliberately not handed out
+            (
+                CStr::from_ptr(username.assume_init())
+                    .to_string_lossy()
+                    .to_string(),
+                CStr::from_ptr(database.assume_init())
+                    .to_string_lossy()
+                    .to_string(),
+            )
+        }
+    }
+
     fn ide_get_text(&self) -> String {
         unsafe {
             let ide_get_text = self.ide_get_text.assume_init();
@@ -183,6 +463,15 @@ impl PlsqlDevApi for NativePlsqlDevApi {
         }
     }
 
+    fn ide_get_cursor_word(&self) -> String {
+        unsafe {
+            let ide_get_cursor_word = self.ide_get_cursor_word.assume_init();
+            CStr::from_ptr(ide_get_cursor_word())
+                .to_string_lossy()
+                .to_string()
+        }
+    }
+
     fn ide_create_popup_item(&self, id: i32, index: i32, name: &str, object_type: &str) {
         let ide_create_popup_item = unsafe { self.ide_create_popup_item.assume_init() };
         let c_name: CString = CString::new(name).unwrap();
@@ -255,6 +544,16 @@ impl PlsqlDevApi for NativePlsqlDevApi {
         object_owner: &str,
         object_name: &str,
     ) -> String {
+        let bytes = self.ide_get_object_source_bytes(object_type, object_owner, object_name);
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    fn ide_get_object_source_bytes(
+        &self,
+        object_type: &str,
+        object_owner: &str,
+        object_name: &str,
+    ) -> Vec<u8> {
         unsafe {
             let ide_get_object_source = self.ide_get_object_source.assume_init();
 
@@ -268,8 +567,63 @@ impl PlsqlDevApi for NativePlsqlDevApi {
                 c_object_name.as_ptr(),
             );
 
-            CStr::from_ptr(object_source).to_string_lossy().to_string()
+            CStr::from_ptr(object_source).to_bytes().to_vec()
+        }
+    }
+
+    fn ide_get_object_last_ddl_time(
+        &self,
+        object_type: &str,
+        object_owner: &str,
+        object_name: &str,
+    ) -> Option<String> {
+        let variables = [
+            ("object_type", object_type),
+            ("owner", object_owner),
+            ("object_name", object_name),
+        ];
+        let last_ddl_time = self
+            .query(LAST_DDL_TIME_QUERY, &variables, 1, 1)?
+            .pop()?
+            .pop()?;
+        match last_ddl_time.is_empty() {
+            true => None,
+            false => Some(last_ddl_time),
+        }
+    }
+
+    fn ide_query_value(&self, query: &str) -> Option<String> {
+        self.query(query, &[], 1, 1)?.pop()?.pop()
+    }
+
+    fn ide_get_comments(&self, object_owner: &str, object_name: &str) -> Vec<(String, String)> {
+        let variables = [("owner", object_owner), ("object_name", object_name)];
+        self.query(COMMENTS_QUERY, &variables, 2, usize::MAX)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|mut row| {
+                let comment = row.pop().unwrap_or_default();
+                (row.pop().unwrap_or_default(), comment)
+            })
+            .collect()
+    }
+
+    fn ide_set_menu_name(&self, id: i32, index: i32, name: &str) -> bool {
+        // older IDE versions don't offer it
+        if !self.registered_callbacks.contains(150) {
+            return false;
+        }
+        let ide_set_menu_name = unsafe { self.ide_set_menu_name.assume_init() };
+        let c_name = CString::new(name).unwrap();
+        ide_set_menu_name(id, index, c_name.as_ptr())
+    }
+
+    fn ide_set_menu_check(&self, id: i32, index: i32, checked: bool) -> bool {
+        if !self.registered_callbacks.contains(151) {
+            return false;
         }
+        let ide_set_menu_check = unsafe { self.ide_set_menu_check.assume_init() };
+        ide_set_menu_check(id, index, checked)
     }
 
     fn ide_debug_log(&self, message: &str) {
@@ -278,13 +632,14 @@ impl PlsqlDevApi for NativePlsqlDevApi {
         ide_debug_log(c_message.as_ptr());
     }
 
-    fn ide_plugin_setting(&self, id: i32, setting: &str, value: &str) {
+    fn ide_plugin_setting(&self, id: i32, setting: &str, value: &str) -> bool {
         let ide_plugin_setting = unsafe { self.ide_plugin_setting.assume_init() };
         let c_setting = CString::new(setting).unwrap();
         let c_value = CString::new(value).unwrap();
-        ide_plugin_setting(id, c_setting.as_ptr(), c_value.as_ptr());
+        ide_plugin_setting(id, c_setting.as_ptr(), c_value.as_ptr())
     }
 
+    #[allow(clippy::missing_transmute_annotations)]
     unsafe fn set_callback_from_address(&mut self, index: c_int, address: *mut c_void) {
         match index {
             1 => self.sys_version.as_mut_ptr().write(mem::transmute(address)),
@@ -296,6 +651,10 @@ impl PlsqlDevApi for NativePlsqlDevApi {
                 .ide_connected
                 .as_mut_ptr()
                 .write(mem::transmute(address)),
+            12 => self
+                .ide_get_connection_info
+                .as_mut_ptr()
+                .write(mem::transmute(address)),
             30 => self
                 .ide_get_text
                 .as_mut_ptr()
@@ -304,6 +663,22 @@ impl PlsqlDevApi for NativePlsqlDevApi {
                 .ide_get_selected_text
                 .as_mut_ptr()
                 .write(mem::transmute(address)),
+            32 => self
+                .ide_get_cursor_word
+                .as_mut_ptr()
+                .write(mem::transmute(address)),
+            40 => self.sql_execute.as_mut_ptr().write(mem::transmute(address)),
+            42 => self.sql_eof.as_mut_ptr().write(mem::transmute(address)),
+            43 => self.sql_next.as_mut_ptr().write(mem::transmute(address)),
+            44 => self.sql_field.as_mut_ptr().write(mem::transmute(address)),
+            54 => self
+                .sql_set_variable
+                .as_mut_ptr()
+                .write(mem::transmute(address)),
+            56 => self
+                .sql_clear_variables
+                .as_mut_ptr()
+                .write(mem::transmute(address)),
             69 => self
                 .ide_create_popup_item
                 .as_mut_ptr()
@@ -320,6 +695,14 @@ impl PlsqlDevApi for NativePlsqlDevApi {
                 .ide_get_object_source
                 .as_mut_ptr()
                 .write(mem::transmute(address)),
+            150 => self
+                .ide_set_menu_name
+                .as_mut_ptr()
+                .write(mem::transmute(address)),
+            151 => self
+                .ide_set_menu_check
+                .as_mut_ptr()
+                .write(mem::transmute(address)),
             173 => self
                 .ide_debug_log
                 .as_mut_ptr()
@@ -328,7 +711,275 @@ impl PlsqlDevApi for NativePlsqlDevApi {
                 .ide_plugin_setting
                 .as_mut_ptr()
                 .write(mem::transmute(address)),
-            _ => (),
+            _ => return,
+        };
+        match address.is_null() {
+            true => self.registered_callbacks.remove(index),
+            false => self.registered_callbacks.insert(index),
+        }
+    }
+
+    fn missing_callbacks(&self) -> Vec<c_int> {
+        CALLBACK_INDICES
+            .iter()
+            .filter(|index| !self.registered_callbacks.contains(**index))
+            .copied()
+            .collect()
+    }
+
+    fn wired_callbacks(&self) -> Vec<c_int> {
+        self.registered_callbacks.indices()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::ffi::c_void;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::config::{Config, TimestampSource};
+    use crate::flyway::migration_timestamp;
+    use crate::plsqldev_api::*;
+
+    // A database behind the fake SQL_* callbacks, one per test thread: the rows of a query are
+    // only returned if it was run with the expected variables
+    #[derive(Default)]
+    struct FakeDatabase {
+        variables: Vec<(String, String)>,
+        expected_variables: Vec<(String, String)>,
+        rows: Vec<Vec<CString>>,
+        row: usize,
+    }
+
+    thread_local! {
+        static FAKE_DATABASE: RefCell<FakeDatabase> = RefCell::new(FakeDatabase::default());
+    }
+
+    extern "C" fn fake_sql_execute(_sql: *const c_char) -> c_int {
+        FAKE_DATABASE.with(|db| {
+            let mut db = db.borrow_mut();
+            db.row = match db.variables == db.expected_variables {
+                true => 0,
+                false => db.rows.len(),
+            };
+        });
+        0
+    }
+
+    extern "C" fn fake_sql_eof() -> bool {
+        FAKE_DATABASE.with(|db| db.borrow().row >= db.borrow().rows.len())
+    }
+
+    extern "C" fn fake_sql_next() -> c_int {
+        FAKE_DATABASE.with(|db| db.borrow_mut().row += 1);
+        0
+    }
+
+    extern "C" fn fake_sql_field(field: c_int) -> *mut c_char {
+        FAKE_DATABASE.with(|db| {
+            let db = db.borrow();
+            db.rows[db.row][field as usize].as_ptr() as *mut c_char
+        })
+    }
+
+    extern "C" fn fake_sql_set_variable(name: *const c_char, value: *const c_char) {
+        let (name, value) = unsafe {
+            (
+                CStr::from_ptr(name).to_string_lossy().into_owned(),
+                CStr::from_ptr(value).to_string_lossy().into_owned(),
+            )
+        };
+        FAKE_DATABASE.with(|db| db.borrow_mut().variables.push((name, value)));
+    }
+
+    extern "C" fn fake_sql_clear_variables() {
+        FAKE_DATABASE.with(|db| db.borrow_mut().variables.clear());
+    }
+
+    // An API wired to the fake database, which returns the rows for the variables
+    fn api_with_fake_database(
+        expected_variables: &[(&str, &str)],
+        rows: &[&[&str]],
+    ) -> NativePlsqlDevApi {
+        FAKE_DATABASE.with(|db| {
+            *db.borrow_mut() = FakeDatabase {
+                expected_variables: expected_variables
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+                rows: rows
+                    .iter()
+                    .map(|row| row.iter().map(|f| CString::new(*f).unwrap()).collect())
+                    .collect(),
+                ..FakeDatabase::default()
+            }
+        });
+        let mut api = NativePlsqlDevApi::new();
+        unsafe {
+            api.set_callback_from_address(40, fake_sql_execute as *mut c_void);
+            api.set_callback_from_address(42, fake_sql_eof as *mut c_void);
+            api.set_callback_from_address(43, fake_sql_next as *mut c_void);
+            api.set_callback_from_address(44, fake_sql_field as *mut c_void);
+            api.set_callback_from_address(54, fake_sql_set_variable as *mut c_void);
+            api.set_callback_from_address(56, fake_sql_clear_variables as *mut c_void);
+        }
+        api
+    }
+
+    #[test]
+    fn ide_query_value_should_read_the_first_field_of_the_first_row() {
+        let api = api_with_fake_database(
+            &[],
+            &[
+                &["2021 07 18 10 15 00 123", "ignored"],
+                &["2022 01 01 00 00 00 000", ""],
+            ],
+        );
+        assert_eq!(
+            Some("2021 07 18 10 15 00 123".to_string()),
+            api.ide_query_value("select 1 from dual")
+        );
+
+        let config = Config {
+            timestamp_source: TimestampSource::DatabaseSysdate,
+            ..Config::default()
         };
+        assert_eq!(
+            Utc.with_ymd_and_hms(2021, 7, 18, 10, 15, 0).unwrap()
+                + chrono::Duration::milliseconds(123),
+            migration_timestamp(&api, &config)
+        );
+
+        // the cursor is empty
+        let api = api_with_fake_database(&[("owner", "APP")], &[&["2021 07 18 10 15 00 123"]]);
+        assert_eq!(None, api.ide_query_value("select 1 from dual"));
+    }
+
+    #[test]
+    fn ide_get_comments_should_bind_owner_and_name_and_read_all_rows() {
+        let api = api_with_fake_database(
+            &[("owner", "APP"), ("object_name", "V_ADDRESS")],
+            &[
+                &["", "All addresses", "0"],
+                &["ID", "Primary key", "1"],
+                &["city", "The city's name", "2"],
+            ],
+        );
+        assert_eq!(
+            vec![
+                ("".to_string(), "All addresses".to_string()),
+                ("ID".to_string(), "Primary key".to_string()),
+                ("city".to_string(), "The city's name".to_string()),
+            ],
+            api.ide_get_comments("APP", "V_ADDRESS")
+        );
+        assert!(api.ide_get_comments("APP", "V_OTHER").is_empty());
+    }
+
+    extern "C" fn dummy_callback() -> c_int {
+        42
+    }
+
+    #[test]
+    fn set_callback_from_address_should_track_wired_callbacks() {
+        let mut api = NativePlsqlDevApi::new();
+        assert!(api.wired_callbacks().is_empty());
+        unsafe {
+            api.set_callback_from_address(1, dummy_callback as *mut c_void);
+            api.set_callback_from_address(31, dummy_callback as *mut c_void);
+            // not used by the plugin
+            api.set_callback_from_address(2, dummy_callback as *mut c_void);
+            // registered as null
+            api.set_callback_from_address(30, std::ptr::null_mut());
+        }
+        assert_eq!(vec![1, 31], api.wired_callbacks());
+        assert_eq!(CALLBACK_INDICES.len() - 2, api.missing_callbacks().len());
+        assert_eq!(42, api.sys_version());
+
+        unsafe { api.set_callback_from_address(31, std::ptr::null_mut()) };
+        assert_eq!(vec![1], api.wired_callbacks());
+    }
+
+    #[test]
+    fn set_callback_from_address_should_wire_sql_variables_at_their_documented_indices() {
+        let mut api = NativePlsqlDevApi::new();
+        unsafe {
+            // SQL_UsePlugInSession and SQL_GetDBMSGetOutput, not used by the plugin
+            api.set_callback_from_address(51, dummy_callback as *mut c_void);
+            api.set_callback_from_address(53, dummy_callback as *mut c_void);
+            assert!(api.wired_callbacks().is_empty());
+            // SQL_SetVariable and SQL_ClearVariables
+            api.set_callback_from_address(54, dummy_callback as *mut c_void);
+            api.set_callback_from_address(56, dummy_callback as *mut c_void);
+        }
+        assert_eq!(vec![54, 56], api.wired_callbacks());
+    }
+
+    #[test]
+    fn callback_name_should_follow_the_index_table() {
+        assert_eq!("SQL_SetVariable", callback_name(54));
+        assert_eq!("SQL_ClearVariables", callback_name(56));
+        assert_eq!("unknown", callback_name(51));
+        assert!(CALLBACK_INDICES
+            .iter()
+            .all(|index| callback_name(*index) != "unknown"));
+    }
+
+    // The object browser, with the two objects selected
+    struct TwoSelectedObjects {
+        next: Cell<usize>,
+    }
+
+    impl TwoSelectedObjects {
+        fn object(&self, index: usize) -> Option<SelectedObject> {
+            self.next.set(index + 1);
+            match index {
+                0 => Some(SelectedObject::new("TYPE", "APP", "T_ADDRESS", "")),
+                1 => Some(SelectedObject::new("PACKAGE", "APP", "PKG_ADDRESS", "")),
+                _ => None,
+            }
+        }
+    }
+
+    impl PlsqlDevApi for TwoSelectedObjects {
+        fn ide_first_selected_object(&self) -> Option<SelectedObject> {
+            self.object(0)
+        }
+        fn ide_next_selected_object(&self) -> Option<SelectedObject> {
+            self.object(self.next.get())
+        }
+    }
+
+    #[test]
+    fn ide_selected_objects_should_collect_objects_in_selection_order() {
+        let api = TwoSelectedObjects { next: Cell::new(0) };
+        let names: Vec<String> = api
+            .ide_selected_objects()
+            .into_iter()
+            .map(|o| o.to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "(object_type: TYPE, object_owner: APP, object_name: T_ADDRESS, sub_object: )",
+                "(object_type: PACKAGE, object_owner: APP, object_name: PKG_ADDRESS, sub_object: )",
+            ],
+            names
+        );
+        // a second call starts over
+        assert_eq!(2, api.ide_selected_objects().len());
+    }
+
+    #[test]
+    fn callback_set_should_ignore_out_of_range_indices() {
+        let mut set = CallbackSet::default();
+        for index in [-1, 0, 63, 64, 219, 255, 256] {
+            set.insert(index);
+        }
+        assert_eq!(vec![0, 63, 64, 219, 255], set.indices());
+        assert!(!set.contains(256) && !set.contains(-1));
+        set.remove(64);
+        assert!(!set.contains(64) && set.contains(63));
     }
 }
@@ -1,32 +1,45 @@
 use std::env;
-use std::ffi::{CStr, CString};
-use std::fs::File;
+use std::ffi::CString;
 use std::os::raw::c_char;
 use std::os::raw::c_int;
 use std::os::raw::c_void;
-use std::path::PathBuf;
-use std::sync::{RwLock, RwLockReadGuard};
-
-use log::LevelFilter;
-use simplelog::Config as LogConfig;
-use simplelog::WriteLogger;
-use winapi::um::winuser::MB_ICONINFORMATION;
-use winapi::um::winuser::MB_OK;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::SystemTime;
 
+use crate::callback_skeletons::generate_callback_skeletons;
+use crate::checksums::verify_checksums;
 use crate::config::Config;
+use crate::cursor_object::export_object_under_cursor;
+use crate::dbms_output::format_dbms_output_as_table;
+use crate::diagnostics::show_diagnostics;
+use crate::documentation::create_documentation;
 use crate::flyway::create_repeatable_migration;
 use crate::flyway::create_versioned_migration;
+use crate::flyway_conf::config_with_flyway_settings;
+use crate::flyway_help::show_naming_help;
+use crate::folder_compare::compare_repeatable_folders;
+use crate::init_tasks::{InitTask, InitTasks};
+use crate::logging::{
+    clear_log, init_logging, log_path, read_last_session, set_log_levels, target, LogLevels,
+};
+use crate::menu::{menu_captions, menu_item, validate_accelerators, Menu, MenuKey, MENU_ITEMS};
 use crate::plsqldev_api::{NativePlsqlDevApi, PlsqlDevApi};
-use crate::windows_api::show_message_box;
+use crate::project_config::{config_for_folder, save_project_setting};
+use crate::review_snippet::copy_review_snippets;
+use crate::schema_history::copy_schema_history_insert;
+use crate::selection_migration::repeatable_migration_from_selection;
+use crate::self_test::run_self_test;
+use crate::temp_files::{sweep_orphans, ORPHAN_MAX_AGE, TEMP_FILES};
+use crate::ui::{clipboard, dialogs, MessageKind};
+use crate::window_history::create_migrations_from_window_history;
+use crate::zip_export::export_selection_as_zip;
+use toml::Value;
 
 const PLUGIN_NAME: &[u8] = b"Xanthidae\0";
 const TAB_NAME: &[u8] = b"TAB=Xanthidae\0";
 const FLYWAY_GROUP_NAME: &[u8] = b"GROUP=Flyway\0";
-const ITEM_NAME_VERSIONED_MIGRATION: &[u8] = b"ITEM=Versioned migration\0";
-const ITEM_NAME_REPEATABLE_MIGRATION: &[u8] = b"ITEM=Repeatable migration\0";
-const ITEM_NAME_REPEATABLE_AND_VERSIONED_MIGRATION: &[u8] =
-    b"ITEM=Repeatable + versioned migration\0";
-const ITEM_NAME_VERSION_INFO: &[u8] = b"ITEM=Plugin version\0";
 const EMPTY: &[u8] = b"\0";
 
 const FUNCTION_OBJECT_TYPE: &str = "FUNCTION";
@@ -34,7 +47,9 @@ const PROCEDURE_OBJECT_TYPE: &str = "PROCEDURE";
 const PACKAGE_OBJECT_TYPE: &str = "PACKAGE";
 const TYPE_OBJECT_TYPE: &str = "TYPE";
 const VIEW_OBJECT_TYPE: &str = "VIEW";
+const MATERIALIZED_VIEW_OBJECT_TYPE: &str = "MATERIALIZED VIEW";
 const TRIGGER_OBJECT_TYPE: &str = "TRIGGER";
+const TABLE_OBJECT_TYPE: &str = "TABLE";
 
 /*const FUNCTIONS_OBJECT_TYPE: &'static [u8] = b"FUNCTION+\0";
 const PROCEDURES_OBJECT_TYPE: &'static [u8] = b"PROCEDURE+\0";
@@ -47,30 +62,45 @@ const SQL_WINDOW: &str = "SQLWINDOW";
 const TEST_WINDOW: &str = "TESTWINDOW";
 const COMMAND_WINDOW: &str = "COMMANDWINDOW";
 
-const VERSIONED_MIGRATION_INDEX: c_int = 11;
-const REPEATABLE_MIGRATION_INDEX: c_int = 12;
-const REPEATABLE_AND_VERSIONED_MIGRATION_INDEX: c_int = 13;
-const VERSION_INFO_INDEX: c_int = 14;
-
 const POPUP_ITEM_NAME_VERSIONED_MIGRATION: &str = "Versioned migration...";
+const POPUP_ITEM_NAME_VERSIONED_AND_UNDO_MIGRATION: &str = "Versioned + undo migration...";
 const POPUP_ITEM_NAME_REPEATABLE_MIGRATION: &str = "Repeatable migration...";
 const POPUP_ITEM_NAME_REPEATABLE_AND_VERSIONED_MIGRATION: &str =
     "Repeatable + versioned migration...";
+const POPUP_ITEM_NAME_EXPORT_DOCUMENTATION: &str = "Export documentation...";
+const POPUP_ITEM_NAME_EXPORT_OBJECT_UNDER_CURSOR: &str = "Export object under cursor...";
+const POPUP_ITEM_NAME_VERIFY_CHECKSUMS: &str = "Verify checksums...";
+const POPUP_ITEM_NAME_REVIEW_SNIPPET: &str = "Copy as review snippet";
+const POPUP_ITEM_NAME_SELECTION_MIGRATION: &str = "Repeatable migration from selection";
+const POPUP_ITEM_NAME_DBMS_OUTPUT_TABLE: &str = "Format DBMS_OUTPUT as table...";
 
-const VERSION_INFO_CAPTION: &[u8] = b"Version info\0";
+const VERSION_INFO_CAPTION: &str = "Version info";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const BUILD_TIMESTAMP: &str = env!("VERGEN_BUILD_TIMESTAMP");
 const VERGEN_GIT_SHA: &str = env!("VERGEN_GIT_SHA");
 const HOMEPAGE: &str = "https://github.com/xanthidae/xanthidae";
 
-static mut PLUGIN_ID: c_int = 0;
+static PLUGIN_ID: AtomicI32 = AtomicI32::new(0);
+// set if the IDE rejected the configured CHARMODE, reported by the self-test
+pub static CHARMODE_REJECTED: AtomicBool = AtomicBool::new(false);
 
+// PL/SQL Developer may call the exported functions from more than one thread, and re-enters the
+// plugin while one of our dialogs is shown. So none of the locks below is held across a call
+// into the IDE or a dialog: exported functions take a snapshot (`api()`, `config()`) and work on
+// that. The only nested acquisition is CALLBACKS -> API in RegisterCallback.
+// MENU is only replaced while the IDE creates the menu, and not held while a handler runs.
 lazy_static! {
     // Trait object style global wrapper around PL/SQL-Developer API
     // We need to specify this Send type bound, otherwise the code would not compile
     // See https://stackoverflow.com/questions/59679968/static-array-of-trait-objects
-    pub static ref API: RwLock<Box<dyn PlsqlDevApi + Send + Sync>> = RwLock::new(Box::new(NativePlsqlDevApi::new()));
+    pub static ref API: RwLock<Arc<dyn PlsqlDevApi + Send + Sync>> = RwLock::new(Arc::new(NativePlsqlDevApi::new()));
+    // the callbacks registered so far; every registration publishes a fresh copy as API
+    static ref CALLBACKS: Mutex<NativePlsqlDevApi> = Mutex::new(NativePlsqlDevApi::new());
     pub static ref CONFIG: RwLock<Config> = RwLock::new(Config::default());
+    // the menu as created by CreateMenuItem
+    static ref MENU: RwLock<Menu> = RwLock::new(Menu::new(&[]));
+    // the work AfterStart hands to background threads
+    pub static ref INIT_TASKS: Arc<InitTasks> = InitTasks::new();
     static ref VERSION_MESSAGE: CString = CString::new(format!(
         "This is version {} of Xanthidae, a plugin written in Rust.\n\
         \n\
@@ -82,12 +112,44 @@ lazy_static! {
     .unwrap();
 }
 
+#[cfg(test)]
+lazy_static! {
+    // tests replacing API or CONFIG must hold this lock, as they run in parallel
+    pub static ref API_TEST_LOCK: Mutex<()> = Mutex::new(());
+}
+
+// The current PL/SQL Developer API; the read lock is only held while cloning the handle
+pub fn api() -> Arc<dyn PlsqlDevApi + Send + Sync> {
+    Arc::clone(&API.read().unwrap())
+}
+
+// A copy of the current configuration
+pub fn config() -> Config {
+    read_config().clone()
+}
+
+// Lock the configuration for reading. A panic while it was written (which must not take down the
+// IDE) poisons the lock; every setting is still valid then, so it's used as it is.
+pub fn read_config() -> RwLockReadGuard<'static, Config> {
+    CONFIG.read().unwrap_or_else(|poisoned| {
+        warn!(target: target::CONFIG, "Config lock was poisoned, using the config anyway");
+        poisoned.into_inner()
+    })
+}
+
+// Lock the configuration for writing, recovering a poisoned lock like read_config
+pub fn write_config() -> RwLockWriteGuard<'static, Config> {
+    CONFIG.write().unwrap_or_else(|poisoned| {
+        warn!(target: target::CONFIG, "Config lock was poisoned, using the config anyway");
+        CONFIG.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
 #[allow(non_snake_case)]
 #[no_mangle]
 pub extern "C" fn IdentifyPlugIn(ID: c_int) -> *mut c_char {
-    unsafe {
-        PLUGIN_ID = ID;
-    }
+    PLUGIN_ID.store(ID, Ordering::SeqCst);
     PLUGIN_NAME.as_ptr() as *mut c_char
 }
 
@@ -95,15 +157,20 @@ pub extern "C" fn IdentifyPlugIn(ID: c_int) -> *mut c_char {
 #[no_mangle]
 pub extern "C" fn CreateMenuItem(Index: c_int) -> *mut c_char {
     let result = match Index {
-        1 => TAB_NAME.as_ptr(),
-        10 => FLYWAY_GROUP_NAME.as_ptr(),
-        VERSIONED_MIGRATION_INDEX => ITEM_NAME_VERSIONED_MIGRATION.as_ptr(),
-        REPEATABLE_MIGRATION_INDEX => ITEM_NAME_REPEATABLE_MIGRATION.as_ptr(),
-        REPEATABLE_AND_VERSIONED_MIGRATION_INDEX => {
-            ITEM_NAME_REPEATABLE_AND_VERSIONED_MIGRATION.as_ptr()
+        1 => {
+            // the IDE asks for the items in index order, starting over with the tab
+            *MENU.write().unwrap() = create_menu(&config());
+            TAB_NAME.as_ptr()
         }
-        VERSION_INFO_INDEX => ITEM_NAME_VERSION_INFO.as_ptr(),
-        _ => EMPTY.as_ptr(),
+        10 => FLYWAY_GROUP_NAME.as_ptr(),
+        index => match MENU.read().unwrap().item_at(index) {
+            // hidden unless enabled, it's meant for support requests
+            Some(item) if item.key == MenuKey::SelfTest && !config().enable_self_test => {
+                EMPTY.as_ptr()
+            }
+            Some(item) => item.definition.as_ptr(),
+            None => EMPTY.as_ptr(),
+        },
     };
     result as *mut c_char
 }
@@ -111,16 +178,113 @@ pub extern "C" fn CreateMenuItem(Index: c_int) -> *mut c_char {
 #[allow(non_snake_case)]
 #[no_mangle]
 pub extern "C" fn OnMenuClick(Index: c_int) {
-    let api = API.read().unwrap();
-    let config = CONFIG.read().unwrap();
-    match Index {
-        VERSIONED_MIGRATION_INDEX => create_versioned_migration(&api, &config),
-        REPEATABLE_MIGRATION_INDEX => create_repeatable_migration(&api, &config, false),
-        REPEATABLE_AND_VERSIONED_MIGRATION_INDEX => {
-            create_repeatable_migration(&api, &config, true)
+    on_menu_click(Index);
+}
+
+fn create_menu(config: &Config) -> Menu {
+    if let Err(e) = validate_accelerators(&menu_captions()) {
+        warn!(target: target::MENU, "{}", e);
+    }
+    Menu::new(&config.menu_order)
+}
+
+// The menu index of the item, as currently ordered
+fn menu_index(key: MenuKey) -> c_int {
+    MENU.read().unwrap().index_of(key)
+}
+
+fn on_menu_click(index: c_int) {
+    let key = match MENU.read().unwrap().item_at(index) {
+        Some(item) => item.key,
+        None => return,
+    };
+    let api = api();
+    let config = config_with_flyway_settings(&config());
+    match key {
+        MenuKey::VersionedMigration => create_versioned_migration(&*api, &config, false),
+        MenuKey::VersionedAndUndoMigration => create_versioned_migration(&*api, &config, true),
+        MenuKey::RepeatableMigration => create_repeatable_migration(&*api, &config, false),
+        MenuKey::RepeatableAndVersionedMigration => {
+            create_repeatable_migration(&*api, &config, true)
+        }
+        MenuKey::FlywayHelp => show_naming_help(),
+        MenuKey::PluginVersion => show_plugin_version(),
+        MenuKey::ClearLog => clear_plugin_log(),
+        MenuKey::CopySessionLog => copy_session_log(&config),
+        MenuKey::Diagnostics => show_diagnostics(&*api, &config, &INIT_TASKS.states()),
+        MenuKey::ExportDocumentation => create_documentation(&*api, &config),
+        MenuKey::ExportObjectUnderCursor => export_object_under_cursor(&*api, &config),
+        MenuKey::SelectionMigration => repeatable_migration_from_selection(&*api, &config),
+        MenuKey::DbmsOutputTable => format_dbms_output_as_table(&config),
+        MenuKey::ExportAsZip => export_selection_as_zip(&*api, &config),
+        MenuKey::CallbackSkeletons => generate_callback_skeletons(&*api, &config),
+        MenuKey::VerifyChecksums => verify_checksums(&*api, &config),
+        MenuKey::CompareFolders => compare_repeatable_folders(&config),
+        MenuKey::SchemaHistoryInsert => copy_schema_history_insert(&config),
+        MenuKey::SelfTest => run_self_test(&*api, &config),
+        MenuKey::ReviewSnippet => copy_review_snippets(&*api, &config),
+        MenuKey::WindowHistory => create_migrations_from_window_history(&*api, &config),
+        MenuKey::MillisecondPrecision => {
+            toggle_millisecond_precision(&*api, PLUGIN_ID.load(Ordering::SeqCst))
+        }
+    }
+}
+
+// The state of a checkable menu item, i.e. of the setting it toggles
+fn is_checked(key: MenuKey, config: &Config) -> bool {
+    match key {
+        MenuKey::MillisecondPrecision => config.use_millisecond_precision,
+        _ => false,
+    }
+}
+
+// Show the state with a check mark, or in the caption if the IDE can't show check marks
+fn show_menu_check(api: &dyn PlsqlDevApi, plugin_id: c_int, key: MenuKey, checked: bool) {
+    let item = menu_item(key);
+    let index = menu_index(key);
+    let caption = match api.ide_set_menu_check(plugin_id, index, checked) {
+        true => item.caption().to_string(),
+        false => item.checked_caption(checked),
+    };
+    if !api.ide_set_menu_name(plugin_id, index, &caption) {
+        debug!(target: target::MENU, "The IDE did not update the caption of {}", item.name);
+    }
+}
+
+fn show_menu_checks(api: &dyn PlsqlDevApi, plugin_id: c_int, config: &Config) {
+    for item in MENU_ITEMS.iter().filter(|item| item.checkable) {
+        show_menu_check(api, plugin_id, item.key, is_checked(item.key, config));
+    }
+}
+
+// Stored in the .xanthidae.toml of repo_root, so it survives restarts; without a repo_root it's
+// only switched for this session
+fn toggle_millisecond_precision(api: &dyn PlsqlDevApi, plugin_id: c_int) {
+    let (checked, repo_root) = {
+        let mut config = write_config();
+        config.use_millisecond_precision = !config.use_millisecond_precision;
+        (config.use_millisecond_precision, config.repo_root.clone())
+    };
+    info!(target: target::MENU, "Millisecond precision in versions switched to {}", checked);
+    show_menu_check(api, plugin_id, MenuKey::MillisecondPrecision, checked);
+    if let Some(repo_root) = repo_root {
+        let value = Value::Boolean(checked);
+        match save_project_setting(Path::new(&repo_root), "use_millisecond_precision", &value) {
+            Ok(path) => {
+                debug!(target: target::CONFIG, "Stored millisecond precision in {:?}", path)
+            }
+            Err(e) => warn!(target: target::CONFIG, "Could not store millisecond precision: {}", e),
         }
-        VERSION_INFO_INDEX => show_plugin_version(),
-        _ => (),
+    }
+}
+
+// Read the settings switched from the menu back from the project config of repo_root
+fn load_persisted_settings() {
+    let config = config();
+    if let Some(repo_root) = &config.repo_root {
+        // the file is read without holding the lock
+        let persisted = config_for_folder(&config, Path::new(repo_root));
+        write_config().use_millisecond_precision = persisted.use_millisecond_precision;
     }
 }
 
@@ -130,11 +294,18 @@ pub extern "C" fn About() -> *mut c_char {
     VERSION_MESSAGE.as_ptr() as *mut c_char
 }
 
-#[allow(non_snake_case)]
+#[allow(non_snake_case, clippy::not_unsafe_ptr_arg_deref)]
 #[no_mangle]
 pub extern "C" fn RegisterCallback(Index: c_int, Addr: *mut c_void) {
-    let mut api = API.write().unwrap();
-    unsafe { api.set_callback_from_address(Index, Addr) };
+    // a null address would become a null function pointer, which must never be called
+    if Addr.is_null() {
+        warn!(target: target::WINAPI, "Ignoring null address for callback {}", Index);
+        return;
+    }
+    let mut callbacks = CALLBACKS.lock().unwrap();
+    unsafe { callbacks.set_callback_from_address(Index, Addr) };
+    // copy on write: handles taken before keep working with the callbacks they were taken with
+    *API.write().unwrap() = Arc::new(callbacks.clone());
 }
 
 #[allow(non_snake_case)]
@@ -144,142 +315,597 @@ pub extern "C" fn OnCreate() {
     let log_file_path: PathBuf = [user_profile, "rustplugin.log".to_string()]
         .iter()
         .collect();
-    WriteLogger::init(
-        LevelFilter::Debug,
-        LogConfig::default(),
-        File::create(log_file_path).unwrap(),
-    )
-    .unwrap();
+    init_logging(&log_file_path).unwrap();
+    set_log_levels(LogLevels::from_config(&config()));
 }
 
 #[allow(non_snake_case)]
 #[no_mangle]
 pub extern "C" fn AfterStart() {
-    let api = API.read().unwrap();
-    let plugin_id = unsafe { PLUGIN_ID };
-    create_menu_items(&api, plugin_id);
-    set_charmode(&api, plugin_id);
+    load_persisted_settings();
+    let api = api();
+    let plugin_id = PLUGIN_ID.load(Ordering::SeqCst);
+    after_start(&*api, plugin_id, &config(), &INIT_TASKS, startup_tasks());
 }
 
-fn create_menu_items_for_repeatable_migrations(
-    api: &RwLockReadGuard<Box<dyn PlsqlDevApi + Send + Sync>>,
+// What AfterStart does: only the calls into the IDE are made right away, the startup tasks run
+// in the background, so the IDE's startup isn't delayed by e.g. a slow network drive
+fn after_start(
+    api: &dyn PlsqlDevApi,
     plugin_id: c_int,
+    config: &Config,
+    init_tasks: &Arc<InitTasks>,
+    startup_tasks: Vec<(&'static str, InitTask)>,
 ) {
+    create_menu_items(api, plugin_id);
+    show_menu_checks(api, plugin_id, config);
+    let charmode_accepted = set_charmode(api, plugin_id, config);
+    CHARMODE_REJECTED.store(!charmode_accepted, Ordering::SeqCst);
+    for (name, task) in startup_tasks {
+        init_tasks.spawn(name, task);
+    }
+}
+
+fn startup_tasks() -> Vec<(&'static str, InitTask)> {
+    vec![(
+        "Sweep orphaned temporary files",
+        Box::new(sweep_orphaned_temp_files),
+    )]
+}
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn OnDestroy() {
+    TEMP_FILES.release_all();
+}
+
+// Remove spill files left behind by IDE instances that crashed or were killed
+fn sweep_orphaned_temp_files() -> Result<String, String> {
+    match sweep_orphans(TEMP_FILES.dir(), ORPHAN_MAX_AGE, SystemTime::now()) {
+        Ok(deleted) => Ok(format!("deleted {} files", deleted)),
+        Err(e) => Err(format!("{}", e)),
+    }
+}
+
+fn create_menu_items_for_repeatable_migrations(api: &dyn PlsqlDevApi, plugin_id: c_int) {
     api.ide_create_popup_item(
         plugin_id,
-        REPEATABLE_MIGRATION_INDEX,
+        menu_index(MenuKey::RepeatableMigration),
         POPUP_ITEM_NAME_REPEATABLE_MIGRATION,
         FUNCTION_OBJECT_TYPE,
     );
     api.ide_create_popup_item(
         plugin_id,
-        REPEATABLE_MIGRATION_INDEX,
+        menu_index(MenuKey::RepeatableMigration),
         POPUP_ITEM_NAME_REPEATABLE_MIGRATION,
         PROCEDURE_OBJECT_TYPE,
     );
     api.ide_create_popup_item(
         plugin_id,
-        REPEATABLE_MIGRATION_INDEX,
+        menu_index(MenuKey::RepeatableMigration),
         POPUP_ITEM_NAME_REPEATABLE_MIGRATION,
         PACKAGE_OBJECT_TYPE,
     );
     api.ide_create_popup_item(
         plugin_id,
-        REPEATABLE_MIGRATION_INDEX,
+        menu_index(MenuKey::RepeatableMigration),
         POPUP_ITEM_NAME_REPEATABLE_MIGRATION,
         TYPE_OBJECT_TYPE,
     );
     api.ide_create_popup_item(
         plugin_id,
-        REPEATABLE_MIGRATION_INDEX,
+        menu_index(MenuKey::RepeatableMigration),
         POPUP_ITEM_NAME_REPEATABLE_MIGRATION,
         VIEW_OBJECT_TYPE,
     );
     api.ide_create_popup_item(
         plugin_id,
-        REPEATABLE_MIGRATION_INDEX,
+        menu_index(MenuKey::RepeatableMigration),
+        POPUP_ITEM_NAME_REPEATABLE_MIGRATION,
+        MATERIALIZED_VIEW_OBJECT_TYPE,
+    );
+    api.ide_create_popup_item(
+        plugin_id,
+        menu_index(MenuKey::RepeatableMigration),
         POPUP_ITEM_NAME_REPEATABLE_MIGRATION,
         TRIGGER_OBJECT_TYPE,
     );
 }
 
 fn create_menu_items_for_repeatable_and_versioned_migrations(
-    api: &RwLockReadGuard<Box<dyn PlsqlDevApi + Send + Sync>>,
+    api: &dyn PlsqlDevApi,
     plugin_id: c_int,
 ) {
     api.ide_create_popup_item(
         plugin_id,
-        REPEATABLE_AND_VERSIONED_MIGRATION_INDEX,
+        menu_index(MenuKey::RepeatableAndVersionedMigration),
         POPUP_ITEM_NAME_REPEATABLE_AND_VERSIONED_MIGRATION,
         FUNCTION_OBJECT_TYPE,
     );
     api.ide_create_popup_item(
         plugin_id,
-        REPEATABLE_AND_VERSIONED_MIGRATION_INDEX,
+        menu_index(MenuKey::RepeatableAndVersionedMigration),
         POPUP_ITEM_NAME_REPEATABLE_AND_VERSIONED_MIGRATION,
         PROCEDURE_OBJECT_TYPE,
     );
     api.ide_create_popup_item(
         plugin_id,
-        REPEATABLE_AND_VERSIONED_MIGRATION_INDEX,
+        menu_index(MenuKey::RepeatableAndVersionedMigration),
         POPUP_ITEM_NAME_REPEATABLE_AND_VERSIONED_MIGRATION,
         PACKAGE_OBJECT_TYPE,
     );
     api.ide_create_popup_item(
         plugin_id,
-        REPEATABLE_AND_VERSIONED_MIGRATION_INDEX,
+        menu_index(MenuKey::RepeatableAndVersionedMigration),
         POPUP_ITEM_NAME_REPEATABLE_AND_VERSIONED_MIGRATION,
         TYPE_OBJECT_TYPE,
     );
     api.ide_create_popup_item(
         plugin_id,
-        REPEATABLE_AND_VERSIONED_MIGRATION_INDEX,
+        menu_index(MenuKey::RepeatableAndVersionedMigration),
         POPUP_ITEM_NAME_REPEATABLE_AND_VERSIONED_MIGRATION,
         VIEW_OBJECT_TYPE,
     );
     api.ide_create_popup_item(
         plugin_id,
-        REPEATABLE_AND_VERSIONED_MIGRATION_INDEX,
+        menu_index(MenuKey::RepeatableAndVersionedMigration),
+        POPUP_ITEM_NAME_REPEATABLE_AND_VERSIONED_MIGRATION,
+        MATERIALIZED_VIEW_OBJECT_TYPE,
+    );
+    api.ide_create_popup_item(
+        plugin_id,
+        menu_index(MenuKey::RepeatableAndVersionedMigration),
         POPUP_ITEM_NAME_REPEATABLE_AND_VERSIONED_MIGRATION,
         TRIGGER_OBJECT_TYPE,
     );
+    // tables have no repeatable migration, only the versioned one is written
+    api.ide_create_popup_item(
+        plugin_id,
+        menu_index(MenuKey::RepeatableAndVersionedMigration),
+        POPUP_ITEM_NAME_REPEATABLE_AND_VERSIONED_MIGRATION,
+        TABLE_OBJECT_TYPE,
+    );
 }
 
-fn create_menu_items_for_versioned_migrations(
-    api: &RwLockReadGuard<Box<dyn PlsqlDevApi + Send + Sync>>,
-    plugin_id: c_int,
-) {
+fn create_menu_items_for_versioned_migrations(api: &dyn PlsqlDevApi, plugin_id: c_int) {
+    for (key, name) in [
+        (
+            MenuKey::VersionedMigration,
+            POPUP_ITEM_NAME_VERSIONED_MIGRATION,
+        ),
+        (
+            MenuKey::VersionedAndUndoMigration,
+            POPUP_ITEM_NAME_VERSIONED_AND_UNDO_MIGRATION,
+        ),
+    ] {
+        for window_type in [SQL_WINDOW, TEST_WINDOW, COMMAND_WINDOW] {
+            api.ide_create_popup_item(plugin_id, menu_index(key), name, window_type);
+        }
+    }
+}
+
+fn create_menu_items_for_documentation(api: &dyn PlsqlDevApi, plugin_id: c_int) {
+    for object_type in &[
+        FUNCTION_OBJECT_TYPE,
+        PROCEDURE_OBJECT_TYPE,
+        PACKAGE_OBJECT_TYPE,
+        TYPE_OBJECT_TYPE,
+        VIEW_OBJECT_TYPE,
+        TRIGGER_OBJECT_TYPE,
+    ] {
+        api.ide_create_popup_item(
+            plugin_id,
+            menu_index(MenuKey::ExportDocumentation),
+            POPUP_ITEM_NAME_EXPORT_DOCUMENTATION,
+            object_type,
+        );
+    }
+}
+
+fn create_menu_items_for_checksums(api: &dyn PlsqlDevApi, plugin_id: c_int) {
+    for object_type in &[
+        FUNCTION_OBJECT_TYPE,
+        PROCEDURE_OBJECT_TYPE,
+        PACKAGE_OBJECT_TYPE,
+        TYPE_OBJECT_TYPE,
+        VIEW_OBJECT_TYPE,
+        TRIGGER_OBJECT_TYPE,
+    ] {
+        api.ide_create_popup_item(
+            plugin_id,
+            menu_index(MenuKey::VerifyChecksums),
+            POPUP_ITEM_NAME_VERIFY_CHECKSUMS,
+            object_type,
+        );
+    }
+}
+
+fn create_menu_items_for_review_snippets(api: &dyn PlsqlDevApi, plugin_id: c_int) {
+    for object_type in &[
+        FUNCTION_OBJECT_TYPE,
+        PROCEDURE_OBJECT_TYPE,
+        PACKAGE_OBJECT_TYPE,
+        TYPE_OBJECT_TYPE,
+        VIEW_OBJECT_TYPE,
+        TRIGGER_OBJECT_TYPE,
+    ] {
+        api.ide_create_popup_item(
+            plugin_id,
+            menu_index(MenuKey::ReviewSnippet),
+            POPUP_ITEM_NAME_REVIEW_SNIPPET,
+            object_type,
+        );
+    }
+}
+
+fn create_menu_items_for_object_under_cursor(api: &dyn PlsqlDevApi, plugin_id: c_int) {
+    for window_type in &[SQL_WINDOW, TEST_WINDOW, COMMAND_WINDOW] {
+        api.ide_create_popup_item(
+            plugin_id,
+            menu_index(MenuKey::ExportObjectUnderCursor),
+            POPUP_ITEM_NAME_EXPORT_OBJECT_UNDER_CURSOR,
+            window_type,
+        );
+    }
+}
+
+fn create_menu_items_for_selection_migration(api: &dyn PlsqlDevApi, plugin_id: c_int) {
     api.ide_create_popup_item(
         plugin_id,
-        VERSIONED_MIGRATION_INDEX,
-        POPUP_ITEM_NAME_VERSIONED_MIGRATION,
+        menu_index(MenuKey::SelectionMigration),
+        POPUP_ITEM_NAME_SELECTION_MIGRATION,
         SQL_WINDOW,
     );
+}
+
+fn create_menu_items_for_dbms_output(api: &dyn PlsqlDevApi, plugin_id: c_int) {
     api.ide_create_popup_item(
         plugin_id,
-        VERSIONED_MIGRATION_INDEX,
-        POPUP_ITEM_NAME_VERSIONED_MIGRATION,
+        menu_index(MenuKey::DbmsOutputTable),
+        POPUP_ITEM_NAME_DBMS_OUTPUT_TABLE,
         TEST_WINDOW,
     );
-    api.ide_create_popup_item(
-        plugin_id,
-        VERSIONED_MIGRATION_INDEX,
-        POPUP_ITEM_NAME_VERSIONED_MIGRATION,
-        COMMAND_WINDOW,
-    );
 }
 
-fn create_menu_items(api: &RwLockReadGuard<Box<dyn PlsqlDevApi + Send + Sync>>, plugin_id: c_int) {
-    create_menu_items_for_repeatable_migrations(&api, plugin_id);
-    create_menu_items_for_versioned_migrations(&api, plugin_id);
-    create_menu_items_for_repeatable_and_versioned_migrations(&api, plugin_id);
+fn create_menu_items(api: &dyn PlsqlDevApi, plugin_id: c_int) {
+    create_menu_items_for_repeatable_migrations(api, plugin_id);
+    create_menu_items_for_versioned_migrations(api, plugin_id);
+    create_menu_items_for_repeatable_and_versioned_migrations(api, plugin_id);
+    create_menu_items_for_documentation(api, plugin_id);
+    create_menu_items_for_checksums(api, plugin_id);
+    create_menu_items_for_review_snippets(api, plugin_id);
+    create_menu_items_for_object_under_cursor(api, plugin_id);
+    create_menu_items_for_selection_migration(api, plugin_id);
+    create_menu_items_for_dbms_output(api, plugin_id);
 }
 
-fn set_charmode(api: &RwLockReadGuard<Box<dyn PlsqlDevApi + Send + Sync>>, plugin_id: c_int) {
-    api.ide_plugin_setting(plugin_id, "CHARMODE", "UTF8");
+// Returns false if the IDE rejected the configured charmode
+fn set_charmode(api: &dyn PlsqlDevApi, plugin_id: c_int, config: &Config) -> bool {
+    let charmode = match &config.charmode {
+        Some(charmode) => charmode,
+        None => {
+            info!(target: target::MENU, "Not setting CHARMODE, as configured");
+            return true;
+        }
+    };
+    let accepted = api.ide_plugin_setting(plugin_id, "CHARMODE", charmode);
+    if !accepted {
+        warn!(target: target::MENU,
+            "!!! The IDE rejected CHARMODE={}, exports of non-ASCII text will likely be broken !!!",
+            charmode
+        );
+    }
+    accepted
 }
 
 fn show_plugin_version() {
-    let caption = CStr::from_bytes_with_nul(VERSION_INFO_CAPTION).unwrap();
-    show_message_box(&VERSION_MESSAGE, caption, MB_OK | MB_ICONINFORMATION);
+    let message = VERSION_MESSAGE.to_string_lossy();
+    dialogs().show_message(&message, VERSION_INFO_CAPTION, MessageKind::Information);
+}
+
+fn clear_plugin_log() {
+    match clear_log() {
+        Ok(path) => debug!(target: target::MENU, "Cleared log file {:?}", path),
+        Err(e) => dialogs().show_message(
+            &format!(
+                "Could not clear the log file, it may be locked by another program.\n{}",
+                e
+            ),
+            "Clear log",
+            MessageKind::Error,
+        ),
+    }
+}
+
+// Copy the log since the IDE was started, e.g. for a bug report
+fn copy_session_log(config: &Config) {
+    let caption = "Copy last session log";
+    let copied = match log_path() {
+        Some(path) => read_last_session(&path, config.max_log_copy_bytes)
+            .map_err(|e| format!("Could not read the log file {}: {}", path.display(), e)),
+        None => Err("Logging has not been initialized.".to_string()),
+    }
+    .and_then(|log| {
+        clipboard()
+            .copy_to_clipboard(&log, config.max_clipboard_bytes)
+            .map(|_| log.len())
+            .map_err(|e| format!("{}", e))
+    });
+    match copied {
+        Ok(bytes) => dialogs().show_message(
+            &format!(
+                "The log of the last session ({} bytes) was copied to the clipboard.",
+                bytes
+            ),
+            caption,
+            MessageKind::Information,
+        ),
+        Err(message) => dialogs().show_message(&message, caption, MessageKind::Error),
+    }
+}
+
+#[cfg(all(test, not(all(windows, feature = "windows-ui"))))]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::export::{export_finished, export_init, export_prepare, export_value};
+    use crate::init_tasks::TaskState;
+    use crate::plsqldev_api::PlsqlDevApi;
+    use crate::prelude::*;
+    use crate::ui::headless::CLIPBOARD_TEST_LOCK;
+
+    // Nothing selected, no text: every menu action ends in a (headless) message or dialog
+    struct IdleApi;
+
+    impl PlsqlDevApi for IdleApi {}
+
+    // Rejects (or accepts) every plugin setting, and records the requests
+    struct SettingApi {
+        accept: bool,
+        requests: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl PlsqlDevApi for SettingApi {
+        fn ide_plugin_setting(&self, _id: i32, setting: &str, value: &str) -> bool {
+            self.requests
+                .lock()
+                .unwrap()
+                .push(format!("{}={}", setting, value));
+            self.accept
+        }
+    }
+
+    #[test]
+    fn after_start_should_not_wait_for_slow_startup_tasks() {
+        let init_tasks = InitTasks::new();
+        // the task only finishes once it is released after after_start returned
+        let (release, released) = mpsc::channel::<()>();
+        let slow: InitTask = Box::new(move || match released.recv() {
+            Ok(()) => Ok("released".to_string()),
+            Err(_) => Err("never released".to_string()),
+        });
+        after_start(
+            &IdleApi,
+            42,
+            &Config::default(),
+            &init_tasks,
+            vec![("Slow", slow)],
+        );
+        let (name, state) = init_tasks.states().remove(0);
+        assert_eq!("Slow", name);
+        assert!(matches!(state, TaskState::Pending | TaskState::Running));
+        release.send(()).unwrap();
+    }
+
+    #[test]
+    fn set_charmode_should_report_rejected_setting() {
+        let api = SettingApi {
+            accept: false,
+            requests: std::sync::Mutex::new(vec![]),
+        };
+        assert!(!set_charmode(&api, 42, &Config::default()));
+        assert_eq!(vec!["CHARMODE=UTF8"], *api.requests.lock().unwrap());
+    }
+
+    #[test]
+    fn set_charmode_should_use_configured_charmode_or_skip_it() {
+        let api = SettingApi {
+            accept: true,
+            requests: std::sync::Mutex::new(vec![]),
+        };
+        let mut config = Config {
+            charmode: Some("ANSI".to_string()),
+            ..Config::default()
+        };
+        assert!(set_charmode(&api, 42, &config));
+        config.charmode = None;
+        assert!(set_charmode(&api, 42, &config));
+        assert_eq!(vec!["CHARMODE=ANSI"], *api.requests.lock().unwrap());
+    }
+
+    // Records the menu updates; shows check marks only if `check_marks` is set
+    struct MenuApi {
+        check_marks: bool,
+        updates: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl PlsqlDevApi for MenuApi {
+        fn ide_set_menu_name(&self, _id: i32, index: i32, name: &str) -> bool {
+            self.updates
+                .lock()
+                .unwrap()
+                .push(format!("{}: {}", index, name));
+            true
+        }
+        fn ide_set_menu_check(&self, _id: i32, index: i32, checked: bool) -> bool {
+            if self.check_marks {
+                self.updates
+                    .lock()
+                    .unwrap()
+                    .push(format!("{}: checked={}", index, checked));
+            }
+            self.check_marks
+        }
+    }
+
+    #[test]
+    fn register_callback_should_ignore_null_addresses() {
+        let _api_lock = API_TEST_LOCK.lock().unwrap();
+        let before = api();
+        RegisterCallback(54, std::ptr::null_mut());
+        assert!(Arc::ptr_eq(&before, &api()));
+    }
+
+    #[test]
+    fn config_should_be_usable_after_a_panic_poisoned_its_lock() {
+        let _api_lock = API_TEST_LOCK.lock().unwrap();
+        *CONFIG.write().unwrap() = Config::default();
+        let _ = std::thread::spawn(|| {
+            let mut config = CONFIG.write().unwrap();
+            config.version_prefix = "M".to_string();
+            panic!("poisoning the config lock");
+        })
+        .join();
+        assert!(CONFIG.is_poisoned());
+
+        assert_eq!("M", config().version_prefix);
+        assert_eq!("M", read_config().version_prefix);
+        write_config().version_prefix = "V".to_string();
+        assert!(!CONFIG.is_poisoned());
+        assert_eq!("V", config().version_prefix);
+    }
+
+    #[test]
+    fn millisecond_precision_item_should_toggle_the_config() {
+        let _api_lock = API_TEST_LOCK.lock().unwrap();
+        let api = Arc::new(MenuApi {
+            check_marks: false,
+            updates: std::sync::Mutex::new(vec![]),
+        });
+        *API.write().unwrap() = api.clone();
+        let repo = std::env::temp_dir().join("xanthidae_millisecond_precision_test");
+        let _ = std::fs::remove_dir_all(&repo);
+        std::fs::create_dir_all(&repo).unwrap();
+        let restarted = Config {
+            repo_root: Some(repo.to_string_lossy().to_string()),
+            ..Config::default()
+        };
+        *CONFIG.write().unwrap() = restarted.clone();
+        let index = menu_index(MenuKey::MillisecondPrecision);
+
+        show_menu_checks(&*api, 42, &config());
+        on_menu_click(index);
+        assert!(config().use_millisecond_precision);
+        // stored, so it's still switched on after a restart
+        *CONFIG.write().unwrap() = restarted.clone();
+        load_persisted_settings();
+        assert!(config().use_millisecond_precision);
+        on_menu_click(index);
+        assert!(!config().use_millisecond_precision);
+        *CONFIG.write().unwrap() = Config {
+            use_millisecond_precision: true,
+            ..restarted
+        };
+        load_persisted_settings();
+        assert!(!config().use_millisecond_precision);
+        std::fs::remove_dir_all(&repo).unwrap();
+
+        assert_eq!(
+            vec![
+                format!("{}: Use &millisecond precision in versions (off)", index),
+                format!("{}: Use &millisecond precision in versions (on)", index),
+                format!("{}: Use &millisecond precision in versions (off)", index),
+            ],
+            *api.updates.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn show_menu_checks_should_prefer_check_marks() {
+        let api = MenuApi {
+            check_marks: true,
+            updates: std::sync::Mutex::new(vec![]),
+        };
+        let config = Config {
+            use_millisecond_precision: true,
+            ..Config::default()
+        };
+        let index = menu_index(MenuKey::MillisecondPrecision);
+        show_menu_checks(&api, 42, &config);
+        assert_eq!(
+            vec![
+                format!("{}: checked=true", index),
+                format!("{}: Use &millisecond precision in versions", index),
+            ],
+            *api.updates.lock().unwrap()
+        );
+    }
+
+    const ITERATIONS: usize = 200;
+
+    #[test]
+    fn concurrent_menu_and_export_calls_should_not_deadlock() {
+        let _api_lock = API_TEST_LOCK.lock().unwrap();
+        let _lock = CLIPBOARD_TEST_LOCK.lock().unwrap();
+        *API.write().unwrap() = Arc::new(IdleApi);
+
+        let mut handles = vec![];
+        for key in &[
+            MenuKey::VersionedMigration,
+            MenuKey::RepeatableMigration,
+            MenuKey::RepeatableAndVersionedMigration,
+            MenuKey::VersionedAndUndoMigration,
+            MenuKey::PluginVersion,
+            MenuKey::ExportDocumentation,
+            MenuKey::ExportObjectUnderCursor,
+            MenuKey::SelectionMigration,
+            MenuKey::DbmsOutputTable,
+            MenuKey::ExportAsZip,
+            MenuKey::CallbackSkeletons,
+            MenuKey::FlywayHelp,
+            MenuKey::VerifyChecksums,
+            MenuKey::CompareFolders,
+            MenuKey::SchemaHistoryInsert,
+            MenuKey::ReviewSnippet,
+            MenuKey::WindowHistory,
+            MenuKey::Diagnostics,
+        ] {
+            let index = menu_index(*key);
+            handles.push(thread::spawn(move || {
+                for _ in 0..ITERATIONS {
+                    on_menu_click(index);
+                }
+            }));
+        }
+        handles.push(thread::spawn(|| {
+            for _ in 0..ITERATIONS {
+                export_init();
+                export_value("ID".to_string());
+                export_prepare();
+                export_value("1".to_string());
+                export_finished();
+            }
+        }));
+        // what RegisterCallback does while the IDE starts up
+        handles.push(thread::spawn(|| {
+            for _ in 0..ITERATIONS {
+                *API.write().unwrap() = Arc::new(IdleApi);
+                *CONFIG.write().unwrap() = Config::default();
+            }
+        }));
+
+        let (done, finished) = mpsc::channel();
+        thread::spawn(move || {
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            done.send(()).unwrap();
+        });
+        assert!(
+            finished.recv_timeout(Duration::from_secs(30)).is_ok(),
+            "menu and export calls did not finish, probably a deadlock"
+        );
+    }
 }
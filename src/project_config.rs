@@ -0,0 +1,344 @@
+// Project-local settings from a `.xanthidae.toml` in the export folder or one of its parents.
+//
+// Different repositories have different conventions, so the closest such file is overlaid on
+// the global config for a single export; exports never change the global config itself. Only
+// top-level `key = value` settings are read, tables are not supported.
+//
+// Settings switched from the menu are written to the file in repo_root, so they survive
+// restarts; the plugin reads them back when the IDE starts.
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use toml::value::{Table, Value};
+
+use crate::config::{Config, LineEnding, Versioning};
+use crate::flyway::validate_version_format;
+use crate::logging::target;
+
+const PROJECT_CONFIG_FILE_NAME: &str = ".xanthidae.toml";
+// the settings with string values; the others are booleans
+const STRING_KEYS: [&str; 11] = [
+    "version_prefix",
+    "repeatable_prefix",
+    "undo_prefix",
+    "version_description_separator",
+    "timestamp_field_separator",
+    "version_format",
+    "callback_file_name",
+    "header_template",
+    "footer_template",
+    "versioning",
+    "line_ending",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum ProjectConfigError {
+    // the message of the TOML parser
+    Syntax(String),
+    UnknownKey(String),
+    // key, value
+    InvalidValue(String, String),
+    // name of the table
+    TableNotSupported(String),
+}
+
+impl Display for ProjectConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            ProjectConfigError::Syntax(e) => format!("Cannot parse the project config: {}", e),
+            ProjectConfigError::UnknownKey(key) => format!("Unknown setting {}", key),
+            ProjectConfigError::InvalidValue(key, value) => {
+                format!("Invalid value {} for {}", value, key)
+            }
+            ProjectConfigError::TableNotSupported(table) => {
+                format!("Tables are not supported, ignoring [{}]", table)
+            }
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+// The closest .xanthidae.toml in `folder` or one of its parents
+pub fn find_project_config(folder: &Path) -> Option<PathBuf> {
+    // canonicalize so we can walk up from relative folders, too
+    let folder = folder
+        .canonicalize()
+        .unwrap_or_else(|_| folder.to_path_buf());
+    folder
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+// The config for an export into `folder`: the given one, overlaid with the project config.
+// Errors in the project config are logged, the settings that could be read still apply.
+pub fn config_for_folder(config: &Config, folder: &Path) -> Config {
+    let mut result = config.clone();
+    let path = match find_project_config(folder) {
+        Some(path) => path,
+        None => return result,
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => {
+            debug!(target: target::CONFIG, "Using project config {:?}", path);
+            for e in apply_project_config(&mut result, &content) {
+                warn!(target: target::CONFIG, "{:?}: {}", path, e);
+            }
+        }
+        Err(e) => warn!(target: target::CONFIG, "Could not read project config {:?}: {}", path, e),
+    }
+    result
+}
+
+// Store a setting in the closest .xanthidae.toml of `folder` (a new one in `folder` if there is
+// none), replacing the line setting it so far; the rest of the file, comments included, is kept
+pub fn save_project_setting(folder: &Path, key: &str, value: &Value) -> io::Result<PathBuf> {
+    let path = find_project_config(folder).unwrap_or_else(|| folder.join(PROJECT_CONFIG_FILE_NAME));
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let setting = format!("{} = {}", key, value);
+    let sets_key = |line: &str| {
+        line.trim_start()
+            .strip_prefix(key)
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    };
+    let mut lines: Vec<&str> = content.lines().collect();
+    match lines.iter().position(|line| sets_key(line)) {
+        Some(index) => lines[index] = &setting,
+        None => lines.push(&setting),
+    }
+    fs::write(&path, format!("{}\n", lines.join("\n")))?;
+    Ok(path)
+}
+
+// Apply the settings of a project config; returns the problems found
+pub fn apply_project_config(config: &mut Config, content: &str) -> Vec<ProjectConfigError> {
+    let settings: Table = match toml::from_str(content) {
+        Ok(settings) => settings,
+        Err(e) => return vec![ProjectConfigError::Syntax(format!("{}", e))],
+    };
+    let mut errors = vec![];
+    for (key, value) in settings {
+        let result = match value {
+            Value::Table(_) => Err(ProjectConfigError::TableNotSupported(key)),
+            value => set(config, &key, value),
+        };
+        if let Err(e) = result {
+            errors.push(e);
+        }
+    }
+    errors
+}
+
+fn set(config: &mut Config, key: &str, value: Value) -> Result<(), ProjectConfigError> {
+    let invalid =
+        |value: &Value| ProjectConfigError::InvalidValue(key.to_string(), value.to_string());
+    let flag = match key {
+        "use_millisecond_precision" => Some(&mut config.use_millisecond_precision),
+        "force_views" => Some(&mut config.force_views),
+        "force_types" => Some(&mut config.force_types),
+        "normalize_end_label" => Some(&mut config.normalize_end_label),
+        "include_header_comment" => Some(&mut config.include_header_comment),
+        "reindent_ddl" => Some(&mut config.reindent_ddl),
+        "manage_gitattributes" => Some(&mut config.manage_gitattributes),
+        "write_callback" => Some(&mut config.write_callback),
+        "repeatable_order_prefix" => Some(&mut config.repeatable_order_prefix),
+        _ => None,
+    };
+    if let Some(flag) = flag {
+        return match value {
+            Value::Boolean(b) => {
+                *flag = b;
+                Ok(())
+            }
+            value => Err(invalid(&value)),
+        };
+    }
+    if !STRING_KEYS.contains(&key) {
+        return Err(ProjectConfigError::UnknownKey(key.to_string()));
+    }
+    let text = match &value {
+        Value::String(s) => s.clone(),
+        value => return Err(invalid(value)),
+    };
+    match key {
+        "version_prefix" if !text.is_empty() => config.version_prefix = text,
+        "repeatable_prefix" if !text.is_empty() => config.repeatable_prefix = text,
+        "undo_prefix" if !text.is_empty() => config.undo_prefix = text,
+        "version_description_separator" if !text.is_empty() => {
+            config.version_description_separator = text
+        }
+        "timestamp_field_separator" => config.timestamp_field_separator = text,
+        // an empty format switches back to the separated fields
+        "version_format" if text.is_empty() => config.version_format = None,
+        "version_format" => {
+            match validate_version_format(&text, &config.version_description_separator) {
+                Ok(()) => config.version_format = Some(text),
+                Err(_) => return Err(invalid(&value)),
+            }
+        }
+        "callback_file_name" if !text.is_empty() => config.callback_file_name = text,
+        // an empty template switches it off
+        "header_template" => config.header_template = Some(text).filter(|t| !t.is_empty()),
+        "footer_template" => config.footer_template = Some(text).filter(|t| !t.is_empty()),
+        "versioning" => {
+            config.versioning = match text.to_lowercase().as_str() {
+                "timestamp" => Versioning::Timestamp,
+                "sequential" => Versioning::Sequential,
+                _ => return Err(invalid(&value)),
+            }
+        }
+        "line_ending" => {
+            config.line_ending = match text.to_lowercase().as_str() {
+                "keep" => LineEnding::Keep,
+                "lf" => LineEnding::Lf,
+                "crlf" => LineEnding::CrLf,
+                _ => return Err(invalid(&value)),
+            }
+        }
+        _ => return Err(invalid(&value)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::TimeZone;
+
+    use crate::config::{Config, LineEnding, Versioning};
+    use crate::flyway::get_versioned_filename_impl;
+    use crate::project_config::*;
+
+    #[test]
+    fn apply_project_config_should_overlay_known_settings() {
+        let mut config = Config::default();
+        let content = "# conventions of this repository\n\
+                       version_prefix = \"M\"  # for migrations\n\
+                       versioning = 'Sequential'\n\
+                       force_views = false\n\
+                       line_ending = \"crlf\"\n\
+                       header_template = \"-- {name}\\n\"\n";
+        assert!(apply_project_config(&mut config, content).is_empty());
+        assert_eq!("M", config.version_prefix);
+        assert_eq!(Versioning::Sequential, config.versioning);
+        assert!(!config.force_views);
+        assert_eq!(LineEnding::CrLf, config.line_ending);
+        assert_eq!(Some("-- {name}\n".to_string()), config.header_template);
+    }
+
+    #[test]
+    fn apply_project_config_should_report_problems_and_keep_going() {
+        let mut config = Config::default();
+        let content = "force_types = \"no\"\n\
+                       colour = true\n\
+                       write_callback = true\n\
+                       [flyway]\n\
+                       force_views = false\n";
+        assert_eq!(
+            vec![
+                ProjectConfigError::UnknownKey("colour".to_string()),
+                ProjectConfigError::TableNotSupported("flyway".to_string()),
+                ProjectConfigError::InvalidValue("force_types".to_string(), "\"no\"".to_string()),
+            ],
+            apply_project_config(&mut config, content)
+        );
+        assert!(config.force_types);
+        assert!(config.write_callback);
+        assert!(config.force_views);
+    }
+
+    #[test]
+    fn apply_project_config_should_reject_invalid_version_formats() {
+        for format in ["%Y%.2f", "%d_%m_%Y", "%Y__%m"] {
+            let mut config = Config::default();
+            assert_eq!(
+                vec![ProjectConfigError::InvalidValue(
+                    "version_format".to_string(),
+                    format!("{:?}", format)
+                )],
+                apply_project_config(&mut config, &format!("version_format = {:?}\n", format))
+            );
+            assert_eq!(None, config.version_format);
+        }
+    }
+
+    #[test]
+    fn apply_project_config_should_ignore_a_file_that_is_not_toml() {
+        let mut config = Config::default();
+        let errors = apply_project_config(
+            &mut config,
+            "write_callback = true\nversion_prefix = \"unterminated\n",
+        );
+        assert!(matches!(errors.as_slice(), [ProjectConfigError::Syntax(_)]));
+        assert!(!config.write_callback);
+        assert_eq!("V", config.version_prefix);
+    }
+
+    #[test]
+    fn config_for_folder_should_override_version_prefix_for_that_export_only() {
+        let repo = std::env::temp_dir().join("xanthidae_project_config_test");
+        let _ = fs::remove_dir_all(&repo);
+        let folder = repo.join("db").join("migration");
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(
+            repo.join(PROJECT_CONFIG_FILE_NAME),
+            "version_prefix = \"M\"\n",
+        )
+        .unwrap();
+
+        let global = Config::default();
+        let timestamp = chrono::Utc.with_ymd_and_hms(1970, 1, 2, 3, 4, 5).unwrap();
+        let local = config_for_folder(&global, &folder);
+        assert_eq!(
+            "M1970_01_02_03_04_05__snafu.sql",
+            get_versioned_filename_impl(&local, timestamp, "snafu")
+        );
+        assert_eq!(
+            "V1970_01_02_03_04_05__snafu.sql",
+            get_versioned_filename_impl(&global, timestamp, "snafu")
+        );
+        assert_eq!(
+            "V",
+            config_for_folder(&global, &std::env::temp_dir()).version_prefix
+        );
+        fs::remove_dir_all(&repo).unwrap();
+    }
+
+    #[test]
+    fn save_project_setting_should_replace_the_setting_and_keep_the_rest() {
+        let repo = std::env::temp_dir().join("xanthidae_project_setting_test");
+        let _ = fs::remove_dir_all(&repo);
+        fs::create_dir_all(&repo).unwrap();
+        let path = repo.join(PROJECT_CONFIG_FILE_NAME);
+
+        let value = Value::Boolean(true);
+        assert_eq!(
+            path,
+            save_project_setting(&repo, "use_millisecond_precision", &value).unwrap()
+        );
+        assert_eq!(
+            "use_millisecond_precision = true\n",
+            fs::read_to_string(&path).unwrap()
+        );
+
+        fs::write(
+            &path,
+            "# conventions of this repository\nuse_millisecond_precision=true\nforce_views = false",
+        )
+        .unwrap();
+        let value = Value::Boolean(false);
+        save_project_setting(&repo, "use_millisecond_precision", &value).unwrap();
+        assert_eq!(
+            "# conventions of this repository\nuse_millisecond_precision = false\nforce_views = false\n",
+            fs::read_to_string(&path).unwrap()
+        );
+        fs::remove_dir_all(&repo).unwrap();
+    }
+}
@@ -0,0 +1,112 @@
+// Retries of IDE calls that fail transiently, e.g. an object source coming back empty over a
+// flaky VPN although the object exists, while asking again a moment later works.
+use std::thread;
+use std::time::Duration;
+
+use crate::logging::target;
+
+// The delay between attempts, so tests can skip it
+pub trait Sleeper {
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct ThreadSleeper;
+
+impl Sleeper for ThreadSleeper {
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+// Fetch until the result has other than whitespace, at most `retries` times more than once;
+// None if it stayed empty. `what` names the fetched thing in the log.
+pub fn retry_while_empty<F>(
+    what: &str,
+    retries: u32,
+    delay: Duration,
+    sleeper: &dyn Sleeper,
+    mut fetch: F,
+) -> Option<Vec<u8>>
+where
+    F: FnMut() -> Vec<u8>,
+{
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            info!(target: target::FLYWAY,
+                "{} was empty, retry {} of {} in {} ms",
+                what,
+                attempt,
+                retries,
+                delay.as_millis()
+            );
+            sleeper.sleep(delay);
+        }
+        let bytes = fetch();
+        if !bytes.iter().all(u8::is_ascii_whitespace) {
+            return Some(bytes);
+        }
+    }
+    warn!(target: target::FLYWAY, "{} still empty after {} retries", what, retries);
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::time::Duration;
+
+    use crate::retry::*;
+
+    #[derive(Default)]
+    struct RecordingSleeper {
+        sleeps: RefCell<Vec<Duration>>,
+    }
+
+    impl Sleeper for RecordingSleeper {
+        fn sleep(&self, duration: Duration) {
+            self.sleeps.borrow_mut().push(duration);
+        }
+    }
+
+    #[test]
+    fn retry_while_empty_should_stop_at_the_first_non_blank_result() {
+        let sleeper = RecordingSleeper::default();
+        let attempts = Cell::new(0);
+        let fetch = || {
+            attempts.set(attempts.get() + 1);
+            match attempts.get() {
+                1 => b" \r\n".to_vec(),
+                _ => b"create view v as select 1 x from dual;".to_vec(),
+            }
+        };
+
+        let result = retry_while_empty("source", 2, Duration::from_millis(500), &sleeper, fetch);
+
+        assert_eq!(
+            Some(b"create view v as select 1 x from dual;".to_vec()),
+            result
+        );
+        assert_eq!(2, attempts.get());
+        assert_eq!(vec![Duration::from_millis(500)], *sleeper.sleeps.borrow());
+    }
+
+    #[test]
+    fn retry_while_empty_should_give_up_after_the_retries() {
+        let sleeper = RecordingSleeper::default();
+        let attempts = Cell::new(0);
+        let fetch = || {
+            attempts.set(attempts.get() + 1);
+            vec![]
+        };
+
+        let result = retry_while_empty("source", 2, Duration::from_millis(10), &sleeper, fetch);
+
+        assert_eq!(None, result);
+        assert_eq!(3, attempts.get());
+        assert_eq!(2, sleeper.sleeps.borrow().len());
+
+        let result = retry_while_empty("source", 0, Duration::from_millis(10), &sleeper, Vec::new);
+        assert_eq!(None, result);
+        assert_eq!(2, sleeper.sleeps.borrow().len());
+    }
+}
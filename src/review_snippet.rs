@@ -0,0 +1,78 @@
+// Copy the DDL of the selected objects as review snippets: a Git style unified diff header
+// followed by the DDL as context lines, which review tools render like a diff.
+use crate::config::Config;
+use crate::flyway::{
+    fetch_object_source, repeatable_file_name, NO_OBJECT_SELECTED_CAPTION,
+    NO_OBJECT_SELECTED_MESSAGE, SUPPORTED_OBJECT_TYPES,
+};
+use crate::plsqldev_api::PlsqlDevApi;
+use crate::ui::{clipboard, dialogs, MessageKind};
+
+const REVIEW_SNIPPET_CAPTION: &str = "Copy as review snippet";
+
+pub fn copy_review_snippets(api: &dyn PlsqlDevApi, config: &Config) {
+    let selected_objects = api.ide_selected_objects();
+    if selected_objects.is_empty() {
+        dialogs().show_message(
+            NO_OBJECT_SELECTED_MESSAGE,
+            NO_OBJECT_SELECTED_CAPTION,
+            MessageKind::Information,
+        );
+        return;
+    }
+
+    let snippets: Vec<String> = selected_objects
+        .iter()
+        .filter(|o| SUPPORTED_OBJECT_TYPES.contains(&o.object_type.as_str()))
+        .map(|o| {
+            // named like the repeatable migration, as that's what ends up in the repository
+            review_snippet(
+                &repeatable_file_name(o, config),
+                &fetch_object_source(api, o, config),
+            )
+        })
+        .collect();
+    if snippets.is_empty() {
+        let message = "Please select one or more objects of a supported type!";
+        dialogs().show_message(message, REVIEW_SNIPPET_CAPTION, MessageKind::Error);
+        return;
+    }
+
+    if let Err(e) = clipboard().copy_to_clipboard(&snippets.concat(), config.max_clipboard_bytes) {
+        dialogs().show_message(
+            &format!("{}", e),
+            REVIEW_SNIPPET_CAPTION,
+            MessageKind::Error,
+        );
+    }
+}
+
+// `--- a/<name>` and `+++ b/<name>`, then every line of the DDL prefixed with a space
+pub fn review_snippet(name: &str, ddl: &str) -> String {
+    let mut snippet = format!("--- a/{}\n+++ b/{}\n", name, name);
+    for line in ddl.lines() {
+        snippet.push(' ');
+        snippet.push_str(line);
+        snippet.push('\n');
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::review_snippet::*;
+
+    #[test]
+    fn review_snippet_should_add_header_and_context_prefix() {
+        let ddl = "create or replace view V_SNAFU as\r\nselect 1\n\nfrom dual;\n";
+        assert_eq!(
+            "--- a/R__V_SNAFU.sql\n\
+             +++ b/R__V_SNAFU.sql\n \
+             create or replace view V_SNAFU as\n \
+             select 1\n \
+             \n \
+             from dual;\n",
+            review_snippet("R__V_SNAFU.sql", ddl)
+        );
+    }
+}
@@ -0,0 +1,292 @@
+// The flyway_schema_history row of a migration applied by hand, e.g. in an emergency, so the
+// DBAs can record it the way Flyway would have.
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::Config;
+use crate::ui::{clipboard, dialogs, MessageKind};
+
+const SCHEMA_HISTORY_CAPTION: &str = "Schema history INSERT";
+const MIGRATION_SUFFIX: &str = ".sql";
+
+lazy_static! {
+    // the migration written last, offered instead of the file dialog
+    static ref LAST_MIGRATION: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+pub fn remember_migration(path: &Path) {
+    *LAST_MIGRATION.lock().unwrap() = Some(path.to_path_buf());
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MigrationFileName {
+    // e.g. 1.1 for V1_1__add_city.sql; None for repeatable migrations
+    pub version: Option<String>,
+    // e.g. "add city"
+    pub description: String,
+    pub script: String,
+}
+
+#[derive(Debug)]
+pub enum SchemaHistoryError {
+    NotAMigration(String),
+    Unreadable(PathBuf, std::io::Error),
+}
+
+impl Display for SchemaHistoryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaHistoryError::NotAMigration(file_name) => write!(
+                f,
+                "{} is not named like a versioned or repeatable migration",
+                file_name
+            ),
+            SchemaHistoryError::Unreadable(path, e) => {
+                write!(f, "Could not read {}: {}", path.display(), e)
+            }
+        }
+    }
+}
+
+// Split the file name like Flyway does: underscores in the version become dots, those in the
+// description spaces
+pub fn parse_migration_file_name(
+    file_name: &str,
+    config: &Config,
+) -> Result<MigrationFileName, SchemaHistoryError> {
+    let not_a_migration = || SchemaHistoryError::NotAMigration(file_name.to_string());
+    let stem = file_name
+        .strip_suffix(MIGRATION_SUFFIX)
+        .ok_or_else(not_a_migration)?;
+    let separator = &config.version_description_separator;
+    let (version, description) = match stem.strip_prefix(config.repeatable_prefix.as_str()) {
+        Some(rest) if rest.starts_with(separator.as_str()) => (None, &rest[separator.len()..]),
+        _ => {
+            let (version, description) = stem
+                .strip_prefix(&config.version_prefix)
+                .and_then(|rest| rest.split_once(separator.as_str()))
+                .ok_or_else(not_a_migration)?;
+            if version.is_empty() {
+                return Err(not_a_migration());
+            }
+            (Some(version.replace('_', ".")), description)
+        }
+    };
+    Ok(MigrationFileName {
+        version,
+        description: description.replace('_', " "),
+        script: file_name.to_string(),
+    })
+}
+
+// The checksum Flyway records for an SQL migration: the CRC-32 of its lines without line
+// breaks (\n, \r\n or \r, as Java's BufferedReader.readLine splits them) and without a BOM,
+// as a signed 32 bit integer
+pub fn flyway_checksum(content: &[u8]) -> i32 {
+    let text = String::from_utf8_lossy(content);
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(&text);
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(end) = rest.find(['\r', '\n']) {
+        bytes.extend_from_slice(&rest.as_bytes()[..end]);
+        let break_length = match rest[end..].starts_with("\r\n") {
+            true => 2,
+            false => 1,
+        };
+        rest = &rest[end + break_length..];
+    }
+    bytes.extend_from_slice(rest.as_bytes());
+    // the CRC of the concatenation equals updating it line by line
+    crc32fast::hash(&bytes) as i32
+}
+
+fn sql_literal(value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("'{}'", value.replace('\'', "''")),
+        None => "null".to_string(),
+    }
+}
+
+// The INSERT Flyway would have done; the installed rank and the execution time (in ms) are
+// bind variables, so the IDE asks for them
+pub fn schema_history_insert(migration: &MigrationFileName, checksum: i32, table: &str) -> String {
+    format!(
+        "insert into \"{}\"\n  \
+         (\"installed_rank\", \"version\", \"description\", \"type\", \"script\", \"checksum\",\n   \
+         \"installed_by\", \"installed_on\", \"execution_time\", \"success\")\n\
+         values\n  \
+         (:installed_rank, {}, {}, 'SQL', {}, {},\n   \
+         user, current_timestamp, :execution_time, 1);\n",
+        table.replace('"', ""),
+        sql_literal(migration.version.as_deref()),
+        sql_literal(Some(&migration.description)),
+        sql_literal(Some(&migration.script)),
+        checksum
+    )
+}
+
+fn insert_for_file(path: &Path, config: &Config) -> Result<String, SchemaHistoryError> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let migration = parse_migration_file_name(&file_name, config)?;
+    let content =
+        fs::read(path).map_err(|e| SchemaHistoryError::Unreadable(path.to_path_buf(), e))?;
+    Ok(schema_history_insert(
+        &migration,
+        flyway_checksum(&content),
+        &config.flyway_table,
+    ))
+}
+
+// The migration just written, if the user wants it, or one chosen in the file dialog
+fn choose_migration() -> Option<PathBuf> {
+    let last = LAST_MIGRATION.lock().unwrap().clone();
+    if let Some(last) = last {
+        let question = format!("Use the migration just written?\n{}", last.display());
+        if dialogs().ask_yes_no(&question, SCHEMA_HISTORY_CAPTION) {
+            return Some(last);
+        }
+    }
+    dialogs().get_open_file_name()
+}
+
+pub fn copy_schema_history_insert(config: &Config) {
+    let path = match choose_migration() {
+        Some(path) => path,
+        // user cancelled
+        None => return,
+    };
+    let result = insert_for_file(&path, config).map_err(|e| format!("{}", e));
+    let result = result.and_then(|insert| {
+        clipboard()
+            .copy_to_clipboard(&insert, config.max_clipboard_bytes)
+            .map_err(|e| format!("{}", e))
+    });
+    match result {
+        Ok(_) => dialogs().show_message(
+            "The INSERT was copied to the clipboard.\n\
+             Fill in the installed rank and the execution time when running it.",
+            SCHEMA_HISTORY_CAPTION,
+            MessageKind::Information,
+        ),
+        Err(e) => dialogs().show_message(&e, SCHEMA_HISTORY_CAPTION, MessageKind::Error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::config::Config;
+    use crate::schema_history::*;
+
+    fn fixtures() -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "fixtures", "flyway_checksums"]
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn flyway_checksum_should_match_fixtures() {
+        let expected = fs::read_to_string(fixtures().join("checksums.txt")).unwrap();
+        let mut checked = 0;
+        for line in expected.lines().filter(|line| !line.starts_with('#')) {
+            let (file_name, checksum) = line.split_once('\t').unwrap();
+            let content = fs::read(fixtures().join(file_name)).unwrap();
+            assert_eq!(
+                checksum.parse::<i32>().unwrap(),
+                flyway_checksum(&content),
+                "{}",
+                file_name
+            );
+            checked += 1;
+        }
+        assert_eq!(6, checked);
+    }
+
+    #[test]
+    fn flyway_checksum_should_ignore_line_break_style_and_bom() {
+        let lf = flyway_checksum(b"select 1\nfrom dual;\n");
+        assert_eq!(lf, flyway_checksum(b"select 1\r\nfrom dual;\r\n"));
+        assert_eq!(lf, flyway_checksum(b"select 1\rfrom dual;"));
+        assert_eq!(lf, flyway_checksum(b"\xEF\xBB\xBFselect 1\nfrom dual;\n"));
+        // an empty line is a line of its own, but contributes no bytes
+        assert_eq!(lf, flyway_checksum(b"select 1\n\nfrom dual;\n"));
+        assert_ne!(lf, flyway_checksum(b"select 1 from dual;\n"));
+    }
+
+    #[test]
+    fn parse_migration_file_name_should_split_version_and_description() {
+        let config = Config::default();
+        assert_eq!(
+            MigrationFileName {
+                version: Some("1.1".to_string()),
+                description: "add city crlf".to_string(),
+                script: "V1_1__add_city_crlf.sql".to_string(),
+            },
+            parse_migration_file_name("V1_1__add_city_crlf.sql", &config).unwrap()
+        );
+        let timestamp =
+            parse_migration_file_name("V2021_07_18_10_15_00.123__PKG_SNAFU.sql", &config).unwrap();
+        assert_eq!(
+            Some("2021.07.18.10.15.00.123".to_string()),
+            timestamp.version
+        );
+        assert_eq!("PKG SNAFU", timestamp.description);
+        let repeatable = parse_migration_file_name("R__mixed_line_endings.sql", &config).unwrap();
+        assert_eq!(None, repeatable.version);
+        assert_eq!("mixed line endings", repeatable.description);
+
+        for file_name in [
+            "afterMigrate.sql",
+            "V__no_version.sql",
+            "V1_no_separator.sql",
+            "V1__not_sql.txt",
+        ] {
+            assert!(
+                parse_migration_file_name(file_name, &config).is_err(),
+                "{}",
+                file_name
+            );
+        }
+
+        let config = Config {
+            version_prefix: "M".to_string(),
+            version_description_separator: "-".to_string(),
+            ..Config::default()
+        };
+        let custom = parse_migration_file_name("M3-fix_it.sql", &config).unwrap();
+        assert_eq!(Some("3".to_string()), custom.version);
+        assert_eq!("fix it", custom.description);
+    }
+
+    #[test]
+    fn insert_for_file_should_fill_in_parsed_name_and_checksum() {
+        let insert =
+            insert_for_file(&fixtures().join("V4__pkg_address.sql"), &Config::default()).unwrap();
+        assert_eq!(
+            "insert into \"flyway_schema_history\"\n  \
+             (\"installed_rank\", \"version\", \"description\", \"type\", \"script\", \"checksum\",\n   \
+             \"installed_by\", \"installed_on\", \"execution_time\", \"success\")\n\
+             values\n  \
+             (:installed_rank, '4', 'pkg address', 'SQL', 'V4__pkg_address.sql', -1598859226,\n   \
+             user, current_timestamp, :execution_time, 1);\n",
+            insert
+        );
+
+        let repeatable = MigrationFileName {
+            version: None,
+            description: "it's fixed".to_string(),
+            script: "R__it's_fixed.sql".to_string(),
+        };
+        let insert = schema_history_insert(&repeatable, 42, "history");
+        assert!(insert.starts_with("insert into \"history\"\n"));
+        assert!(insert
+            .contains("(:installed_rank, null, 'it''s fixed', 'SQL', 'R__it''s_fixed.sql', 42,"));
+    }
+}
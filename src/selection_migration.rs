@@ -0,0 +1,325 @@
+// Save the DDL selected in a SQL window as a repeatable migration, e.g. a view that was edited
+// as text rather than in the object browser.
+//
+// The object is taken from the first statement of the selection; the whole selection is
+// written, so a package specification and body selected together end up in one migration.
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::config::Config;
+use crate::cursor_object::normalize_identifier;
+use crate::flyway::{
+    configured_folder, ensure_gitattributes, ensure_owner_in_ddl, probe_writable,
+    repeatable_file_name, write_migration, FlywayError, WriteOutcome,
+};
+use crate::logging::target;
+use crate::paths;
+use crate::plsqldev_api::{PlsqlDevApi, SelectedObject};
+use crate::project_config::config_for_folder;
+use crate::statements::{split_statements, statement_starts};
+use crate::ui::{dialogs, MessageKind};
+
+const SELECTION_CAPTION: &str = "Repeatable migration from selection";
+
+#[derive(Debug, PartialEq)]
+pub enum SelectionError {
+    NothingSelected,
+    // the first statement of the selection
+    NotCreateStatement(String),
+    NotConnected,
+}
+
+impl Display for SelectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            SelectionError::NothingSelected => {
+                "Please select the DDL of the object first!".to_string()
+            }
+            SelectionError::NotCreateStatement(statement) => format!(
+                "The selection doesn't start with the creation of a view, package, type, trigger, function or procedure:\n{}",
+                statement
+            ),
+            SelectionError::NotConnected => {
+                "Not connected, the schema of the object can't be determined".to_string()
+            }
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+pub fn repeatable_migration_from_selection(api: &dyn PlsqlDevApi, config: &Config) {
+    let ddl = api.ide_get_selected_text();
+    let selected_object = match object_of_selection(api, &ddl) {
+        Ok(selected_object) => selected_object,
+        Err(e) => {
+            let kind = match e {
+                SelectionError::NothingSelected => MessageKind::Information,
+                _ => MessageKind::Error,
+            };
+            dialogs().show_message(&format!("{}", e), SELECTION_CAPTION, kind);
+            return;
+        }
+    };
+    debug!(target: target::FLYWAY, "Object of the selection: {}", selected_object);
+
+    let folder_name = match configured_folder(config, config.repeatable_folder.as_deref()) {
+        Some(folder) => folder,
+        None => dialogs().get_save_folder_name(),
+    };
+    // the folder picker returns an empty string if cancelled
+    if folder_name.is_empty() {
+        return;
+    }
+    let folder = Path::new(&folder_name);
+    let folder_display = paths::display(config.repo_root.as_deref(), &folder_name);
+    if let Err(e) = probe_writable(folder) {
+        let error = FlywayError::FolderNotWritable(folder_display, format!("{}", e));
+        dialogs().show_message(&format!("{}", error), SELECTION_CAPTION, MessageKind::Error);
+        return;
+    }
+    // the conventions of the target repository apply to this migration
+    let config = &config_for_folder(config, folder);
+    ensure_gitattributes(folder, config);
+
+    let (file_name, ddl) = selection_migration(&selected_object, &ddl, config);
+    let folder_display = paths::display(config.repo_root.as_deref(), &folder_name);
+    match write_migration(&folder.join(&file_name), &ddl, config) {
+        Ok(WriteOutcome::Written(_)) => dialogs().show_message(
+            &format!("Written {} to {}.", file_name, folder_display),
+            SELECTION_CAPTION,
+            MessageKind::Information,
+        ),
+        Ok(WriteOutcome::Unchanged(_)) => dialogs().show_message(
+            &format!(
+                "{} in {} already has this content and was left unchanged.",
+                file_name, folder_display
+            ),
+            SELECTION_CAPTION,
+            MessageKind::Information,
+        ),
+        Err(e) => dialogs().show_message(&format!("{}", e), SELECTION_CAPTION, MessageKind::Error),
+    }
+}
+
+// The object created by the first statement of the selection; unqualified names belong to the
+// schema of the current connection
+pub fn object_of_selection(
+    api: &dyn PlsqlDevApi,
+    ddl: &str,
+) -> Result<SelectedObject, SelectionError> {
+    let statement = match split_statements(ddl).into_iter().next() {
+        Some(statement) => statement,
+        None => return Err(SelectionError::NothingSelected),
+    };
+    let (object_type, owner, name) = match created_object(&statement) {
+        Some(created) => created,
+        None => {
+            let first_line = statement.lines().next().unwrap_or_default();
+            return Err(SelectionError::NotCreateStatement(first_line.to_string()));
+        }
+    };
+    let owner = match owner {
+        Some(owner) => owner,
+        None => {
+            let (username, _) = api.ide_get_connection_info();
+            if username.is_empty() {
+                return Err(SelectionError::NotConnected);
+            }
+            username.to_uppercase()
+        }
+    };
+    Ok(SelectedObject::new(&object_type, &owner, &name, ""))
+}
+
+// The object type, owner (if qualified) and name created by the statement; a package or type
+// body counts as the package or type
+fn created_object(statement: &str) -> Option<(String, Option<String>, String)> {
+    lazy_static! {
+        static ref CREATE: Regex = RegexBuilder::new(
+            r#"^\s*create\s+(?:or\s+replace\s+)?(?:(?:editionable|noneditionable)\s+)?(?:(?:no\s+)?force\s+)?(?:editioning\s+)?(package|type|view|trigger|function|procedure)(?:\s+body)?\s+((?:"[^"]+"|[a-z0-9_$#]+)(?:\s*\.\s*(?:"[^"]+"|[a-z0-9_$#]+))?)"#
+        )
+        .case_insensitive(true)
+        .build()
+        .unwrap();
+        // comments in front of the statement belong to it
+        static ref LEADING_COMMENTS: Regex =
+            Regex::new(r"^(?:\s*(?:--[^\n]*(?:\n|$)|/\*(?s:.*?)\*/))*").unwrap();
+        // a quoted name may contain dots itself
+        static ref NAME_PART: Regex = RegexBuilder::new(r#""[^"]+"|[a-z0-9_$#]+"#)
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+    }
+    let code = LEADING_COMMENTS.replace(statement, "");
+    let caps = CREATE.captures(&code)?;
+    let parts: Vec<String> = NAME_PART
+        .find_iter(&caps[2])
+        .map(|part| normalize_identifier(part.as_str()))
+        .collect();
+    let (owner, name) = match parts.as_slice() {
+        [owner, name] => (Some(owner.clone()), name.clone()),
+        _ => (None, parts[0].clone()),
+    };
+    Some((caps[1].to_uppercase(), owner, name))
+}
+
+// Write the create headers the way the IDE returns object sources, which the DDL transforms
+// expect: `create or replace` (a repeatable migration must be rerunnable), lower case, and
+// without the force keyword, which is added as configured. Only the starts of statements are
+// rewritten, not e.g. a `create view` in a comment or string literal.
+fn normalize_create_headers(ddl: &str) -> String {
+    lazy_static! {
+        static ref HEADER: Regex = RegexBuilder::new(
+            r"\Acreate\s+(?:or\s+replace\s+)?(?:(editionable|noneditionable)\s+)?(?:(?:no\s+)?force\s+)?(?:(editioning)\s+)?(package|type|view|trigger|function|procedure)(\s+body)?\s+"
+        )
+        .case_insensitive(true)
+        .build()
+        .unwrap();
+    }
+    let mut result = String::with_capacity(ddl.len());
+    let mut copied = 0;
+    for start in statement_starts(ddl) {
+        let caps = match HEADER.captures(&ddl[start..]) {
+            Some(caps) => caps,
+            None => continue,
+        };
+        result.push_str(&ddl[copied..start]);
+        result.push_str("create or replace ");
+        for keyword in [caps.get(1), caps.get(2), caps.get(3)]
+            .into_iter()
+            .flatten()
+        {
+            result.push_str(&format!("{} ", keyword.as_str().to_lowercase()));
+        }
+        if caps.get(4).is_some() {
+            result.push_str("body ");
+        }
+        copied = start + caps[0].len();
+    }
+    result.push_str(&ddl[copied..]);
+    result
+}
+
+// The file name and content of the migration: the DDL with the owner in it
+pub fn selection_migration(
+    selected_object: &SelectedObject,
+    ddl: &str,
+    config: &Config,
+) -> (String, String) {
+    let ddl = ensure_owner_in_ddl(
+        &normalize_create_headers(ddl.trim()),
+        &selected_object.object_type,
+        &selected_object.object_owner,
+        &selected_object.object_name,
+        config,
+    );
+    (repeatable_file_name(selected_object, config), ddl)
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::config::Config;
+    use crate::plsqldev_api::PlsqlDevApi;
+    use crate::selection_migration::*;
+
+    struct ConnectedApi;
+
+    impl PlsqlDevApi for ConnectedApi {
+        fn ide_get_connection_info(&self) -> (String, String) {
+            ("app".to_string(), "secret".to_string())
+        }
+    }
+
+    struct DisconnectedApi;
+
+    impl PlsqlDevApi for DisconnectedApi {}
+
+    fn parts(selected_object: &SelectedObject) -> (&str, &str, &str) {
+        (
+            &selected_object.object_type,
+            &selected_object.object_owner,
+            &selected_object.object_name,
+        )
+    }
+
+    #[test]
+    fn object_of_selection_should_take_name_and_type_from_the_ddl() {
+        let ddl = indoc! {"
+            -- addresses with their country
+            CREATE FORCE VIEW v_address AS
+            select a.*, c.name country_name
+              from address a join country c on c.id = a.country_id;
+        "};
+
+        let selected_object = object_of_selection(&ConnectedApi, ddl).unwrap();
+
+        assert_eq!(("VIEW", "APP", "V_ADDRESS"), parts(&selected_object));
+        let (file_name, migration) = selection_migration(&selected_object, ddl, &Config::default());
+        assert_eq!("R__V_ADDRESS.sql", file_name);
+        assert!(
+            migration.contains("create or replace force view APP.V_ADDRESS as"),
+            "{}",
+            migration
+        );
+    }
+
+    #[test]
+    fn object_of_selection_should_keep_qualified_and_quoted_names() {
+        let selected_object = object_of_selection(
+            &DisconnectedApi,
+            "CREATE OR REPLACE EDITIONABLE PACKAGE BODY hr.\"pkg_Mixed\" IS\nEND;\n/\n",
+        )
+        .unwrap();
+        assert_eq!(("PACKAGE", "HR", "pkg_Mixed"), parts(&selected_object));
+
+        let error = |ddl: &str| object_of_selection(&DisconnectedApi, ddl).err().unwrap();
+        assert_eq!(
+            SelectionError::NotConnected,
+            error("create view v_a as select 1 x from dual;")
+        );
+        assert_eq!(
+            SelectionError::NotCreateStatement("select * from address;".to_string()),
+            error("select * from address;")
+        );
+        assert_eq!(SelectionError::NothingSelected, error("  \n"));
+    }
+
+    #[test]
+    fn object_of_selection_should_keep_dots_in_quoted_names() {
+        let selected_object = object_of_selection(
+            &DisconnectedApi,
+            "create view hr.\"v.a\" as select 1 x from dual;",
+        )
+        .unwrap();
+        assert_eq!(("VIEW", "HR", "v.a"), parts(&selected_object));
+    }
+
+    #[test]
+    fn normalize_create_headers_should_only_rewrite_statement_starts() {
+        let ddl = indoc! {"
+            -- Create view v_a as a replacement
+            CREATE VIEW v_a AS
+            select 'create view v_b as' x from dual;
+            CREATE OR REPLACE PACKAGE BODY p IS
+              /* create type t */
+            END;
+            /
+        "};
+        assert_eq!(
+            indoc! {"
+                -- Create view v_a as a replacement
+                create or replace view v_a AS
+                select 'create view v_b as' x from dual;
+                create or replace package body p IS
+                  /* create type t */
+                END;
+                /
+            "},
+            normalize_create_headers(ddl)
+        );
+    }
+}
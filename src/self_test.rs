@@ -0,0 +1,262 @@
+// A self-test of the plugin environment for support requests, run from a hidden menu item.
+//
+// None of the checks touch the database. The report has one `PASS <check>` or
+// `FAIL <check>: <reason>` line per check, so it can be grepped from the log.
+use std::fs;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use chrono::Utc;
+
+use crate::config::{Config, ExportFormat};
+use crate::export::{render_export, ExportData, RenderOptions};
+use crate::flyway::{get_versioned_filename_impl, probe_writable};
+use crate::logging::target;
+use crate::plsqldev_api::PlsqlDevApi;
+use crate::prelude::CHARMODE_REJECTED;
+use crate::temp_files::spill_dir;
+use crate::ui::{clipboard, dialogs, MessageKind};
+
+const SELF_TEST_CAPTION: &str = "Self-test";
+const CLIPBOARD_PROBE: &str = "Xanthidae self-test äöü €";
+
+pub type Check<'a> = (String, Box<dyn Fn() -> Result<(), String> + 'a>);
+
+#[derive(Debug, PartialEq)]
+pub struct CheckResult {
+    pub name: String,
+    pub outcome: Result<(), String>,
+}
+
+pub fn run_self_test(api: &dyn PlsqlDevApi, config: &Config) {
+    let results = run_checks(environment_checks(api, config));
+    info!(target: target::EXPORT, "Self-test report:\n{}", render_report(&results));
+    let kind = match results.iter().all(|result| result.outcome.is_ok()) {
+        true => MessageKind::Information,
+        false => MessageKind::Error,
+    };
+    dialogs().show_report(
+        SELF_TEST_CAPTION,
+        &report_summary(&results),
+        &render_results(&results),
+        kind,
+    );
+}
+
+// Run all checks, turning a panicking check into a failed one
+pub fn run_checks(checks: Vec<Check>) -> Vec<CheckResult> {
+    checks
+        .into_iter()
+        .map(|(name, check)| {
+            let outcome = catch_unwind(AssertUnwindSafe(&check))
+                .unwrap_or_else(|_| Err("the check panicked".to_string()));
+            CheckResult { name, outcome }
+        })
+        .collect()
+}
+
+// One PASS / FAIL line per check
+pub fn render_results(results: &[CheckResult]) -> String {
+    let lines: Vec<String> = results
+        .iter()
+        .map(|result| match &result.outcome {
+            Ok(()) => format!("PASS {}", result.name),
+            Err(reason) => format!("FAIL {}: {}", result.name, reason),
+        })
+        .collect();
+    lines.join("\n")
+}
+
+pub fn report_summary(results: &[CheckResult]) -> String {
+    let passed = results.iter().filter(|r| r.outcome.is_ok()).count();
+    format!("{} of {} checks passed", passed, results.len())
+}
+
+pub fn render_report(results: &[CheckResult]) -> String {
+    format!("{}\n{}", render_results(results), report_summary(results))
+}
+
+fn environment_checks<'a>(api: &'a dyn PlsqlDevApi, config: &'a Config) -> Vec<Check<'a>> {
+    let mut checks: Vec<Check> = vec![(
+        "Temp folder is writable".to_string(),
+        Box::new(move || check_folder(&spill_dir(config))),
+    )];
+    if let Some(repo_root) = &config.repo_root {
+        checks.push((
+            "Repository root is writable".to_string(),
+            Box::new(move || check_folder(Path::new(repo_root))),
+        ));
+    }
+    checks.push((
+        "Clipboard round trip".to_string(),
+        Box::new(move || check_clipboard(config)),
+    ));
+    for format in ExportFormat::ALL {
+        checks.push((
+            format!("{:?} export", format),
+            Box::new(move || check_formatter(format, config)),
+        ));
+    }
+    checks.push((
+        "Versioned file name".to_string(),
+        Box::new(move || check_versioned_filename(config)),
+    ));
+    checks.push((
+        "Callbacks registered".to_string(),
+        Box::new(move || match api.missing_callbacks().as_slice() {
+            [] => Ok(()),
+            missing => Err(format!("missing callback indices {:?}", missing)),
+        }),
+    ));
+    checks.push((
+        "Charmode accepted".to_string(),
+        Box::new(move || match CHARMODE_REJECTED.load(Ordering::SeqCst) {
+            true => Err(format!(
+                "the IDE rejected CHARMODE={}",
+                config.charmode.as_deref().unwrap_or_default()
+            )),
+            false => Ok(()),
+        }),
+    ));
+    checks
+}
+
+fn check_folder(folder: &Path) -> Result<(), String> {
+    fs::create_dir_all(folder)
+        .and_then(|_| probe_writable(folder))
+        .map_err(|e| format!("{}: {}", folder.display(), e))
+}
+
+// Round trip a probe through the clipboard, restoring the previous content
+fn check_clipboard(config: &Config) -> Result<(), String> {
+    let previous = clipboard().get_clipboard_text().ok();
+    let result = clipboard()
+        .copy_to_clipboard(CLIPBOARD_PROBE, config.max_clipboard_bytes)
+        .and_then(|_| clipboard().get_clipboard_text());
+    if let Some(previous) = previous {
+        let _ = clipboard().copy_to_clipboard(&previous, config.max_clipboard_bytes);
+    }
+    match result {
+        Ok(text) if text == CLIPBOARD_PROBE => Ok(()),
+        Ok(text) => Err(format!("read back {:?}", text)),
+        Err(e) => Err(format!("{}", e)),
+    }
+}
+
+fn sample_data() -> ExportData {
+    ExportData {
+        headers: vec!["ID".to_string(), "NAME".to_string(), "AMOUNT".to_string()],
+        data: vec![
+            vec![
+                Some("1".to_string()),
+                Some("Snafu".to_string()),
+                Some("1.5".to_string()),
+            ],
+            vec![Some("2".to_string()), Some("O'Brien".to_string()), None],
+        ],
+        current_row: vec![],
+        prepared: true,
+        rows_received: 0,
+        ..ExportData::new()
+    }
+}
+
+fn check_formatter(format: ExportFormat, config: &Config) -> Result<(), String> {
+    let mut options = RenderOptions::from_config(config);
+    options.table_name = "SELF_TEST".to_string();
+    options.key_columns = vec!["ID".to_string()];
+    match render_export(&sample_data(), format, &options) {
+        Ok(text) if text.trim().is_empty() => Err("rendered nothing".to_string()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("{}", e)),
+    }
+}
+
+fn check_versioned_filename(config: &Config) -> Result<(), String> {
+    let file_name = get_versioned_filename_impl(config, Utc::now(), "self_test");
+    let valid = file_name.starts_with(&config.version_prefix)
+        && file_name.ends_with(&format!(
+            "{}self_test.sql",
+            config.version_description_separator
+        ))
+        && !file_name.contains(|c| "<>:\"/\\|?*".contains(c));
+    match valid {
+        true => Ok(()),
+        false => Err(format!("invalid file name {:?}", file_name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::self_test::*;
+
+    #[test]
+    fn run_checks_should_report_failing_and_panicking_checks() {
+        let checks: Vec<Check> = vec![
+            ("passes".to_string(), Box::new(|| Ok(()))),
+            (
+                "fails".to_string(),
+                Box::new(|| Err("disk full".to_string())),
+            ),
+            ("panics".to_string(), Box::new(|| panic!("boom"))),
+        ];
+
+        let results = run_checks(checks);
+
+        assert_eq!(
+            vec![
+                CheckResult {
+                    name: "passes".to_string(),
+                    outcome: Ok(())
+                },
+                CheckResult {
+                    name: "fails".to_string(),
+                    outcome: Err("disk full".to_string())
+                },
+                CheckResult {
+                    name: "panics".to_string(),
+                    outcome: Err("the check panicked".to_string())
+                },
+            ],
+            results
+        );
+        assert_eq!(
+            "PASS passes\nFAIL fails: disk full\nFAIL panics: the check panicked\n1 of 3 checks passed",
+            render_report(&results)
+        );
+    }
+
+    #[test]
+    fn environment_checks_should_pass_with_default_config() {
+        struct RegisteredApi;
+        impl PlsqlDevApi for RegisteredApi {}
+        let config = Config::default();
+
+        let results: Vec<CheckResult> = run_checks(environment_checks(&RegisteredApi, &config))
+            .into_iter()
+            // the clipboard is shared with other tests
+            .filter(|result| result.name != "Clipboard round trip")
+            .collect();
+
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        for format in ExportFormat::ALL {
+            assert!(names.contains(&format!("{:?} export", format).as_str()));
+        }
+        for name in [
+            "Temp folder is writable",
+            "Versioned file name",
+            "Callbacks registered",
+            "Charmode accepted",
+        ] {
+            assert!(names.contains(&name), "{} is missing", name);
+        }
+        assert_eq!(
+            format!("{} of {} checks passed", results.len(), results.len()),
+            report_summary(&results)
+        );
+        for result in &results {
+            assert_eq!(Ok(()), result.outcome, "{}", result.name);
+        }
+    }
+}
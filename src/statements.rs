@@ -0,0 +1,244 @@
+// Splitting a script (e.g. the text of an SQL window) into its statements.
+//
+// SQL statements end with `;`. PL/SQL blocks and stored code contain semicolons themselves,
+// so they end with a `/` on a line of its own, as in SQL*Plus. Semicolons and slashes inside
+// comments, string literals and quoted identifiers are ignored.
+use regex::{Regex, RegexBuilder};
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Code,
+    LineComment,
+    BlockComment,
+    // in a literal closed by this character followed by a quote; ' for plain literals
+    Literal(char),
+    QuotedIdentifier,
+}
+
+// Whether the statement starting with this code is terminated by a slash
+fn is_plsql(code: &str) -> bool {
+    lazy_static! {
+        static ref PLSQL: Regex = RegexBuilder::new(
+            r"^\s*(declare|begin|create\s+(or\s+replace\s+)?((editionable|noneditionable)\s+)?(function|procedure|package|type|trigger|library))\b"
+        )
+        .case_insensitive(true)
+        .build()
+        .unwrap();
+    }
+    PLSQL.is_match(code)
+}
+
+// The closing delimiter of a q'<delimiter>...' literal
+fn closing_delimiter(opening: char) -> char {
+    match opening {
+        '[' => ']',
+        '{' => '}',
+        '(' => ')',
+        '<' => '>',
+        c => c,
+    }
+}
+
+struct Splitter {
+    statements: Vec<String>,
+    // the byte offset of the first code of each statement, after the comments in front of it
+    starts: Vec<usize>,
+    // the current statement, and its code without comments
+    text: String,
+    code: String,
+    start: Option<usize>,
+    state: State,
+}
+
+impl Splitter {
+    fn finish_statement(&mut self, terminator: &str) {
+        if !self.code.trim().is_empty() {
+            self.statements
+                .push(format!("{}{}", self.text.trim(), terminator));
+            self.starts.extend(self.start);
+        }
+        self.text.clear();
+        self.code.clear();
+        self.start = None;
+    }
+
+    // `offset` is the byte offset of the line in the script
+    fn split_line(&mut self, line: &str, offset: usize) {
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let (position, c) = chars[i];
+            let next = chars.get(i + 1).map(|(_, c)| *c);
+            self.text.push(c);
+            let is_code_start = self.state == State::Code
+                && self.start.is_none()
+                && !c.is_whitespace()
+                && !(c == '-' && next == Some('-'))
+                && !(c == '/' && next == Some('*'));
+            if is_code_start {
+                self.start = Some(offset + position);
+            }
+            match self.state {
+                State::Code => match c {
+                    '-' if next == Some('-') => self.state = State::LineComment,
+                    '/' if next == Some('*') => {
+                        self.text.push('*');
+                        i += 1;
+                        self.state = State::BlockComment;
+                    }
+                    '\'' => {
+                        self.code.push(c);
+                        self.state = State::Literal('\'');
+                    }
+                    'q' | 'Q' if next == Some('\'') && chars.get(i + 2).is_some() => {
+                        let opening = chars[i + 2].1;
+                        self.text.push('\'');
+                        self.text.push(opening);
+                        self.code.push(c);
+                        i += 2;
+                        self.state = State::Literal(closing_delimiter(opening));
+                    }
+                    '"' => {
+                        self.code.push(c);
+                        self.state = State::QuotedIdentifier;
+                    }
+                    ';' if !is_plsql(&self.code) => {
+                        self.text.pop();
+                        self.finish_statement(";");
+                    }
+                    c => self.code.push(c),
+                },
+                State::LineComment => (),
+                State::BlockComment => {
+                    if c == '*' && next == Some('/') {
+                        self.text.push('/');
+                        i += 1;
+                        self.state = State::Code;
+                    }
+                }
+                State::Literal(closing) => {
+                    self.code.push(c);
+                    if closing == '\'' && c == '\'' {
+                        // '' is an escaped quote and ends and restarts the literal anyway
+                        self.state = State::Code;
+                    } else if c == closing && next == Some('\'') {
+                        self.text.push('\'');
+                        i += 1;
+                        self.state = State::Code;
+                    }
+                }
+                State::QuotedIdentifier => {
+                    self.code.push(c);
+                    if c == '"' {
+                        self.state = State::Code;
+                    }
+                }
+            }
+            i += 1;
+        }
+    }
+}
+
+// The statements of the script, trimmed and with their terminator: `;` for SQL statements,
+// a `/` line for PL/SQL. Comments in front of a statement belong to it; chunks consisting of
+// comments only are dropped.
+pub fn split_statements(script: &str) -> Vec<String> {
+    split(script).statements
+}
+
+// The byte offsets at which the statements of the script start, after the comments in front of
+// them
+pub fn statement_starts(script: &str) -> Vec<usize> {
+    split(script).starts
+}
+
+fn split(script: &str) -> Splitter {
+    let mut splitter = Splitter {
+        statements: vec![],
+        starts: vec![],
+        text: String::new(),
+        code: String::new(),
+        start: None,
+        state: State::Code,
+    };
+    let mut offset = 0;
+    for line in script.split_inclusive('\n') {
+        let line_offset = offset;
+        offset += line.len();
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if splitter.state == State::Code && line.trim() == "/" {
+            let terminator = match is_plsql(&splitter.code) {
+                true => "\n/",
+                // a slash after an SQL statement just executes it
+                false => ";",
+            };
+            splitter.finish_statement(terminator);
+            continue;
+        }
+        splitter.split_line(line, line_offset);
+        if splitter.state == State::LineComment {
+            splitter.state = State::Code;
+        }
+        splitter.text.push('\n');
+        splitter.code.push('\n');
+    }
+    let terminator = match is_plsql(&splitter.code) {
+        true => "\n/",
+        false => ";",
+    };
+    splitter.finish_statement(terminator);
+    splitter
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::statements::*;
+
+    #[test]
+    fn split_statements_should_split_sql_at_semicolons() {
+        let script = "create table t (id number);\n\n-- the key\nalter table t add constraint t_pk primary key (id);\ninsert into t values (1)";
+        assert_eq!(
+            vec![
+                "create table t (id number);",
+                "-- the key\nalter table t add constraint t_pk primary key (id);",
+                "insert into t values (1);",
+            ],
+            split_statements(script)
+        );
+    }
+
+    #[test]
+    fn split_statements_should_end_plsql_at_slash_lines() {
+        let script = "create or replace procedure p is\nbegin\n  null; -- nothing to do; yet\nend p;\n/\nbegin\n  p;\nend;\n/\nselect 1 from dual\n/\n";
+        assert_eq!(
+            vec![
+                "create or replace procedure p is\nbegin\n  null; -- nothing to do; yet\nend p;\n/",
+                "begin\n  p;\nend;\n/",
+                "select 1 from dual;",
+            ],
+            split_statements(script)
+        );
+    }
+
+    #[test]
+    fn split_statements_should_ignore_terminators_in_literals_and_comments() {
+        let script = "insert into t values ('a;b', q'[it's; fine]', 'x''; y');\n/* ; \n / */\ncomment on table \"T;1\" is 'c';\n-- only a comment;\n";
+        assert_eq!(
+            vec![
+                "insert into t values ('a;b', q'[it's; fine]', 'x''; y');",
+                "/* ; \n / */\ncomment on table \"T;1\" is 'c';",
+            ],
+            split_statements(script)
+        );
+    }
+
+    #[test]
+    fn statement_starts_should_skip_comments_in_front_of_statements() {
+        let script = "create view v as select 'a;' x from dual;\r\n/* the body */ -- ü\n  create or replace package body p is\nend;\n/\n-- only a comment\n";
+        let starts = statement_starts(script);
+        assert_eq!(2, starts.len());
+        assert_eq!(0, starts[0]);
+        assert!(script[starts[1]..].starts_with("create or replace package body p"));
+    }
+}
@@ -2,15 +2,13 @@ use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 use std::slice::from_raw_parts;
 
-use winapi::um::winnt::PWSTR;
+use crate::config::{LineEnding, SourceEncoding};
 
 // Convert a C string (const char*) into a Rust string
 // see https://doc.rust-lang.org/std/ffi/struct.CStr.html
 #[allow(dead_code)]
 pub fn ptr_to_string(ptr: *const c_char) -> String {
-    unsafe {
-        return CStr::from_ptr(ptr).to_string_lossy().into_owned();
-    }
+    unsafe { CStr::from_ptr(ptr).to_string_lossy().into_owned() }
 }
 
 // Simply converts a raw C string into a owned Rust CString
@@ -20,16 +18,17 @@ pub fn ptr_to_cstring(ptr: *const c_char) -> CString {
 }
 
 // Converts a Vec<u8> buffer reference to an owned Rust String
+#[cfg_attr(not(all(windows, feature = "windows-ui")), allow(dead_code))]
 pub fn vec_with_nul_to_string(bytes: &[u8]) -> String {
     let first_nul_char_pos = bytes
         .iter()
         .position(|&c| c == b'\0')
         .expect("Could not find null character in buffer");
 
-    return CStr::from_bytes_with_nul(&bytes[0..first_nul_char_pos + 1])
+    CStr::from_bytes_with_nul(&bytes[0..first_nul_char_pos + 1])
         .expect("CStr::from_bytes_with_nul failed")
         .to_string_lossy()
-        .into_owned();
+        .into_owned()
 }
 
 // Converts a Vec<u8> buffer reference to an owned CString
@@ -40,28 +39,281 @@ pub fn vec_with_nul_to_cstring(bytes: &[u8]) -> CString {
         .position(|&c| c == b'\0')
         .expect("Could not find null character in buffer");
 
-    return CStr::from_bytes_with_nul(&bytes[0..first_nul_char_pos + 1])
+    CStr::from_bytes_with_nul(&bytes[0..first_nul_char_pos + 1])
         .expect("CStr::from_bytes_with_nul failed")
-        .to_owned();
+        .to_owned()
 }
 
 // Converts a Windows PWSTR (a wchar*) into a Rust CString
-pub fn pwstr_to_cstring(ptr: PWSTR) -> CString {
+// (takes a plain pointer to u16, so this doesn't depend on winapi and can be tested everywhere)
+// Surrogate pairs are combined into a single (supplementary plane) character; lone surrogates
+// are replaced by U+FFFD, so the result is always valid UTF-8. As the scan stops at the first
+// NUL unit, the resulting string can't contain an interior NUL byte either.
+#[cfg_attr(not(all(windows, feature = "windows-ui")), allow(dead_code))]
+pub fn pwstr_to_cstring(ptr: *const u16) -> CString {
     unsafe {
         let len = (0_usize..)
-            .find(|&n| *ptr.offset(n as isize) == 0)
+            .find(|&n| *ptr.add(n) == 0)
             .expect("Null terminator not found");
 
         let array: &[u16] = from_raw_parts(ptr, len);
-        let str = String::from_utf16_lossy(array);
-        CString::new(str).unwrap()
+        let str: String = char::decode_utf16(array.iter().cloned())
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+        CString::new(str).expect("UTF-16 string contains no NUL character")
     }
 }
 
+// The name with the characters Windows doesn't allow in file names replaced by an underscore
+pub fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+// Escape the pipes in a cell of a Jira / Confluence wiki table, where they would separate cells
+// (and `||` header cells); every pipe is escaped on its own. Backslashes are escaped first, as a
+// cell ending in one would escape the pipe after it; `\\` is a line break, so the entity is used.
+pub fn escape_wiki_pipe(s: &str) -> String {
+    s.replace('\\', "&#92;").replace('|', "\\|")
+}
+
+// The value as a JSON string literal, quotes included
+pub fn json_string(value: &str) -> String {
+    let mut result = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+// The value with the characters that have a meaning in HTML replaced by entities, so it can be
+// used as text or an attribute value
+pub fn escape_html(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#39;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+// Converts all line endings in the given text to the given style; `Keep` leaves the text untouched
+pub fn convert_line_endings(text: &str, line_ending: LineEnding) -> String {
+    match line_ending {
+        LineEnding::Keep => text.to_string(),
+        LineEnding::Lf => text.replace("\r\n", "\n"),
+        LineEnding::CrLf => text.replace("\r\n", "\n").replace('\n', "\r\n"),
+    }
+}
+
+// Windows-1252 characters of the bytes 0x80 - 0x9F; the five undefined bytes are mapped to the
+// C1 control characters of the same value, like Windows' MultiByteToWideChar does
+const CP1252_HIGH_CONTROL: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+fn cp1252_char(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => CP1252_HIGH_CONTROL[(byte - 0x80) as usize],
+        // everything else is identical to ISO-8859-1, i.e. the first 256 code points
+        _ => byte as char,
+    }
+}
+
+fn cp1252_byte(c: char) -> Option<u8> {
+    match c as u32 {
+        0x00..=0x7F | 0xA0..=0xFF => Some(c as u8),
+        _ => CP1252_HIGH_CONTROL
+            .iter()
+            .position(|&high| high == c)
+            .map(|i| 0x80 + i as u8),
+    }
+}
+
+// Interpret the bytes as Windows-1252 text; never fails, as every byte has a meaning
+pub fn decode_cp1252(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| cp1252_char(b)).collect()
+}
+
+// The Windows-1252 bytes of the text, or None if it contains characters Windows-1252 can't encode
+pub fn encode_cp1252(text: &str) -> Option<Vec<u8>> {
+    text.chars().map(cp1252_byte).collect()
+}
+
+// Check for the typical result of UTF-8 being decoded as Windows-1252 once too often:
+// a lead byte of a two byte sequence (e.g. Ã for 0xC3) followed by a continuation byte (0x80 - 0xBF)
+pub fn looks_double_encoded(text: &str) -> bool {
+    let is_lead = |c: char| matches!(cp1252_byte(c), Some(0xC2..=0xDF));
+    let is_continuation = |c: char| matches!(cp1252_byte(c), Some(0x80..=0xBF));
+    text.chars()
+        .zip(text.chars().skip(1))
+        .any(|(a, b)| is_lead(a) && is_continuation(b))
+}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+pub fn strip_utf8_bom(bytes: &[u8]) -> &[u8] {
+    bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes)
+}
+
+// Compare file contents, ignoring a leading UTF-8 byte order mark on either side
+pub fn same_content_ignoring_bom(a: &[u8], b: &[u8]) -> bool {
+    strip_utf8_bom(a) == strip_utf8_bom(b)
+}
+
+// How `decode_ide_text` interpreted its input
+#[derive(Debug, PartialEq)]
+pub enum DecodingPath {
+    Utf8,
+    // not valid UTF-8, decoded as Windows-1252
+    Cp1252,
+    // valid UTF-8, but double encoded; reverted
+    RepairedDoubleEncoding,
+}
+
+// Decode text delivered by the IDE. In `Auto` mode, invalid UTF-8 is taken as Windows-1252,
+// and double encoded UTF-8 (mojibake like "Ã¼" instead of "ü") is reverted if that yields
+// valid UTF-8. A leading byte order mark is dropped, as files are written without one.
+pub fn decode_ide_text(bytes: &[u8], encoding: SourceEncoding) -> (String, DecodingPath) {
+    let (text, path) = match encoding {
+        SourceEncoding::Utf8 => (
+            String::from_utf8_lossy(bytes).into_owned(),
+            DecodingPath::Utf8,
+        ),
+        SourceEncoding::Cp1252 => (decode_cp1252(bytes), DecodingPath::Cp1252),
+        SourceEncoding::Auto => match std::str::from_utf8(bytes) {
+            Err(_) => (decode_cp1252(bytes), DecodingPath::Cp1252),
+            Ok(text) if looks_double_encoded(text) => {
+                match encode_cp1252(text).and_then(|single| String::from_utf8(single).ok()) {
+                    Some(repaired) => (repaired, DecodingPath::RepairedDoubleEncoding),
+                    None => (text.to_string(), DecodingPath::Utf8),
+                }
+            }
+            Ok(text) => (text.to_string(), DecodingPath::Utf8),
+        },
+    };
+    match text.strip_prefix('\u{FEFF}') {
+        Some(without_bom) => (without_bom.to_string(), path),
+        None => (text, path),
+    }
+}
+
+// Whether the character attaches to the one before it (combining marks, variation selectors,
+// zero width joiner), so a line must not be broken before it
+fn is_combining(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{200D}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{E0100}'..='\u{E01EF}')
+}
+
+// Splits the text into characters with their combining sequences
+fn char_sequences(text: &str) -> Vec<&str> {
+    let mut sequences: Vec<&str> = vec![];
+    let mut start = 0;
+    let mut joined = false;
+    for (pos, c) in text.char_indices() {
+        if pos > 0 && !is_combining(c) && !joined {
+            sequences.push(&text[start..pos]);
+            start = pos;
+        }
+        // the character following a zero width joiner belongs to the same sequence
+        joined = c == '\u{200D}';
+    }
+    if start < text.len() {
+        sequences.push(&text[start..]);
+    }
+    sequences
+}
+
+// Wraps the text into lines of at most `width` characters (a character with its combining
+// marks counting as one), breaking at the last whitespace that fits, or after `width`
+// characters if there is none. A width of 0 doesn't wrap.
+pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let sequences = char_sequences(text);
+    let is_whitespace = |sequence: &str| sequence.chars().all(char::is_whitespace);
+    let mut lines = vec![];
+    let mut start = 0;
+    while width > 0 && sequences.len() - start > width {
+        // whitespace right after the limit is a break point, too
+        let window = &sequences[start..=start + width];
+        match window.iter().rposition(|sequence| is_whitespace(sequence)) {
+            Some(0) => start += 1,
+            Some(pos) => {
+                lines.push(window[..pos].concat().trim_end().to_string());
+                start += pos + 1;
+                // the next line doesn't start with the rest of the whitespace
+                while start < sequences.len() && is_whitespace(sequences[start]) {
+                    start += 1;
+                }
+            }
+            None => {
+                lines.push(window[..width].concat());
+                start += width;
+            }
+        }
+    }
+    lines.push(sequences[start..].concat());
+    lines
+}
+
 #[cfg(test)]
 mod tests {
     use crate::string_utils::*;
 
+    #[test]
+    fn json_string_should_escape_quotes_and_control_characters() {
+        assert_eq!(r#""a\"b\\c\n\u0001""#, json_string("a\"b\\c\n\u{1}"));
+    }
+
+    #[test]
+    fn escape_html_should_replace_markup_characters() {
+        assert_eq!(
+            "&lt;b&gt;Tom &amp; &quot;Jerry&quot;&#39;s&lt;/b&gt;",
+            escape_html("<b>Tom & \"Jerry\"'s</b>")
+        );
+        assert_eq!("&amp;amp;", escape_html("&amp;"));
+    }
+
+    #[test]
+    fn escape_wiki_pipe_should_escape_every_pipe() {
+        assert_eq!("a\\|b", escape_wiki_pipe("a|b"));
+        assert_eq!("a\\|\\|b", escape_wiki_pipe("a||b"));
+        assert_eq!("\\|a\\|", escape_wiki_pipe("|a|"));
+        assert_eq!("\\|\\|\\|", escape_wiki_pipe("|||"));
+        assert_eq!("no pipes", escape_wiki_pipe("no pipes"));
+        assert_eq!("C:&#92;temp&#92;", escape_wiki_pipe("C:\\temp\\"));
+        assert_eq!("a&#92;\\|b", escape_wiki_pipe("a\\|b"));
+    }
+
     #[test]
     fn pwstr_to_cstr_should_work_for_ascii() {
         let input: Vec<u16> = vec![65, 0]; // 65: ASCII code of 'A', PWSTR is just a synonym for *mut u16
@@ -82,4 +334,152 @@ mod tests {
         let got: CString = pwstr_to_cstring(input.as_ptr() as *mut u16);
         assert_eq!(CString::new("и").unwrap(), got);
     }
+
+    #[test]
+    fn pwstr_to_cstr_should_combine_surrogate_pairs() {
+        let input: Vec<u16> = vec![0xD83D, 0xDE00, 0]; // surrogate pair for U+1F600 (grinning face)
+        let got: CString = pwstr_to_cstring(input.as_ptr() as *mut u16);
+        assert_eq!(CString::new("\u{1F600}").unwrap(), got);
+        assert_eq!("\u{1F600}", got.to_str().unwrap());
+    }
+
+    #[test]
+    fn pwstr_to_cstr_should_replace_lone_surrogates() {
+        let input: Vec<u16> = vec![0xD83D, 65, 0xDE00, 0]; // high surrogate, 'A', low surrogate
+        let got: CString = pwstr_to_cstring(input.as_ptr() as *mut u16);
+        assert_eq!("\u{FFFD}A\u{FFFD}", got.to_str().unwrap());
+    }
+
+    #[test]
+    fn convert_line_endings_should_not_duplicate_carriage_returns() {
+        let input = "create or replace view v as\r\nselect *\nfrom dual;\n";
+        assert_eq!(
+            "create or replace view v as\r\nselect *\r\nfrom dual;\r\n",
+            convert_line_endings(input, LineEnding::CrLf)
+        );
+        assert_eq!(
+            "create or replace view v as\nselect *\nfrom dual;\n",
+            convert_line_endings(input, LineEnding::Lf)
+        );
+    }
+
+    #[test]
+    fn decode_cp1252_should_decode_german_and_french_characters() {
+        assert_eq!(
+            "Grüße, Öl, Äpfel",
+            decode_cp1252(b"Gr\xfc\xdfe, \xd6l, \xc4pfel")
+        );
+        assert_eq!(
+            "café à la crème, cœur, 5€",
+            decode_cp1252(b"caf\xe9 \xe0 la cr\xe8me, c\x9cur, 5\x80")
+        );
+    }
+
+    #[test]
+    fn encode_cp1252_should_reverse_decode_cp1252() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(
+            Some(all_bytes.clone()),
+            encode_cp1252(&decode_cp1252(&all_bytes))
+        );
+        assert_eq!(None, encode_cp1252("Привет"));
+    }
+
+    #[test]
+    fn looks_double_encoded_should_detect_mojibake() {
+        // "Grüße" and "cœur" as UTF-8, decoded as Windows-1252
+        assert!(looks_double_encoded("GrÃ¼ÃŸe"));
+        assert!(looks_double_encoded("cÅ“ur"));
+        assert!(!looks_double_encoded("Grüße"));
+        assert!(!looks_double_encoded("Ã la carte"));
+        assert!(!looks_double_encoded("select 'Â' from dual"));
+    }
+
+    #[test]
+    fn decode_ide_text_should_repair_cp1252_and_double_encoding() {
+        assert_eq!(
+            ("Grüße".to_string(), DecodingPath::Utf8),
+            decode_ide_text("Grüße".as_bytes(), SourceEncoding::Auto)
+        );
+        assert_eq!(
+            ("Grüße".to_string(), DecodingPath::Cp1252),
+            decode_ide_text(b"Gr\xfc\xdfe", SourceEncoding::Auto)
+        );
+        assert_eq!(
+            (
+                "crème brûlée".to_string(),
+                DecodingPath::RepairedDoubleEncoding
+            ),
+            decode_ide_text("crÃ¨me brÃ»lÃ©e".as_bytes(), SourceEncoding::Auto)
+        );
+        // forcing an interpretation disables the heuristics
+        assert_eq!(
+            ("GrÃ¼ÃŸe".to_string(), DecodingPath::Utf8),
+            decode_ide_text("GrÃ¼ÃŸe".as_bytes(), SourceEncoding::Utf8)
+        );
+        assert_eq!(
+            ("GrÃ¼ÃŸe".to_string(), DecodingPath::Cp1252),
+            decode_ide_text("Grüße".as_bytes(), SourceEncoding::Cp1252)
+        );
+    }
+
+    #[test]
+    fn decode_ide_text_should_drop_byte_order_mark() {
+        assert_eq!(
+            ("select 1 from dual".to_string(), DecodingPath::Utf8),
+            decode_ide_text(b"\xef\xbb\xbfselect 1 from dual", SourceEncoding::Auto)
+        );
+    }
+
+    #[test]
+    fn same_content_ignoring_bom_should_ignore_leading_bom_only() {
+        let with_bom = b"\xEF\xBB\xBFcreate or replace view v as select 1 from dual;\n";
+        let without_bom = b"create or replace view v as select 1 from dual;\n";
+        assert!(same_content_ignoring_bom(with_bom, without_bom));
+        assert!(same_content_ignoring_bom(without_bom, with_bom));
+        assert!(same_content_ignoring_bom(with_bom, with_bom));
+        assert!(!same_content_ignoring_bom(b"select 1\n", b"select 2\n"));
+        assert!(!same_content_ignoring_bom(
+            b"select 1\xEF\xBB\xBF\n",
+            b"select 1\n"
+        ));
+    }
+
+    #[test]
+    fn wrap_text_should_keep_text_of_exactly_the_width() {
+        assert_eq!(vec!["abcde"], wrap_text("abcde", 5));
+        assert_eq!(vec!["abcde", "f"], wrap_text("abcdef", 5));
+        assert_eq!(vec!["abcde", "fgh"], wrap_text("abcde fgh", 5));
+        assert_eq!(vec!["",], wrap_text("", 5));
+        assert_eq!(vec!["abcdef"], wrap_text("abcdef", 0));
+    }
+
+    #[test]
+    fn wrap_text_should_break_at_whitespace() {
+        assert_eq!(
+            vec!["Lorem", "ipsum", "dolor sit", "amet"],
+            wrap_text("Lorem ipsum dolor sit amet", 10)
+        );
+        assert_eq!(vec!["ab", "cd"], wrap_text("ab   cd", 3));
+    }
+
+    #[test]
+    fn wrap_text_should_break_strings_without_whitespace_at_the_width() {
+        assert_eq!(vec!["abcd", "efgh", "ij"], wrap_text("abcdefghij", 4));
+    }
+
+    #[test]
+    fn wrap_text_should_not_split_multi_byte_characters_or_combining_sequences() {
+        // U+1F600 is a surrogate pair in UTF-16 and 4 bytes in UTF-8
+        assert_eq!(vec!["ab😀", "cd"], wrap_text("ab😀cd", 3));
+        assert_eq!(vec!["äöü", "ß€"], wrap_text("äöüß€", 3));
+        // "e" followed by a combining acute accent counts as one character
+        assert_eq!(vec!["abe\u{0301}", "f"], wrap_text("abe\u{0301}f", 3));
+        // family emoji joined by zero width joiners
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        assert_eq!(
+            vec![format!("a{}", family), "b".to_string()],
+            wrap_text(&format!("a{}b", family), 2)
+        );
+    }
 }
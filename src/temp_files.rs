@@ -0,0 +1,264 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::logging::target;
+use crate::prelude::read_config;
+
+// all spill files start with this prefix, so the sweeper never touches foreign files
+const TEMP_FILE_PREFIX: &str = "xanthidae_";
+const TEMP_FILE_SUFFIX: &str = ".tmp";
+const DEFAULT_SPILL_FOLDER: &str = "xanthidae";
+// spill files older than this are considered orphans of a crashed / killed IDE instance
+pub const ORPHAN_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    pub static ref TEMP_FILES: TempFileRegistry = TempFileRegistry::new(&spill_dir(&read_config()));
+}
+
+// The folder spill files are written to: either the configured one, or %TEMP%\xanthidae
+pub fn spill_dir(config: &Config) -> PathBuf {
+    match &config.spill_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::temp_dir().join(DEFAULT_SPILL_FOLDER),
+    }
+}
+
+// Keeps track of the temporary files (and folders) created by this plugin instance.
+//
+// Folders are created with `create_dir`, and deleted with everything in them by `release` once
+// they are no longer needed. A file that should survive (e.g. because it was moved to its final destination) can be
+// taken out of the registry with `claim`. Everything still registered is deleted by `release_all`
+// when the plugin is unloaded.
+pub struct TempFileRegistry {
+    dir: PathBuf,
+    files: Mutex<Vec<PathBuf>>,
+}
+
+impl TempFileRegistry {
+    pub fn new(dir: &Path) -> TempFileRegistry {
+        TempFileRegistry {
+            dir: dir.to_path_buf(),
+            files: Mutex::new(vec![]),
+        }
+    }
+
+    // Create a new, empty temporary folder and register it, e.g. to stage files before packing
+    // them. The name contains the process id, a timestamp and a counter, and the folder is
+    // created exclusively, so two IDE instances can never end up using the same one.
+    pub fn create_dir(&self, purpose: &str) -> std::io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        loop {
+            let path = self.dir.join(unique_file_name(purpose));
+            match fs::create_dir(&path) {
+                Ok(_) => {
+                    debug!(target: target::EXPORT, "Created temporary folder {:?}", path);
+                    self.files.lock().unwrap().push(path.clone());
+                    return Ok(path);
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Take the file out of the registry; the caller is now responsible for it.
+    pub fn claim(&self, path: &Path) -> Option<PathBuf> {
+        let mut files = self.files.lock().unwrap();
+        let position = files.iter().position(|p| p == path)?;
+        Some(files.remove(position))
+    }
+
+    // Delete the file and remove it from the registry.
+    pub fn release(&self, path: &Path) -> std::io::Result<()> {
+        if self.claim(path).is_none() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("{:?} is not a registered temporary file", path),
+            ));
+        }
+        debug!(target: target::EXPORT, "Deleting temporary file {:?}", path);
+        match remove(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    // Delete all files that are still registered, e.g. when the plugin is unloaded.
+    pub fn release_all(&self) {
+        let files: Vec<PathBuf> = self.files.lock().unwrap().drain(..).collect();
+        for path in files {
+            if let Err(e) = remove(&path) {
+                warn!(target: target::EXPORT, "Could not delete temporary file {:?}: {}", path, e);
+            }
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+// Delete a temporary file, or a temporary folder with its content
+fn remove(path: &Path) -> std::io::Result<()> {
+    match path.is_dir() {
+        true => fs::remove_dir_all(path),
+        false => fs::remove_file(path),
+    }
+}
+
+fn unique_file_name(purpose: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!(
+        "{}{}_{}_{}_{}{}",
+        TEMP_FILE_PREFIX,
+        process::id(),
+        nanos,
+        COUNTER.fetch_add(1, Ordering::SeqCst),
+        purpose,
+        TEMP_FILE_SUFFIX
+    )
+}
+
+// Delete spill files (and folders) in `dir` that were last modified more than `max_age` before
+// `now`.
+// Returns the number of deleted files. Files not created by this plugin are left alone.
+pub fn sweep_orphans(dir: &Path, max_age: Duration, now: SystemTime) -> std::io::Result<usize> {
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+    let mut deleted = 0;
+    for entry in fs::read_dir(dir)?.flatten() {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if !file_name.starts_with(TEMP_FILE_PREFIX) || !file_name.ends_with(TEMP_FILE_SUFFIX) {
+            continue;
+        }
+        let modified = match entry.metadata().and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(_) => continue,
+        };
+        let is_orphan = now
+            .duration_since(modified)
+            .map(|age| age > max_age)
+            .unwrap_or(false);
+        if is_orphan {
+            match remove(&entry.path()) {
+                Ok(_) => deleted += 1,
+                Err(e) => {
+                    warn!(target: target::EXPORT, "Could not delete orphaned file {:?}: {}", entry.path(), e)
+                }
+            }
+        }
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    use crate::temp_files::*;
+
+    fn create_test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xanthidae_test_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn create_dir_should_return_unique_folders() {
+        let dir = create_test_dir("unique");
+        let registry = TempFileRegistry::new(&dir);
+        let first = registry.create_dir("export").unwrap();
+        let second = registry.create_dir("export").unwrap();
+        assert_ne!(first, second);
+        assert!(first.exists());
+        assert!(second.exists());
+        registry.release_all();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn release_should_delete_folder() {
+        let dir = create_test_dir("release");
+        let registry = TempFileRegistry::new(&dir);
+        let path = registry.create_dir("export").unwrap();
+        registry.release(&path).unwrap();
+        assert!(!path.exists());
+        assert!(registry.release(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn claimed_folder_should_survive_release_all() {
+        let dir = create_test_dir("claim");
+        let registry = TempFileRegistry::new(&dir);
+        let claimed = registry.create_dir("export").unwrap();
+        let unclaimed = registry.create_dir("export").unwrap();
+        assert_eq!(Some(claimed.clone()), registry.claim(&claimed));
+        registry.release_all();
+        assert!(claimed.exists());
+        assert!(!unclaimed.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sweep_orphans_should_only_delete_old_spill_files() {
+        let dir = create_test_dir("sweep");
+        let registry = TempFileRegistry::new(&dir);
+        let spill_file = registry.create_dir("export").unwrap();
+        let foreign_file = dir.join("foreign.tmp");
+        fs::write(&foreign_file, "keep me").unwrap();
+
+        // right now, nothing is old enough to be swept
+        let deleted = sweep_orphans(&dir, ORPHAN_MAX_AGE, SystemTime::now()).unwrap();
+        assert_eq!(0, deleted);
+        assert!(spill_file.exists());
+
+        // pretend two days have passed
+        let later = SystemTime::now() + Duration::from_secs(2 * 24 * 60 * 60);
+        let deleted = sweep_orphans(&dir, ORPHAN_MAX_AGE, later).unwrap();
+        assert_eq!(1, deleted);
+        assert!(!spill_file.exists());
+        assert!(foreign_file.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn release_should_delete_temporary_folders_with_their_content() {
+        let dir = create_test_dir("folder");
+        let registry = TempFileRegistry::new(&dir);
+        let staging = registry.create_dir("zip").unwrap();
+        fs::write(staging.join("R__V_SNAFU.sql"), "select 1 from dual;").unwrap();
+        let kept = registry.create_dir("zip").unwrap();
+        assert_ne!(staging, kept);
+
+        registry.release(&staging).unwrap();
+        assert!(!staging.exists());
+        registry.release_all();
+        assert!(!kept.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sweep_orphans_should_ignore_missing_folder() {
+        let dir = std::env::temp_dir().join("xanthidae_test_does_not_exist");
+        assert_eq!(
+            0,
+            sweep_orphans(&dir, ORPHAN_MAX_AGE, SystemTime::now()).unwrap()
+        );
+    }
+}
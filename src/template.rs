@@ -0,0 +1,134 @@
+// Header / footer templates for migration files.
+//
+// Templates contain tokens like `{owner}` that are substituted when the file is written;
+// unknown tokens are kept as they are, so e.g. braces in SQL comments don't get lost.
+use std::borrow::Cow;
+
+use chrono::{DateTime, Utc};
+use regex::{Captures, Regex};
+
+use crate::config::Config;
+
+// The values available for substitution; empty values are substituted as empty strings
+#[derive(Default)]
+pub struct TemplateValues {
+    pub object_type: String,
+    pub owner: String,
+    pub name: String,
+    pub timestamp: String,
+    // the date part of the timestamp
+    pub date: String,
+    pub author: String,
+    // the version of a versioned migration, e.g. 2021_07_18_10_15_00 or 42
+    pub version: String,
+}
+
+impl TemplateValues {
+    // Values for an export happening now, by the current Windows user
+    pub fn new(object_type: &str, owner: &str, name: &str, now: DateTime<Utc>) -> TemplateValues {
+        TemplateValues {
+            object_type: object_type.to_string(),
+            owner: owner.to_string(),
+            name: name.to_string(),
+            timestamp: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            date: now.format("%Y-%m-%d").to_string(),
+            author: current_user(),
+            version: String::new(),
+        }
+    }
+
+    fn get(&self, token: &str) -> Option<&str> {
+        match token {
+            "object_type" => Some(&self.object_type),
+            "owner" => Some(&self.owner),
+            "name" => Some(&self.name),
+            "timestamp" => Some(&self.timestamp),
+            "date" => Some(&self.date),
+            "author" => Some(&self.author),
+            "version" => Some(&self.version),
+            _ => None,
+        }
+    }
+}
+
+fn current_user() -> String {
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_default()
+}
+
+// Replace all known {token}s in the template with their values
+pub fn substitute_tokens(template: &str, values: &TemplateValues) -> String {
+    lazy_static! {
+        static ref TOKEN: Regex = Regex::new(r"\{(\w+)\}").unwrap();
+    }
+    TOKEN
+        .replace_all(template, |caps: &Captures| match values.get(&caps[1]) {
+            Some(value) => value.to_string(),
+            None => caps[0].to_string(),
+        })
+        .into_owned()
+}
+
+// Surround the migration content with the configured header and footer
+pub fn apply_templates<'a>(
+    content: &'a str,
+    values: &TemplateValues,
+    config: &Config,
+) -> Cow<'a, str> {
+    if config.header_template.is_none() && config.footer_template.is_none() {
+        return Cow::Borrowed(content);
+    }
+    let render = |template: &Option<String>| match template {
+        Some(template) => substitute_tokens(template, values),
+        None => String::new(),
+    };
+    Cow::Owned(format!(
+        "{}{}{}",
+        render(&config.header_template),
+        content,
+        render(&config.footer_template)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::template::*;
+
+    fn create_test_values() -> TemplateValues {
+        TemplateValues {
+            object_type: "PACKAGE".to_string(),
+            owner: "SCOTT".to_string(),
+            name: "PKG_SNAFU".to_string(),
+            timestamp: "2021-07-18 10:15:00".to_string(),
+            date: "2021-07-18".to_string(),
+            author: "jdoe".to_string(),
+            version: "42".to_string(),
+        }
+    }
+
+    #[test]
+    fn substitute_tokens_should_replace_supported_and_keep_unknown_tokens() {
+        let template = "-- {object_type} {owner}.{name} exported {timestamp} ({date}) by {author} (V{version}) {ticket}\n";
+        assert_eq!(
+            "-- PACKAGE SCOTT.PKG_SNAFU exported 2021-07-18 10:15:00 (2021-07-18) by jdoe (V42) {ticket}\n",
+            substitute_tokens(template, &create_test_values())
+        );
+    }
+
+    #[test]
+    fn apply_templates_should_add_header_and_footer() {
+        let mut config = Config::default();
+        assert_eq!(
+            "select 1 from dual;\n",
+            apply_templates("select 1 from dual;\n", &create_test_values(), &config)
+        );
+        config.header_template = Some("-- {name}\n".to_string());
+        config.footer_template = Some("-- end of {name}\n".to_string());
+        assert_eq!(
+            "-- PKG_SNAFU\nselect 1 from dual;\n-- end of PKG_SNAFU\n",
+            apply_templates("select 1 from dual;\n", &create_test_values(), &config)
+        );
+    }
+}
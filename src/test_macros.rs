@@ -0,0 +1,12 @@
+// Macros shared by the tests of several modules; declared before all other modules, so they
+// can use them
+
+// Create a vector from string literals, i.e. vec_of_strings!["a", "b", "c"]
+macro_rules! vec_of_strings {
+  ($($x:expr),*) => (vec![$($x.to_string()),*]);
+}
+
+// Create a row from string literals, an empty one is NULL (as captured from the IDE)
+macro_rules! vec_of_cells {
+  ($($x:expr),*) => (vec![$(Some($x.to_string()).filter(|v: &String| !v.is_empty())),*]);
+}
@@ -0,0 +1,528 @@
+// Abstraction over the user interface (dialogs and clipboard).
+//
+// On Windows (with the `windows-ui` feature), these are backed by the Windows API. Everywhere else,
+// headless stubs are used, so the platform independent core of the plugin can be built and tested
+// e.g. on Linux.
+use std::fmt::{Display, Formatter};
+use std::io::Error;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+
+const MEGABYTE: f64 = 1024.0 * 1024.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageKind {
+    Information,
+    Error,
+}
+
+// What came out of the save file dialog; the headless one is always cancelled
+#[derive(Debug, PartialEq)]
+#[cfg_attr(not(all(windows, feature = "windows-ui")), allow(dead_code))]
+pub enum SaveDialogOutcome {
+    // the folder and the file name chosen
+    Chosen(PathBuf, String),
+    Cancelled,
+    // confirmed without entering a name
+    EmptyName,
+    Failed(DialogError),
+}
+
+// A common dialog failed (rather than being cancelled), with the CommDlgExtendedError code
+#[derive(Debug, PartialEq)]
+pub struct DialogError(pub u32);
+
+impl Display for DialogError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // CDERR_* and FNERR_* from commdlg.h
+        let reason = match self.0 {
+            0x0001 => "the size of the dialog structure is invalid",
+            0x0002 => "the dialog could not be initialized",
+            0x0003 | 0x0004 => "the dialog template is missing",
+            0x0005 => "a string resource could not be loaded",
+            0x0006 => "a dialog resource could not be found",
+            0x0007 | 0x0008 => "a dialog resource could not be loaded",
+            0x0009 | 0x000A => "memory could not be allocated",
+            0x000B => "the hook procedure is missing",
+            0x000C => "a dialog message could not be registered",
+            0x3001 => "the list box could not be subclassed because memory ran out",
+            0x3002 => "the file name is invalid",
+            0x3003 => "the buffer for the file name is too small",
+            _ => "the dialog failed",
+        };
+        write!(f, "{} (error code 0x{:04X})", reason, self.0)
+    }
+}
+
+pub trait Dialogs: Sync {
+    fn show_message(&self, message: &str, caption: &str, kind: MessageKind);
+    // show a task dialog, with the summary as main instruction above the details
+    fn show_report(&self, caption: &str, summary: &str, details: &str, kind: MessageKind);
+    // ask a yes / no question; returns true if the user answered yes
+    fn ask_yes_no(&self, question: &str, caption: &str) -> bool;
+    // ask for a file name, returning the folder and the name chosen
+    fn get_save_file_name(&self) -> SaveDialogOutcome;
+    // ask for an existing file; None if the user cancelled
+    fn get_open_file_name(&self) -> Option<PathBuf>;
+    // ask for a folder; returns an empty string if the user cancelled
+    fn get_save_folder_name(&self) -> String;
+    // ask for a line of text, returned trimmed; Err if the user cancelled or entered nothing
+    fn get_user_input(&self, prompt: &str) -> Result<String, &'static str>;
+    // let the user tick the columns to export; returns one flag per header, or None if cancelled
+    fn select_columns(&self, headers: &[String]) -> Option<Vec<bool>>;
+    // let the user tick items of a checklist, all ticked initially; returns one flag per item,
+    // or None if cancelled
+    fn select_items(&self, title: &str, items: &[String]) -> Option<Vec<bool>>;
+    // show the progress of a long running operation until the result is dropped; its Cancel
+    // button sets `cancel`, which is cleared first
+    fn start_progress(&self, title: &str, cancel: &'static AtomicBool) -> Box<dyn Progress>;
+    // open a web page in the default browser
+    fn open_url(&self, url: &str) -> Result<(), String>;
+}
+
+pub trait Progress {
+    fn update(&mut self, message: &str);
+}
+
+pub trait Clipboard: Sync {
+    // refuses texts taking more than `max_bytes` on the clipboard, see `clipboard_size`
+    fn copy_to_clipboard(&self, text: &str, max_bytes: usize) -> Result<(), ClipboardError>;
+    // copies the markup as text, and as "HTML Format" for applications pasting formatted text
+    fn copy_html_to_clipboard(&self, html: &str, max_bytes: usize) -> Result<(), ClipboardError>;
+    fn get_clipboard_text(&self) -> Result<String, ClipboardError>;
+}
+
+#[derive(Debug)]
+pub enum ClipboardError {
+    // size of the text on the clipboard and the configured maximum, in bytes
+    TooLarge(usize, usize),
+    // requested size in bytes
+    #[cfg_attr(not(all(windows, feature = "windows-ui")), allow(dead_code))]
+    AllocationFailed(usize, Error),
+    Os(Error),
+}
+
+impl Display for ClipboardError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            ClipboardError::TooLarge(size, max) => format!(
+                "The result is too large for the clipboard ({:.1} MB, the limit is {:.1} MB).\n\
+                 Please export it to a file instead.",
+                *size as f64 / MEGABYTE,
+                *max as f64 / MEGABYTE
+            ),
+            ClipboardError::AllocationFailed(size, e) => format!(
+                "Could not allocate {:.1} MB for the clipboard: {}",
+                *size as f64 / MEGABYTE,
+                e
+            ),
+            ClipboardError::Os(e) => format!("Could not copy to the clipboard: {}", e),
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl From<Error> for ClipboardError {
+    fn from(e: Error) -> ClipboardError {
+        ClipboardError::Os(e)
+    }
+}
+
+// Bytes needed to put the text on the clipboard as CF_UNICODETEXT,
+// i.e. UTF-16 code units (two for characters outside the BMP) including the terminating NUL
+pub fn clipboard_size(text: &str) -> usize {
+    (text.chars().map(char::len_utf16).sum::<usize>() + 1) * std::mem::size_of::<u16>()
+}
+
+// Fail fast before anything is converted or allocated; returns the size in bytes
+pub fn check_clipboard_size(text: &str, max_bytes: usize) -> Result<usize, ClipboardError> {
+    let size = clipboard_size(text);
+    match size > max_bytes {
+        true => Err(ClipboardError::TooLarge(size, max_bytes)),
+        false => Ok(size),
+    }
+}
+
+// The HTML as "HTML Format" (CF_HTML): a header with the byte offsets of the document and of
+// the fragment in it, followed by the document; all of it UTF-8
+pub fn cf_html(fragment: &str) -> String {
+    const PREFIX: &str = "<html>\r\n<body>\r\n<!--StartFragment-->";
+    const SUFFIX: &str = "<!--EndFragment-->\r\n</body>\r\n</html>";
+    let header =
+        |start_html: usize, end_html: usize, start_fragment: usize, end_fragment: usize| {
+            format!(
+                "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\n\
+             StartFragment:{:010}\r\nEndFragment:{:010}\r\n",
+                start_html, end_html, start_fragment, end_fragment
+            )
+        };
+    // the offsets are zero-padded, so the header has the same length whatever they are
+    let start_html = header(0, 0, 0, 0).len();
+    let start_fragment = start_html + PREFIX.len();
+    let end_fragment = start_fragment + fragment.len();
+    let end_html = end_fragment + SUFFIX.len();
+    format!(
+        "{}{}{}{}",
+        header(start_html, end_html, start_fragment, end_fragment),
+        PREFIX,
+        fragment,
+        SUFFIX
+    )
+}
+
+// Like `check_clipboard_size`, for the markup as text plus its CF_HTML (with the terminating NUL)
+pub fn check_html_clipboard_size(
+    html: &str,
+    cf_html: &str,
+    max_bytes: usize,
+) -> Result<usize, ClipboardError> {
+    let size = clipboard_size(html) + cf_html.len() + 1;
+    match size > max_bytes {
+        true => Err(ClipboardError::TooLarge(size, max_bytes)),
+        false => Ok(size),
+    }
+}
+
+#[cfg(all(windows, feature = "windows-ui"))]
+mod native {
+    use std::ffi::CString;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicBool;
+
+    use winapi::um::winuser::{
+        IDYES, MB_ICONERROR, MB_ICONINFORMATION, MB_ICONQUESTION, MB_OK, MB_YESNO,
+    };
+
+    use crate::clipboard::{copy_html_to_clipboard, copy_to_clipboard, get_clipboard_text};
+    use crate::ui::{Clipboard, ClipboardError, Dialogs, MessageKind, Progress, SaveDialogOutcome};
+    use crate::windows_api::{
+        get_open_file_name, get_save_file_name, get_save_folder_name, get_user_input, open_url,
+        select_items, show_message_box, show_task_dialog, start_progress, ProgressDialog,
+    };
+
+    pub struct WindowsDialogs;
+
+    impl Dialogs for WindowsDialogs {
+        fn show_message(&self, message: &str, caption: &str, kind: MessageKind) {
+            let icon = match kind {
+                MessageKind::Information => MB_ICONINFORMATION,
+                MessageKind::Error => MB_ICONERROR,
+            };
+            // interior NUL characters can't be displayed anyway
+            let message = CString::new(message.replace('\0', "")).unwrap();
+            let caption = CString::new(caption.replace('\0', "")).unwrap();
+            show_message_box(&message, &caption, MB_OK | icon);
+        }
+
+        fn show_report(&self, caption: &str, summary: &str, details: &str, kind: MessageKind) {
+            show_task_dialog(caption, summary, details, kind == MessageKind::Error);
+        }
+
+        fn ask_yes_no(&self, question: &str, caption: &str) -> bool {
+            let question = CString::new(question.replace('\0', "")).unwrap();
+            let caption = CString::new(caption.replace('\0', "")).unwrap();
+            show_message_box(&question, &caption, MB_YESNO | MB_ICONQUESTION) == IDYES
+        }
+
+        fn get_save_file_name(&self) -> SaveDialogOutcome {
+            get_save_file_name()
+        }
+
+        fn get_open_file_name(&self) -> Option<PathBuf> {
+            get_open_file_name()
+        }
+
+        fn get_save_folder_name(&self) -> String {
+            get_save_folder_name()
+        }
+
+        fn get_user_input(&self, prompt: &str) -> Result<String, &'static str> {
+            get_user_input(prompt)
+        }
+
+        fn select_columns(&self, headers: &[String]) -> Option<Vec<bool>> {
+            select_items("Columns to export", headers)
+        }
+
+        fn select_items(&self, title: &str, items: &[String]) -> Option<Vec<bool>> {
+            select_items(title, items)
+        }
+
+        fn start_progress(&self, title: &str, cancel: &'static AtomicBool) -> Box<dyn Progress> {
+            Box::new(start_progress(title, cancel))
+        }
+
+        fn open_url(&self, url: &str) -> Result<(), String> {
+            open_url(url)
+        }
+    }
+
+    impl Progress for ProgressDialog {
+        fn update(&mut self, message: &str) {
+            self.set_message(message);
+        }
+    }
+
+    pub struct WindowsClipboard;
+
+    impl Clipboard for WindowsClipboard {
+        fn copy_to_clipboard(&self, text: &str, max_bytes: usize) -> Result<(), ClipboardError> {
+            copy_to_clipboard(text, max_bytes)
+        }
+
+        fn copy_html_to_clipboard(
+            &self,
+            html: &str,
+            max_bytes: usize,
+        ) -> Result<(), ClipboardError> {
+            copy_html_to_clipboard(html, max_bytes)
+        }
+
+        fn get_clipboard_text(&self) -> Result<String, ClipboardError> {
+            get_clipboard_text()
+        }
+    }
+}
+
+#[cfg(not(all(windows, feature = "windows-ui")))]
+pub mod headless {
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    use crate::logging::target;
+    use crate::ui::{
+        cf_html, check_clipboard_size, check_html_clipboard_size, Clipboard, ClipboardError,
+        Dialogs, MessageKind, Progress, SaveDialogOutcome,
+    };
+
+    lazy_static! {
+        // what was last copied to the (fake) clipboard
+        pub static ref CLIPBOARD_TEXT: Mutex<String> = Mutex::new(String::new());
+        // the CF_HTML copied along with it, empty for plain text
+        pub static ref CLIPBOARD_HTML: Mutex<String> = Mutex::new(String::new());
+    }
+
+    #[cfg(test)]
+    lazy_static! {
+        // tests using the clipboard must hold this lock, as they run in parallel
+        pub static ref CLIPBOARD_TEST_LOCK: Mutex<()> = Mutex::new(());
+    }
+
+    // Logs messages, and behaves as if the user cancelled every dialog
+    pub struct HeadlessDialogs;
+
+    impl Dialogs for HeadlessDialogs {
+        fn show_message(&self, message: &str, caption: &str, kind: MessageKind) {
+            info!(target: target::WINAPI, "{:?} message box '{}': {}", kind, caption, message);
+        }
+
+        fn show_report(&self, caption: &str, summary: &str, details: &str, kind: MessageKind) {
+            info!(target: target::WINAPI, "{:?} report '{}': {}\n{}", kind, caption, summary, details);
+        }
+
+        fn ask_yes_no(&self, question: &str, caption: &str) -> bool {
+            info!(target: target::WINAPI, "Question '{}': {} -> no", caption, question);
+            false
+        }
+
+        fn get_save_file_name(&self) -> SaveDialogOutcome {
+            SaveDialogOutcome::Cancelled
+        }
+
+        fn get_open_file_name(&self) -> Option<PathBuf> {
+            None
+        }
+
+        fn get_save_folder_name(&self) -> String {
+            "".to_string()
+        }
+
+        fn get_user_input(&self, _prompt: &str) -> Result<String, &'static str> {
+            Err("Cancelled")
+        }
+
+        fn select_columns(&self, _headers: &[String]) -> Option<Vec<bool>> {
+            None
+        }
+
+        fn select_items(&self, _title: &str, _items: &[String]) -> Option<Vec<bool>> {
+            None
+        }
+
+        fn start_progress(&self, title: &str, cancel: &'static AtomicBool) -> Box<dyn Progress> {
+            cancel.store(false, Ordering::SeqCst);
+            Box::new(HeadlessProgress(title.to_string()))
+        }
+
+        fn open_url(&self, url: &str) -> Result<(), String> {
+            info!(target: target::WINAPI, "Not opening {} without a browser", url);
+            Ok(())
+        }
+    }
+
+    // Logs the progress messages
+    pub struct HeadlessProgress(pub String);
+
+    impl Progress for HeadlessProgress {
+        fn update(&mut self, message: &str) {
+            debug!(target: target::WINAPI, "Progress '{}': {}", self.0, message);
+        }
+    }
+
+    // Keeps the copied text in memory
+    pub struct HeadlessClipboard;
+
+    impl Clipboard for HeadlessClipboard {
+        fn copy_to_clipboard(&self, text: &str, max_bytes: usize) -> Result<(), ClipboardError> {
+            check_clipboard_size(text, max_bytes)?;
+            *CLIPBOARD_TEXT.lock().unwrap() = text.to_string();
+            CLIPBOARD_HTML.lock().unwrap().clear();
+            Ok(())
+        }
+
+        fn copy_html_to_clipboard(
+            &self,
+            html: &str,
+            max_bytes: usize,
+        ) -> Result<(), ClipboardError> {
+            let cf_html = cf_html(html);
+            check_html_clipboard_size(html, &cf_html, max_bytes)?;
+            *CLIPBOARD_TEXT.lock().unwrap() = html.to_string();
+            *CLIPBOARD_HTML.lock().unwrap() = cf_html;
+            Ok(())
+        }
+
+        fn get_clipboard_text(&self) -> Result<String, ClipboardError> {
+            Ok(CLIPBOARD_TEXT.lock().unwrap().clone())
+        }
+    }
+}
+
+#[cfg(all(windows, feature = "windows-ui"))]
+pub fn dialogs() -> &'static dyn Dialogs {
+    &native::WindowsDialogs
+}
+
+#[cfg(all(windows, feature = "windows-ui"))]
+pub fn clipboard() -> &'static dyn Clipboard {
+    &native::WindowsClipboard
+}
+
+#[cfg(not(all(windows, feature = "windows-ui")))]
+pub fn dialogs() -> &'static dyn Dialogs {
+    &headless::HeadlessDialogs
+}
+
+#[cfg(not(all(windows, feature = "windows-ui")))]
+pub fn clipboard() -> &'static dyn Clipboard {
+    &headless::HeadlessClipboard
+}
+
+#[cfg(all(test, not(all(windows, feature = "windows-ui"))))]
+mod tests {
+    use crate::ui::headless::{CLIPBOARD_HTML, CLIPBOARD_TEST_LOCK, CLIPBOARD_TEXT};
+    use crate::ui::*;
+
+    #[test]
+    fn headless_clipboard_should_keep_copied_text() {
+        let _lock = CLIPBOARD_TEST_LOCK.lock().unwrap();
+        clipboard()
+            .copy_to_clipboard("||h1||\n|d1|\n", usize::MAX)
+            .unwrap();
+        assert_eq!("||h1||\n|d1|\n", *CLIPBOARD_TEXT.lock().unwrap());
+    }
+
+    #[test]
+    fn cf_html_should_point_to_the_document_and_the_fragment() {
+        let fragment = "<table><tr><td>Müller</td></tr></table>";
+        let cf_html = cf_html(fragment);
+        let offset = |name: &str| -> usize {
+            let start = cf_html.find(&format!("{}:", name)).unwrap() + name.len() + 1;
+            cf_html[start..start + 10].parse().unwrap()
+        };
+        assert!(cf_html.starts_with("Version:0.9\r\nStartHTML:0000000105\r\n"));
+        assert_eq!(105, offset("StartHTML"));
+        assert!(cf_html[offset("StartHTML")..].starts_with("<html>"));
+        assert_eq!(cf_html.len(), offset("EndHTML"));
+        // byte offsets, the ü takes two
+        assert_eq!(
+            fragment,
+            &cf_html[offset("StartFragment")..offset("EndFragment")]
+        );
+    }
+
+    #[test]
+    fn headless_clipboard_should_keep_html_along_with_the_text() {
+        let _lock = CLIPBOARD_TEST_LOCK.lock().unwrap();
+        let html = "<table><tr><th>h1</th></tr></table>\n";
+        clipboard()
+            .copy_html_to_clipboard(html, usize::MAX)
+            .unwrap();
+        assert_eq!(html, *CLIPBOARD_TEXT.lock().unwrap());
+        assert_eq!(cf_html(html), *CLIPBOARD_HTML.lock().unwrap());
+        let size = clipboard_size(html) + cf_html(html).len() + 1;
+        match clipboard().copy_html_to_clipboard(html, size - 1) {
+            Err(ClipboardError::TooLarge(s, _)) if s == size => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        // plain text leaves no stale HTML behind
+        clipboard().copy_to_clipboard("text", usize::MAX).unwrap();
+        assert_eq!("", *CLIPBOARD_HTML.lock().unwrap());
+    }
+
+    #[test]
+    fn clipboard_size_should_count_utf16_code_units() {
+        // just the terminating NUL
+        assert_eq!(2, clipboard_size(""));
+        assert_eq!(8, clipboard_size("abc"));
+        // 'ü' and '€' are 2 and 3 bytes in UTF-8, but a single UTF-16 code unit each
+        assert_eq!(6, clipboard_size("ü€"));
+        // characters outside the BMP are surrogate pairs: 4 bytes in UTF-8 and in UTF-16
+        assert_eq!(10, clipboard_size("😀😀"));
+    }
+
+    #[test]
+    fn headless_clipboard_should_refuse_text_larger_than_limit() {
+        let _lock = CLIPBOARD_TEST_LOCK.lock().unwrap();
+        CLIPBOARD_TEXT.lock().unwrap().clear();
+        match clipboard().copy_to_clipboard("😀😀", 9) {
+            Err(ClipboardError::TooLarge(10, 9)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert_eq!("", *CLIPBOARD_TEXT.lock().unwrap());
+        assert!(check_clipboard_size("😀😀", 10).is_ok());
+    }
+
+    #[test]
+    fn headless_dialogs_should_behave_like_cancelled_dialogs() {
+        assert_eq!(SaveDialogOutcome::Cancelled, dialogs().get_save_file_name());
+        assert_eq!(Err("Cancelled"), dialogs().get_user_input("Table name"));
+        assert_eq!("", dialogs().get_save_folder_name());
+        assert_eq!(None, dialogs().select_columns(&["ID".to_string()]));
+        assert_eq!(
+            None,
+            dialogs().select_items("Statements", &["select 1 from dual;".to_string()])
+        );
+    }
+
+    #[test]
+    fn dialog_error_should_explain_known_codes() {
+        assert_eq!(
+            "the file name is invalid (error code 0x3002)",
+            format!("{}", DialogError(0x3002))
+        );
+        assert_eq!(
+            "the buffer for the file name is too small (error code 0x3003)",
+            format!("{}", DialogError(0x3003))
+        );
+        assert_eq!(
+            "the list box could not be subclassed because memory ran out (error code 0x3001)",
+            format!("{}", DialogError(0x3001))
+        );
+        assert_eq!(
+            "the dialog failed (error code 0xFFFF)",
+            format!("{}", DialogError(0xFFFF))
+        );
+    }
+}
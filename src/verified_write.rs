@@ -0,0 +1,146 @@
+// Writing files with a read-back check, so e.g. a migration quarantined by a virus scanner
+// right after it was written is reported as a failed export rather than a success.
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum VerifyError {
+    Unreadable(PathBuf, Error),
+    // (path, expected length, length read back) in bytes
+    LengthMismatch(PathBuf, usize, usize),
+    ContentMismatch(PathBuf),
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::Unreadable(path, e) => write!(
+                f,
+                "{} could not be read back after writing it: {}",
+                path.display(),
+                e
+            ),
+            VerifyError::LengthMismatch(path, expected, actual) => write!(
+                f,
+                "{} has {} bytes after writing it, but {} were written",
+                path.display(),
+                actual,
+                expected
+            ),
+            VerifyError::ContentMismatch(path) => write!(
+                f,
+                "{} has a different content after writing it",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl From<VerifyError> for Error {
+    fn from(e: VerifyError) -> Error {
+        Error::new(ErrorKind::InvalidData, format!("{}", e))
+    }
+}
+
+// Check that the file holds exactly the expected content
+fn verify_written(path: &Path, expected: &[u8]) -> Result<(), VerifyError> {
+    let actual = fs::read(path).map_err(|e| VerifyError::Unreadable(path.to_path_buf(), e))?;
+    if actual.len() != expected.len() {
+        return Err(VerifyError::LengthMismatch(
+            path.to_path_buf(),
+            expected.len(),
+            actual.len(),
+        ));
+    }
+    if actual != expected {
+        return Err(VerifyError::ContentMismatch(path.to_path_buf()));
+    }
+    Ok(())
+}
+
+// Write the file, and read it back if `verify` is set
+pub fn write_verified(path: &Path, content: &[u8], verify: bool) -> std::io::Result<()> {
+    write_verified_with_hook(path, content, verify, |_| {})
+}
+
+// `after_write` runs between writing and verifying, for tests
+fn write_verified_with_hook<F: FnOnce(&Path)>(
+    path: &Path,
+    content: &[u8],
+    verify: bool,
+    after_write: F,
+) -> std::io::Result<()> {
+    {
+        let mut file = File::create(path)?;
+        file.write_all(content)?;
+    }
+    after_write(path);
+    if verify {
+        verify_written(path, content)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::path::{Path, PathBuf};
+
+    use crate::verified_write::*;
+
+    fn test_file(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join("xanthidae_verified_write");
+        fs::create_dir_all(&folder).unwrap();
+        folder.join(name)
+    }
+
+    #[test]
+    fn write_verified_should_accept_untouched_file() {
+        let path = test_file("untouched.sql");
+        write_verified(&path, b"select 1 from dual;\n", true).unwrap();
+        assert_eq!(b"select 1 from dual;\n".to_vec(), fs::read(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn write_verified_should_detect_tampering_between_write_and_verify() {
+        let path = test_file("tampered.sql");
+        let content = b"select 1 from dual;\n";
+
+        let truncate = |path: &Path| fs::write(path, b"select").unwrap();
+        let e = write_verified_with_hook(&path, content, true, truncate).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, e.kind());
+        assert_eq!(
+            format!(
+                "{} has 6 bytes after writing it, but 20 were written",
+                path.display()
+            ),
+            e.to_string()
+        );
+
+        let replace = |path: &Path| fs::write(path, b"select 2 from dual;\n").unwrap();
+        let e = write_verified_with_hook(&path, content, true, replace).unwrap_err();
+        assert_eq!(
+            format!(
+                "{} has a different content after writing it",
+                path.display()
+            ),
+            e.to_string()
+        );
+
+        // quarantined by a virus scanner
+        let remove = |path: &Path| fs::remove_file(path).unwrap();
+        let e = write_verified_with_hook(&path, content, true, remove).unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with(&format!("{} could not be read back", path.display())));
+
+        // without verification, nothing is noticed
+        write_verified_with_hook(&path, content, false, replace).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
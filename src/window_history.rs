@@ -0,0 +1,278 @@
+// Versioned migrations from the statements executed in an SQL window, e.g. during a modelling
+// session: the user ticks the statements to keep, and each becomes a migration of its own,
+// numbered in the order of the window.
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+
+use crate::config::{Config, Versioning};
+use crate::flyway::{
+    get_sequential_filename, get_versioned_filename_impl, migration_timestamp,
+    next_sequential_version, offset_timestamps, probe_writable, version_of, write_migration,
+    FlywayError,
+};
+use crate::paths;
+use crate::plsqldev_api::PlsqlDevApi;
+use crate::project_config::config_for_folder;
+use crate::statements::split_statements;
+use crate::template::{apply_templates, TemplateValues};
+use crate::ui::{dialogs, MessageKind, SaveDialogOutcome};
+
+const WINDOW_HISTORY_CAPTION: &str = "Versioned migrations from window history";
+const MAX_LABEL_CHARS: usize = 80;
+
+// The statements of a window, labelled for a checklist
+pub struct StatementChecklist {
+    statements: Vec<String>,
+}
+
+impl StatementChecklist {
+    pub fn new(script: &str) -> StatementChecklist {
+        StatementChecklist {
+            statements: split_statements(script),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty()
+    }
+
+    // The first non-blank line of each statement, shortened if necessary
+    pub fn labels(&self) -> Vec<String> {
+        self.statements
+            .iter()
+            .map(|statement| {
+                let first_line = statement
+                    .lines()
+                    .map(str::trim)
+                    .find(|line| !line.is_empty())
+                    .unwrap_or("");
+                match first_line.char_indices().nth(MAX_LABEL_CHARS) {
+                    Some((end, _)) => format!("{}...", &first_line[..end]),
+                    None => first_line.to_string(),
+                }
+            })
+            .collect()
+    }
+
+    // The statements ticked in the checklist, in their original order
+    pub fn kept(&self, selection: &[bool]) -> Vec<&str> {
+        self.statements
+            .iter()
+            .zip(selection)
+            .filter(|(_, &selected)| selected)
+            .map(|(statement, _)| statement.as_str())
+            .collect()
+    }
+}
+
+// V<version>__<basename>_NN.sql for `count` statements. Timestamp versions are offset by one
+// second (or millisecond, with millisecond precision) per statement, so they sort like the window;
+// the last statement gets the current time.
+pub fn history_file_names(
+    config: &Config,
+    now: DateTime<Utc>,
+    first_sequential_version: u64,
+    basename: &str,
+    count: usize,
+) -> Vec<String> {
+    let basename = basename.trim_end_matches(".sql");
+    let timestamps = offset_timestamps(now, count, config.use_millisecond_precision);
+    (0..count)
+        .map(|i| {
+            let numbered = format!("{}_{:02}", basename, i + 1);
+            match config.versioning {
+                Versioning::Timestamp => {
+                    get_versioned_filename_impl(config, timestamps[i], &numbered)
+                }
+                Versioning::Sequential => {
+                    get_sequential_filename(config, first_sequential_version + i as u64, &numbered)
+                }
+            }
+        })
+        .collect()
+}
+
+pub fn create_migrations_from_window_history(api: &dyn PlsqlDevApi, config: &Config) {
+    let checklist = StatementChecklist::new(&api.ide_get_text());
+    if checklist.is_empty() {
+        dialogs().show_message(
+            "The window contains no statements!",
+            WINDOW_HISTORY_CAPTION,
+            MessageKind::Information,
+        );
+        return;
+    }
+    let selection = match dialogs().select_items("Statements to export", &checklist.labels()) {
+        Some(selection) => selection,
+        // user cancelled
+        None => return,
+    };
+    let kept = checklist.kept(&selection);
+    if kept.is_empty() {
+        dialogs().show_message(
+            "No statements were selected, so no migrations were created.",
+            WINDOW_HISTORY_CAPTION,
+            MessageKind::Information,
+        );
+        return;
+    }
+    let (folder, basename) = match dialogs().get_save_file_name() {
+        SaveDialogOutcome::Chosen(folder, basename) => (folder, basename),
+        SaveDialogOutcome::Cancelled => return,
+        SaveDialogOutcome::EmptyName => {
+            show_error(FlywayError::EmptyFileName);
+            return;
+        }
+        SaveDialogOutcome::Failed(e) => {
+            show_error(FlywayError::DialogFailed(e));
+            return;
+        }
+    };
+    let folder_name = paths::display(config.repo_root.as_deref(), &folder.to_string_lossy());
+    if let Err(e) = probe_writable(&folder) {
+        show_error(FlywayError::FolderNotWritable(
+            folder_name,
+            format!("{}", e),
+        ));
+        return;
+    }
+    // the conventions of the target repository apply to these migrations
+    let config = &config_for_folder(config, &folder);
+    let now = migration_timestamp(api, config);
+    match write_history_migrations(&folder, &kept, &basename, now, config) {
+        Ok(count) => dialogs().show_message(
+            &format!(
+                "Created {} versioned migration(s) in {}.",
+                count, folder_name
+            ),
+            WINDOW_HISTORY_CAPTION,
+            MessageKind::Information,
+        ),
+        Err(e) => show_error(FlywayError::from(e)),
+    }
+}
+
+fn show_error(error: FlywayError) {
+    dialogs().show_message(&format!("{}", error), "Error", MessageKind::Error);
+}
+
+// Write one migration per statement into the folder; returns the number written
+fn write_history_migrations(
+    folder: &Path,
+    statements: &[&str],
+    basename: &str,
+    now: DateTime<Utc>,
+    config: &Config,
+) -> std::io::Result<usize> {
+    let file_names = history_file_names(
+        config,
+        now,
+        next_sequential_version(folder, config),
+        basename,
+        statements.len(),
+    );
+    for (statement, file_name) in statements.iter().zip(&file_names) {
+        let mut template_values = TemplateValues::new("", "", "", now);
+        template_values.version = version_of(file_name, config).to_string();
+        let content = format!("{}\n", statement);
+        let content = apply_templates(&content, &template_values, config);
+        write_migration(&folder.join(file_name), &content, config)?;
+    }
+    Ok(file_names.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::TimeZone;
+
+    use crate::window_history::*;
+
+    const SCRIPT: &str = "create table t (id number);\nselect * from t;\ncreate or replace view v as\n  select id from t;\n";
+
+    #[test]
+    fn checklist_should_label_statements_and_keep_selected_ones() {
+        let checklist = StatementChecklist::new(SCRIPT);
+        assert_eq!(
+            vec![
+                "create table t (id number);",
+                "select * from t;",
+                "create or replace view v as",
+            ],
+            checklist.labels()
+        );
+        assert_eq!(
+            vec![
+                "create table t (id number);",
+                "create or replace view v as\n  select id from t;",
+            ],
+            checklist.kept(&[true, false, true])
+        );
+        assert!(StatementChecklist::new("-- nothing here\n").is_empty());
+    }
+
+    #[test]
+    fn checklist_labels_should_be_shortened() {
+        let long_name = "x".repeat(100);
+        let checklist = StatementChecklist::new(&format!("\n  drop table {};", long_name));
+        let label = &checklist.labels()[0];
+        assert_eq!(MAX_LABEL_CHARS + 3, label.chars().count());
+        assert!(label.starts_with("drop table xxx") && label.ends_with("x..."));
+    }
+
+    #[test]
+    fn history_file_names_should_offset_versions_and_number_statements() {
+        let now = Utc.with_ymd_and_hms(2021, 7, 18, 10, 15, 59).unwrap();
+        let mut config = Config::default();
+        assert_eq!(
+            vec![
+                "V2021_07_18_10_15_58__model_01.sql",
+                "V2021_07_18_10_15_59__model_02.sql",
+            ],
+            history_file_names(&config, now, 1, "model.sql", 2)
+        );
+        config.use_millisecond_precision = true;
+        assert_eq!(
+            vec![
+                "V2021_07_18_10_15_58.999__model_01.sql",
+                "V2021_07_18_10_15_59.000__model_02.sql",
+            ],
+            history_file_names(&config, now, 1, "model", 2)
+        );
+        config.versioning = Versioning::Sequential;
+        assert_eq!(
+            vec!["V7__model_01.sql", "V8__model_02.sql", "V9__model_03.sql"],
+            history_file_names(&config, now, 7, "model", 3)
+        );
+    }
+
+    #[test]
+    fn write_history_migrations_should_write_one_file_per_statement() {
+        let folder = std::env::temp_dir().join("xanthidae_window_history_test");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("V3__existing.sql"), "select 1 from dual;\n").unwrap();
+        let config = Config {
+            versioning: Versioning::Sequential,
+            ..Config::default()
+        };
+
+        let checklist = StatementChecklist::new(SCRIPT);
+        let kept = checklist.kept(&[true, false, true]);
+        assert_eq!(
+            2,
+            write_history_migrations(&folder, &kept, "model", Utc::now(), &config).unwrap()
+        );
+        assert_eq!(
+            "create table t (id number);\n",
+            fs::read_to_string(folder.join("V4__model_01.sql")).unwrap()
+        );
+        assert_eq!(
+            "create or replace view v as\n  select id from t;\n",
+            fs::read_to_string(folder.join("V5__model_02.sql")).unwrap()
+        );
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
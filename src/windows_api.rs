@@ -1,34 +1,62 @@
+use std::cell::{Cell, RefCell};
 use std::ffi::{CStr, CString};
 use std::mem::MaybeUninit;
 use std::os::raw::c_uint;
 use std::os::raw::{c_char, c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::{mem, ptr};
 
+use winapi::shared::basetsd::INT_PTR;
+use winapi::shared::minwindef::{HINSTANCE, LOWORD, LPARAM, UINT, WPARAM};
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::windef::HWND;
 use winapi::shared::winerror::SUCCEEDED;
 use winapi::um::combaseapi::{
     CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, CLSCTX_INPROC,
 };
+use winapi::um::commctrl::{
+    TASKDIALOG_COMMON_BUTTON_FLAGS, TDCBF_OK_BUTTON, TD_ERROR_ICON, TD_INFORMATION_ICON,
+};
+use winapi::um::commdlg::CommDlgExtendedError;
+use winapi::um::commdlg::GetOpenFileNameA;
 use winapi::um::commdlg::GetSaveFileNameA;
 use winapi::um::commdlg::LPOPENFILENAMEA;
 use winapi::um::commdlg::OFN_ENABLESIZING;
+use winapi::um::commdlg::OFN_FILEMUSTEXIST;
 use winapi::um::commdlg::OFN_HIDEREADONLY;
 use winapi::um::commdlg::OFN_NODEREFERENCELINKS;
 use winapi::um::commdlg::OFN_NONETWORKBUTTON;
 use winapi::um::commdlg::OFN_OVERWRITEPROMPT;
 use winapi::um::commdlg::OPENFILENAMEA;
+use winapi::um::libloaderapi::{GetModuleHandleW, GetProcAddress};
 use winapi::um::objbase::COINIT_APARTMENTTHREADED;
+use winapi::um::shellapi::{
+    ShellExecuteW, SE_ERR_ACCESSDENIED, SE_ERR_ASSOCINCOMPLETE, SE_ERR_NOASSOC, SE_ERR_OOM,
+};
 use winapi::um::shobjidl::{
     IFileDialog, IFileOpenDialog, FILEOPENDIALOGOPTIONS, FOS_FORCEFILESYSTEM, FOS_FORCESHOWHIDDEN,
     FOS_PATHMUSTEXIST, FOS_PICKFOLDERS,
 };
 use winapi::um::shobjidl_core::{CLSID_FileOpenDialog, IShellItem, SIGDN_FILESYSPATH};
-use winapi::um::winnt::PWSTR;
-use winapi::um::winuser::MessageBoxA;
+use winapi::um::winnt::{PCWSTR, PWSTR};
+use winapi::um::winuser::{
+    CreateDialogIndirectParamW, DestroyWindow, DialogBoxIndirectParamW, DispatchMessageW,
+    EnableWindow, EndDialog, GetDlgItem, GetDlgItemTextW, IsDialogMessageW, MessageBoxA,
+    MessageBoxW, PeekMessageW, SendDlgItemMessageW, SetDlgItemTextW, TranslateMessage,
+    BS_DEFPUSHBUTTON, DS_CENTER, DS_MODALFRAME, DS_SETFONT, EM_LIMITTEXT, ES_AUTOHSCROLL, IDCANCEL,
+    IDOK, LBS_MULTIPLESEL, LBS_NOINTEGRALHEIGHT, LB_ADDSTRING, LB_GETSEL, LB_SETSEL, MB_ICONERROR,
+    MB_ICONINFORMATION, MB_OK, MSG, PM_REMOVE, SS_LEFT, SW_SHOWNORMAL, WM_COMMAND, WM_INITDIALOG,
+    WS_BORDER, WS_CAPTION, WS_CHILD, WS_POPUP, WS_SYSMENU, WS_TABSTOP, WS_VISIBLE, WS_VSCROLL,
+};
 use winapi::Interface;
 
+use crate::logging::target;
 use crate::string_utils::{pwstr_to_cstring, vec_with_nul_to_string};
+use crate::ui::{DialogError, SaveDialogOutcome};
 
 const FILE_FILTER: &[u8] = b"All Files\0*.*\0\0";
+const MIGRATION_FILTER: &[u8] = b"SQL migrations\0*.sql\0All Files\0*.*\0\0";
 const DEFAULT_EXTENSION: &[u8] = b"sql\0";
 const BUFFER_SIZE: usize = 1000;
 
@@ -46,8 +74,66 @@ pub fn show_message_box(message: &CStr, caption: &CStr, message_box_type: c_uint
     }
 }
 
+// TaskDialog, looked up at runtime: it's only exported by version 6 of the common controls,
+// and linking it would keep the plugin from loading into an IDE using an older version
+type TaskDialogFn = unsafe extern "system" fn(
+    HWND,
+    HINSTANCE,
+    PCWSTR,
+    PCWSTR,
+    PCWSTR,
+    TASKDIALOG_COMMON_BUTTON_FLAGS,
+    PCWSTR,
+    *mut c_int,
+) -> HRESULT;
+
+// Show a task dialog with the main instruction above the content, or a message box with both
+// if task dialogs aren't available
+pub fn show_task_dialog(caption: &str, instruction: &str, content: &str, error: bool) {
+    let wide = |text: &str| text.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    let caption = wide(caption);
+    unsafe {
+        let comctl32 = GetModuleHandleW(wide("comctl32.dll").as_ptr());
+        let address = match comctl32.is_null() {
+            true => ptr::null_mut(),
+            false => GetProcAddress(comctl32, b"TaskDialog\0".as_ptr() as *const c_char),
+        };
+        if !address.is_null() {
+            let task_dialog: TaskDialogFn = mem::transmute(address);
+            let icon = match error {
+                true => TD_ERROR_ICON,
+                false => TD_INFORMATION_ICON,
+            };
+            let result = task_dialog(
+                ptr::null_mut(),
+                ptr::null_mut(),
+                caption.as_ptr(),
+                wide(instruction).as_ptr(),
+                wide(content).as_ptr(),
+                TDCBF_OK_BUTTON,
+                icon,
+                ptr::null_mut(),
+            );
+            if SUCCEEDED(result) {
+                return;
+            }
+            warn!(target: target::WINAPI, "TaskDialog failed with 0x{:08X}", result);
+        }
+        let icon = match error {
+            true => MB_ICONERROR,
+            false => MB_ICONINFORMATION,
+        };
+        MessageBoxW(
+            ptr::null_mut(),
+            wide(&format!("{}\n\n{}", instruction, content)).as_ptr(),
+            caption.as_ptr(),
+            MB_OK | icon,
+        );
+    }
+}
+
 // TODO: Also replace with the more modern IFileDialog from `get_save_folder_name()`
-pub fn get_save_file_name() -> Result<String, &'static str> {
+pub fn get_save_file_name() -> SaveDialogOutcome {
     unsafe {
         let mut file_name: Vec<u8> = vec![0; BUFFER_SIZE + 1];
         let mut file_title: Vec<u8> = vec![0; BUFFER_SIZE + 1];
@@ -83,18 +169,75 @@ pub fn get_save_file_name() -> Result<String, &'static str> {
             FlagsEx: 0,
         };
 
-        //        debug!("file_name: {:?}\n", file_name);
-        //        debug!("file_title: {:?}\n", file_title);
+        //        debug!(target: target::WINAPI, "file_name: {:?}\n", file_name);
+        //        debug!(target: target::WINAPI, "file_title: {:?}\n", file_title);
 
         match GetSaveFileNameA(&mut ofn as LPOPENFILENAMEA) {
             1 => {
                 let file_name_str = vec_with_nul_to_string(&file_title);
-                match file_name_str.as_ref() {
-                    "" => Err("Empty name"),
-                    _ => Ok(file_name_str),
+                if file_name_str.is_empty() {
+                    return SaveDialogOutcome::EmptyName;
+                }
+                let path = PathBuf::from(vec_with_nul_to_string(&file_name));
+                let folder = path
+                    .parent()
+                    .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+                SaveDialogOutcome::Chosen(folder, file_name_str)
+            }
+            // cancelled, unless the dialog reports an error
+            _ => match CommDlgExtendedError() {
+                0 => SaveDialogOutcome::Cancelled,
+                code => SaveDialogOutcome::Failed(DialogError(code)),
+            },
+        }
+    }
+}
+
+// Ask for an existing file; None if the user cancelled (or the dialog failed, which is logged)
+pub fn get_open_file_name() -> Option<PathBuf> {
+    unsafe {
+        let mut file_name: Vec<u8> = vec![0; BUFFER_SIZE + 1];
+        let size = mem::size_of::<OPENFILENAMEA>() as u32;
+
+        let mut ofn = OPENFILENAMEA {
+            lStructSize: size,
+            hwndOwner: ptr::null_mut(),
+            hInstance: ptr::null_mut(),
+            lpstrFilter: MIGRATION_FILTER.as_ptr() as *const c_char,
+            lpstrCustomFilter: ptr::null_mut(),
+            nMaxCustFilter: 0,
+            nFilterIndex: 0,
+            lpstrFile: file_name.as_mut_ptr() as *mut c_char,
+            nMaxFile: BUFFER_SIZE as u32,
+            lpstrFileTitle: ptr::null_mut(),
+            nMaxFileTitle: 0,
+            lpstrInitialDir: ptr::null_mut(),
+            lpstrTitle: ptr::null_mut(),
+            Flags: OFN_ENABLESIZING
+                | OFN_HIDEREADONLY
+                | OFN_NODEREFERENCELINKS
+                | OFN_NONETWORKBUTTON
+                | OFN_FILEMUSTEXIST,
+            nFileOffset: 0,
+            nFileExtension: 0,
+            lpstrDefExt: DEFAULT_EXTENSION.as_ptr() as *const c_char,
+            lCustData: 0,
+            lpfnHook: None,
+            lpTemplateName: ptr::null_mut(),
+            pvReserved: ptr::null_mut(),
+            dwReserved: 0,
+            FlagsEx: 0,
+        };
+
+        match GetOpenFileNameA(&mut ofn as LPOPENFILENAMEA) {
+            1 => Some(PathBuf::from(vec_with_nul_to_string(&file_name))),
+            _ => {
+                let code = CommDlgExtendedError();
+                if code != 0 {
+                    warn!(target: target::WINAPI, "The open file dialog failed: {}", DialogError(code));
                 }
+                None
             }
-            _ => Err("Cancelled"),
         }
     }
 }
@@ -147,3 +290,484 @@ pub fn get_save_folder_name() -> String {
         selected_folder.to_string_lossy().into_owned()
     }
 }
+
+// The operation and the file passed to ShellExecuteW, as NUL terminated UTF-16. Only web
+// pages are opened: anything else would let a configured "URL" start an arbitrary program.
+fn shell_execute_args(url: &str) -> Result<(Vec<u16>, Vec<u16>), String> {
+    let is_web_page = ["https://", "http://"].iter().any(|scheme| {
+        url.len() > scheme.len()
+            && url
+                .get(..scheme.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+    });
+    if !is_web_page || url.contains(|c: char| c == '\0' || c.is_whitespace()) {
+        return Err(format!("{:?} is not a web page", url));
+    }
+    let wide = |text: &str| text.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    Ok((wide("open"), wide(url)))
+}
+
+// Open the web page in the default browser
+pub fn open_url(url: &str) -> Result<(), String> {
+    let (operation, file) = shell_execute_args(url)?;
+    let result = unsafe {
+        ShellExecuteW(
+            ptr::null_mut(),
+            operation.as_ptr(),
+            file.as_ptr(),
+            ptr::null(),
+            ptr::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // values up to 32 are error codes
+    match result as usize as u32 {
+        code if code > 32 => Ok(()),
+        SE_ERR_NOASSOC | SE_ERR_ASSOCINCOMPLETE => {
+            Err("no browser is associated with web links".to_string())
+        }
+        SE_ERR_ACCESSDENIED => Err("access was denied".to_string()),
+        0 | SE_ERR_OOM => Err("out of memory".to_string()),
+        code => Err(format!("ShellExecute failed with error code {}", code)),
+    }
+}
+
+// predefined window classes, used as atoms in dialog templates
+const BUTTON_CLASS_ATOM: u16 = 0x0080;
+const STATIC_CLASS_ATOM: u16 = 0x0082;
+const LISTBOX_CLASS_ATOM: u16 = 0x0083;
+const EDIT_CLASS_ATOM: u16 = 0x0081;
+const ID_ITEM_LIST: c_int = 100;
+const ID_PROGRESS_TEXT: c_int = 101;
+const ID_INPUT_PROMPT: c_int = 102;
+const ID_INPUT_TEXT: c_int = 103;
+const INPUT_CAPTION: &str = "Xanthidae";
+
+thread_local! {
+    // items and selection flags of the checklist dialog currently shown on this thread
+    static CHECKLIST_DIALOG: RefCell<(Vec<String>, Vec<bool>)> = const { RefCell::new((vec![], vec![])) };
+}
+
+// Let the user pick items (e.g. columns) from a multi-select list box; all items are selected
+// initially. There are no resources in this DLL, so the dialog template is assembled in memory.
+pub fn select_items(title: &str, items: &[String]) -> Option<Vec<bool>> {
+    CHECKLIST_DIALOG.with(|dialog| {
+        *dialog.borrow_mut() = (items.to_vec(), vec![true; items.len()]);
+    });
+    let template = checklist_dialog_template(title);
+    let result = unsafe {
+        DialogBoxIndirectParamW(
+            ptr::null_mut(),
+            template.as_ptr() as *const _,
+            ptr::null_mut(),
+            Some(checklist_dialog_proc),
+            0,
+        )
+    };
+    let selection = CHECKLIST_DIALOG.with(|dialog| dialog.replace((vec![], vec![])).1);
+    match result as c_int {
+        IDOK => Some(selection),
+        _ => None,
+    }
+}
+
+unsafe extern "system" fn checklist_dialog_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    _lparam: LPARAM,
+) -> INT_PTR {
+    match msg {
+        WM_INITDIALOG => {
+            CHECKLIST_DIALOG.with(|dialog| {
+                let (items, selection) = &*dialog.borrow();
+                for (index, (item, &selected)) in items.iter().zip(selection).enumerate() {
+                    let wide: Vec<u16> = item.encode_utf16().chain(Some(0)).collect();
+                    SendDlgItemMessageW(
+                        hwnd,
+                        ID_ITEM_LIST,
+                        LB_ADDSTRING,
+                        0,
+                        wide.as_ptr() as LPARAM,
+                    );
+                    SendDlgItemMessageW(
+                        hwnd,
+                        ID_ITEM_LIST,
+                        LB_SETSEL,
+                        selected as WPARAM,
+                        index as LPARAM,
+                    );
+                }
+            });
+            1
+        }
+        WM_COMMAND => match LOWORD(wparam as u32) as c_int {
+            IDOK => {
+                CHECKLIST_DIALOG.with(|dialog| {
+                    let (_, selection) = &mut *dialog.borrow_mut();
+                    for (index, selected) in selection.iter_mut().enumerate() {
+                        *selected =
+                            SendDlgItemMessageW(hwnd, ID_ITEM_LIST, LB_GETSEL, index as WPARAM, 0)
+                                > 0;
+                    }
+                });
+                EndDialog(hwnd, IDOK as INT_PTR);
+                1
+            }
+            IDCANCEL => {
+                EndDialog(hwnd, IDCANCEL as INT_PTR);
+                1
+            }
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+// Build a DLGTEMPLATE with a list box and OK / Cancel buttons (all coordinates in dialog units).
+// The template is returned as Vec<u32>, as it has to be DWORD aligned.
+fn checklist_dialog_template(title: &str) -> Vec<u32> {
+    let mut t = dialog_header(
+        WS_POPUP | WS_CAPTION | WS_SYSMENU | DS_MODALFRAME | DS_CENTER,
+        3,
+        [200, 220],
+        title,
+    );
+
+    push_control(
+        &mut t,
+        WS_CHILD
+            | WS_VISIBLE
+            | WS_BORDER
+            | WS_VSCROLL
+            | WS_TABSTOP
+            | LBS_MULTIPLESEL
+            | LBS_NOINTEGRALHEIGHT,
+        [7, 7, 186, 184],
+        ID_ITEM_LIST as u16,
+        LISTBOX_CLASS_ATOM,
+        "",
+    );
+    push_control(
+        &mut t,
+        WS_CHILD | WS_VISIBLE | WS_TABSTOP | BS_DEFPUSHBUTTON,
+        [89, 198, 50, 14],
+        IDOK as u16,
+        BUTTON_CLASS_ATOM,
+        "OK",
+    );
+    push_control(
+        &mut t,
+        WS_CHILD | WS_VISIBLE | WS_TABSTOP,
+        [143, 198, 50, 14],
+        IDCANCEL as u16,
+        BUTTON_CLASS_ATOM,
+        "Cancel",
+    );
+    dword_aligned(t)
+}
+
+thread_local! {
+    // prompt and entered text of the input dialog currently shown on this thread
+    static INPUT_DIALOG: RefCell<(String, String)> = const { RefCell::new((String::new(), String::new())) };
+}
+
+// Ask the user for a single line of text in a small dialog with the prompt, a text field and
+// OK / Cancel buttons; the text is returned trimmed.
+pub fn get_user_input(prompt: &str) -> Result<String, &'static str> {
+    INPUT_DIALOG.with(|dialog| *dialog.borrow_mut() = (prompt.to_string(), String::new()));
+    let template = input_dialog_template(INPUT_CAPTION);
+    let result = unsafe {
+        DialogBoxIndirectParamW(
+            ptr::null_mut(),
+            template.as_ptr() as *const _,
+            ptr::null_mut(),
+            Some(input_dialog_proc),
+            0,
+        )
+    };
+    let input = INPUT_DIALOG.with(|dialog| dialog.replace((String::new(), String::new())).1);
+    match (result as c_int, input.trim()) {
+        (IDOK, "") => Err("Empty input"),
+        (IDOK, trimmed) => Ok(trimmed.to_string()),
+        _ => Err("Cancelled"),
+    }
+}
+
+unsafe extern "system" fn input_dialog_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    _lparam: LPARAM,
+) -> INT_PTR {
+    match msg {
+        WM_INITDIALOG => {
+            INPUT_DIALOG.with(|dialog| {
+                let wide: Vec<u16> = dialog.borrow().0.encode_utf16().chain(Some(0)).collect();
+                SetDlgItemTextW(hwnd, ID_INPUT_PROMPT, wide.as_ptr());
+            });
+            SendDlgItemMessageW(hwnd, ID_INPUT_TEXT, EM_LIMITTEXT, BUFFER_SIZE, 0);
+            // the focus goes to the text field, the first control with a tab stop
+            1
+        }
+        WM_COMMAND => match LOWORD(wparam as u32) as c_int {
+            IDOK => {
+                let mut buffer: Vec<u16> = vec![0; BUFFER_SIZE + 1];
+                let len = GetDlgItemTextW(
+                    hwnd,
+                    ID_INPUT_TEXT,
+                    buffer.as_mut_ptr(),
+                    buffer.len() as c_int,
+                );
+                let text = String::from_utf16_lossy(&buffer[..len as usize]);
+                INPUT_DIALOG.with(|dialog| dialog.borrow_mut().1 = text);
+                EndDialog(hwnd, IDOK as INT_PTR);
+                1
+            }
+            IDCANCEL => {
+                EndDialog(hwnd, IDCANCEL as INT_PTR);
+                1
+            }
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+// Build a DLGTEMPLATE with a prompt, a text field and OK / Cancel buttons, see
+// `checklist_dialog_template`
+fn input_dialog_template(title: &str) -> Vec<u32> {
+    let mut t = dialog_header(
+        WS_POPUP | WS_CAPTION | WS_SYSMENU | DS_MODALFRAME | DS_CENTER,
+        4,
+        [240, 76],
+        title,
+    );
+
+    push_control(
+        &mut t,
+        WS_CHILD | WS_VISIBLE | SS_LEFT,
+        [7, 7, 226, 18],
+        ID_INPUT_PROMPT as u16,
+        STATIC_CLASS_ATOM,
+        "",
+    );
+    push_control(
+        &mut t,
+        WS_CHILD | WS_VISIBLE | WS_BORDER | WS_TABSTOP | ES_AUTOHSCROLL,
+        [7, 29, 226, 14],
+        ID_INPUT_TEXT as u16,
+        EDIT_CLASS_ATOM,
+        "",
+    );
+    push_control(
+        &mut t,
+        WS_CHILD | WS_VISIBLE | WS_TABSTOP | BS_DEFPUSHBUTTON,
+        [129, 55, 50, 14],
+        IDOK as u16,
+        BUTTON_CLASS_ATOM,
+        "OK",
+    );
+    push_control(
+        &mut t,
+        WS_CHILD | WS_VISIBLE | WS_TABSTOP,
+        [183, 55, 50, 14],
+        IDCANCEL as u16,
+        BUTTON_CLASS_ATOM,
+        "Cancel",
+    );
+    dword_aligned(t)
+}
+
+thread_local! {
+    // the flag set by the Cancel button of the progress dialog shown on this thread
+    static PROGRESS_CANCEL: Cell<Option<&'static AtomicBool>> = const { Cell::new(None) };
+}
+
+// A modeless dialog showing what a long running operation is doing, with a Cancel button
+// setting a flag the operation checks. It is closed when dropped.
+pub struct ProgressDialog {
+    hwnd: HWND,
+}
+
+pub fn start_progress(title: &str, cancel: &'static AtomicBool) -> ProgressDialog {
+    cancel.store(false, Ordering::SeqCst);
+    PROGRESS_CANCEL.with(|flag| flag.set(Some(cancel)));
+    let template = progress_dialog_template(title);
+    let hwnd = unsafe {
+        CreateDialogIndirectParamW(
+            ptr::null_mut(),
+            template.as_ptr() as *const _,
+            ptr::null_mut(),
+            Some(progress_dialog_proc),
+            0,
+        )
+    };
+    if hwnd.is_null() {
+        warn!(target: target::WINAPI, "Could not create the progress dialog");
+    }
+    ProgressDialog { hwnd }
+}
+
+impl ProgressDialog {
+    // Show the message and handle pending messages of the dialog and its controls, e.g. a click
+    // on Cancel, as the operation runs on the UI thread. The IDE's own messages stay queued, so
+    // nothing else reacts to input while the operation runs.
+    pub fn set_message(&self, message: &str) {
+        if self.hwnd.is_null() {
+            return;
+        }
+        let wide: Vec<u16> = message.encode_utf16().chain(Some(0)).collect();
+        unsafe {
+            SetDlgItemTextW(self.hwnd, ID_PROGRESS_TEXT, wide.as_ptr());
+            let mut msg = MaybeUninit::<MSG>::uninit();
+            while PeekMessageW(msg.as_mut_ptr(), self.hwnd, 0, 0, PM_REMOVE) != 0 {
+                if IsDialogMessageW(self.hwnd, msg.as_mut_ptr()) == 0 {
+                    TranslateMessage(msg.as_ptr());
+                    DispatchMessageW(msg.as_ptr());
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ProgressDialog {
+    fn drop(&mut self) {
+        if !self.hwnd.is_null() {
+            unsafe { DestroyWindow(self.hwnd) };
+        }
+        PROGRESS_CANCEL.with(|flag| flag.set(None));
+    }
+}
+
+unsafe extern "system" fn progress_dialog_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    _lparam: LPARAM,
+) -> INT_PTR {
+    match msg {
+        WM_INITDIALOG => 1,
+        WM_COMMAND if LOWORD(wparam as u32) as c_int == IDCANCEL => {
+            if let Some(cancel) = PROGRESS_CANCEL.with(Cell::get) {
+                cancel.store(true, Ordering::SeqCst);
+            }
+            // the current object is finished first, so there's nothing to press twice
+            EnableWindow(GetDlgItem(hwnd, IDCANCEL), 0);
+            1
+        }
+        _ => 0,
+    }
+}
+
+// Build a DLGTEMPLATE with a text and a Cancel button, see `checklist_dialog_template`
+fn progress_dialog_template(title: &str) -> Vec<u32> {
+    let mut t = dialog_header(
+        WS_POPUP | WS_CAPTION | WS_VISIBLE | DS_MODALFRAME | DS_CENTER,
+        2,
+        [240, 62],
+        title,
+    );
+
+    push_control(
+        &mut t,
+        WS_CHILD | WS_VISIBLE | SS_LEFT,
+        [7, 7, 226, 24],
+        ID_PROGRESS_TEXT as u16,
+        STATIC_CLASS_ATOM,
+        "",
+    );
+    push_control(
+        &mut t,
+        WS_CHILD | WS_VISIBLE | WS_TABSTOP | BS_DEFPUSHBUTTON,
+        [183, 41, 50, 14],
+        IDCANCEL as u16,
+        BUTTON_CLASS_ATOM,
+        "Cancel",
+    );
+    dword_aligned(t)
+}
+
+// The DLGTEMPLATE header of a dialog with the given number of controls and size, using the
+// default dialog font
+fn dialog_header(style: u32, controls: u16, size: [u16; 2], title: &str) -> Vec<u16> {
+    let mut t: Vec<u16> = vec![];
+    push_dword(&mut t, style | DS_SETFONT);
+    push_dword(&mut t, 0); // extended style
+    t.push(controls);
+    t.extend_from_slice(&[0, 0, size[0], size[1]]); // x, y, width, height
+    t.push(0); // no menu
+    t.push(0); // default dialog class
+    push_wide(&mut t, title);
+    t.push(8); // font size
+    push_wide(&mut t, "MS Shell Dlg");
+    t
+}
+
+// The template as DWORDs, as it has to be DWORD aligned
+fn dword_aligned(mut t: Vec<u16>) -> Vec<u32> {
+    if t.len() % 2 == 1 {
+        t.push(0);
+    }
+    t.chunks(2)
+        .map(|pair| pair[0] as u32 | (pair[1] as u32) << 16)
+        .collect()
+}
+
+fn push_control(
+    t: &mut Vec<u16>,
+    style: u32,
+    rect: [u16; 4],
+    id: u16,
+    class_atom: u16,
+    text: &str,
+) {
+    // each DLGITEMTEMPLATE starts on a DWORD boundary
+    if t.len() % 2 == 1 {
+        t.push(0);
+    }
+    push_dword(t, style);
+    push_dword(t, 0); // extended style
+    t.extend_from_slice(&rect);
+    t.push(id);
+    t.extend_from_slice(&[0xFFFF, class_atom]);
+    push_wide(t, text);
+    t.push(0); // no creation data
+}
+
+fn push_dword(t: &mut Vec<u16>, value: u32) {
+    t.push(value as u16);
+    t.push((value >> 16) as u16);
+}
+
+fn push_wide(t: &mut Vec<u16>, text: &str) {
+    t.extend(text.encode_utf16());
+    t.push(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::windows_api::*;
+
+    #[test]
+    fn shell_execute_args_should_only_accept_web_pages() {
+        let (operation, file) = shell_execute_args("https://flywaydb.org/a#b").unwrap();
+        assert_eq!("open\0".encode_utf16().collect::<Vec<u16>>(), operation);
+        assert_eq!(
+            "https://flywaydb.org/a#b\0"
+                .encode_utf16()
+                .collect::<Vec<u16>>(),
+            file
+        );
+        assert!(shell_execute_args("HTTP://example.com").is_ok());
+        for url in [
+            "calc.exe",
+            "file:///C:/Windows/System32/calc.exe",
+            "https://",
+            "https://example.com\0calc.exe",
+            "https://example.com calc.exe",
+        ] {
+            assert!(shell_execute_args(url).is_err(), "{:?}", url);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+// A minimal writer of zip archives, for handing in an export as a single attachment.
+//
+// The entries are stored without compression: migrations are small text files, and storing
+// keeps the format simple enough to get by without another dependency. Zip64 isn't supported,
+// so neither entries nor the archive may exceed 4 GiB. Names are written with forward slashes
+// and flagged as UTF-8, so object names with non-ASCII characters survive.
+use std::fmt::{Display, Formatter};
+
+use chrono::{Datelike, NaiveDateTime, Timelike};
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+// 2.0, the version needed for folders in entry names
+const VERSION: u16 = 20;
+// general purpose bit 11: the name is UTF-8
+const UTF8_NAME_FLAG: u16 = 1 << 11;
+const METHOD_STORED: u16 = 0;
+
+pub struct ZipEntry {
+    // relative path, with forward or backward slashes
+    pub name: String,
+    pub content: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ZipError {
+    // name of the entry
+    EntryTooLarge(String),
+    TooManyEntries(usize),
+    ArchiveTooLarge,
+}
+
+impl Display for ZipError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            ZipError::EntryTooLarge(name) => format!("{} is too large for a zip archive", name),
+            ZipError::TooManyEntries(count) => {
+                format!("{} files are too many for a zip archive", count)
+            }
+            ZipError::ArchiveTooLarge => "The zip archive would exceed 4 GiB".to_string(),
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+// The name as stored in the archive: relative, with forward slashes
+pub fn entry_name(name: &str) -> String {
+    name.replace('\\', "/").trim_start_matches('/').to_string()
+}
+
+// MS-DOS time and date; DOS dates start in 1980, and have a resolution of two seconds
+fn dos_time_and_date(modified: NaiveDateTime) -> (u16, u16) {
+    let time = (modified.hour() << 11) | (modified.minute() << 5) | (modified.second() / 2);
+    let date = match modified.year() {
+        year if year < 1980 => (1 << 5) | 1,
+        year => (((year - 1980) as u32) << 9) | (modified.month() << 5) | modified.day(),
+    };
+    (time as u16, date as u16)
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+// The archive of the entries, in the given order, all with the given modification time
+pub fn write_zip(entries: &[ZipEntry], modified: NaiveDateTime) -> Result<Vec<u8>, ZipError> {
+    let count =
+        u16::try_from(entries.len()).map_err(|_| ZipError::TooManyEntries(entries.len()))?;
+    let (time, date) = dos_time_and_date(modified);
+    let mut archive = vec![];
+    let mut central_directory = vec![];
+    for entry in entries {
+        let name = entry_name(&entry.name);
+        let size = u32::try_from(entry.content.len())
+            .map_err(|_| ZipError::EntryTooLarge(name.clone()))?;
+        let name_length =
+            u16::try_from(name.len()).map_err(|_| ZipError::EntryTooLarge(name.clone()))?;
+        let offset = u32::try_from(archive.len()).map_err(|_| ZipError::ArchiveTooLarge)?;
+        let crc = crc32fast::hash(&entry.content);
+
+        // version needed, flags, method, time, date, CRC, sizes and name length are the same
+        // in the local header and the central directory
+        let mut common = vec![];
+        put_u16(&mut common, VERSION);
+        put_u16(&mut common, UTF8_NAME_FLAG);
+        put_u16(&mut common, METHOD_STORED);
+        put_u16(&mut common, time);
+        put_u16(&mut common, date);
+        put_u32(&mut common, crc);
+        put_u32(&mut common, size);
+        put_u32(&mut common, size);
+        put_u16(&mut common, name_length);
+
+        put_u32(&mut archive, LOCAL_FILE_HEADER_SIGNATURE);
+        archive.extend_from_slice(&common);
+        // extra field length
+        put_u16(&mut archive, 0);
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(&entry.content);
+
+        put_u32(&mut central_directory, CENTRAL_DIRECTORY_SIGNATURE);
+        // version made by
+        put_u16(&mut central_directory, VERSION);
+        central_directory.extend_from_slice(&common);
+        // extra field length, comment length, disk number, internal and external attributes
+        put_u16(&mut central_directory, 0);
+        put_u16(&mut central_directory, 0);
+        put_u16(&mut central_directory, 0);
+        put_u16(&mut central_directory, 0);
+        put_u32(&mut central_directory, 0);
+        put_u32(&mut central_directory, offset);
+        central_directory.extend_from_slice(name.as_bytes());
+    }
+    let directory_offset = u32::try_from(archive.len()).map_err(|_| ZipError::ArchiveTooLarge)?;
+    let directory_size =
+        u32::try_from(central_directory.len()).map_err(|_| ZipError::ArchiveTooLarge)?;
+    archive.extend_from_slice(&central_directory);
+
+    put_u32(&mut archive, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+    // number of this disk, disk with the central directory
+    put_u16(&mut archive, 0);
+    put_u16(&mut archive, 0);
+    // entries on this disk, entries in total
+    put_u16(&mut archive, count);
+    put_u16(&mut archive, count);
+    put_u32(&mut archive, directory_size);
+    put_u32(&mut archive, directory_offset);
+    // comment length
+    put_u16(&mut archive, 0);
+    u32::try_from(archive.len()).map_err(|_| ZipError::ArchiveTooLarge)?;
+    Ok(archive)
+}
+
+// Reads back the archives written, for the tests
+#[cfg(test)]
+pub mod reader {
+    use crate::zip::*;
+
+    // An entry as read back from the central directory and the local header it points to
+    #[derive(Debug)]
+    pub struct ReadEntry {
+        pub name: String,
+        pub flags: u16,
+        pub method: u16,
+        pub crc: u32,
+        pub time: u16,
+        pub date: u16,
+        pub content: Vec<u8>,
+    }
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    // A reader of just what write_zip writes: no archive comment, no extra fields
+    pub fn read_zip(archive: &[u8]) -> Vec<ReadEntry> {
+        let end = archive.len() - 22;
+        assert_eq!(END_OF_CENTRAL_DIRECTORY_SIGNATURE, u32_at(archive, end));
+        let count = u16_at(archive, end + 10) as usize;
+        assert_eq!(count, u16_at(archive, end + 8) as usize);
+        let directory_size = u32_at(archive, end + 12) as usize;
+        let mut offset = u32_at(archive, end + 16) as usize;
+        assert_eq!(end, offset + directory_size);
+
+        let mut entries = vec![];
+        for _ in 0..count {
+            assert_eq!(CENTRAL_DIRECTORY_SIGNATURE, u32_at(archive, offset));
+            let size = u32_at(archive, offset + 20) as usize;
+            assert_eq!(size, u32_at(archive, offset + 24) as usize);
+            let name_length = u16_at(archive, offset + 28) as usize;
+            let local_offset = u32_at(archive, offset + 42) as usize;
+            let name = &archive[offset + 46..offset + 46 + name_length];
+
+            // the local header must agree with the central directory
+            assert_eq!(LOCAL_FILE_HEADER_SIGNATURE, u32_at(archive, local_offset));
+            assert_eq!(
+                archive[offset + 6..offset + 30],
+                archive[local_offset + 4..local_offset + 28]
+            );
+            let data =
+                local_offset + 30 + name_length + u16_at(archive, local_offset + 28) as usize;
+            assert_eq!(
+                name,
+                &archive[local_offset + 30..local_offset + 30 + name_length]
+            );
+
+            entries.push(ReadEntry {
+                name: String::from_utf8(name.to_vec()).unwrap(),
+                flags: u16_at(archive, offset + 8),
+                method: u16_at(archive, offset + 10),
+                time: u16_at(archive, offset + 12),
+                date: u16_at(archive, offset + 14),
+                crc: u32_at(archive, offset + 16),
+                content: archive[data..data + size].to_vec(),
+            });
+            offset += 46 + name_length;
+        }
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::zip::reader::read_zip;
+    use crate::zip::*;
+
+    fn modified() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 17)
+            .unwrap()
+            .and_hms_opt(14, 30, 59)
+            .unwrap()
+    }
+
+    #[test]
+    fn write_zip_should_write_entries_readable_from_the_central_directory() {
+        let entries = vec![
+            ZipEntry {
+                name: "manifest.json".to_string(),
+                content: b"{}\n".to_vec(),
+            },
+            ZipEntry {
+                name: "packages\\R__PKG_GRÜN.sql".to_string(),
+                content: b"create or replace package APP.PKG_GRUEN is\nend;\n/\n".to_vec(),
+            },
+            ZipEntry {
+                name: "views/R__V_EMPTY.sql".to_string(),
+                content: vec![],
+            },
+        ];
+
+        let archive = write_zip(&entries, modified()).unwrap();
+        let read = read_zip(&archive);
+
+        assert_eq!(
+            vec![
+                "manifest.json",
+                "packages/R__PKG_GRÜN.sql",
+                "views/R__V_EMPTY.sql"
+            ],
+            read.iter().map(|e| e.name.as_str()).collect::<Vec<_>>()
+        );
+        for (entry, read) in entries.iter().zip(&read) {
+            assert_eq!(entry.content, read.content);
+            assert_eq!(crc32fast::hash(&entry.content), read.crc);
+            assert_eq!(UTF8_NAME_FLAG, read.flags);
+            assert_eq!(METHOD_STORED, read.method);
+            // 14:30:58 and 2024-03-17
+            assert_eq!((14 << 11) | (30 << 5) | 29, read.time);
+            assert_eq!((44 << 9) | (3 << 5) | 17, read.date);
+        }
+    }
+
+    #[test]
+    fn write_zip_should_write_an_empty_archive() {
+        let archive = write_zip(&[], modified()).unwrap();
+        assert_eq!(22, archive.len());
+        assert!(read_zip(&archive).is_empty());
+    }
+
+    #[test]
+    fn entry_name_should_use_forward_slashes_and_be_relative() {
+        assert_eq!("types/R__T_A.sql", entry_name("\\types\\R__T_A.sql"));
+        assert_eq!("R__V_A.sql", entry_name("R__V_A.sql"));
+    }
+}
@@ -0,0 +1,309 @@
+// Export the selected objects as repeatable migrations into a single zip archive, e.g. as the
+// one attachment a change request takes. The migrations are written to a staging folder
+// first, then packed in a subfolder per object type, with a manifest listing them at the root.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use chrono::Local;
+
+use crate::bulk::{BulkSummary, BULK_CANCEL};
+use crate::config::Config;
+use crate::documentation::type_subfolder;
+use crate::flyway::{
+    export_objects_as_repeatable_migrations, FlywayError, NO_OBJECT_SELECTED_CAPTION,
+    NO_OBJECT_SELECTED_MESSAGE,
+};
+use crate::logging::target;
+use crate::paths;
+use crate::plsqldev_api::{PlsqlDevApi, SelectedObject};
+use crate::string_utils::json_string;
+use crate::temp_files::TEMP_FILES;
+use crate::ui::{dialogs, MessageKind, Progress, SaveDialogOutcome};
+use crate::verified_write::write_verified;
+use crate::zip::{entry_name, write_zip, ZipEntry};
+
+const ZIP_CAPTION: &str = "Export as zip";
+const MANIFEST_NAME: &str = "manifest.json";
+
+pub fn export_selection_as_zip(api: &dyn PlsqlDevApi, config: &Config) {
+    let mut selected_objects = api.ide_selected_objects();
+    if selected_objects.is_empty() {
+        dialogs().show_message(
+            NO_OBJECT_SELECTED_MESSAGE,
+            NO_OBJECT_SELECTED_CAPTION,
+            MessageKind::Information,
+        );
+        return;
+    }
+    let zip_path = match dialogs().get_save_file_name() {
+        SaveDialogOutcome::Chosen(folder, file_name) => zip_file_path(&folder, &file_name),
+        SaveDialogOutcome::Cancelled => return,
+        SaveDialogOutcome::EmptyName => {
+            dialogs().show_message(
+                &format!("{}", FlywayError::EmptyFileName),
+                ZIP_CAPTION,
+                MessageKind::Error,
+            );
+            return;
+        }
+        SaveDialogOutcome::Failed(e) => {
+            dialogs().show_message(
+                &format!("{}", FlywayError::DialogFailed(e)),
+                ZIP_CAPTION,
+                MessageKind::Error,
+            );
+            return;
+        }
+    };
+    // registered, so it is deleted on unload (or swept later) if the IDE dies while packing
+    let staging = match TEMP_FILES.create_dir("zip") {
+        Ok(staging) => staging,
+        Err(e) => {
+            dialogs().show_message(
+                &format!("{}", FlywayError::IOError(format!("{}", e))),
+                ZIP_CAPTION,
+                MessageKind::Error,
+            );
+            return;
+        }
+    };
+
+    let result = {
+        let mut progress = dialogs().start_progress(ZIP_CAPTION, &BULK_CANCEL);
+        export_into_zip(
+            api,
+            config,
+            &mut selected_objects,
+            &staging,
+            &zip_path,
+            &BULK_CANCEL,
+            progress.as_mut(),
+        )
+    };
+    if let Err(e) = TEMP_FILES.release(&staging) {
+        warn!(target: target::FLYWAY, "Could not delete the staging folder {:?}: {}", staging, e);
+    }
+
+    let zip_name = paths::display(config.repo_root.as_deref(), &zip_path.to_string_lossy());
+    match result {
+        Ok((summary, _, _)) if summary.succeeded == 0 => dialogs().show_message(
+            "No migrations were exported, so no archive was written.\nPlease make sure you have selected one or more supported\nobject types.",
+            ZIP_CAPTION,
+            MessageKind::Error,
+        ),
+        Ok((summary, files, failures)) => {
+            let mut message = format!(
+                "Exported {} objects as {} repeatable migration(s) into {}.",
+                summary.succeeded, files, zip_name
+            );
+            if !failures.is_empty() {
+                message.push_str(&format!(
+                    "\n\nThese objects could not be exported:\n{}",
+                    failures.join("\n")
+                ));
+            }
+            dialogs().show_message(&message, ZIP_CAPTION, MessageKind::Information);
+        }
+        Err(e) => dialogs().show_message(&format!("{}", e), ZIP_CAPTION, MessageKind::Error),
+    }
+}
+
+// The chosen file, with the .zip extension (the save dialog suggests .sql)
+fn zip_file_path(folder: &Path, file_name: &str) -> PathBuf {
+    let mut path = folder.join(file_name);
+    let is_zip = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"));
+    if !is_zip {
+        path.set_extension("zip");
+    }
+    path
+}
+
+// Export the objects into the (emptied) staging folder and pack the migrations into the zip
+// file; returns the summary, the number of files packed and the objects that failed
+pub fn export_into_zip(
+    api: &dyn PlsqlDevApi,
+    config: &Config,
+    selected_objects: &mut [SelectedObject],
+    staging: &Path,
+    zip_path: &Path,
+    cancel: &AtomicBool,
+    progress: &mut dyn Progress,
+) -> Result<(BulkSummary, usize, Vec<String>), FlywayError> {
+    let io_error = |e: std::io::Error| FlywayError::IOError(format!("{}", e));
+    if staging.exists() {
+        fs::remove_dir_all(staging).map_err(io_error)?;
+    }
+    fs::create_dir_all(staging).map_err(io_error)?;
+    let (summary, exported, failures) = export_objects_as_repeatable_migrations(
+        api,
+        &staging.to_string_lossy(),
+        selected_objects,
+        config,
+        None,
+        cancel,
+        progress,
+    );
+    if summary.succeeded == 0 {
+        return Ok((summary, 0, failures));
+    }
+
+    // (object, entry) in export order
+    let mut migrations = vec![];
+    for object in &exported {
+        for file in &object.files {
+            migrations.push((
+                object.name.clone(),
+                ZipEntry {
+                    name: format!("{}/{}", type_subfolder(&object.object_type), file.name),
+                    content: fs::read(staging.join(&file.name)).map_err(io_error)?,
+                },
+            ));
+        }
+    }
+    let now = Local::now();
+    let mut entries = vec![ZipEntry {
+        name: MANIFEST_NAME.to_string(),
+        content: manifest(&migrations, &now.to_rfc3339()).into_bytes(),
+    }];
+    let files = migrations.len();
+    entries.extend(migrations.into_iter().map(|(_, entry)| entry));
+    let archive = write_zip(&entries, now.naive_local())
+        .map_err(|e| FlywayError::IOError(format!("{}", e)))?;
+    write_verified(zip_path, &archive, config.verify_written_files).map_err(io_error)?;
+    Ok((summary, files, failures))
+}
+
+// The manifest of the archive: when it was created, and each migration with the object it
+// belongs to, its size and its CRC-32
+fn manifest(migrations: &[(String, ZipEntry)], created: &str) -> String {
+    let files: Vec<String> = migrations
+        .iter()
+        .map(|(object, entry)| {
+            format!(
+                "    {{\"object\": {}, \"file\": {}, \"bytes\": {}, \"crc32\": \"{:08x}\"}}",
+                json_string(object),
+                json_string(&entry_name(&entry.name)),
+                entry.content.len(),
+                crc32fast::hash(&entry.content)
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \"created\": {},\n  \"files\": [\n{}\n  ]\n}}\n",
+        json_string(created),
+        files.join(",\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::AtomicBool;
+
+    use crate::config::Config;
+    use crate::plsqldev_api::{PlsqlDevApi, SelectedObject};
+    use crate::ui::Progress;
+    use crate::zip::reader::read_zip;
+    use crate::zip_export::*;
+
+    struct SourceApi;
+
+    impl PlsqlDevApi for SourceApi {
+        fn ide_get_object_source(
+            &self,
+            object_type: &str,
+            object_owner: &str,
+            object_name: &str,
+        ) -> String {
+            match object_type {
+                "VIEW" => format!(
+                    "create or replace force view {}.{} as\nselect * from dual;\n",
+                    object_owner, object_name
+                ),
+                _ => format!(
+                    "create or replace {} {}.{} is\nend {};\n",
+                    object_type.to_lowercase(),
+                    object_owner,
+                    object_name,
+                    object_name
+                ),
+            }
+        }
+    }
+
+    struct NoProgress;
+
+    impl Progress for NoProgress {
+        fn update(&mut self, _message: &str) {}
+    }
+
+    #[test]
+    fn zip_file_path_should_end_in_zip() {
+        let folder = Path::new("exports");
+        assert_eq!(folder.join("release.zip"), zip_file_path(folder, "release"));
+        assert_eq!(
+            folder.join("release.zip"),
+            zip_file_path(folder, "release.sql")
+        );
+        assert_eq!(
+            folder.join("release.ZIP"),
+            zip_file_path(folder, "release.ZIP")
+        );
+    }
+
+    #[test]
+    fn export_into_zip_should_pack_migrations_by_type_with_manifest() {
+        let tmp: PathBuf = [&std::env::temp_dir(), Path::new("xanthidae_zip_export")]
+            .iter()
+            .collect();
+        let _ = fs::remove_dir_all(&tmp);
+        fs::create_dir_all(&tmp).unwrap();
+        let staging = tmp.join("staging");
+        let zip_path = tmp.join("release.zip");
+        let mut selected_objects = vec![
+            SelectedObject::new("VIEW", "APP", "V_GRÜN", ""),
+            SelectedObject::new("PACKAGE", "APP", "PKG_ADDRESS", ""),
+            SelectedObject::new("TABLE", "APP", "ADDRESS", ""),
+        ];
+
+        let (summary, files, failures) = export_into_zip(
+            &SourceApi,
+            &Config::default(),
+            &mut selected_objects,
+            &staging,
+            &zip_path,
+            &AtomicBool::new(false),
+            &mut NoProgress,
+        )
+        .unwrap();
+
+        assert_eq!(2, summary.succeeded);
+        assert_eq!(2, files);
+        assert!(failures.is_empty());
+        let entries = read_zip(&fs::read(&zip_path).unwrap());
+        // in export order, which follows the configured object type weights
+        assert_eq!(
+            vec![
+                "manifest.json",
+                "views/R__V_GRÜN.sql",
+                "packages/R__PKG_ADDRESS.sql"
+            ],
+            entries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            fs::read(staging.join("R__V_GRÜN.sql")).unwrap(),
+            entries[1].content
+        );
+        let manifest = String::from_utf8(entries[0].content.clone()).unwrap();
+        assert!(manifest.contains(&format!(
+            "{{\"object\": \"APP.V_GRÜN\", \"file\": \"views/R__V_GRÜN.sql\", \"bytes\": {}, \"crc32\": \"{:08x}\"}}",
+            entries[1].content.len(),
+            entries[1].crc
+        )));
+        fs::remove_dir_all(&tmp).unwrap();
+    }
+}